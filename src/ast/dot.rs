@@ -0,0 +1,249 @@
+//! Graphviz DOT export of the AST.
+//!
+//! Each statement and expression becomes one box labelled with its kind and
+//! token literal; edges are labelled with the field that holds the child
+//! (`left`, `right`, `condition`, `consequence`, `arguments`, ...). Render with:
+//!
+//! ```text
+//! monkey parse --dot file.monkey | dot -Tsvg > ast.svg
+//! ```
+
+use crate::ast::{
+    Program,
+    expression::{BlockStatement, Expression, Identifier},
+    statement::{LetStatement, Statement},
+};
+use crate::lexer::token::Token;
+
+impl Program {
+    /// Renders the program as a Graphviz `digraph`.
+    pub fn to_dot(&self) -> String {
+        let mut graph = Graph::default();
+        let root = graph.node("Program", None);
+        for stmt in &self.statements {
+            let child = graph.statement(stmt);
+            graph.edge(root, child, "statements");
+        }
+        format!(
+            "digraph AST {{\n  node [shape=box, fontname=\"monospace\"];\n{}}}\n",
+            graph.body
+        )
+    }
+}
+
+/// Accumulates node and edge lines, numbering nodes `n0`, `n1`, ...
+#[derive(Default)]
+struct Graph {
+    body: String,
+    next: usize,
+}
+
+impl Graph {
+    /// Adds a node labelled with its kind and, if given, its token literal.
+    fn node(&mut self, kind: &str, token: Option<&Token>) -> usize {
+        let id = self.next;
+        self.next += 1;
+        let label = match token {
+            Some(token) => format!("{}\\n{}", kind, escape(&token.literal)),
+            None => kind.to_string(),
+        };
+        self.body
+            .push_str(&format!("  n{} [label=\"{}\"];\n", id, label));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        self.body
+            .push_str(&format!("  n{} -> n{} [label=\"{}\"];\n", from, to, label));
+    }
+
+    fn identifier(&mut self, ident: &Identifier) -> usize {
+        self.node("Identifier", Some(&ident.token))
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> usize {
+        match stmt {
+            Statement::Let(s) => self.let_statement(s),
+            Statement::Return(s) => {
+                let id = self.node("ReturnStatement", Some(&s.token));
+                if let Some(value) = &s.value {
+                    let child = self.expression(value);
+                    self.edge(id, child, "value");
+                }
+                id
+            }
+            Statement::Expression(s) => {
+                let id = self.node("ExpressionStatement", Some(stmt.token()));
+                let child = self.expression(&s.value);
+                self.edge(id, child, "value");
+                id
+            }
+            Statement::Import(s) => {
+                let id = self.node("ImportStatement", Some(&s.token));
+                let path = self.node("StringLiteral", Some(&s.path.token));
+                self.edge(id, path, "path");
+                let alias = self.identifier(&s.alias);
+                self.edge(id, alias, "alias");
+                id
+            }
+            Statement::Export(s) => {
+                let id = self.node("ExportStatement", Some(&s.token));
+                let child = self.let_statement(&s.statement);
+                self.edge(id, child, "statement");
+                id
+            }
+        }
+    }
+
+    fn let_statement(&mut self, stmt: &LetStatement) -> usize {
+        let id = self.node("LetStatement", Some(&stmt.token));
+        let name = self.identifier(&stmt.name);
+        self.edge(id, name, "name");
+        if let Some(value) = &stmt.value {
+            let child = self.expression(value);
+            self.edge(id, child, "value");
+        }
+        id
+    }
+
+    fn block(&mut self, block: &BlockStatement) -> usize {
+        let id = self.node("BlockStatement", Some(&block.token));
+        for stmt in &block.statements {
+            let child = self.statement(stmt);
+            self.edge(id, child, "statements");
+        }
+        id
+    }
+
+    fn expression(&mut self, expr: &Expression) -> usize {
+        match expr {
+            Expression::Identifier(e) => self.identifier(e),
+            Expression::IntegerLiteral(e) => self.node("IntegerLiteral", Some(&e.token)),
+            Expression::BooleanLiteral(e) => self.node("BooleanLiteral", Some(&e.token)),
+            Expression::StringLiteral(e) => self.node("StringLiteral", Some(&e.token)),
+            Expression::PrefixExpression(e) => {
+                let id = self.node("PrefixExpression", Some(&e.token));
+                let right = self.expression(&e.right);
+                self.edge(id, right, "right");
+                id
+            }
+            Expression::InfixExpression(e) => {
+                let id = self.node("InfixExpression", Some(&e.token));
+                let left = self.expression(&e.left);
+                self.edge(id, left, "left");
+                let right = self.expression(&e.right);
+                self.edge(id, right, "right");
+                id
+            }
+            Expression::IfExpression(e) => {
+                let id = self.node("IfExpression", Some(&e.token));
+                let condition = self.expression(&e.condition);
+                self.edge(id, condition, "condition");
+                let consequence = self.expression(&e.consequence);
+                self.edge(id, consequence, "consequence");
+                if let Some(alternative) = &e.alternative {
+                    let alternative = self.expression(alternative);
+                    self.edge(id, alternative, "alternative");
+                }
+                id
+            }
+            Expression::BlockStatement(block) => self.block(block),
+            Expression::FunctionLiteral(e) => {
+                let id = self.node("FunctionLiteral", Some(&e.token));
+                for param in &e.parameters {
+                    let child = self.identifier(param);
+                    self.edge(id, child, "parameters");
+                }
+                let body = self.block(&e.body);
+                self.edge(id, body, "body");
+                id
+            }
+            Expression::CallExpression(e) => {
+                let id = self.node("CallExpression", Some(&e.token));
+                let function = self.expression(&e.function);
+                self.edge(id, function, "function");
+                for arg in &e.arguments {
+                    let child = self.expression(arg);
+                    self.edge(id, child, "arguments");
+                }
+                id
+            }
+            Expression::MemberExpression(e) => {
+                let id = self.node("MemberExpression", Some(&e.token));
+                let object = self.expression(&e.object);
+                self.edge(id, object, "object");
+                let property = self.identifier(&e.property);
+                self.edge(id, property, "property");
+                id
+            }
+        }
+    }
+}
+
+/// Escapes text for use inside a double-quoted DOT string.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::FileId;
+
+    #[test]
+    fn test_to_dot() {
+        let mut parser = Parser::new(Lexer::new(
+            "if (a < 1) { f(a, \"x\"); };".to_string(),
+            FileId::DETACHED,
+        ));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty());
+
+        assert_eq!(
+            program.to_dot(),
+            r#"digraph AST {
+  node [shape=box, fontname="monospace"];
+  n0 [label="Program"];
+  n1 [label="ExpressionStatement\nif"];
+  n2 [label="IfExpression\nif"];
+  n3 [label="InfixExpression\n<"];
+  n4 [label="Identifier\na"];
+  n3 -> n4 [label="left"];
+  n5 [label="IntegerLiteral\n1"];
+  n3 -> n5 [label="right"];
+  n2 -> n3 [label="condition"];
+  n6 [label="BlockStatement\n{"];
+  n7 [label="ExpressionStatement\nf"];
+  n8 [label="CallExpression\n("];
+  n9 [label="Identifier\nf"];
+  n8 -> n9 [label="function"];
+  n10 [label="Identifier\na"];
+  n8 -> n10 [label="arguments"];
+  n11 [label="StringLiteral\nx"];
+  n8 -> n11 [label="arguments"];
+  n7 -> n8 [label="value"];
+  n6 -> n7 [label="statements"];
+  n2 -> n6 [label="consequence"];
+  n1 -> n2 [label="value"];
+  n0 -> n1 [label="statements"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r#"say "hi"\"#), r#"say \"hi\"\\"#);
+    }
+}
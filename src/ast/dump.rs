@@ -0,0 +1,380 @@
+//! Debugging views of the AST.
+//!
+//! `Display` re-sugars the tree into source text, which hides how operators
+//! were grouped. These views show the structure instead:
+//!
+//! - `to_sexpr` prints one S-expression per statement, e.g. `(let x (+ 1 (* 2 3)))`
+//! - `to_tree` prints one node per line, indented by depth, with node kinds and
+//!   `line:column` positions
+
+use crate::ast::{
+    Program,
+    expression::{BlockStatement, Expression, Identifier},
+    statement::{LetStatement, Statement},
+    types::TypeExpr,
+};
+use crate::lexer::token::Token;
+
+impl Program {
+    /// Renders the program as S-expressions, one statement per line.
+    ///
+    /// `let x = 1 + 2 * 3;` renders as `(let x (+ 1 (* 2 3)))`.
+    pub fn to_sexpr(&self) -> String {
+        self.statements
+            .iter()
+            .map(Statement::to_sexpr)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Renders the program as an indented tree of node kinds and positions.
+    pub fn to_tree(&self) -> String {
+        let mut tree = Tree::default();
+        tree.line(None, "Program", None);
+        tree.depth += 1;
+        for stmt in &self.statements {
+            tree.statement(None, stmt);
+        }
+        tree.out
+    }
+}
+
+// ============ S-EXPRESSIONS ============
+
+impl Statement {
+    /// Renders the statement as an S-expression.
+    pub fn to_sexpr(&self) -> String {
+        match self {
+            Statement::Let(s) => let_sexpr(s),
+            Statement::Return(s) => match &s.value {
+                Some(value) => format!("(return {})", value.to_sexpr()),
+                None => "(return)".to_string(),
+            },
+            Statement::Expression(s) => s.value.to_sexpr(),
+            Statement::Import(s) => format!("(import \"{}\" {})", s.path.value, s.alias.value),
+            Statement::Export(s) => format!("(export {})", let_sexpr(&s.statement)),
+        }
+    }
+}
+
+impl Expression {
+    /// Renders the expression as an S-expression.
+    pub fn to_sexpr(&self) -> String {
+        match self {
+            Expression::Identifier(e) => e.value.clone(),
+            Expression::IntegerLiteral(e) => e.value.to_string(),
+            Expression::BooleanLiteral(e) => e.value.to_string(),
+            Expression::StringLiteral(e) => format!("\"{}\"", e.value),
+            Expression::PrefixExpression(e) => format!("({} {})", e.operator, e.right.to_sexpr()),
+            Expression::InfixExpression(e) => format!(
+                "({} {} {})",
+                e.operator,
+                e.left.to_sexpr(),
+                e.right.to_sexpr()
+            ),
+            Expression::IfExpression(e) => {
+                let mut out = format!(
+                    "(if {} {}",
+                    e.condition.to_sexpr(),
+                    e.consequence.to_sexpr()
+                );
+                if let Some(alternative) = &e.alternative {
+                    out.push_str(&format!(" {}", alternative.to_sexpr()));
+                }
+                out.push(')');
+                out
+            }
+            Expression::BlockStatement(block) => block_sexpr(block),
+            Expression::FunctionLiteral(e) => {
+                let params: Vec<String> = e
+                    .parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(i, param)| match e.parameter_types.get(i) {
+                        Some(Some(ty)) => format!("({} : {})", param.value, ty),
+                        _ => param.value.clone(),
+                    })
+                    .collect();
+                let ret = match &e.return_type {
+                    Some(ty) => format!(" -> {}", ty),
+                    None => String::new(),
+                };
+                format!(
+                    "(fn ({}){} {})",
+                    params.join(" "),
+                    ret,
+                    block_sexpr(&e.body)
+                )
+            }
+            Expression::CallExpression(e) => {
+                let mut out = format!("(call {}", e.function.to_sexpr());
+                for arg in &e.arguments {
+                    out.push(' ');
+                    out.push_str(&arg.to_sexpr());
+                }
+                out.push(')');
+                out
+            }
+            Expression::MemberExpression(e) => {
+                format!("(. {} {})", e.object.to_sexpr(), e.property.value)
+            }
+        }
+    }
+}
+
+fn let_sexpr(stmt: &LetStatement) -> String {
+    let name = match &stmt.type_annotation {
+        Some(ty) => format!("({} : {})", stmt.name.value, ty),
+        None => stmt.name.value.clone(),
+    };
+    match &stmt.value {
+        Some(value) => format!("(let {} {})", name, value.to_sexpr()),
+        None => format!("(let {})", name),
+    }
+}
+
+fn block_sexpr(block: &BlockStatement) -> String {
+    let mut out = "(block".to_string();
+    for stmt in &block.statements {
+        out.push(' ');
+        out.push_str(&stmt.to_sexpr());
+    }
+    out.push(')');
+    out
+}
+
+// ============ TREE ============
+
+/// Accumulates tree lines, indenting two spaces per depth.
+#[derive(Default)]
+struct Tree {
+    out: String,
+    depth: usize,
+}
+
+impl Tree {
+    /// Writes `[label: ]kind[ `detail`][ line:column]`.
+    fn line(&mut self, label: Option<&str>, kind: &str, token: Option<(&Token, &str)>) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out.push_str(&"  ".repeat(self.depth));
+        if let Some(label) = label {
+            self.out.push_str(label);
+            self.out.push_str(": ");
+        }
+        self.out.push_str(kind);
+        if let Some((token, detail)) = token {
+            if !detail.is_empty() {
+                self.out.push_str(&format!(" `{}`", detail));
+            }
+            self.out
+                .push_str(&format!(" {}:{}", token.line, token.column));
+        }
+    }
+
+    /// Writes a node line, then its children one level deeper.
+    fn node(
+        &mut self,
+        label: Option<&str>,
+        kind: &str,
+        token: &Token,
+        detail: &str,
+        children: impl FnOnce(&mut Self),
+    ) {
+        self.line(label, kind, Some((token, detail)));
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+    }
+
+    fn identifier(&mut self, label: Option<&str>, ident: &Identifier) {
+        self.line(label, "Identifier", Some((&ident.token, &ident.value)));
+    }
+
+    fn type_expr(&mut self, label: &str, ty: &TypeExpr) {
+        let token = match ty {
+            TypeExpr::Named(t) => &t.token,
+            TypeExpr::Array(t) => &t.token,
+            TypeExpr::Hash(t) => &t.token,
+            TypeExpr::Function(t) => &t.token,
+            TypeExpr::Optional(t) => &t.token,
+        };
+        self.line(Some(label), "Type", Some((token, &ty.to_string())));
+    }
+
+    fn statement(&mut self, label: Option<&str>, stmt: &Statement) {
+        match stmt {
+            Statement::Let(s) => self.let_statement(label, s),
+            Statement::Return(s) => self.node(label, "ReturnStatement", &s.token, "", |t| {
+                if let Some(value) = &s.value {
+                    t.expression("value", value);
+                }
+            }),
+            Statement::Expression(s) => {
+                self.node(label, "ExpressionStatement", stmt.token(), "", |t| {
+                    t.expression("value", &s.value)
+                })
+            }
+            Statement::Import(s) => self.node(label, "ImportStatement", &s.token, "", |t| {
+                t.line(
+                    Some("path"),
+                    "StringLiteral",
+                    Some((&s.path.token, &s.path.value)),
+                );
+                t.identifier(Some("alias"), &s.alias);
+            }),
+            Statement::Export(s) => self.node(label, "ExportStatement", &s.token, "", |t| {
+                t.let_statement(Some("statement"), &s.statement)
+            }),
+        }
+    }
+
+    fn let_statement(&mut self, label: Option<&str>, stmt: &LetStatement) {
+        self.node(label, "LetStatement", &stmt.token, "", |t| {
+            t.identifier(Some("name"), &stmt.name);
+            if let Some(ty) = &stmt.type_annotation {
+                t.type_expr("type", ty);
+            }
+            if let Some(value) = &stmt.value {
+                t.expression("value", value);
+            }
+        });
+    }
+
+    fn block(&mut self, label: &str, block: &BlockStatement) {
+        self.node(Some(label), "BlockStatement", &block.token, "", |t| {
+            for stmt in &block.statements {
+                t.statement(None, stmt);
+            }
+        });
+    }
+
+    fn expression(&mut self, name: &str, expr: &Expression) {
+        let label = Some(name);
+        match expr {
+            Expression::Identifier(e) => self.identifier(label, e),
+            Expression::IntegerLiteral(e) => {
+                self.line(label, "IntegerLiteral", Some((&e.token, &e.token.literal)))
+            }
+            Expression::BooleanLiteral(e) => {
+                self.line(label, "BooleanLiteral", Some((&e.token, &e.token.literal)))
+            }
+            Expression::StringLiteral(e) => {
+                self.line(label, "StringLiteral", Some((&e.token, &e.value)))
+            }
+            Expression::PrefixExpression(e) => {
+                self.node(label, "PrefixExpression", &e.token, &e.operator, |t| {
+                    t.expression("right", &e.right)
+                })
+            }
+            Expression::InfixExpression(e) => {
+                self.node(label, "InfixExpression", &e.token, &e.operator, |t| {
+                    t.expression("left", &e.left);
+                    t.expression("right", &e.right);
+                })
+            }
+            Expression::IfExpression(e) => self.node(label, "IfExpression", &e.token, "", |t| {
+                t.expression("condition", &e.condition);
+                t.expression("consequence", &e.consequence);
+                if let Some(alternative) = &e.alternative {
+                    t.expression("alternative", alternative);
+                }
+            }),
+            Expression::BlockStatement(block) => self.block(name, block),
+            Expression::FunctionLiteral(e) => {
+                self.node(label, "FunctionLiteral", &e.token, "", |t| {
+                    for (i, param) in e.parameters.iter().enumerate() {
+                        t.identifier(Some("parameter"), param);
+                        if let Some(Some(ty)) = e.parameter_types.get(i) {
+                            t.depth += 1;
+                            t.type_expr("type", ty);
+                            t.depth -= 1;
+                        }
+                    }
+                    if let Some(ty) = &e.return_type {
+                        t.type_expr("return_type", ty);
+                    }
+                    t.block("body", &e.body);
+                })
+            }
+            Expression::CallExpression(e) => {
+                self.node(label, "CallExpression", &e.token, "", |t| {
+                    t.expression("function", &e.function);
+                    for arg in &e.arguments {
+                        t.expression("argument", arg);
+                    }
+                })
+            }
+            Expression::MemberExpression(e) => {
+                self.node(label, "MemberExpression", &e.token, "", |t| {
+                    t.expression("object", &e.object);
+                    t.identifier(Some("property"), &e.property);
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Program;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::FileId;
+
+    fn parse(input: &str) -> Program {
+        let mut parser = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        program
+    }
+
+    #[test]
+    fn test_to_sexpr() {
+        let tests = [
+            ("let x = 1 + 2 * 3;", "(let x (+ 1 (* 2 3)))"),
+            ("-a * b;", "(* (- a) b)"),
+            ("return !true;", "(return (! true))"),
+            (
+                "if (a < b) { a; } else { b; };",
+                "(if (< a b) (block a) (block b))",
+            ),
+            (
+                "let f: fn(int) -> int = fn(x: int, y) -> int { x; };",
+                "(let (f : fn(int) -> int) (fn ((x : int) y) -> int (block x)))",
+            ),
+            ("add(1, f(2))(3);", "(call (call add 1 (call f 2)) 3)"),
+            (
+                r#"import "m" as m; export let s = m.name;"#,
+                "(import \"m\" m)\n(export (let s (. m name)))",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse(input).to_sexpr(), expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_to_tree() {
+        let program = parse("let x = 1 + f(y);\nif (x) { x; };");
+        assert_eq!(
+            program.to_tree(),
+            "\
+Program
+  LetStatement 1:1
+    name: Identifier `x` 1:5
+    value: InfixExpression `+` 1:11
+      left: IntegerLiteral `1` 1:9
+      right: CallExpression 1:14
+        function: Identifier `f` 1:13
+        argument: Identifier `y` 1:15
+  ExpressionStatement 2:1
+    value: IfExpression 2:1
+      condition: Identifier `x` 2:5
+      consequence: BlockStatement 2:8
+        ExpressionStatement 2:10
+          value: Identifier `x` 2:10"
+        );
+    }
+}
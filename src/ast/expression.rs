@@ -3,14 +3,16 @@
 //! Expressions represent values and computations that evaluate to a value.
 //! All expression types are consolidated in this module.
 
-use crate::ast::{Node, Statement};
+use crate::ast::{Node, Statement, TypeExpr};
 use crate::lexer::token::Token;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter, Result};
 
 // ============ ENUM ============
 
 /// Enum representing all expression types in the AST.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum Expression {
     /// An identifier expression (variable name, function name, etc.)
     Identifier(Identifier),
@@ -30,6 +32,10 @@ pub enum Expression {
     FunctionLiteral(FunctionLiteral),
     /// A call expression (e.g., `add(1, 2)`)
     CallExpression(CallExpression),
+    /// A string literal expression (e.g., `"hello"`)
+    StringLiteral(StringLiteral),
+    /// A member access expression (e.g., `math.add`)
+    MemberExpression(MemberExpression),
 }
 
 // ============ STRUCTS ============
@@ -37,14 +43,14 @@ pub enum Expression {
 /// Represents an identifier expression in the Monkey language AST.
 ///
 /// An identifier is a name that refers to a variable, function, or other named entity.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Identifier {
     pub token: Token,
     pub value: String,
 }
 
 /// Represents an integer literal expression in the Monkey language AST.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IntegerLiteral {
     pub token: Token,
     pub value: i64,
@@ -52,14 +58,14 @@ pub struct IntegerLiteral {
 
 /// Represents a boolean literal expression in the Monkey language AST.
 /// Boolean literal: true, false
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BooleanLiteral {
     pub token: Token,
     pub value: bool,
 }
 
 /// Represents a prefix expression (e.g., `!true`, `-5`).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrefixExpression {
     pub token: Token,
     pub operator: String,
@@ -68,7 +74,7 @@ pub struct PrefixExpression {
 
 /// Represents an infix expression (e.g., `5 + 3`, `x == y`).
 /// Infix expression: <left> <operator> <right>
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InfixExpression {
     pub token: Token,
     pub left: Box<Expression>,
@@ -78,7 +84,7 @@ pub struct InfixExpression {
 
 /// Represents an if expression in the Monkey language AST. Every if expression has a condition, a consequence, and an optional alternative.
 /// if expression: if (<condition>) <consequence> else <alternative>
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IfExpression {
     pub token: Token,
     pub condition: Box<Expression>,
@@ -88,30 +94,70 @@ pub struct IfExpression {
 
 /// Represents a block statement in the Monkey language AST. A block statement is a list of statements enclosed in curly braces.
 /// block statement: { <statements> }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockStatement {
     pub token: Token,
     pub statements: Vec<Statement>,
 }
 
 /// Represents a function literal expression in the Monkey language AST.
-/// The format of a function literal is: fn(<parameters>) <body>
-#[derive(Debug, Clone)]
+/// The format of a function literal is: fn(<parameters>) [-> <return_type>] <body>
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionLiteral {
     pub token: Token,
     pub parameters: Vec<Identifier>,
+    /// Annotation of each parameter, index-aligned with `parameters`.
+    pub parameter_types: Vec<Option<TypeExpr>>,
+    pub return_type: Option<TypeExpr>,
     pub body: BlockStatement,
 }
 
 /// Represents a call expression in the Monkey language AST.
 /// The format of a call expression is: <function>(<arguments>)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallExpression {
     pub token: Token,
     pub function: Box<Expression>,
     pub arguments: Vec<Expression>,
 }
 
+/// Represents a string literal expression in the Monkey language AST.
+/// String literal: "<characters>"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StringLiteral {
+    pub token: Token,
+    pub value: String,
+}
+
+/// Represents a member access expression in the Monkey language AST.
+/// Used to reach the exports of an imported module.
+/// The format of a member expression is: <object>.<property>
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberExpression {
+    pub token: Token,
+    pub object: Box<Expression>,
+    pub property: Identifier,
+}
+
+impl Expression {
+    /// The first token of the expression, where it starts in the source.
+    pub fn first_token(&self) -> &Token {
+        match self {
+            Expression::InfixExpression(infix) => infix.left.first_token(),
+            Expression::CallExpression(call) => call.function.first_token(),
+            Expression::MemberExpression(member) => member.object.first_token(),
+            Expression::Identifier(e) => &e.token,
+            Expression::IntegerLiteral(e) => &e.token,
+            Expression::BooleanLiteral(e) => &e.token,
+            Expression::StringLiteral(e) => &e.token,
+            Expression::PrefixExpression(e) => &e.token,
+            Expression::IfExpression(e) => &e.token,
+            Expression::BlockStatement(e) => &e.token,
+            Expression::FunctionLiteral(e) => &e.token,
+        }
+    }
+}
+
 // ============ TRAIT IMPLEMENTATIONS ============
 
 impl Node for Expression {
@@ -126,6 +172,8 @@ impl Node for Expression {
             Expression::BlockStatement(bs) => bs.token_literal(),
             Expression::FunctionLiteral(fl) => fl.token_literal(),
             Expression::CallExpression(ce) => ce.token_literal(),
+            Expression::StringLiteral(sl) => sl.token_literal(),
+            Expression::MemberExpression(me) => me.token_literal(),
         }
     }
 }
@@ -142,6 +190,8 @@ impl Display for Expression {
             Expression::BlockStatement(bs) => write!(f, "{}", bs),
             Expression::FunctionLiteral(fl) => write!(f, "{}", fl),
             Expression::CallExpression(ce) => write!(f, "{}", ce),
+            Expression::StringLiteral(sl) => write!(f, "{}", sl),
+            Expression::MemberExpression(me) => write!(f, "{}", me),
         }
     }
 }
@@ -220,8 +270,15 @@ impl Display for FunctionLiteral {
                 write!(f, ", ")?;
             }
             write!(f, "{}", param)?;
+            if let Some(Some(annotation)) = self.parameter_types.get(i) {
+                write!(f, ": {}", annotation)?;
+            }
+        }
+        write!(f, ")")?;
+        if let Some(return_type) = &self.return_type {
+            write!(f, " -> {}", return_type)?;
         }
-        write!(f, ") {}", self.body)
+        write!(f, " {}", self.body)
     }
 }
 
@@ -278,6 +335,30 @@ impl Display for IfExpression {
     }
 }
 
+impl Node for StringLiteral {
+    fn token_literal(&self) -> &str {
+        &self.token.literal
+    }
+}
+
+impl Display for StringLiteral {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "\"{}\"", self.value)
+    }
+}
+
+impl Node for MemberExpression {
+    fn token_literal(&self) -> &str {
+        &self.token.literal
+    }
+}
+
+impl Display for MemberExpression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}.{}", self.object, self.property)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,396 @@
+//! Owning transformation of the AST.
+//!
+//! A `Fold` consumes a tree and rebuilds it, one node at a time. Each method
+//! takes a node by value and returns a node of the same type; the default
+//! `walk_*` functions fold the children and reassemble the node unchanged.
+//! To replace a node with a different variant, override `fold_expression` or
+//! `fold_statement`:
+//!
+//! ```
+//! use monkey_lang::ast::{Expression, Fold, fold};
+//!
+//! /// Replaces every `-<integer>` with the negated literal.
+//! struct NegateLiterals;
+//!
+//! impl Fold for NegateLiterals {
+//!     fn fold_expression(&mut self, expr: Expression) -> Expression {
+//!         match fold::walk_expression(self, expr) {
+//!             Expression::PrefixExpression(prefix) if prefix.operator == "-" => {
+//!                 match *prefix.right {
+//!                     Expression::IntegerLiteral(mut lit) => {
+//!                         lit.value = -lit.value;
+//!                         Expression::IntegerLiteral(lit)
+//!                     }
+//!                     right => Expression::PrefixExpression(
+//!                         monkey_lang::ast::PrefixExpression { right: Box::new(right), ..prefix },
+//!                     ),
+//!                 }
+//!             }
+//!             other => other,
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::ast::{
+    Program,
+    expression::{
+        BlockStatement, BooleanLiteral, CallExpression, Expression, FunctionLiteral, Identifier,
+        IfExpression, InfixExpression, IntegerLiteral, MemberExpression, PrefixExpression,
+        StringLiteral,
+    },
+    statement::{
+        ExportStatement, ExpressionStatement, ImportStatement, LetStatement, ReturnStatement,
+        Statement,
+    },
+    types::{ArrayType, FunctionType, HashType, OptionalType, TypeExpr},
+};
+
+/// An owning AST transformer. See the module documentation for usage.
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        walk_program(self, program)
+    }
+
+    // ============ STATEMENTS ============
+
+    fn fold_statement(&mut self, stmt: Statement) -> Statement {
+        walk_statement(self, stmt)
+    }
+
+    fn fold_let_statement(&mut self, stmt: LetStatement) -> LetStatement {
+        walk_let_statement(self, stmt)
+    }
+
+    fn fold_return_statement(&mut self, stmt: ReturnStatement) -> ReturnStatement {
+        walk_return_statement(self, stmt)
+    }
+
+    fn fold_expression_statement(&mut self, stmt: ExpressionStatement) -> ExpressionStatement {
+        walk_expression_statement(self, stmt)
+    }
+
+    fn fold_import_statement(&mut self, stmt: ImportStatement) -> ImportStatement {
+        walk_import_statement(self, stmt)
+    }
+
+    fn fold_export_statement(&mut self, stmt: ExportStatement) -> ExportStatement {
+        walk_export_statement(self, stmt)
+    }
+
+    fn fold_block_statement(&mut self, block: BlockStatement) -> BlockStatement {
+        walk_block_statement(self, block)
+    }
+
+    // ============ EXPRESSIONS ============
+
+    fn fold_expression(&mut self, expr: Expression) -> Expression {
+        walk_expression(self, expr)
+    }
+
+    /// Called for uses and for binding sites (let names, parameters, import aliases).
+    fn fold_identifier(&mut self, ident: Identifier) -> Identifier {
+        ident
+    }
+
+    fn fold_integer_literal(&mut self, lit: IntegerLiteral) -> IntegerLiteral {
+        lit
+    }
+
+    fn fold_boolean_literal(&mut self, lit: BooleanLiteral) -> BooleanLiteral {
+        lit
+    }
+
+    fn fold_string_literal(&mut self, lit: StringLiteral) -> StringLiteral {
+        lit
+    }
+
+    fn fold_prefix_expression(&mut self, expr: PrefixExpression) -> PrefixExpression {
+        walk_prefix_expression(self, expr)
+    }
+
+    fn fold_infix_expression(&mut self, expr: InfixExpression) -> InfixExpression {
+        walk_infix_expression(self, expr)
+    }
+
+    fn fold_if_expression(&mut self, expr: IfExpression) -> IfExpression {
+        walk_if_expression(self, expr)
+    }
+
+    fn fold_function_literal(&mut self, function: FunctionLiteral) -> FunctionLiteral {
+        walk_function_literal(self, function)
+    }
+
+    fn fold_call_expression(&mut self, call: CallExpression) -> CallExpression {
+        walk_call_expression(self, call)
+    }
+
+    fn fold_member_expression(&mut self, member: MemberExpression) -> MemberExpression {
+        walk_member_expression(self, member)
+    }
+
+    // ============ TYPES ============
+
+    fn fold_type_expr(&mut self, ty: TypeExpr) -> TypeExpr {
+        walk_type_expr(self, ty)
+    }
+}
+
+fn fold_boxed<F: Fold + ?Sized>(f: &mut F, expr: Box<Expression>) -> Box<Expression> {
+    Box::new(f.fold_expression(*expr))
+}
+
+pub fn walk_program<F: Fold + ?Sized>(f: &mut F, program: Program) -> Program {
+    Program {
+        statements: program
+            .statements
+            .into_iter()
+            .map(|stmt| f.fold_statement(stmt))
+            .collect(),
+    }
+}
+
+pub fn walk_statement<F: Fold + ?Sized>(f: &mut F, stmt: Statement) -> Statement {
+    match stmt {
+        Statement::Let(s) => Statement::Let(f.fold_let_statement(s)),
+        Statement::Return(s) => Statement::Return(f.fold_return_statement(s)),
+        Statement::Expression(s) => Statement::Expression(f.fold_expression_statement(s)),
+        Statement::Import(s) => Statement::Import(f.fold_import_statement(s)),
+        Statement::Export(s) => Statement::Export(f.fold_export_statement(s)),
+    }
+}
+
+pub fn walk_let_statement<F: Fold + ?Sized>(f: &mut F, stmt: LetStatement) -> LetStatement {
+    LetStatement {
+        token: stmt.token,
+        name: f.fold_identifier(stmt.name),
+        type_annotation: stmt.type_annotation.map(|ty| f.fold_type_expr(ty)),
+        value: stmt.value.map(|value| f.fold_expression(value)),
+    }
+}
+
+pub fn walk_return_statement<F: Fold + ?Sized>(
+    f: &mut F,
+    stmt: ReturnStatement,
+) -> ReturnStatement {
+    ReturnStatement {
+        token: stmt.token,
+        value: stmt.value.map(|value| f.fold_expression(value)),
+    }
+}
+
+pub fn walk_expression_statement<F: Fold + ?Sized>(
+    f: &mut F,
+    stmt: ExpressionStatement,
+) -> ExpressionStatement {
+    ExpressionStatement {
+        token: stmt.token,
+        value: f.fold_expression(stmt.value),
+    }
+}
+
+pub fn walk_import_statement<F: Fold + ?Sized>(
+    f: &mut F,
+    stmt: ImportStatement,
+) -> ImportStatement {
+    ImportStatement {
+        token: stmt.token,
+        path: f.fold_string_literal(stmt.path),
+        alias: f.fold_identifier(stmt.alias),
+    }
+}
+
+pub fn walk_export_statement<F: Fold + ?Sized>(
+    f: &mut F,
+    stmt: ExportStatement,
+) -> ExportStatement {
+    ExportStatement {
+        token: stmt.token,
+        statement: f.fold_let_statement(stmt.statement),
+    }
+}
+
+pub fn walk_block_statement<F: Fold + ?Sized>(f: &mut F, block: BlockStatement) -> BlockStatement {
+    BlockStatement {
+        token: block.token,
+        statements: block
+            .statements
+            .into_iter()
+            .map(|stmt| f.fold_statement(stmt))
+            .collect(),
+    }
+}
+
+pub fn walk_expression<F: Fold + ?Sized>(f: &mut F, expr: Expression) -> Expression {
+    match expr {
+        Expression::Identifier(e) => Expression::Identifier(f.fold_identifier(e)),
+        Expression::IntegerLiteral(e) => Expression::IntegerLiteral(f.fold_integer_literal(e)),
+        Expression::BooleanLiteral(e) => Expression::BooleanLiteral(f.fold_boolean_literal(e)),
+        Expression::StringLiteral(e) => Expression::StringLiteral(f.fold_string_literal(e)),
+        Expression::PrefixExpression(e) => {
+            Expression::PrefixExpression(f.fold_prefix_expression(e))
+        }
+        Expression::InfixExpression(e) => Expression::InfixExpression(f.fold_infix_expression(e)),
+        Expression::IfExpression(e) => Expression::IfExpression(f.fold_if_expression(e)),
+        Expression::BlockStatement(e) => Expression::BlockStatement(f.fold_block_statement(e)),
+        Expression::FunctionLiteral(e) => Expression::FunctionLiteral(f.fold_function_literal(e)),
+        Expression::CallExpression(e) => Expression::CallExpression(f.fold_call_expression(e)),
+        Expression::MemberExpression(e) => {
+            Expression::MemberExpression(f.fold_member_expression(e))
+        }
+    }
+}
+
+pub fn walk_prefix_expression<F: Fold + ?Sized>(
+    f: &mut F,
+    expr: PrefixExpression,
+) -> PrefixExpression {
+    PrefixExpression {
+        token: expr.token,
+        operator: expr.operator,
+        right: fold_boxed(f, expr.right),
+    }
+}
+
+pub fn walk_infix_expression<F: Fold + ?Sized>(
+    f: &mut F,
+    expr: InfixExpression,
+) -> InfixExpression {
+    InfixExpression {
+        token: expr.token,
+        left: fold_boxed(f, expr.left),
+        operator: expr.operator,
+        right: fold_boxed(f, expr.right),
+    }
+}
+
+pub fn walk_if_expression<F: Fold + ?Sized>(f: &mut F, expr: IfExpression) -> IfExpression {
+    IfExpression {
+        token: expr.token,
+        condition: fold_boxed(f, expr.condition),
+        consequence: fold_boxed(f, expr.consequence),
+        alternative: expr.alternative.map(|alt| fold_boxed(f, alt)),
+    }
+}
+
+pub fn walk_function_literal<F: Fold + ?Sized>(
+    f: &mut F,
+    function: FunctionLiteral,
+) -> FunctionLiteral {
+    FunctionLiteral {
+        token: function.token,
+        parameters: function
+            .parameters
+            .into_iter()
+            .map(|param| f.fold_identifier(param))
+            .collect(),
+        parameter_types: function
+            .parameter_types
+            .into_iter()
+            .map(|ty| ty.map(|ty| f.fold_type_expr(ty)))
+            .collect(),
+        return_type: function.return_type.map(|ty| f.fold_type_expr(ty)),
+        body: f.fold_block_statement(function.body),
+    }
+}
+
+pub fn walk_call_expression<F: Fold + ?Sized>(f: &mut F, call: CallExpression) -> CallExpression {
+    CallExpression {
+        token: call.token,
+        function: fold_boxed(f, call.function),
+        arguments: call
+            .arguments
+            .into_iter()
+            .map(|arg| f.fold_expression(arg))
+            .collect(),
+    }
+}
+
+/// Folds the object only, like `visit::walk_member_expression`.
+pub fn walk_member_expression<F: Fold + ?Sized>(
+    f: &mut F,
+    member: MemberExpression,
+) -> MemberExpression {
+    MemberExpression {
+        token: member.token,
+        object: fold_boxed(f, member.object),
+        property: member.property,
+    }
+}
+
+pub fn walk_type_expr<F: Fold + ?Sized>(f: &mut F, ty: TypeExpr) -> TypeExpr {
+    match ty {
+        TypeExpr::Named(t) => TypeExpr::Named(t),
+        TypeExpr::Array(t) => TypeExpr::Array(ArrayType {
+            token: t.token,
+            element: Box::new(f.fold_type_expr(*t.element)),
+        }),
+        TypeExpr::Hash(t) => TypeExpr::Hash(HashType {
+            token: t.token,
+            key: Box::new(f.fold_type_expr(*t.key)),
+            value: Box::new(f.fold_type_expr(*t.value)),
+        }),
+        TypeExpr::Function(t) => TypeExpr::Function(FunctionType {
+            token: t.token,
+            parameters: t
+                .parameters
+                .into_iter()
+                .map(|param| f.fold_type_expr(param))
+                .collect(),
+            return_type: Box::new(f.fold_type_expr(*t.return_type)),
+        }),
+        TypeExpr::Optional(t) => TypeExpr::Optional(OptionalType {
+            token: t.token,
+            inner: Box::new(f.fold_type_expr(*t.inner)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::lexer::token::{Token, TokenType};
+    use crate::parser::Parser;
+    use crate::source::FileId;
+
+    /// Folds integer arithmetic on literals into a single literal.
+    struct ConstantFolder;
+
+    impl Fold for ConstantFolder {
+        fn fold_expression(&mut self, expr: Expression) -> Expression {
+            let infix = match walk_expression(self, expr) {
+                Expression::InfixExpression(infix) => infix,
+                other => return other,
+            };
+            let (Expression::IntegerLiteral(l), Expression::IntegerLiteral(r)) =
+                (infix.left.as_ref(), infix.right.as_ref())
+            else {
+                return Expression::InfixExpression(infix);
+            };
+            let value = match infix.operator.as_str() {
+                "+" => l.value + r.value,
+                "*" => l.value * r.value,
+                _ => return Expression::InfixExpression(infix),
+            };
+            Expression::IntegerLiteral(IntegerLiteral {
+                token: Token::new(TokenType::INT, value.to_string(), 0, 0),
+                value,
+            })
+        }
+    }
+
+    #[test]
+    fn test_fold_rebuilds_nested_expressions() {
+        let input = "let f = fn(a) { if (a) { 1 + 2 * 3; } else { a + 4; }; }; f(2 * 5);";
+        let mut parser = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty());
+
+        let folded = ConstantFolder.fold_program(program);
+        assert_eq!(
+            folded.to_string(),
+            "let f = fn(a) {ifa{7}else {(a + 4)}};f(10)"
+        );
+    }
+}
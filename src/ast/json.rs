@@ -0,0 +1,153 @@
+//! JSON serialization of the AST.
+//!
+//! A serialized program is wrapped in a versioned envelope:
+//!
+//! ```json
+//! { "version": 1, "program": { "statements": [ ... ] } }
+//! ```
+//!
+//! Every node keeps its `token`, including its line and column, and enum
+//! nodes (`Statement`, `Expression`, `TypeExpr`) carry a `"kind"` tag naming
+//! the variant. The full schema is documented in `md/ast-json.md`; bump
+//! `SCHEMA_VERSION` whenever it changes in a way old readers cannot handle.
+
+use crate::ast::Program;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Version of the JSON schema written by `to_json` and accepted by `from_json`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct DocumentRef<'a> {
+    version: u32,
+    program: &'a Program,
+}
+
+#[derive(Deserialize)]
+struct Document {
+    program: Program,
+}
+
+/// Only the version, read first so unsupported documents fail clearly.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// An error reading a JSON AST document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+    /// The text is not valid JSON or does not match the schema.
+    Invalid(String),
+    /// The document was written with a schema version this reader does not know.
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Invalid(message) => write!(f, "invalid AST JSON: {}", message),
+            JsonError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported AST JSON version {} (expected {})",
+                version, SCHEMA_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(error: serde_json::Error) -> Self {
+        JsonError::Invalid(error.to_string())
+    }
+}
+
+/// Serializes a program to compact JSON.
+pub fn to_json(program: &Program) -> String {
+    serde_json::to_string(&envelope(program)).expect("the AST always serializes")
+}
+
+/// Serializes a program to indented JSON, for reading and golden files.
+pub fn to_json_pretty(program: &Program) -> String {
+    serde_json::to_string_pretty(&envelope(program)).expect("the AST always serializes")
+}
+
+/// Deserializes a program written by `to_json` or `to_json_pretty`.
+///
+/// # Errors
+/// Returns `JsonError::UnsupportedVersion` if the document's version is not
+/// `SCHEMA_VERSION`, and `JsonError::Invalid` if it does not match the schema.
+pub fn from_json(text: &str) -> Result<Program, JsonError> {
+    let header: Header = serde_json::from_str(text)?;
+    if header.version != SCHEMA_VERSION {
+        return Err(JsonError::UnsupportedVersion(header.version));
+    }
+    let document: Document = serde_json::from_str(text)?;
+    Ok(document.program)
+}
+
+fn envelope(program: &Program) -> DocumentRef<'_> {
+    DocumentRef {
+        version: SCHEMA_VERSION,
+        program,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::FileId;
+
+    fn parse(input: &str) -> Program {
+        let mut parser = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        program
+    }
+
+    #[test]
+    fn test_round_trip_reproduces_display() {
+        let inputs = [
+            "let x = 5; return x;",
+            "let add: fn(int, int) -> int = fn(a: int, b) -> int { a + b * -c; };",
+            "if (a < b) { add(1, 2); } else { !true; };",
+            r#"import "lib/math" as math; export let y = math.pi;"#,
+            "let names: [string]? = \"monkey\";",
+            "let h: {string: (fn() -> bool)?} = 1;",
+        ];
+        for input in inputs {
+            let program = parse(input);
+            for json in [to_json(&program), to_json_pretty(&program)] {
+                let decoded = from_json(&json).unwrap();
+                assert_eq!(decoded.to_string(), program.to_string(), "input: {}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_shape() {
+        let json = to_json(&parse("x;"));
+        assert_eq!(
+            json,
+            r#"{"version":1,"program":{"statements":[{"kind":"Expression","token":{"token_type":"IDENT","literal":"x","line":1,"column":1,"file":4294967295},"value":{"kind":"Identifier","token":{"token_type":"IDENT","literal":"x","line":1,"column":1,"file":4294967295},"value":"x"}}]}}"#
+        );
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        assert_eq!(
+            from_json(r#"{"version":2,"program":{"statements":[]}}"#).unwrap_err(),
+            JsonError::UnsupportedVersion(2)
+        );
+        assert!(matches!(
+            from_json(r#"{"version":1,"program":{"statements":[{"kind":"Loop"}]}}"#),
+            Err(JsonError::Invalid(_))
+        ));
+        assert!(matches!(from_json("not json"), Err(JsonError::Invalid(_))));
+    }
+}
@@ -3,15 +3,30 @@
 //! This module defines the building blocks of the AST and their common
 //! behavior.
 
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter, Result};
 
+pub mod dot;
+pub mod dump;
 pub mod expression;
+pub mod fold;
+pub mod json;
 pub mod statement;
+pub mod types;
+pub mod visit;
+pub mod visit_mut;
 
 pub use expression::{
-    BooleanLiteral, Expression, Identifier, InfixExpression, IntegerLiteral, PrefixExpression,
+    BooleanLiteral, Expression, Identifier, InfixExpression, IntegerLiteral, MemberExpression,
+    PrefixExpression, StringLiteral,
 };
-pub use statement::{ExpressionStatement, LetStatement, ReturnStatement, Statement};
+pub use fold::Fold;
+pub use statement::{
+    ExportStatement, ExpressionStatement, ImportStatement, LetStatement, ReturnStatement, Statement,
+};
+pub use types::TypeExpr;
+pub use visit::Visitor;
+pub use visit_mut::VisitorMut;
 
 pub trait Node {
     /// Returns the literal string representation of the token that
@@ -20,6 +35,7 @@ pub trait Node {
 }
 
 /// The root node of the AST, containing all top-level statements.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
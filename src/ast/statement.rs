@@ -5,43 +5,80 @@
 
 use crate::ast::{
     Node,
-    expression::{Expression, Identifier},
+    expression::{Expression, Identifier, StringLiteral},
+    types::TypeExpr,
 };
 use crate::lexer::token::Token;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter, Result};
 
 // ============ STRUCTS ============
 
 /// Represents a `let` statement in the Monkey language AST.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LetStatement {
     pub token: Token,
     pub name: Identifier,
+    /// Optional annotation written after the name (e.g., `let x: int = 5;`).
+    pub type_annotation: Option<TypeExpr>,
     pub value: Option<Expression>,
 }
 
 /// Represents a `return` statement in the Monkey language AST.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReturnStatement {
     pub token: Token,
     pub value: Option<Expression>,
 }
 
 /// Represents an expression statement (standalone expression).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExpressionStatement {
     pub token: Token,
     pub value: Expression,
 }
 
+/// Represents an `import` statement in the Monkey language AST.
+/// import statement: import "<path>" as <alias>;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportStatement {
+    pub token: Token,
+    pub path: StringLiteral,
+    pub alias: Identifier,
+}
+
+/// Represents an `export` statement in the Monkey language AST.
+/// export statement: export let <identifier> = <expression>;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportStatement {
+    pub token: Token,
+    pub statement: LetStatement,
+}
+
 // ============ ENUM ============
 
 /// Enum representing all statement types in the AST.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum Statement {
     Let(LetStatement),
     Return(ReturnStatement),
     Expression(ExpressionStatement),
+    Import(ImportStatement),
+    Export(ExportStatement),
+}
+
+impl Statement {
+    /// The first token of the statement.
+    pub fn token(&self) -> &Token {
+        match self {
+            Statement::Let(s) => &s.token,
+            Statement::Return(s) => &s.token,
+            Statement::Expression(s) => s.value.first_token(),
+            Statement::Import(s) => &s.token,
+            Statement::Export(s) => &s.token,
+        }
+    }
 }
 
 // ============ TRAIT IMPLEMENTATIONS ============
@@ -56,6 +93,9 @@ impl Display for LetStatement {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(f, "{} ", self.token_literal())?;
         write!(f, "{}", self.name)?;
+        if let Some(annotation) = &self.type_annotation {
+            write!(f, ": {}", annotation)?;
+        }
         write!(f, " = ")?;
         if let Some(value) = &self.value {
             write!(f, "{}", value)?;
@@ -92,12 +132,44 @@ impl Display for ExpressionStatement {
     }
 }
 
+impl Node for ImportStatement {
+    fn token_literal(&self) -> &str {
+        &self.token.literal
+    }
+}
+
+impl Display for ImportStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{} {} as {};",
+            self.token_literal(),
+            self.path,
+            self.alias
+        )
+    }
+}
+
+impl Node for ExportStatement {
+    fn token_literal(&self) -> &str {
+        &self.token.literal
+    }
+}
+
+impl Display for ExportStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} {}", self.token_literal(), self.statement)
+    }
+}
+
 impl Node for Statement {
     fn token_literal(&self) -> &str {
         match self {
             Statement::Let(stmt) => stmt.token_literal(),
             Statement::Return(stmt) => stmt.token_literal(),
             Statement::Expression(stmt) => stmt.token_literal(),
+            Statement::Import(stmt) => stmt.token_literal(),
+            Statement::Export(stmt) => stmt.token_literal(),
         }
     }
 }
@@ -108,6 +180,8 @@ impl Display for Statement {
             Statement::Let(stmt) => write!(f, "{}", stmt),
             Statement::Return(stmt) => write!(f, "{}", stmt),
             Statement::Expression(stmt) => write!(f, "{}", stmt),
+            Statement::Import(stmt) => write!(f, "{}", stmt),
+            Statement::Export(stmt) => write!(f, "{}", stmt),
         }
     }
 }
@@ -133,6 +207,7 @@ mod tests {
         let stmt = LetStatement {
             token,
             name,
+            type_annotation: None,
             value: Some(value),
         };
 
@@ -149,12 +224,38 @@ mod tests {
         let stmt = LetStatement {
             token,
             name,
+            type_annotation: None,
             value: None,
         };
 
         assert_eq!(format!("{}", stmt), "let y = ;");
     }
 
+    #[test]
+    fn test_let_statement_display_with_type_annotation() {
+        let token = Token::new(TokenType::LET, "let".to_string(), 1, 1);
+        let name = Identifier {
+            token: Token::new(TokenType::IDENT, "x".to_string(), 1, 5),
+            value: "x".to_string(),
+        };
+        let annotation = TypeExpr::Named(crate::ast::types::NamedType {
+            token: Token::new(TokenType::IDENT, "int".to_string(), 1, 8),
+            name: "int".to_string(),
+        });
+        let value = Expression::IntegerLiteral(IntegerLiteral {
+            token: Token::new(TokenType::INT, "5".to_string(), 1, 14),
+            value: 5,
+        });
+        let stmt = LetStatement {
+            token,
+            name,
+            type_annotation: Some(annotation),
+            value: Some(value),
+        };
+
+        assert_eq!(format!("{}", stmt), "let x: int = 5;");
+    }
+
     #[test]
     fn test_return_statement_display_with_value() {
         let token = Token::new(TokenType::RETURN, "return".to_string(), 1, 1);
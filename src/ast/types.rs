@@ -0,0 +1,146 @@
+//! Type annotation syntax in the Monkey language AST.
+//!
+//! Annotations are optional and purely syntactic: `let x: int = 5;`,
+//! `fn(a: int, b: [string]) -> bool { ... }`. This module only describes how
+//! they are written; checking them is left to later passes.
+
+use crate::ast::Node;
+use crate::lexer::token::Token;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Result};
+
+// ============ ENUM ============
+
+/// Enum representing all type expressions in the AST.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum TypeExpr {
+    /// A named type (e.g., `int`, `bool`, `string`)
+    Named(NamedType),
+    /// An array type (e.g., `[int]`)
+    Array(ArrayType),
+    /// A hash type (e.g., `{string: int}`)
+    Hash(HashType),
+    /// A function type (e.g., `fn(int, int) -> bool`)
+    Function(FunctionType),
+    /// An optional type (e.g., `int?`)
+    Optional(OptionalType),
+}
+
+// ============ STRUCTS ============
+
+/// Represents a named type such as `int`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamedType {
+    pub token: Token,
+    pub name: String,
+}
+
+/// Represents an array type.
+/// array type: [<element>]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrayType {
+    pub token: Token,
+    pub element: Box<TypeExpr>,
+}
+
+/// Represents a hash type.
+/// hash type: {<key>: <value>}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashType {
+    pub token: Token,
+    pub key: Box<TypeExpr>,
+    pub value: Box<TypeExpr>,
+}
+
+/// Represents a function type.
+/// function type: fn(<parameters>) -> <return_type>
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionType {
+    pub token: Token,
+    pub parameters: Vec<TypeExpr>,
+    pub return_type: Box<TypeExpr>,
+}
+
+/// Represents an optional type. The token is the trailing `?`.
+/// optional type: <inner>?
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionalType {
+    pub token: Token,
+    pub inner: Box<TypeExpr>,
+}
+
+// ============ TRAIT IMPLEMENTATIONS ============
+
+impl Node for TypeExpr {
+    fn token_literal(&self) -> &str {
+        match self {
+            TypeExpr::Named(t) => &t.token.literal,
+            TypeExpr::Array(t) => &t.token.literal,
+            TypeExpr::Hash(t) => &t.token.literal,
+            TypeExpr::Function(t) => &t.token.literal,
+            TypeExpr::Optional(t) => &t.token.literal,
+        }
+    }
+}
+
+impl Display for TypeExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            TypeExpr::Named(t) => write!(f, "{}", t.name),
+            TypeExpr::Array(t) => write!(f, "[{}]", t.element),
+            TypeExpr::Hash(t) => write!(f, "{{{}: {}}}", t.key, t.value),
+            TypeExpr::Function(t) => {
+                let params: Vec<String> = t.parameters.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) -> {}", params.join(", "), t.return_type)
+            }
+            TypeExpr::Optional(t) => match t.inner.as_ref() {
+                // `fn() -> int?` would read as a function returning `int?`
+                TypeExpr::Function(_) => write!(f, "({})?", t.inner),
+                inner => write!(f, "{}?", inner),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::token::TokenType;
+
+    fn named(name: &str) -> TypeExpr {
+        TypeExpr::Named(NamedType {
+            token: Token::new(TokenType::IDENT, name.to_string(), 1, 1),
+            name: name.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_type_expr_display() {
+        let function = TypeExpr::Function(FunctionType {
+            token: Token::new(TokenType::FUNCTION, "fn".to_string(), 1, 1),
+            parameters: vec![
+                named("int"),
+                TypeExpr::Array(ArrayType {
+                    token: Token::new(TokenType::LBRACKET, "[".to_string(), 1, 1),
+                    element: Box::new(named("string")),
+                }),
+            ],
+            return_type: Box::new(TypeExpr::Hash(HashType {
+                token: Token::new(TokenType::LBRACE, "{".to_string(), 1, 1),
+                key: Box::new(named("string")),
+                value: Box::new(named("bool")),
+            })),
+        });
+        assert_eq!(function.to_string(), "fn(int, [string]) -> {string: bool}");
+
+        let optional = TypeExpr::Optional(OptionalType {
+            token: Token::new(TokenType::QUESTION, "?".to_string(), 1, 1),
+            inner: Box::new(function),
+        });
+        assert_eq!(
+            optional.to_string(),
+            "(fn(int, [string]) -> {string: bool})?"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Read-only traversal of the AST.
+//!
+//! Implement `Visitor` and override the methods for the nodes you care about.
+//! Every method defaults to the matching `walk_*` function, which visits the
+//! node's children, so an override that still wants to reach nested nodes
+//! calls the walk function itself:
+//!
+//! ```
+//! use monkey_lang::ast::{Identifier, Program, Visitor};
+//!
+//! struct CountIdentifiers(usize);
+//!
+//! impl<'ast> Visitor<'ast> for CountIdentifiers {
+//!     fn visit_identifier(&mut self, _ident: &'ast Identifier) {
+//!         self.0 += 1;
+//!     }
+//! }
+//!
+//! # let program = Program { statements: vec![] };
+//! let mut counter = CountIdentifiers(0);
+//! counter.visit_program(&program);
+//! ```
+//!
+//! The `'ast` lifetime lets visitors keep references to the nodes they see.
+
+use crate::ast::{
+    Program,
+    expression::{
+        BlockStatement, BooleanLiteral, CallExpression, Expression, FunctionLiteral, Identifier,
+        IfExpression, InfixExpression, IntegerLiteral, MemberExpression, PrefixExpression,
+        StringLiteral,
+    },
+    statement::{
+        ExportStatement, ExpressionStatement, ImportStatement, LetStatement, ReturnStatement,
+        Statement,
+    },
+    types::TypeExpr,
+};
+
+/// A read-only AST visitor. See the module documentation for usage.
+pub trait Visitor<'ast> {
+    fn visit_program(&mut self, program: &'ast Program) {
+        walk_program(self, program);
+    }
+
+    // ============ STATEMENTS ============
+
+    fn visit_statement(&mut self, stmt: &'ast Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_let_statement(&mut self, stmt: &'ast LetStatement) {
+        walk_let_statement(self, stmt);
+    }
+
+    fn visit_return_statement(&mut self, stmt: &'ast ReturnStatement) {
+        walk_return_statement(self, stmt);
+    }
+
+    fn visit_expression_statement(&mut self, stmt: &'ast ExpressionStatement) {
+        walk_expression_statement(self, stmt);
+    }
+
+    fn visit_import_statement(&mut self, stmt: &'ast ImportStatement) {
+        walk_import_statement(self, stmt);
+    }
+
+    fn visit_export_statement(&mut self, stmt: &'ast ExportStatement) {
+        walk_export_statement(self, stmt);
+    }
+
+    fn visit_block_statement(&mut self, block: &'ast BlockStatement) {
+        walk_block_statement(self, block);
+    }
+
+    // ============ EXPRESSIONS ============
+
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        walk_expression(self, expr);
+    }
+
+    /// Called for uses and for binding sites (let names, parameters, import aliases).
+    fn visit_identifier(&mut self, _ident: &'ast Identifier) {}
+
+    fn visit_integer_literal(&mut self, _lit: &'ast IntegerLiteral) {}
+
+    fn visit_boolean_literal(&mut self, _lit: &'ast BooleanLiteral) {}
+
+    fn visit_string_literal(&mut self, _lit: &'ast StringLiteral) {}
+
+    fn visit_prefix_expression(&mut self, expr: &'ast PrefixExpression) {
+        walk_prefix_expression(self, expr);
+    }
+
+    fn visit_infix_expression(&mut self, expr: &'ast InfixExpression) {
+        walk_infix_expression(self, expr);
+    }
+
+    fn visit_if_expression(&mut self, expr: &'ast IfExpression) {
+        walk_if_expression(self, expr);
+    }
+
+    fn visit_function_literal(&mut self, function: &'ast FunctionLiteral) {
+        walk_function_literal(self, function);
+    }
+
+    fn visit_call_expression(&mut self, call: &'ast CallExpression) {
+        walk_call_expression(self, call);
+    }
+
+    fn visit_member_expression(&mut self, member: &'ast MemberExpression) {
+        walk_member_expression(self, member);
+    }
+
+    // ============ TYPES ============
+
+    fn visit_type_expr(&mut self, ty: &'ast TypeExpr) {
+        walk_type_expr(self, ty);
+    }
+}
+
+pub fn walk_program<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, program: &'ast Program) {
+    for stmt in &program.statements {
+        v.visit_statement(stmt);
+    }
+}
+
+pub fn walk_statement<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, stmt: &'ast Statement) {
+    match stmt {
+        Statement::Let(s) => v.visit_let_statement(s),
+        Statement::Return(s) => v.visit_return_statement(s),
+        Statement::Expression(s) => v.visit_expression_statement(s),
+        Statement::Import(s) => v.visit_import_statement(s),
+        Statement::Export(s) => v.visit_export_statement(s),
+    }
+}
+
+pub fn walk_let_statement<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, stmt: &'ast LetStatement) {
+    v.visit_identifier(&stmt.name);
+    if let Some(ty) = &stmt.type_annotation {
+        v.visit_type_expr(ty);
+    }
+    if let Some(value) = &stmt.value {
+        v.visit_expression(value);
+    }
+}
+
+pub fn walk_return_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    stmt: &'ast ReturnStatement,
+) {
+    if let Some(value) = &stmt.value {
+        v.visit_expression(value);
+    }
+}
+
+pub fn walk_expression_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    stmt: &'ast ExpressionStatement,
+) {
+    v.visit_expression(&stmt.value);
+}
+
+pub fn walk_import_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    stmt: &'ast ImportStatement,
+) {
+    v.visit_string_literal(&stmt.path);
+    v.visit_identifier(&stmt.alias);
+}
+
+pub fn walk_export_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    stmt: &'ast ExportStatement,
+) {
+    v.visit_let_statement(&stmt.statement);
+}
+
+pub fn walk_block_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    block: &'ast BlockStatement,
+) {
+    for stmt in &block.statements {
+        v.visit_statement(stmt);
+    }
+}
+
+pub fn walk_expression<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, expr: &'ast Expression) {
+    match expr {
+        Expression::Identifier(e) => v.visit_identifier(e),
+        Expression::IntegerLiteral(e) => v.visit_integer_literal(e),
+        Expression::BooleanLiteral(e) => v.visit_boolean_literal(e),
+        Expression::StringLiteral(e) => v.visit_string_literal(e),
+        Expression::PrefixExpression(e) => v.visit_prefix_expression(e),
+        Expression::InfixExpression(e) => v.visit_infix_expression(e),
+        Expression::IfExpression(e) => v.visit_if_expression(e),
+        Expression::BlockStatement(e) => v.visit_block_statement(e),
+        Expression::FunctionLiteral(e) => v.visit_function_literal(e),
+        Expression::CallExpression(e) => v.visit_call_expression(e),
+        Expression::MemberExpression(e) => v.visit_member_expression(e),
+    }
+}
+
+pub fn walk_prefix_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    expr: &'ast PrefixExpression,
+) {
+    v.visit_expression(&expr.right);
+}
+
+pub fn walk_infix_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    expr: &'ast InfixExpression,
+) {
+    v.visit_expression(&expr.left);
+    v.visit_expression(&expr.right);
+}
+
+pub fn walk_if_expression<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, expr: &'ast IfExpression) {
+    v.visit_expression(&expr.condition);
+    v.visit_expression(&expr.consequence);
+    if let Some(alternative) = &expr.alternative {
+        v.visit_expression(alternative);
+    }
+}
+
+pub fn walk_function_literal<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    function: &'ast FunctionLiteral,
+) {
+    for (i, param) in function.parameters.iter().enumerate() {
+        v.visit_identifier(param);
+        if let Some(Some(ty)) = function.parameter_types.get(i) {
+            v.visit_type_expr(ty);
+        }
+    }
+    if let Some(ty) = &function.return_type {
+        v.visit_type_expr(ty);
+    }
+    v.visit_block_statement(&function.body);
+}
+
+pub fn walk_call_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    call: &'ast CallExpression,
+) {
+    v.visit_expression(&call.function);
+    for arg in &call.arguments {
+        v.visit_expression(arg);
+    }
+}
+
+/// Visits the object only: the property names a member of another module,
+/// not an identifier in scope, so it is not passed to `visit_identifier`.
+pub fn walk_member_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    member: &'ast MemberExpression,
+) {
+    v.visit_expression(&member.object);
+}
+
+pub fn walk_type_expr<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, ty: &'ast TypeExpr) {
+    match ty {
+        TypeExpr::Named(_) => {}
+        TypeExpr::Array(t) => v.visit_type_expr(&t.element),
+        TypeExpr::Hash(t) => {
+            v.visit_type_expr(&t.key);
+            v.visit_type_expr(&t.value);
+        }
+        TypeExpr::Function(t) => {
+            for param in &t.parameters {
+                v.visit_type_expr(param);
+            }
+            v.visit_type_expr(&t.return_type);
+        }
+        TypeExpr::Optional(t) => v.visit_type_expr(&t.inner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::FileId;
+
+    #[derive(Default)]
+    struct Collect<'ast> {
+        identifiers: Vec<&'ast str>,
+        blocks: usize,
+    }
+
+    impl<'ast> Visitor<'ast> for Collect<'ast> {
+        fn visit_identifier(&mut self, ident: &'ast Identifier) {
+            self.identifiers.push(&ident.value);
+        }
+
+        fn visit_block_statement(&mut self, block: &'ast BlockStatement) {
+            self.blocks += 1;
+            walk_block_statement(self, block);
+        }
+    }
+
+    #[test]
+    fn test_visitor_reaches_nested_blocks() {
+        let input = "let f = fn(a) { if (a) { b; } else { c; }; };\nimport \"m\" as m;\nm.d(e);";
+        let mut parser = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty());
+
+        let mut collect = Collect::default();
+        collect.visit_program(&program);
+        assert_eq!(
+            collect.identifiers,
+            vec!["f", "a", "a", "b", "c", "m", "m", "e"]
+        );
+        assert_eq!(collect.blocks, 3);
+    }
+}
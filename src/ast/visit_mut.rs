@@ -0,0 +1,273 @@
+//! In-place traversal of the AST.
+//!
+//! `VisitorMut` mirrors `Visitor` but receives mutable references, so passes
+//! can rewrite nodes where they stand (e.g., renaming identifiers). Every
+//! method defaults to the matching `walk_*` function in this module.
+
+use crate::ast::{
+    Program,
+    expression::{
+        BlockStatement, BooleanLiteral, CallExpression, Expression, FunctionLiteral, Identifier,
+        IfExpression, InfixExpression, IntegerLiteral, MemberExpression, PrefixExpression,
+        StringLiteral,
+    },
+    statement::{
+        ExportStatement, ExpressionStatement, ImportStatement, LetStatement, ReturnStatement,
+        Statement,
+    },
+    types::TypeExpr,
+};
+
+/// A mutable AST visitor. See `visit::Visitor` for usage.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    // ============ STATEMENTS ============
+
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        walk_statement_mut(self, stmt);
+    }
+
+    fn visit_let_statement_mut(&mut self, stmt: &mut LetStatement) {
+        walk_let_statement_mut(self, stmt);
+    }
+
+    fn visit_return_statement_mut(&mut self, stmt: &mut ReturnStatement) {
+        walk_return_statement_mut(self, stmt);
+    }
+
+    fn visit_expression_statement_mut(&mut self, stmt: &mut ExpressionStatement) {
+        walk_expression_statement_mut(self, stmt);
+    }
+
+    fn visit_import_statement_mut(&mut self, stmt: &mut ImportStatement) {
+        walk_import_statement_mut(self, stmt);
+    }
+
+    fn visit_export_statement_mut(&mut self, stmt: &mut ExportStatement) {
+        walk_export_statement_mut(self, stmt);
+    }
+
+    fn visit_block_statement_mut(&mut self, block: &mut BlockStatement) {
+        walk_block_statement_mut(self, block);
+    }
+
+    // ============ EXPRESSIONS ============
+
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr);
+    }
+
+    /// Called for uses and for binding sites (let names, parameters, import aliases).
+    fn visit_identifier_mut(&mut self, _ident: &mut Identifier) {}
+
+    fn visit_integer_literal_mut(&mut self, _lit: &mut IntegerLiteral) {}
+
+    fn visit_boolean_literal_mut(&mut self, _lit: &mut BooleanLiteral) {}
+
+    fn visit_string_literal_mut(&mut self, _lit: &mut StringLiteral) {}
+
+    fn visit_prefix_expression_mut(&mut self, expr: &mut PrefixExpression) {
+        walk_prefix_expression_mut(self, expr);
+    }
+
+    fn visit_infix_expression_mut(&mut self, expr: &mut InfixExpression) {
+        walk_infix_expression_mut(self, expr);
+    }
+
+    fn visit_if_expression_mut(&mut self, expr: &mut IfExpression) {
+        walk_if_expression_mut(self, expr);
+    }
+
+    fn visit_function_literal_mut(&mut self, function: &mut FunctionLiteral) {
+        walk_function_literal_mut(self, function);
+    }
+
+    fn visit_call_expression_mut(&mut self, call: &mut CallExpression) {
+        walk_call_expression_mut(self, call);
+    }
+
+    fn visit_member_expression_mut(&mut self, member: &mut MemberExpression) {
+        walk_member_expression_mut(self, member);
+    }
+
+    // ============ TYPES ============
+
+    fn visit_type_expr_mut(&mut self, ty: &mut TypeExpr) {
+        walk_type_expr_mut(self, ty);
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(v: &mut V, program: &mut Program) {
+    for stmt in &mut program.statements {
+        v.visit_statement_mut(stmt);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut Statement) {
+    match stmt {
+        Statement::Let(s) => v.visit_let_statement_mut(s),
+        Statement::Return(s) => v.visit_return_statement_mut(s),
+        Statement::Expression(s) => v.visit_expression_statement_mut(s),
+        Statement::Import(s) => v.visit_import_statement_mut(s),
+        Statement::Export(s) => v.visit_export_statement_mut(s),
+    }
+}
+
+pub fn walk_let_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut LetStatement) {
+    v.visit_identifier_mut(&mut stmt.name);
+    if let Some(ty) = &mut stmt.type_annotation {
+        v.visit_type_expr_mut(ty);
+    }
+    if let Some(value) = &mut stmt.value {
+        v.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_return_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut ReturnStatement) {
+    if let Some(value) = &mut stmt.value {
+        v.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_expression_statement_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    stmt: &mut ExpressionStatement,
+) {
+    v.visit_expression_mut(&mut stmt.value);
+}
+
+pub fn walk_import_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut ImportStatement) {
+    v.visit_string_literal_mut(&mut stmt.path);
+    v.visit_identifier_mut(&mut stmt.alias);
+}
+
+pub fn walk_export_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut ExportStatement) {
+    v.visit_let_statement_mut(&mut stmt.statement);
+}
+
+pub fn walk_block_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, block: &mut BlockStatement) {
+    for stmt in &mut block.statements {
+        v.visit_statement_mut(stmt);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut Expression) {
+    match expr {
+        Expression::Identifier(e) => v.visit_identifier_mut(e),
+        Expression::IntegerLiteral(e) => v.visit_integer_literal_mut(e),
+        Expression::BooleanLiteral(e) => v.visit_boolean_literal_mut(e),
+        Expression::StringLiteral(e) => v.visit_string_literal_mut(e),
+        Expression::PrefixExpression(e) => v.visit_prefix_expression_mut(e),
+        Expression::InfixExpression(e) => v.visit_infix_expression_mut(e),
+        Expression::IfExpression(e) => v.visit_if_expression_mut(e),
+        Expression::BlockStatement(e) => v.visit_block_statement_mut(e),
+        Expression::FunctionLiteral(e) => v.visit_function_literal_mut(e),
+        Expression::CallExpression(e) => v.visit_call_expression_mut(e),
+        Expression::MemberExpression(e) => v.visit_member_expression_mut(e),
+    }
+}
+
+pub fn walk_prefix_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut PrefixExpression) {
+    v.visit_expression_mut(&mut expr.right);
+}
+
+pub fn walk_infix_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut InfixExpression) {
+    v.visit_expression_mut(&mut expr.left);
+    v.visit_expression_mut(&mut expr.right);
+}
+
+pub fn walk_if_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut IfExpression) {
+    v.visit_expression_mut(&mut expr.condition);
+    v.visit_expression_mut(&mut expr.consequence);
+    if let Some(alternative) = &mut expr.alternative {
+        v.visit_expression_mut(alternative);
+    }
+}
+
+pub fn walk_function_literal_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    function: &mut FunctionLiteral,
+) {
+    for (i, param) in function.parameters.iter_mut().enumerate() {
+        v.visit_identifier_mut(param);
+        if let Some(Some(ty)) = function.parameter_types.get_mut(i) {
+            v.visit_type_expr_mut(ty);
+        }
+    }
+    if let Some(ty) = &mut function.return_type {
+        v.visit_type_expr_mut(ty);
+    }
+    v.visit_block_statement_mut(&mut function.body);
+}
+
+pub fn walk_call_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, call: &mut CallExpression) {
+    v.visit_expression_mut(&mut call.function);
+    for arg in &mut call.arguments {
+        v.visit_expression_mut(arg);
+    }
+}
+
+/// Visits the object only, like `visit::walk_member_expression`.
+pub fn walk_member_expression_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    member: &mut MemberExpression,
+) {
+    v.visit_expression_mut(&mut member.object);
+}
+
+pub fn walk_type_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, ty: &mut TypeExpr) {
+    match ty {
+        TypeExpr::Named(_) => {}
+        TypeExpr::Array(t) => v.visit_type_expr_mut(&mut t.element),
+        TypeExpr::Hash(t) => {
+            v.visit_type_expr_mut(&mut t.key);
+            v.visit_type_expr_mut(&mut t.value);
+        }
+        TypeExpr::Function(t) => {
+            for param in &mut t.parameters {
+                v.visit_type_expr_mut(param);
+            }
+            v.visit_type_expr_mut(&mut t.return_type);
+        }
+        TypeExpr::Optional(t) => v.visit_type_expr_mut(&mut t.inner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::FileId;
+
+    struct Rename<'a> {
+        from: &'a str,
+        to: &'a str,
+    }
+
+    impl VisitorMut for Rename<'_> {
+        fn visit_identifier_mut(&mut self, ident: &mut Identifier) {
+            if ident.value == self.from {
+                ident.value = self.to.to_string();
+                ident.token.literal = self.to.to_string();
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites_in_place() {
+        let input = "let x = 1; let f = fn(x) { if (x) { x; } else { y(x); }; }; f(x);";
+        let mut parser = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+        let mut program = parser.parse_program();
+        assert!(parser.errors.is_empty());
+
+        Rename { from: "x", to: "n" }.visit_program_mut(&mut program);
+        assert_eq!(
+            program.to_string(),
+            "let n = 1;let f = fn(n) {ifn{n}else {y(n)}};f(n)"
+        );
+    }
+}
@@ -0,0 +1,1116 @@
+//! Command-line interface for the `monkey` binary.
+//!
+//! ```text
+#![doc = include_str!("usage.txt")]
+//! ```
+//!
+//! `run` and `disasm` accept either source or an `.mbc` file. `parse --json`
+//! writes the format described in `md/ast-json.md`.
+//!
+//! `run` takes its arguments and streams explicitly so commands can be tested
+//! without spawning a process.
+
+use crate::ast::{Program, json, statement::Statement};
+use crate::compiler::{Bytecode, compile};
+use crate::coverage::Coverage;
+use crate::dap;
+use crate::diagnostic::Diagnostic;
+use crate::disasm::disassemble;
+use crate::evaluator::Evaluator;
+use crate::formatter::{FormatOptions, format_source};
+use crate::highlight;
+use crate::lexer::Lexer;
+use crate::lsp;
+use crate::mbc;
+use crate::module::ModuleLoader;
+use crate::parser::Parser;
+use crate::profiler::Profiler;
+use crate::rename::{apply_edits, rename};
+use crate::repl::repl;
+use crate::source::SourceMap;
+use crate::vm::Vm;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+/// Printed by `monkey help` and after usage errors. The module doc above
+/// includes the same file, so the two cannot drift apart; it has no final
+/// newline, since the text is printed with `writeln!`.
+const USAGE: &str = include_str!("usage.txt");
+/// Exit code for success.
+pub const EXIT_OK: i32 = 0;
+/// Exit code when the input has errors.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code for invalid command-line usage.
+pub const EXIT_USAGE: i32 = 2;
+
+/// How `monkey parse` prints the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseFormat {
+    Source,
+    Json,
+    Dot,
+}
+
+/// Runs the command described by `args` (without the program name).
+/// # Parameters
+/// - `args`: The command-line arguments after the program name
+/// - `stdin`: Input for the REPL
+/// - `stdout`: Where command output goes
+/// - `stderr`: Where errors and usage go
+/// # Returns
+/// The process exit code, or an error if writing to the streams failed.
+pub fn run<R: BufRead, W: Write, E: Write>(
+    args: &[String],
+    stdin: R,
+    mut stdout: W,
+    mut stderr: E,
+) -> io::Result<i32> {
+    match args.first().map(String::as_str) {
+        None => {
+            repl(stdin, stdout)?;
+            Ok(EXIT_OK)
+        }
+        Some("parse") => parse_command(&args[1..], &mut stdout, &mut stderr),
+        Some("fmt") => fmt_command(&args[1..], &mut stdout, &mut stderr),
+        Some("highlight") => highlight_command(&args[1..], &mut stdout, &mut stderr),
+        Some("rename") => rename_command(&args[1..], &mut stderr),
+        Some("run") => run_command(&args[1..], &mut stdout, &mut stderr),
+        Some("profile") => profile_command(&args[1..], &mut stdout, &mut stderr),
+        Some("coverage") => coverage_command(&args[1..], &mut stdout, &mut stderr),
+        Some("compile") => compile_command(&args[1..], &mut stderr),
+        Some("disasm") => disasm_command(&args[1..], &mut stdout, &mut stderr),
+        Some("lsp") => {
+            // Editors commonly pass --stdio; it is the only transport
+            if let Some(arg) = args[1..].iter().find(|arg| *arg != "--stdio") {
+                writeln!(stderr, "error: unexpected argument '{}'\n{}", arg, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            lsp::run(stdin, stdout)
+        }
+        Some("dap") => {
+            if let Some(arg) = args.get(1) {
+                writeln!(stderr, "error: unexpected argument '{}'\n{}", arg, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            dap::run(stdin, stdout)
+        }
+        Some("help" | "--help" | "-h") => {
+            writeln!(stdout, "{}", USAGE)?;
+            Ok(EXIT_OK)
+        }
+        Some(other) => {
+            writeln!(stderr, "error: unknown command '{}'\n{}", other, USAGE)?;
+            Ok(EXIT_USAGE)
+        }
+    }
+}
+
+/// `monkey parse [--json|--dot] <file>`
+fn parse_command<W: Write, E: Write>(
+    args: &[String],
+    stdout: &mut W,
+    stderr: &mut E,
+) -> io::Result<i32> {
+    let mut format = ParseFormat::Source;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => format = ParseFormat::Json,
+            "--dot" => format = ParseFormat::Dot,
+            flag if flag.starts_with('-') => {
+                writeln!(stderr, "error: unknown flag '{}'\n{}", flag, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            file if path.is_none() => path = Some(file),
+            _ => {
+                writeln!(stderr, "error: expected one file\n{}", USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+        }
+    }
+    let Some(path) = path else {
+        writeln!(stderr, "error: missing file\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    };
+
+    let mut sources = SourceMap::new();
+    let Some(program) = parse_file(path, &mut sources, stderr)? else {
+        return Ok(EXIT_FAILURE);
+    };
+    match format {
+        ParseFormat::Source => writeln!(stdout, "{}", program)?,
+        ParseFormat::Json => writeln!(stdout, "{}", json::to_json_pretty(&program))?,
+        ParseFormat::Dot => write!(stdout, "{}", program.to_dot())?,
+    }
+    Ok(EXIT_OK)
+}
+
+/// `monkey fmt [--check] [--indent N] [--width N] <file>...`
+///
+/// Without `--check`, rewrites every file that is not formatted. With it,
+/// prints those files instead and fails if there are any.
+fn fmt_command<W: Write, E: Write>(
+    args: &[String],
+    stdout: &mut W,
+    stderr: &mut E,
+) -> io::Result<i32> {
+    let mut check = false;
+    let mut options = FormatOptions::default();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            flag @ ("--indent" | "--width") => {
+                let Some(value) = args.next().and_then(|v| v.parse::<usize>().ok()) else {
+                    writeln!(stderr, "error: {} expects a number\n{}", flag, USAGE)?;
+                    return Ok(EXIT_USAGE);
+                };
+                if flag == "--indent" {
+                    options.indent_width = value;
+                } else {
+                    options.max_width = value;
+                }
+            }
+            flag if flag.starts_with('-') => {
+                writeln!(stderr, "error: unknown flag '{}'\n{}", flag, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            path => paths.push(path),
+        }
+    }
+    if paths.is_empty() {
+        writeln!(stderr, "error: missing file\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    }
+
+    let mut sources = SourceMap::new();
+    let mut code = EXIT_OK;
+    for path in paths {
+        let Some(text) = read_file(path, stderr)? else {
+            code = EXIT_FAILURE;
+            continue;
+        };
+        let file = sources.add(path, text.as_str());
+        let formatted = match format_source(&text, file, &options) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for error in &errors {
+                    writeln!(stderr, "{}", Diagnostic::from(error).render(&sources))?;
+                }
+                code = EXIT_FAILURE;
+                continue;
+            }
+        };
+        if formatted == text {
+            continue;
+        }
+        if check {
+            writeln!(stdout, "{}", path)?;
+            code = EXIT_FAILURE;
+        } else if let Err(error) = fs::write(path, formatted) {
+            writeln!(stderr, "error: cannot write {}: {}", path, error)?;
+            code = EXIT_FAILURE;
+        }
+    }
+    Ok(code)
+}
+
+/// `monkey highlight [--html] <file>`
+///
+/// Files with parser errors are still highlighted; text the lexer rejects is
+/// marked as unknown.
+fn highlight_command<W: Write, E: Write>(
+    args: &[String],
+    stdout: &mut W,
+    stderr: &mut E,
+) -> io::Result<i32> {
+    let mut html = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--html" => html = true,
+            flag if flag.starts_with('-') => {
+                writeln!(stderr, "error: unknown flag '{}'\n{}", flag, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            file if path.is_none() => path = Some(file),
+            _ => {
+                writeln!(stderr, "error: expected one file\n{}", USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+        }
+    }
+    let Some(path) = path else {
+        writeln!(stderr, "error: missing file\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    };
+
+    let Some(text) = read_file(path, stderr)? else {
+        return Ok(EXIT_FAILURE);
+    };
+    if html {
+        writeln!(stdout, "<pre>{}</pre>", highlight::to_html(&text))?;
+    } else {
+        write!(stdout, "{}", highlight::to_ansi(&text))?;
+    }
+    Ok(EXIT_OK)
+}
+
+/// `monkey rename <file> <line>:<column> <name>`
+///
+/// The position is 1-based, as in diagnostics. The file is rewritten only if
+/// the rename is safe.
+fn rename_command<E: Write>(args: &[String], stderr: &mut E) -> io::Result<i32> {
+    let [path, position, new_name] = args else {
+        writeln!(
+            stderr,
+            "error: expected a file, a position and a name\n{}",
+            USAGE
+        )?;
+        return Ok(EXIT_USAGE);
+    };
+    let position = position
+        .split_once(':')
+        .and_then(|(line, column)| {
+            Some((line.parse::<usize>().ok()?, column.parse::<usize>().ok()?))
+        })
+        .filter(|&(line, column)| line > 0 && column > 0);
+    let Some((line, column)) = position else {
+        writeln!(stderr, "error: expected a position like 3:7\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    };
+
+    let Some(text) = read_file(path, stderr)? else {
+        return Ok(EXIT_FAILURE);
+    };
+    let Some(offset) = offset_of(&text, line, column) else {
+        writeln!(
+            stderr,
+            "error: {}:{}:{} is past the end of the file",
+            path, line, column
+        )?;
+        return Ok(EXIT_FAILURE);
+    };
+    let edits = match rename(&text, offset, new_name) {
+        Ok(edits) => edits,
+        Err(error) => {
+            writeln!(stderr, "error: {}", error)?;
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    if let Err(error) = fs::write(path, apply_edits(&text, &edits)) {
+        writeln!(stderr, "error: cannot write {}: {}", path, error)?;
+        return Ok(EXIT_FAILURE);
+    }
+    Ok(EXIT_OK)
+}
+
+/// `monkey run [--eval] <file>`
+///
+/// The file may be source or an `.mbc` file; `--eval` needs source and
+/// loads the modules it imports with a `ModuleLoader`. What
+/// the program writes with `puts` is copied to `stdout` when it ends;
+/// its value is not printed. A runtime error is printed with its source line
+/// and stack trace and exits with `EXIT_FAILURE`.
+fn run_command<W: Write, E: Write>(
+    args: &[String],
+    stdout: &mut W,
+    stderr: &mut E,
+) -> io::Result<i32> {
+    let mut eval = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--eval" => eval = true,
+            flag if flag.starts_with('-') => {
+                writeln!(stderr, "error: unknown flag '{}'\n{}", flag, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            file if path.is_none() => path = Some(file),
+            _ => {
+                writeln!(stderr, "error: expected one file\n{}", USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+        }
+    }
+    let Some(path) = path else {
+        writeln!(stderr, "error: missing file\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    };
+
+    let output = SharedOutput::default();
+    let mut sources = SourceMap::new();
+    let result = if eval {
+        // Reports compiled bytecode before the loader tries to parse it
+        if read_file(path, stderr)?.is_none() {
+            return Ok(EXIT_FAILURE);
+        }
+        let mut loader = ModuleLoader::from_file_system();
+        let graph = loader.load(path);
+        sources = loader.into_sources();
+        let graph = match graph {
+            Ok(graph) => graph,
+            Err(error) => {
+                match error.to_diagnostics() {
+                    Some(diagnostics) => {
+                        for diagnostic in diagnostics {
+                            writeln!(stderr, "{}", diagnostic.render(&sources))?;
+                        }
+                    }
+                    None => writeln!(stderr, "error: {}", error)?,
+                }
+                return Ok(EXIT_FAILURE);
+            }
+        };
+        Evaluator::with_output(output.clone()).eval_modules(&graph)
+    } else {
+        let Some(bytecode) = load_bytecode(path, &mut sources, stderr)? else {
+            return Ok(EXIT_FAILURE);
+        };
+        Vm::with_output(output.clone()).run(&bytecode)
+    };
+    stdout.write_all(&output.0.borrow())?;
+    match result {
+        Ok(_) => Ok(EXIT_OK),
+        Err(error) => {
+            writeln!(stderr, "{}", error.render(&sources))?;
+            Ok(EXIT_FAILURE)
+        }
+    }
+}
+
+/// `monkey profile [--folded <out>] <file>`
+fn profile_command<W: Write, E: Write>(
+    args: &[String],
+    stdout: &mut W,
+    stderr: &mut E,
+) -> io::Result<i32> {
+    let mut folded = None;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--folded" => {
+                let Some(value) = args.next() else {
+                    writeln!(stderr, "error: --folded expects a path\n{}", USAGE)?;
+                    return Ok(EXIT_USAGE);
+                };
+                folded = Some(value);
+            }
+            flag if flag.starts_with('-') => {
+                writeln!(stderr, "error: unknown flag '{}'\n{}", flag, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            file if path.is_none() => path = Some(file),
+            _ => {
+                writeln!(stderr, "error: expected one file\n{}", USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+        }
+    }
+    let Some(path) = path else {
+        writeln!(stderr, "error: missing file\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    };
+
+    let mut sources = SourceMap::new();
+    let Some(program) = parse_file(path, &mut sources, stderr)? else {
+        return Ok(EXIT_FAILURE);
+    };
+    let output = SharedOutput::default();
+    let mut profiler = Profiler::new();
+    let result = Evaluator::with_output(output.clone()).eval_program_with(&program, &mut profiler);
+    let profile = profiler.finish();
+    stdout.write_all(&output.0.borrow())?;
+    // The profile is still worth having when the program failed
+    let code = match result {
+        Ok(_) => EXIT_OK,
+        Err(error) => {
+            writeln!(stderr, "{}", error.render(&sources))?;
+            EXIT_FAILURE
+        }
+    };
+    write!(stderr, "{}", profile.report(&sources))?;
+    if let Some(out) = folded
+        && let Err(error) = fs::write(out, profile.folded(&sources))
+    {
+        writeln!(stderr, "error: cannot write {}: {}", out, error)?;
+        return Ok(EXIT_FAILURE);
+    }
+    Ok(code)
+}
+
+/// `monkey coverage [--lcov <out>] <file>...`
+fn coverage_command<W: Write, E: Write>(
+    args: &[String],
+    stdout: &mut W,
+    stderr: &mut E,
+) -> io::Result<i32> {
+    let mut lcov = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lcov" => {
+                let Some(value) = args.next() else {
+                    writeln!(stderr, "error: --lcov expects a path\n{}", USAGE)?;
+                    return Ok(EXIT_USAGE);
+                };
+                lcov = Some(value);
+            }
+            flag if flag.starts_with('-') => {
+                writeln!(stderr, "error: unknown flag '{}'\n{}", flag, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            file => paths.push(file),
+        }
+    }
+    if paths.is_empty() {
+        writeln!(stderr, "error: missing file\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    }
+
+    let mut sources = SourceMap::new();
+    let mut programs = Vec::new();
+    for path in paths {
+        let Some(program) = parse_file(path, &mut sources, stderr)? else {
+            return Ok(EXIT_FAILURE);
+        };
+        programs.push(program);
+    }
+    let mut coverage = Coverage::new();
+    let mut code = EXIT_OK;
+    // Each file runs in its own global scope, like separate `monkey run`s
+    for program in &programs {
+        coverage.add_program(program);
+        let output = SharedOutput::default();
+        let result =
+            Evaluator::with_output(output.clone()).eval_program_with(program, &mut coverage);
+        stdout.write_all(&output.0.borrow())?;
+        if let Err(error) = result {
+            writeln!(stderr, "{}", error.render(&sources))?;
+            code = EXIT_FAILURE;
+        }
+    }
+    write!(stderr, "{}", coverage.summary(&sources))?;
+    if let Some(out) = lcov
+        && let Err(error) = fs::write(out, coverage.lcov(&sources))
+    {
+        writeln!(stderr, "error: cannot write {}: {}", out, error)?;
+        return Ok(EXIT_FAILURE);
+    }
+    Ok(code)
+}
+
+/// `monkey disasm <file>`
+fn disasm_command<W: Write, E: Write>(
+    args: &[String],
+    stdout: &mut W,
+    stderr: &mut E,
+) -> io::Result<i32> {
+    let [path] = args else {
+        writeln!(stderr, "error: expected one file\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    };
+    let Some(bytecode) = load_bytecode(path, &mut SourceMap::new(), stderr)? else {
+        return Ok(EXIT_FAILURE);
+    };
+    write!(stdout, "{}", disassemble(&bytecode))?;
+    Ok(EXIT_OK)
+}
+
+/// `monkey compile [--strip] [-o <out>] <file>`
+fn compile_command<E: Write>(args: &[String], stderr: &mut E) -> io::Result<i32> {
+    let mut strip = false;
+    let mut out = None;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strip" => strip = true,
+            "-o" => {
+                let Some(value) = args.next() else {
+                    writeln!(stderr, "error: -o expects a path\n{}", USAGE)?;
+                    return Ok(EXIT_USAGE);
+                };
+                out = Some(value.clone());
+            }
+            flag if flag.starts_with('-') => {
+                writeln!(stderr, "error: unknown flag '{}'\n{}", flag, USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+            file if path.is_none() => path = Some(file),
+            _ => {
+                writeln!(stderr, "error: expected one file\n{}", USAGE)?;
+                return Ok(EXIT_USAGE);
+            }
+        }
+    }
+    let Some(path) = path else {
+        writeln!(stderr, "error: missing file\n{}", USAGE)?;
+        return Ok(EXIT_USAGE);
+    };
+    let out = out.unwrap_or_else(|| {
+        Path::new(path)
+            .with_extension("mbc")
+            .to_string_lossy()
+            .into_owned()
+    });
+    if out == path {
+        writeln!(stderr, "error: {} would overwrite the source", out)?;
+        return Ok(EXIT_FAILURE);
+    }
+
+    let mut sources = SourceMap::new();
+    let Some(program) = parse_file(path, &mut sources, stderr)? else {
+        return Ok(EXIT_FAILURE);
+    };
+    let bytecode = match compile(&program) {
+        Ok(bytecode) => bytecode,
+        Err(error) => {
+            writeln!(stderr, "{}", error.to_diagnostic().render(&sources))?;
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    if let Err(error) = fs::write(&out, mbc::encode(&bytecode, !strip)) {
+        writeln!(stderr, "error: cannot write {}: {}", out, error)?;
+        return Ok(EXIT_FAILURE);
+    }
+    Ok(EXIT_OK)
+}
+
+/// Loads an `.mbc` file, or reads, parses and compiles a source file into
+/// `sources`, reporting errors to `stderr`.
+/// # Returns
+/// `Ok(None)` if the file could not be loaded or compiled.
+fn load_bytecode<E: Write>(
+    path: &str,
+    sources: &mut SourceMap,
+    stderr: &mut E,
+) -> io::Result<Option<Bytecode>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            writeln!(stderr, "error: cannot read {}: {}", path, error)?;
+            return Ok(None);
+        }
+    };
+    if mbc::is_bytecode(&bytes) {
+        return match mbc::decode(&bytes) {
+            Ok(bytecode) => Ok(Some(bytecode)),
+            Err(error) => {
+                writeln!(stderr, "error: cannot load {}: {}", path, error)?;
+                Ok(None)
+            }
+        };
+    }
+    let Some(program) = parse_file(path, sources, stderr)? else {
+        return Ok(None);
+    };
+    match compile(&program) {
+        Ok(bytecode) => Ok(Some(bytecode)),
+        Err(error) => {
+            let mut diagnostic = error.to_diagnostic();
+            if program
+                .statements
+                .iter()
+                .any(|stmt| matches!(stmt, Statement::Import(_)))
+            {
+                diagnostic = diagnostic
+                    .with_note("only the AST interpreter loads modules; run the file with --eval");
+            }
+            writeln!(stderr, "{}", diagnostic.render(sources))?;
+            Ok(None)
+        }
+    }
+}
+
+/// Collects what a program writes, since the engines need a `'static`
+/// writer and `stdout` is borrowed.
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The byte offset of a 1-based line and char column in `text`. A column
+/// just past the end of the line is allowed.
+fn offset_of(text: &str, line: usize, column: usize) -> Option<usize> {
+    let mut start = 0;
+    for _ in 1..line {
+        start += text[start..].find('\n')? + 1;
+    }
+    let rest = &text[start..];
+    let line_text = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    line_text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(line_text.len()))
+        .nth(column - 1)
+        .map(|offset| start + offset)
+}
+
+/// Reads a source file, reporting a read error to `stderr`.
+/// # Returns
+/// `Ok(None)` if the file could not be read or is compiled bytecode.
+fn read_file<E: Write>(path: &str, stderr: &mut E) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(text) if mbc::is_bytecode(text.as_bytes()) => {
+            writeln!(stderr, "error: {} is compiled bytecode, not source", path)?;
+            Ok(None)
+        }
+        Ok(text) => Ok(Some(text)),
+        Err(error) => {
+            writeln!(stderr, "error: cannot read {}: {}", path, error)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Reads and parses a file, reporting read and parser errors to `stderr`.
+/// # Returns
+/// - `Ok(Some(program))` if the file parsed without errors
+/// - `Ok(None)` if it could not be read or had parser errors
+fn parse_file<E: Write>(
+    path: &str,
+    sources: &mut SourceMap,
+    stderr: &mut E,
+) -> io::Result<Option<Program>> {
+    let Some(text) = read_file(path, stderr)? else {
+        return Ok(None);
+    };
+    let file = sources.add(path, text.as_str());
+    let mut parser = Parser::new(Lexer::new(text, file));
+    let program = parser.parse_program();
+    if parser.errors.is_empty() {
+        return Ok(Some(program));
+    }
+    for error in &parser.errors {
+        writeln!(stderr, "{}", Diagnostic::from(error).render(sources))?;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Writes `text` to a fresh file in the temp directory and returns its path.
+    fn temp_file(name: &str, text: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("monkey-cli-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn run_args(args: &[&str]) -> (i32, String, String) {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = run(&args, io::empty(), &mut out, &mut err).unwrap();
+        (
+            code,
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_parse_json_round_trips() {
+        let path = temp_file("json.monkey", "let x = 1 + 2;\nx;\n");
+        let (code, out, err) = run_args(&["parse", "--json", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_OK, "{}", err);
+        assert_eq!(
+            json::from_json(&out).unwrap().to_string(),
+            "let x = (1 + 2);x"
+        );
+    }
+
+    #[test]
+    fn test_parse_dot() {
+        let path = temp_file("dot.monkey", "1 + 2;\n");
+        let (code, out, _) = run_args(&["parse", "--dot", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_OK);
+        assert!(out.starts_with("digraph AST {\n"));
+        assert!(out.contains("[label=\"InfixExpression\\n+\"]"));
+        assert!(out.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_parse_reports_errors() {
+        let path = temp_file("bad.monkey", "let x 5;\n");
+        let (code, out, err) = run_args(&["parse", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(out.is_empty());
+        assert!(err.starts_with("error: expected token to be ASSIGN, got INT\n"));
+        assert!(err.contains("bad.monkey:1:7"));
+    }
+
+    #[test]
+    fn test_fmt_rewrites_file() {
+        let path = temp_file("fmt.monkey", "let x=1;  // one\nx;");
+        let (code, out, err) = run_args(&["fmt", "--indent", "2", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_OK, "{}", err);
+        assert!(out.is_empty());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "let x = 1; // one\nx;\n"
+        );
+    }
+
+    #[test]
+    fn test_fmt_check() {
+        let clean = temp_file("clean.monkey", "let x = 1;\n");
+        let messy = temp_file("messy.monkey", "let x=1;\n");
+        let (clean, messy) = (clean.to_str().unwrap(), messy.to_str().unwrap());
+
+        assert_eq!(run_args(&["fmt", "--check", clean]).0, EXIT_OK);
+        let (code, out, _) = run_args(&["fmt", "--check", clean, messy]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert_eq!(out, format!("{}\n", messy));
+        // --check never writes
+        assert_eq!(fs::read_to_string(messy).unwrap(), "let x=1;\n");
+    }
+
+    #[test]
+    fn test_highlight() {
+        let path = temp_file("highlight.monkey", "let s = \"<b>\";\n");
+        let path = path.to_str().unwrap();
+        let (code, out, _) = run_args(&["highlight", "--html", path]);
+        assert_eq!(code, EXIT_OK);
+        assert_eq!(
+            out,
+            "<pre><span class=\"keyword\">let</span> <span class=\"local\">s</span> \
+             <span class=\"operator\">=</span> <span class=\"string\">&quot;&lt;b&gt;&quot;</span>;\n</pre>\n"
+        );
+        let (code, out, _) = run_args(&["highlight", path]);
+        assert_eq!(code, EXIT_OK);
+        assert!(out.starts_with("\x1b[35mlet\x1b[0m s "));
+    }
+
+    #[test]
+    fn test_run() {
+        let path = temp_file(
+            "run.monkey",
+            "let square = fn(x) { x * x; };\nputs(square(7));\n",
+        );
+        let path = path.to_str().unwrap();
+        for args in [vec!["run", path], vec!["run", "--eval", path]] {
+            let (code, out, err) = run_args(&args);
+            assert_eq!(code, EXIT_OK, "{}", err);
+            assert_eq!(out, "49\n");
+        }
+
+        let path = temp_file("run_error.monkey", "puts(1);\n1 + true;\n");
+        let path = path.to_str().unwrap();
+        for args in [vec!["run", path], vec!["run", "--eval", path]] {
+            let (code, out, err) = run_args(&args);
+            assert_eq!(code, EXIT_FAILURE);
+            assert_eq!(out, "1\n");
+            assert_eq!(
+                err,
+                format!(
+                    "error: type mismatch: INTEGER + BOOLEAN\n --> {}:2:3\n  |\n2 | 1 + true;\n  |   ^\n",
+                    path
+                )
+            );
+        }
+
+        let path = temp_file(
+            "run_trace.monkey",
+            "let div = fn(a, b) { a / b; };\nlet half = fn(x) { div(x, 0); };\nhalf(4);\n",
+        );
+        let path = path.to_str().unwrap();
+        for args in [vec!["run", path], vec!["run", "--eval", path]] {
+            let (code, _, err) = run_args(&args);
+            assert_eq!(code, EXIT_FAILURE);
+            assert_eq!(
+                err,
+                format!(
+                    "error: division by zero\n --> {path}:1:24\n  |\n\
+                     1 | let div = fn(a, b) {{ a / b; }};\n  |                        ^\n\
+                     note: in div, called here\n --> {path}:2:23\n  |\n\
+                     2 | let half = fn(x) {{ div(x, 0); }};\n  |                       ^\n\
+                     note: in half, called here\n --> {path}:3:5\n  |\n\
+                     3 | half(4);\n  |     ^\n"
+                )
+            );
+        }
+
+        let path = temp_file("run_undefined.monkey", "let a = 1;\nb;\n");
+        let (code, _, err) = run_args(&["run", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(err.starts_with("error: identifier not found: b\n"));
+        assert!(err.contains("run_undefined.monkey:2:1"));
+    }
+
+    #[test]
+    fn test_run_imports() {
+        temp_file(
+            "run_math.monkey",
+            "export let base = 21;\nexport let double = fn(x) { x * 2; };\nlet hidden = 1;\n",
+        );
+        let path = temp_file(
+            "run_main.monkey",
+            "import \"run_math.monkey\" as math;\nputs(math.double(math.base));\n",
+        );
+        let path = path.to_str().unwrap();
+        let (code, out, err) = run_args(&["run", "--eval", path]);
+        assert_eq!(code, EXIT_OK, "{}", err);
+        assert_eq!(out, "42\n");
+
+        // The bytecode VM does not load modules
+        let (code, _, err) = run_args(&["run", path]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(
+            err.starts_with("error: import is not supported\n"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("note: only the AST interpreter loads modules"),
+            "{}",
+            err
+        );
+
+        let path = temp_file(
+            "run_hidden.monkey",
+            "import \"run_math.monkey\" as math;\nmath.hidden;\n",
+        );
+        let path = path.to_str().unwrap();
+        let (code, _, err) = run_args(&["run", "--eval", path]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(
+            err.starts_with("error: 'hidden' is not exported by "),
+            "{}",
+            err
+        );
+        assert!(err.contains(&format!("{}:2:6", path)), "{}", err);
+
+        let path = temp_file("run_missing.monkey", "import \"nowhere.monkey\" as m;\n");
+        let (code, _, err) = run_args(&["run", "--eval", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(
+            err.contains("nowhere.monkey: cannot read module"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_disasm() {
+        let path = temp_file("disasm.monkey", "puts(1);\n");
+        let (code, out, err) = run_args(&["disasm", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_OK, "{}", err);
+        assert_eq!(
+            out,
+            "== constants ==\n0 INTEGER 1\n\n== main ==\n\
+             0000 GetBuiltin 1         ; puts\n\
+             0002 Constant 0           ; 1\n\
+             0005 Call 1\n\
+             0007 ReturnValue\n"
+        );
+        let path = temp_file("disasm_undefined.monkey", "x;\n");
+        let (code, _, err) = run_args(&["disasm", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(err.starts_with("error: identifier not found: x\n"));
+    }
+
+    #[test]
+    fn test_profile() {
+        let path = temp_file(
+            "profile.monkey",
+            "let sq = fn(x) { x * x; };\nlet f = fn(n) { sq(n) + 1; };\nputs(f(2));\nf(3);\n",
+        );
+        let path = path.to_str().unwrap();
+        let folded = temp_file("profile.folded", "");
+        let folded = folded.to_str().unwrap();
+        let (code, out, err) = run_args(&["profile", "--folded", folded, path]);
+        assert_eq!(code, EXIT_OK, "{}", err);
+        assert_eq!(out, "5\n");
+        // The times vary, so only the call counts and functions are checked
+        let rows: Vec<String> = err
+            .lines()
+            .filter(|line| line.contains(" at "))
+            .map(|line| {
+                let calls = line.split_whitespace().next().unwrap();
+                let function = line.rsplit_once("  ").unwrap().1;
+                format!("{} {}", calls, function)
+            })
+            .collect();
+        assert_eq!(rows.len(), 2, "{}", err);
+        assert!(rows.contains(&format!("2 sq at {}:1:10", path)));
+        assert!(rows.contains(&format!("2 f at {}:2:9", path)));
+        assert!(err.lines().last().unwrap().starts_with("total "));
+        let stacks: Vec<String> = fs::read_to_string(folded)
+            .unwrap()
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0.to_string())
+            .collect();
+        assert_eq!(
+            stacks,
+            [
+                "<main>".to_string(),
+                format!("<main>;f ({}:2:9)", path),
+                format!("<main>;f ({path}:2:9);sq ({path}:1:10)"),
+            ]
+        );
+
+        // A failing program is still profiled
+        let path = temp_file("profile_error.monkey", "let f = fn() { 1 / 0; };\nf();\n");
+        let (code, _, err) = run_args(&["profile", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(err.starts_with("error: division by zero\n"));
+        assert!(err.contains("  f at "));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let first = temp_file(
+            "covered.monkey",
+            "let abs = fn(n) { if (n < 0) { -n; } else { n; }; };\nputs(abs(-4));\n",
+        );
+        let first = first.to_str().unwrap();
+        let second = temp_file("failing.monkey", "let x = 1;\nx / 0;\nputs(x);\n");
+        let second = second.to_str().unwrap();
+        let lcov = temp_file("coverage.info", "");
+        let lcov = lcov.to_str().unwrap();
+        let (code, out, err) = run_args(&["coverage", "--lcov", lcov, first, second]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert_eq!(out, "4\n");
+        assert!(err.starts_with("error: division by zero\n"), "{}", err);
+        // The columns are as wide as the temp paths
+        let rows: Vec<Vec<&str>> = err
+            .lines()
+            .skip_while(|line| !line.starts_with("file "))
+            .take(4)
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                vec!["file", "statements", "branches"],
+                vec![first, "4/5", "(80.0%)", "1/2", "(50.0%)"],
+                vec![second, "2/3", "(66.7%)", "0/0", "(-)"],
+                vec!["total", "6/8", "(75.0%)", "1/2", "(50.0%)"],
+            ]
+        );
+        assert!(err.ends_with(&format!(
+            "warning: else branch never taken\n --> {}:1:43\n  |\n\
+             1 | let abs = fn(n) {{ if (n < 0) {{ -n; }} else {{ n; }}; }};\n  |                                           ^\n",
+            first
+        )));
+        assert_eq!(
+            fs::read_to_string(lcov).unwrap(),
+            format!(
+                "TN:\nSF:{first}\nBRDA:1,0,0,1\nBRDA:1,0,1,0\nBRF:2\nBRH:1\n\
+                 DA:1,1\nDA:2,1\nLF:2\nLH:2\nend_of_record\n\
+                 TN:\nSF:{second}\nBRF:0\nBRH:0\n\
+                 DA:1,1\nDA:2,1\nDA:3,0\nLF:3\nLH:2\nend_of_record\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_compile_and_run_bytecode() {
+        let source = temp_file("compiled.monkey", "let x = 6;\nputs(x * 7);\n");
+        let source = source.to_str().unwrap();
+        let (code, _, err) = run_args(&["compile", source]);
+        assert_eq!(code, EXIT_OK, "{}", err);
+        let compiled = source.replace(".monkey", ".mbc");
+        let (code, out, err) = run_args(&["run", &compiled]);
+        assert_eq!(code, EXIT_OK, "{}", err);
+        assert_eq!(out, "42\n");
+        assert_eq!(
+            run_args(&["disasm", &compiled]).1,
+            run_args(&["disasm", source]).1
+        );
+        let (code, _, err) = run_args(&["run", "--eval", &compiled]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(err.ends_with("compiled.mbc is compiled bytecode, not source\n"));
+
+        let stripped = source.replace(".monkey", "-stripped.mbc");
+        assert_eq!(
+            run_args(&["compile", "--strip", "-o", &stripped, source]).0,
+            EXIT_OK
+        );
+        assert!(fs::metadata(&stripped).unwrap().len() < fs::metadata(&compiled).unwrap().len());
+
+        let mut corrupt = fs::read(&compiled).unwrap();
+        corrupt.truncate(corrupt.len() - 2);
+        let path = temp_file("corrupt.mbc", "");
+        fs::write(&path, corrupt).unwrap();
+        let (code, _, err) = run_args(&["run", path.to_str().unwrap()]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(err.starts_with("error: cannot load "), "{}", err);
+        assert!(err.contains(": truncated file: data at byte "), "{}", err);
+    }
+
+    #[test]
+    fn test_rename() {
+        let path = temp_file(
+            "rename.monkey",
+            "let x = 1;\nlet f = fn(y) { x + y; };\nx;\n",
+        );
+        let path = path.to_str().unwrap();
+        let (code, _, err) = run_args(&["rename", path, "3:1", "count"]);
+        assert_eq!(code, EXIT_OK, "{}", err);
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "let count = 1;\nlet f = fn(y) { count + y; };\ncount;\n"
+        );
+        let (code, _, err) = run_args(&["rename", path, "2:12", "count"]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert!(err.starts_with("error: the rename would change what 'count'"));
+        assert_eq!(run_args(&["rename", path, "9:1", "y"]).0, EXIT_FAILURE);
+        // Nothing was written by the failed renames
+        assert!(
+            fs::read_to_string(path)
+                .unwrap()
+                .starts_with("let count = 1;")
+        );
+    }
+
+    #[test]
+    fn test_lsp_without_shutdown_fails() {
+        let (code, out, _) = run_args(&["lsp", "--stdio"]);
+        assert_eq!(code, EXIT_FAILURE);
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert_eq!(run_args(&["parse"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["parse", "--yaml", "a"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["frobnicate"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["fmt", "--check"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["fmt", "--width", "wide", "a"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["highlight"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["highlight", "--css", "a"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["rename", "a", "1:1"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["rename", "a", "0:1", "b"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["run"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["disasm", "a", "b"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["compile"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["compile", "a", "-o"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["run", "--jit", "a"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["profile", "a", "--folded"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["coverage"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["coverage", "--html", "a"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["lsp", "--tcp"]).0, EXIT_USAGE);
+        assert_eq!(run_args(&["dap", "--port", "4711"]).0, EXIT_USAGE);
+    }
+}
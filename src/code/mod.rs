@@ -0,0 +1,237 @@
+//! Bytecode instructions: opcodes and their operand encoding.
+//!
+//! An instruction is a one-byte opcode followed by its operands. Each opcode
+//! has a fixed list of operand widths (see `Opcode::operand_widths`);
+//! multi-byte operands are big-endian.
+//!
+//! ```text
+//! Constant 65534   ->  [Constant, 0xFF, 0xFE]
+//! Closure 2 1      ->  [Closure, 0x00, 0x02, 0x01]
+//! ```
+
+use crate::parser::error::Span;
+use std::fmt;
+
+/// A stream of encoded instructions.
+pub type Instructions = Vec<u8>;
+
+/// Where the instructions of a function came from: `(offset, span)` pairs in
+/// increasing offset order. An instruction has the span of the last entry at
+/// or before its offset.
+pub type SpanTable = Vec<(usize, Span)>;
+
+/// Every instruction the VM understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Opcode {
+    /// Pushes the constant at the operand index.
+    Constant,
+    /// Discards the top of the stack.
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    /// Negates an integer (`-x`).
+    Minus,
+    /// Logical not (`!x`).
+    Bang,
+    True,
+    False,
+    Null,
+    /// Jumps to the operand offset.
+    Jump,
+    /// Pops the condition and jumps to the operand offset if it is falsy.
+    JumpNotTruthy,
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    GetBuiltin,
+    /// Pushes a free variable of the running closure.
+    GetFree,
+    /// Pushes the running closure, for a function that calls itself.
+    CurrentClosure,
+    /// Pops the operand count of free variables and pushes a closure over the
+    /// compiled function constant at the first operand.
+    Closure,
+    /// Calls the function below the operand count of arguments.
+    Call,
+    /// Returns the top of the stack from the current function.
+    ReturnValue,
+}
+
+impl Opcode {
+    /// Every opcode, indexed by its byte value.
+    pub const ALL: [Opcode; 27] = [
+        Opcode::Constant,
+        Opcode::Pop,
+        Opcode::Add,
+        Opcode::Sub,
+        Opcode::Mul,
+        Opcode::Div,
+        Opcode::Equal,
+        Opcode::NotEqual,
+        Opcode::GreaterThan,
+        Opcode::LessThan,
+        Opcode::Minus,
+        Opcode::Bang,
+        Opcode::True,
+        Opcode::False,
+        Opcode::Null,
+        Opcode::Jump,
+        Opcode::JumpNotTruthy,
+        Opcode::GetGlobal,
+        Opcode::SetGlobal,
+        Opcode::GetLocal,
+        Opcode::SetLocal,
+        Opcode::GetBuiltin,
+        Opcode::GetFree,
+        Opcode::CurrentClosure,
+        Opcode::Closure,
+        Opcode::Call,
+        Opcode::ReturnValue,
+    ];
+
+    /// Decodes an opcode byte.
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        Opcode::ALL.get(byte as usize).copied()
+    }
+
+    /// The width in bytes of each operand.
+    pub fn operand_widths(self) -> &'static [usize] {
+        match self {
+            Opcode::Constant
+            | Opcode::Jump
+            | Opcode::JumpNotTruthy
+            | Opcode::GetGlobal
+            | Opcode::SetGlobal => &[2],
+            Opcode::GetLocal
+            | Opcode::SetLocal
+            | Opcode::GetBuiltin
+            | Opcode::GetFree
+            | Opcode::Call => &[1],
+            Opcode::Closure => &[2, 1],
+            _ => &[],
+        }
+    }
+
+    /// The size of a whole instruction with this opcode.
+    pub fn width(self) -> usize {
+        1 + self.operand_widths().iter().sum::<usize>()
+    }
+}
+
+impl fmt::Display for Opcode {
+    /// Writes the opcode's name, as used by the disassembler.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Encodes one instruction.
+///
+/// # Panics
+/// If the number of operands does not match the opcode, or an operand does
+/// not fit its width. Both are bugs in the compiler.
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let widths = op.operand_widths();
+    assert_eq!(
+        widths.len(),
+        operands.len(),
+        "{:?} takes {} operand(s)",
+        op,
+        widths.len()
+    );
+    let mut instruction = Vec::with_capacity(op.width());
+    instruction.push(op as u8);
+    for (&operand, &width) in operands.iter().zip(widths) {
+        assert!(
+            operand < 1 << (8 * width),
+            "operand {} of {:?} does not fit in {} byte(s)",
+            operand,
+            op,
+            width
+        );
+        instruction.extend_from_slice(&operand.to_be_bytes()[size_of::<usize>() - width..]);
+    }
+    instruction
+}
+
+/// Decodes the operands of `op` from `bytes`, which starts just after the
+/// opcode.
+/// # Returns
+/// The operands and the number of bytes they took, or `None` if `bytes` is
+/// too short.
+pub fn read_operands(op: Opcode, bytes: &[u8]) -> Option<(Vec<usize>, usize)> {
+    let mut operands = Vec::with_capacity(op.operand_widths().len());
+    let mut offset = 0;
+    for &width in op.operand_widths() {
+        let operand = bytes.get(offset..offset + width)?;
+        operands.push(
+            operand
+                .iter()
+                .fold(0, |value, &byte| value << 8 | byte as usize),
+        );
+        offset += width;
+    }
+    Some((operands, offset))
+}
+
+/// The span of the instruction at `offset`, if the table covers it.
+pub fn span_at(spans: &[(usize, Span)], offset: usize) -> Option<&Span> {
+    let after = spans.partition_point(|(start, _)| *start <= offset);
+    after.checked_sub(1).map(|index| &spans[index].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make() {
+        assert_eq!(
+            make(Opcode::Constant, &[65534]),
+            vec![Opcode::Constant as u8, 255, 254]
+        );
+        assert_eq!(
+            make(Opcode::GetLocal, &[255]),
+            vec![Opcode::GetLocal as u8, 255]
+        );
+        assert_eq!(
+            make(Opcode::Closure, &[65534, 255]),
+            vec![Opcode::Closure as u8, 255, 254, 255]
+        );
+        assert_eq!(make(Opcode::Add, &[]), vec![Opcode::Add as u8]);
+    }
+
+    #[test]
+    fn test_read_operands() {
+        for (op, operands) in [
+            (Opcode::Constant, vec![65535]),
+            (Opcode::GetLocal, vec![255]),
+            (Opcode::Closure, vec![65535, 255]),
+            (Opcode::Pop, vec![]),
+        ] {
+            let instruction = make(op, &operands);
+            assert_eq!(
+                read_operands(op, &instruction[1..]),
+                Some((operands, op.width() - 1))
+            );
+        }
+        assert_eq!(read_operands(Opcode::Constant, &[1]), None);
+    }
+
+    #[test]
+    fn test_opcode_bytes_round_trip() {
+        for (byte, op) in Opcode::ALL.iter().enumerate() {
+            assert_eq!(*op as usize, byte);
+            assert_eq!(Opcode::from_byte(byte as u8), Some(*op));
+        }
+        assert_eq!(Opcode::from_byte(Opcode::ALL.len() as u8), None);
+    }
+}
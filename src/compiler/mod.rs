@@ -0,0 +1,610 @@
+//! Compiles an `ast::Program` to bytecode for the `vm`.
+//!
+//! The compiler walks the AST once, emitting instructions from `code` and
+//! collecting literals and compiled function bodies in a constant pool.
+//! Names are resolved at compile time with a `SymbolTable` into global,
+//! local, builtin and free-variable slots, so the VM never looks a name up.
+//!
+//! Programs mean the same as under the `evaluator`: a block leaves the value
+//! of its last expression statement (or `null`) on the stack, every
+//! function ends by returning that value, and so does the program itself.
+//! `IfExpression`s become conditional jumps and `FunctionLiteral`s become
+//! closures over the variables they capture.
+//!
+//! Imports and member access are not compiled: a program compiles on its
+//! own, and only the evaluator runs module graphs (`Evaluator::eval_modules`).
+
+mod symbol_table;
+
+pub use symbol_table::{Symbol, SymbolScope, SymbolTable, Unresolved};
+
+use crate::ast::{
+    Program,
+    expression::{BlockStatement, Expression, FunctionLiteral, IfExpression},
+    statement::{LetStatement, Statement},
+};
+use crate::code::{Instructions, Opcode, SpanTable, make};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::token::Token;
+use crate::object::{CompiledFunction, Object};
+use crate::parser::error::Span;
+use std::fmt;
+use std::rc::Rc;
+
+/// A compiled program: the instructions of its top level and the constants
+/// they refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Object>,
+    /// Where the top-level instructions came from.
+    pub spans: SpanTable,
+}
+
+/// A construct the compiler cannot translate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl CompileError {
+    fn new(token: &Token, message: impl Into<String>) -> Self {
+        CompileError {
+            span: Span::from_token(token),
+            message: message.into(),
+        }
+    }
+
+    /// Converts the error into a renderable diagnostic.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.span.clone(), self.message.clone())
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+type Compile<T = ()> = Result<T, CompileError>;
+
+/// Compiles a program with a fresh compiler.
+pub fn compile(program: &Program) -> Result<Bytecode, CompileError> {
+    let mut compiler = Compiler::new();
+    compiler.compile_program(program)?;
+    Ok(compiler.bytecode())
+}
+
+/// The output for one function being compiled.
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    instructions: Instructions,
+    spans: SpanTable,
+}
+
+/// Compiles programs, keeping the constant pool and symbol table.
+#[derive(Debug, Clone, Default)]
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    /// Each function being compiled, innermost last.
+    scopes: Vec<Scope>,
+    /// The span given to the instructions emitted next.
+    span: Span,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler {
+            constants: Vec::new(),
+            symbol_table: SymbolTable::new(),
+            scopes: vec![Scope::default()],
+            span: Span::default(),
+        }
+    }
+
+    /// Compiles the statements of a program so that the instructions leave
+    /// its value with `ReturnValue`. The instructions of a previous program
+    /// are dropped, but its globals and constants are kept.
+    pub fn compile_program(&mut self, program: &Program) -> Compile {
+        self.scopes = vec![Scope::default()];
+        self.compile_statements(&program.statements)?;
+        self.emit(Opcode::ReturnValue, &[]);
+        Ok(())
+    }
+
+    /// The compiled program.
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: self.scopes[0].instructions.clone(),
+            constants: self.constants.clone(),
+            spans: self.scopes[0].spans.clone(),
+        }
+    }
+
+    // ============ STATEMENTS ============
+
+    /// Compiles statements so they leave their value: that of the last
+    /// statement if it is an expression statement, `null` otherwise.
+    fn compile_statements(&mut self, statements: &[Statement]) -> Compile {
+        for stmt in statements {
+            match stmt {
+                Statement::Let(let_stmt) => self.symbol_table.declare(&let_stmt.name.value),
+                Statement::Export(export) => {
+                    self.symbol_table.declare(&export.statement.name.value)
+                }
+                _ => {}
+            }
+        }
+        for (i, stmt) in statements.iter().enumerate() {
+            let is_last = i + 1 == statements.len();
+            match stmt {
+                Statement::Let(let_stmt) => self.compile_let(let_stmt)?,
+                Statement::Export(export) => self.compile_let(&export.statement)?,
+                Statement::Import(import) => {
+                    return Err(CompileError::new(&import.token, "import is not supported"));
+                }
+                Statement::Return(ret) => {
+                    match &ret.value {
+                        Some(value) => self.compile_expression(value)?,
+                        None => {
+                            self.emit(Opcode::Null, &[]);
+                        }
+                    }
+                    self.mark(&ret.token);
+                    self.emit(Opcode::ReturnValue, &[]);
+                }
+                Statement::Expression(stmt) => {
+                    self.compile_expression(&stmt.value)?;
+                    if is_last {
+                        return Ok(());
+                    }
+                    self.emit(Opcode::Pop, &[]);
+                }
+            }
+        }
+        self.emit(Opcode::Null, &[]);
+        Ok(())
+    }
+
+    fn compile_let(&mut self, stmt: &LetStatement) -> Compile {
+        let name = &stmt.name.value;
+        let symbol = match &stmt.value {
+            // A function sees its own name, so the binding comes first
+            Some(Expression::FunctionLiteral(function)) => {
+                let symbol = self.symbol_table.define_pending(name);
+                self.compile_function(function, Some(name))?;
+                symbol
+            }
+            Some(value) => {
+                self.compile_expression(value)?;
+                self.symbol_table.define_pending(name)
+            }
+            None => {
+                self.emit(Opcode::Null, &[]);
+                self.symbol_table.define_pending(name)
+            }
+        };
+        self.mark(&stmt.name.token);
+        match symbol.scope {
+            SymbolScope::Global => {
+                let index = self.operand(&stmt.name.token, symbol.index, u16::MAX, "globals")?;
+                self.emit(Opcode::SetGlobal, &[index]);
+            }
+            _ => {
+                let index = self.operand(&stmt.name.token, symbol.index, u8::MAX, "locals")?;
+                self.emit(Opcode::SetLocal, &[index]);
+            }
+        }
+        Ok(())
+    }
+
+    // ============ EXPRESSIONS ============
+
+    fn compile_expression(&mut self, expr: &Expression) -> Compile {
+        match expr {
+            Expression::Identifier(ident) => {
+                let symbol = self
+                    .symbol_table
+                    .resolve(&ident.value)
+                    .map_err(|unresolved| {
+                        let message = match unresolved {
+                            Unresolved::Undefined => {
+                                format!("identifier not found: {}", ident.value)
+                            }
+                            Unresolved::CapturedBeforeDefinition => {
+                                format!("'{}' is captured before its definition", ident.value)
+                            }
+                        };
+                        CompileError::new(&ident.token, message)
+                    })?;
+                self.load_symbol(&symbol, &ident.token)?;
+            }
+            Expression::IntegerLiteral(literal) => {
+                let index = self.add_constant(Object::Integer(literal.value), &literal.token)?;
+                self.mark(&literal.token);
+                self.emit(Opcode::Constant, &[index]);
+            }
+            Expression::StringLiteral(literal) => {
+                let value = Object::String(literal.value.as_str().into());
+                let index = self.add_constant(value, &literal.token)?;
+                self.mark(&literal.token);
+                self.emit(Opcode::Constant, &[index]);
+            }
+            Expression::BooleanLiteral(literal) => {
+                let op = if literal.value {
+                    Opcode::True
+                } else {
+                    Opcode::False
+                };
+                self.mark(&literal.token);
+                self.emit(op, &[]);
+            }
+            Expression::PrefixExpression(prefix) => {
+                self.compile_expression(&prefix.right)?;
+                let op = match prefix.operator.as_str() {
+                    "!" => Opcode::Bang,
+                    "-" => Opcode::Minus,
+                    other => {
+                        let message = format!("unknown operator: {}", other);
+                        return Err(CompileError::new(&prefix.token, message));
+                    }
+                };
+                self.mark(&prefix.token);
+                self.emit(op, &[]);
+            }
+            Expression::InfixExpression(infix) => {
+                self.compile_expression(&infix.left)?;
+                self.compile_expression(&infix.right)?;
+                let op = match infix.operator.as_str() {
+                    "+" => Opcode::Add,
+                    "-" => Opcode::Sub,
+                    "*" => Opcode::Mul,
+                    "/" => Opcode::Div,
+                    "==" => Opcode::Equal,
+                    "!=" => Opcode::NotEqual,
+                    ">" => Opcode::GreaterThan,
+                    "<" => Opcode::LessThan,
+                    other => {
+                        let message = format!("unknown operator: {}", other);
+                        return Err(CompileError::new(&infix.token, message));
+                    }
+                };
+                self.mark(&infix.token);
+                self.emit(op, &[]);
+            }
+            Expression::IfExpression(if_expr) => self.compile_if(if_expr)?,
+            Expression::BlockStatement(block) => self.compile_block(block)?,
+            Expression::FunctionLiteral(function) => self.compile_function(function, None)?,
+            Expression::CallExpression(call) => {
+                self.compile_expression(&call.function)?;
+                for arg in &call.arguments {
+                    self.compile_expression(arg)?;
+                }
+                let count =
+                    self.operand(&call.token, call.arguments.len(), u8::MAX, "arguments")?;
+                self.mark(&call.token);
+                self.emit(Opcode::Call, &[count]);
+            }
+            Expression::MemberExpression(member) => {
+                return Err(CompileError::new(
+                    &member.token,
+                    "member access is not supported",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_if(&mut self, if_expr: &IfExpression) -> Compile {
+        self.compile_expression(&if_expr.condition)?;
+        self.mark(&if_expr.token);
+        let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[0]);
+        self.compile_expression(&if_expr.consequence)?;
+        let jump = self.emit(Opcode::Jump, &[0]);
+        self.patch_jump(jump_not_truthy, &if_expr.token)?;
+        match &if_expr.alternative {
+            Some(alternative) => self.compile_expression(alternative)?,
+            None => {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+        self.patch_jump(jump, &if_expr.token)
+    }
+
+    fn compile_block(&mut self, block: &BlockStatement) -> Compile {
+        self.symbol_table.push_block();
+        let result = self.compile_statements(&block.statements);
+        self.symbol_table.pop_block();
+        result
+    }
+
+    /// Compiles a function literal to a constant and emits the `Closure`
+    /// that captures its free variables.
+    fn compile_function(&mut self, function: &FunctionLiteral, name: Option<&str>) -> Compile {
+        let outer = std::mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::enclosed(outer);
+        self.scopes.push(Scope::default());
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+        for param in &function.parameters {
+            self.symbol_table.define(&param.value);
+        }
+        let result = self.compile_block(&function.body);
+        self.emit(Opcode::ReturnValue, &[]);
+
+        let scope = self.scopes.pop().expect("the function's scope");
+        let table = std::mem::take(&mut self.symbol_table);
+        let (outer, num_locals, free) = table.leave();
+        self.symbol_table = outer;
+        result?;
+
+        self.operand(&function.token, num_locals, u8::MAX, "locals")?;
+        let free_count = self.operand(&function.token, free.len(), u8::MAX, "free variables")?;
+        for symbol in &free {
+            self.load_symbol(symbol, &function.token)?;
+        }
+        let compiled = CompiledFunction {
+            name: name.map(str::to_string),
+            instructions: scope.instructions,
+            num_locals,
+            num_parameters: function.parameters.len(),
+            spans: scope.spans,
+        };
+        let index =
+            self.add_constant(Object::CompiledFunction(Rc::new(compiled)), &function.token)?;
+        self.mark(&function.token);
+        self.emit(Opcode::Closure, &[index, free_count]);
+        Ok(())
+    }
+
+    // ============ EMITTING ============
+
+    fn load_symbol(&mut self, symbol: &Symbol, token: &Token) -> Compile {
+        self.mark(token);
+        match symbol.scope {
+            SymbolScope::Global => {
+                let index = self.operand(token, symbol.index, u16::MAX, "globals")?;
+                self.emit(Opcode::GetGlobal, &[index]);
+            }
+            SymbolScope::Local => {
+                let index = self.operand(token, symbol.index, u8::MAX, "locals")?;
+                self.emit(Opcode::GetLocal, &[index]);
+            }
+            SymbolScope::Builtin => {
+                self.emit(Opcode::GetBuiltin, &[symbol.index]);
+            }
+            SymbolScope::Free => {
+                let index = self.operand(token, symbol.index, u8::MAX, "free variables")?;
+                self.emit(Opcode::GetFree, &[index]);
+            }
+            SymbolScope::Function => {
+                self.emit(Opcode::CurrentClosure, &[]);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_constant(&mut self, value: Object, token: &Token) -> Compile<usize> {
+        self.constants.push(value);
+        self.operand(token, self.constants.len() - 1, u16::MAX, "constants")
+    }
+
+    /// Checks that an operand fits in its encoding.
+    fn operand<T: Into<usize>>(
+        &self,
+        token: &Token,
+        value: usize,
+        max: T,
+        what: &str,
+    ) -> Compile<usize> {
+        let max = max.into();
+        if value > max {
+            let message = format!("too many {} (the limit is {})", what, max + 1);
+            return Err(CompileError::new(token, message));
+        }
+        Ok(value)
+    }
+
+    /// Gives the instructions emitted next the position of `token`.
+    fn mark(&mut self, token: &Token) {
+        self.span = Span::from_token(token);
+    }
+
+    /// Appends an instruction to the current function, recording the current
+    /// span if it changed.
+    /// # Returns
+    /// Its offset.
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> usize {
+        let scope = self.scopes.last_mut().expect("a compilation scope");
+        let offset = scope.instructions.len();
+        if scope
+            .spans
+            .last()
+            .is_none_or(|(_, span)| *span != self.span)
+        {
+            scope.spans.push((offset, self.span.clone()));
+        }
+        scope.instructions.extend(make(op, operands));
+        offset
+    }
+
+    /// Points the jump at `offset` to the current end of the instructions.
+    fn patch_jump(&mut self, offset: usize, token: &Token) -> Compile {
+        let scope = self.scopes.last_mut().expect("a compilation scope");
+        let target = scope.instructions.len();
+        if target > u16::MAX as usize {
+            let message = format!(
+                "too many instructions in a function (the limit is {})",
+                u16::MAX as usize + 1
+            );
+            return Err(CompileError::new(token, message));
+        }
+        scope.instructions[offset + 1..offset + 3].copy_from_slice(&(target as u16).to_be_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::span_at;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::FileId;
+
+    fn compile_source(source: &str) -> Result<Bytecode, CompileError> {
+        let mut parser = Parser::new(Lexer::new(source.to_string(), FileId::DETACHED));
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        compile(&program)
+    }
+
+    fn concat(instructions: &[Instructions]) -> Instructions {
+        instructions.concat()
+    }
+
+    #[test]
+    fn test_arithmetic_and_program_value() {
+        let bytecode = compile_source("1 + 2; 3;").unwrap();
+        assert_eq!(
+            bytecode.instructions,
+            concat(&[
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+        assert_eq!(
+            bytecode.constants,
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)]
+        );
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let bytecode = compile_source("if (true) { 10; };").unwrap();
+        assert_eq!(
+            bytecode.instructions,
+            concat(&[
+                make(Opcode::True, &[]),            // 0000
+                make(Opcode::JumpNotTruthy, &[10]), // 0001
+                make(Opcode::Constant, &[0]),       // 0004
+                make(Opcode::Jump, &[11]),          // 0007
+                make(Opcode::Null, &[]),            // 0010
+                make(Opcode::ReturnValue, &[]),     // 0011
+            ])
+        );
+    }
+
+    #[test]
+    fn test_globals_and_locals() {
+        let bytecode = compile_source("let a = 1; let f = fn(b) { let c = a; c; };").unwrap();
+        assert_eq!(
+            bytecode.instructions,
+            concat(&[
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Closure, &[1, 0]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::Null, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+        let Object::CompiledFunction(function) = &bytecode.constants[1] else {
+            panic!("expected a function, got {:?}", bytecode.constants[1]);
+        };
+        assert_eq!(function.name.as_deref(), Some("f"));
+        assert_eq!((function.num_parameters, function.num_locals), (1, 2));
+        assert_eq!(
+            function.instructions,
+            concat(&[
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::SetLocal, &[1]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_closures_capture_free_variables() {
+        let bytecode = compile_source("fn(a) { fn(b) { a + b; }; };").unwrap();
+        let Object::CompiledFunction(outer) = &bytecode.constants[1] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            outer.instructions,
+            concat(&[
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::Closure, &[0, 1]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+        let Object::CompiledFunction(inner) = &bytecode.constants[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            inner.instructions,
+            concat(&[
+                make(Opcode::GetFree, &[0]),
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::Add, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_recursive_function_refers_to_itself() {
+        let bytecode = compile_source("let f = fn() { let g = fn() { g(); }; g; };").unwrap();
+        let Object::CompiledFunction(g) = &bytecode.constants[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            g.instructions,
+            concat(&[
+                make(Opcode::CurrentClosure, &[]),
+                make(Opcode::Call, &[0]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_span_table() {
+        let bytecode = compile_source("let f = fn(x) {\n  x +\n  true;\n};\nf(1);").unwrap();
+        let Object::CompiledFunction(f) = &bytecode.constants[0] else {
+            panic!("expected a function");
+        };
+        // GetLocal 0, True, Add, ReturnValue
+        assert_eq!(span_at(&f.spans, 0), Some(&Span::new(2, 3)));
+        assert_eq!(span_at(&f.spans, 2), Some(&Span::new(3, 3)));
+        assert_eq!(span_at(&f.spans, 3), Some(&Span::new(2, 5)));
+        // Closure, SetGlobal 0, GetGlobal 0, Constant 1, Call 1
+        assert_eq!(span_at(&bytecode.spans, 0), Some(&Span::new(1, 9)));
+        assert_eq!(span_at(&bytecode.spans, 4), Some(&Span::new(1, 5)));
+        assert_eq!(span_at(&bytecode.spans, 13), Some(&Span::new(5, 2)));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let error = compile_source("let a = 1;\nb;").unwrap_err();
+        assert_eq!(error.message, "identifier not found: b");
+        assert_eq!(error.span, Span::new(2, 1));
+        let error = compile_source("fn() { let f = fn() { x; }; let x = 1; };").unwrap_err();
+        assert_eq!(error.message, "'x' is captured before its definition");
+        assert_eq!(
+            compile_source("import \"m\" as m;").unwrap_err().message,
+            "import is not supported"
+        );
+    }
+}
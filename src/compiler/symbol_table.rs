@@ -0,0 +1,316 @@
+//! Symbol tables: where the compiler stores each binding.
+//!
+//! There is one table per function being compiled, chained to the table of
+//! the enclosing function; the outermost table holds the globals. Inside a
+//! table, every block statement opens a nested block of names that share the
+//! function's slots.
+//!
+//! The `let` bindings of a block are declared before its statements are
+//! compiled, like the resolver's pending bindings. A use in a nested function
+//! may then refer to a binding defined later in an enclosing block: globals
+//! are looked up when the function runs, but a local would have to be
+//! captured before it has a value, which is reported as
+//! `Unresolved::CapturedBeforeDefinition`.
+
+use crate::object::BUILTINS;
+
+/// Where a symbol's value lives at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    Builtin,
+    /// A variable captured from an enclosing function.
+    Free,
+    /// The function being compiled, referring to itself by its `let` name.
+    Function,
+}
+
+/// A name and the slot it is stored in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// Why a name cannot be resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unresolved {
+    Undefined,
+    /// A nested function uses a local whose `let` has not run yet.
+    CapturedBeforeDefinition,
+}
+
+/// The names of one block.
+#[derive(Debug, Clone, Default)]
+struct Block {
+    /// Bindings whose definition has been compiled, in order.
+    defined: Vec<Symbol>,
+    /// `let` bindings of the block that have not been compiled yet.
+    pending: Vec<Symbol>,
+}
+
+/// The symbols of one function, or of the program for the globals.
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    blocks: Vec<Block>,
+    num_definitions: usize,
+    /// The symbols of enclosing functions this function captures, in the
+    /// order of their `Free` indexes.
+    free_symbols: Vec<Symbol>,
+    function_name: Option<Symbol>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTable {
+    /// The table of the globals.
+    pub fn new() -> Self {
+        SymbolTable {
+            outer: None,
+            blocks: vec![Block::default()],
+            num_definitions: 0,
+            free_symbols: Vec::new(),
+            function_name: None,
+        }
+    }
+
+    /// The table of a function nested in the one `outer` belongs to.
+    pub fn enclosed(outer: SymbolTable) -> Self {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::new()
+        }
+    }
+
+    /// Ends a function's table.
+    /// # Returns
+    /// The enclosing table, the number of slots the function needs and the
+    /// symbols it captures.
+    pub fn leave(self) -> (SymbolTable, usize, Vec<Symbol>) {
+        let outer = self.outer.expect("leaving the global table");
+        (*outer, self.num_definitions, self.free_symbols)
+    }
+
+    /// The number of slots used so far.
+    pub fn num_definitions(&self) -> usize {
+        self.num_definitions
+    }
+
+    pub fn push_block(&mut self) {
+        self.blocks.push(Block::default());
+    }
+
+    pub fn pop_block(&mut self) {
+        self.blocks.pop();
+    }
+
+    /// Reserves a slot for a `let` of the current block that has not been
+    /// compiled yet.
+    pub fn declare(&mut self, name: &str) {
+        let symbol = self.new_symbol(name);
+        self.current_block().pending.push(symbol);
+    }
+
+    /// Makes the first pending binding of `name` in the current block
+    /// visible, or defines a new one if there is none.
+    pub fn define_pending(&mut self, name: &str) -> Symbol {
+        let block = self.current_block();
+        match block.pending.iter().position(|symbol| symbol.name == name) {
+            Some(position) => {
+                let symbol = block.pending.remove(position);
+                block.defined.push(symbol.clone());
+                symbol
+            }
+            None => self.define(name),
+        }
+    }
+
+    /// Defines a binding in the current block, such as a parameter.
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let symbol = self.new_symbol(name);
+        self.current_block().defined.push(symbol.clone());
+        symbol
+    }
+
+    /// Lets the function refer to itself by the name it is bound to.
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+        self.function_name = Some(symbol.clone());
+        symbol
+    }
+
+    /// Finds the symbol `name` refers to at this point, capturing it as a
+    /// free variable if it belongs to an enclosing function.
+    pub fn resolve(&mut self, name: &str) -> Result<Symbol, Unresolved> {
+        self.resolve_from(name, false)
+    }
+
+    /// Resolves `name`; `nested` is true when the use is in a function
+    /// nested in this one, where pending bindings are visible.
+    fn resolve_from(&mut self, name: &str, nested: bool) -> Result<Symbol, Unresolved> {
+        for block in self.blocks.iter().rev() {
+            if let Some(symbol) = block.defined.iter().rev().find(|s| s.name == name) {
+                return Ok(symbol.clone());
+            }
+            if !nested {
+                continue;
+            }
+            if let Some(symbol) = block.pending.iter().find(|s| s.name == name) {
+                return match symbol.scope {
+                    SymbolScope::Global => Ok(symbol.clone()),
+                    _ => Err(Unresolved::CapturedBeforeDefinition),
+                };
+            }
+        }
+        if let Some(symbol) = self.function_name.as_ref().filter(|s| s.name == name) {
+            return Ok(symbol.clone());
+        }
+        if let Some(index) = self.free_symbols.iter().position(|s| s.name == name) {
+            return Ok(Symbol {
+                name: name.to_string(),
+                scope: SymbolScope::Free,
+                index,
+            });
+        }
+        let Some(outer) = self.outer.as_mut() else {
+            return match BUILTINS.iter().position(|builtin| builtin.name == name) {
+                Some(index) => Ok(Symbol {
+                    name: name.to_string(),
+                    scope: SymbolScope::Builtin,
+                    index,
+                }),
+                None => Err(Unresolved::Undefined),
+            };
+        };
+        let symbol = outer.resolve_from(name, true)?;
+        if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Builtin) {
+            return Ok(symbol);
+        }
+        self.free_symbols.push(symbol);
+        Ok(Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len() - 1,
+        })
+    }
+
+    fn new_symbol(&mut self, name: &str) -> Symbol {
+        let scope = if self.outer.is_none() {
+            SymbolScope::Global
+        } else {
+            SymbolScope::Local
+        };
+        self.num_definitions += 1;
+        Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions - 1,
+        }
+    }
+
+    fn current_block(&mut self) -> &mut Block {
+        self.blocks
+            .last_mut()
+            .expect("the outermost block is never popped")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            scope,
+            index,
+        }
+    }
+
+    #[test]
+    fn test_blocks_shadow_and_share_slots() {
+        let mut globals = SymbolTable::new();
+        globals.define("a");
+        let mut local = SymbolTable::enclosed(globals);
+        local.define("b");
+        local.push_block();
+        local.define("b");
+        assert_eq!(local.resolve("b"), Ok(symbol("b", SymbolScope::Local, 1)));
+        local.pop_block();
+        assert_eq!(local.resolve("b"), Ok(symbol("b", SymbolScope::Local, 0)));
+        assert_eq!(local.resolve("a"), Ok(symbol("a", SymbolScope::Global, 0)));
+        assert_eq!(
+            local.resolve("len"),
+            Ok(symbol("len", SymbolScope::Builtin, 0))
+        );
+        assert_eq!(local.resolve("c"), Err(Unresolved::Undefined));
+        assert_eq!(local.num_definitions(), 2);
+    }
+
+    #[test]
+    fn test_free_variables() {
+        let mut outer = SymbolTable::enclosed(SymbolTable::new());
+        outer.define("a");
+        outer.define("b");
+        let mut inner = SymbolTable::enclosed(outer);
+        inner.define("c");
+        assert_eq!(inner.resolve("b"), Ok(symbol("b", SymbolScope::Free, 0)));
+        assert_eq!(inner.resolve("a"), Ok(symbol("a", SymbolScope::Free, 1)));
+        assert_eq!(inner.resolve("b"), Ok(symbol("b", SymbolScope::Free, 0)));
+        let (_, num_locals, free) = inner.leave();
+        assert_eq!(num_locals, 1);
+        assert_eq!(
+            free,
+            vec![
+                symbol("b", SymbolScope::Local, 1),
+                symbol("a", SymbolScope::Local, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pending_bindings() {
+        let mut globals = SymbolTable::new();
+        globals.declare("later");
+        // Not visible in its own scope before the definition
+        assert_eq!(globals.resolve("later"), Err(Unresolved::Undefined));
+        let mut function = SymbolTable::enclosed(globals);
+        assert_eq!(
+            function.resolve("later"),
+            Ok(symbol("later", SymbolScope::Global, 0))
+        );
+        function.declare("local");
+        let mut inner = SymbolTable::enclosed(function);
+        assert_eq!(
+            inner.resolve("local"),
+            Err(Unresolved::CapturedBeforeDefinition)
+        );
+    }
+
+    #[test]
+    fn test_function_name() {
+        let mut function = SymbolTable::enclosed(SymbolTable::new());
+        function.define_function_name("f");
+        assert_eq!(
+            function.resolve("f"),
+            Ok(symbol("f", SymbolScope::Function, 0))
+        );
+        // A parameter of the same name hides it
+        function.define("f");
+        assert_eq!(
+            function.resolve("f"),
+            Ok(symbol("f", SymbolScope::Local, 0))
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! Statement and branch coverage for the AST evaluator.
+//!
+//! `Coverage` is an evaluator `Hook` that counts how often each statement
+//! runs and which way each `if` goes. Statements are identified by their
+//! first token and `if`s by their `if` keyword, so the counts of several
+//! runs of the same program add up. Registering a program with `add_program`
+//! first makes the statements and branches that never ran show up with a
+//! count of zero.
+//!
+//! Every `if` has two branches: the consequence, and the alternative, which
+//! for an `if` without `else` is the condition being false.
+//!
+//! `Coverage::lcov` writes the counts as an LCOV tracefile, which `genhtml`
+//! and most CI services read: a `DA` line per source line holding a
+//! statement, with the highest count of the statements starting on it, and
+//! a `BRDA` line per branch. `Coverage::summary` gives the percentages per
+//! file and shows each `else` arm that never ran.
+
+use crate::ast::expression::{Expression, IfExpression};
+use crate::ast::statement::Statement;
+use crate::ast::{Program, Visitor, visit};
+use crate::diagnostic::Diagnostic;
+use crate::evaluator::Hook;
+use crate::object::{Env, RuntimeError};
+use crate::parser::error::Span;
+use crate::source::{FileId, SourceMap};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// What is known about one `if`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Branches {
+    /// How often the consequence and the alternative were taken.
+    taken: [u64; 2],
+    /// Where the `else` block starts, if there is one.
+    alternative: Option<Span>,
+}
+
+/// Hits and totals of one file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Totals {
+    statements: (usize, usize),
+    branches: (usize, usize),
+}
+
+/// Execution counts of statements and `if` branches.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    statements: HashMap<Span, u64>,
+    branches: HashMap<Span, Branches>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers every statement and `if` of `program`, including those in
+    /// function bodies, so the ones that never run are reported.
+    pub fn add_program(&mut self, program: &Program) {
+        struct Register<'a>(&'a mut Coverage);
+
+        impl<'ast> Visitor<'ast> for Register<'_> {
+            fn visit_statement(&mut self, stmt: &'ast Statement) {
+                let span = Span::from_token(stmt.token());
+                self.0.statements.entry(span).or_default();
+                visit::walk_statement(self, stmt);
+            }
+
+            fn visit_if_expression(&mut self, expr: &'ast IfExpression) {
+                self.0.if_branches(expr);
+                visit::walk_if_expression(self, expr);
+            }
+        }
+
+        Register(self).visit_program(program);
+    }
+
+    fn if_branches(&mut self, expr: &IfExpression) -> &mut Branches {
+        self.branches
+            .entry(Span::from_token(&expr.token))
+            .or_insert_with(|| Branches {
+                taken: [0, 0],
+                alternative: match expr.alternative.as_deref() {
+                    Some(Expression::BlockStatement(block)) => Some(Span::from_token(&block.token)),
+                    _ => None,
+                },
+            })
+    }
+
+    /// How often the statement starting at `span` ran, if it is known.
+    pub fn statement_count(&self, span: &Span) -> Option<u64> {
+        self.statements.get(span).copied()
+    }
+
+    /// How often the consequence and the alternative of the `if` at `span`
+    /// were taken, if it is known.
+    pub fn branch_counts(&self, span: &Span) -> Option<[u64; 2]> {
+        self.branches.get(span).map(|branches| branches.taken)
+    }
+
+    /// The files with counts, in the order they were added to the source map.
+    fn files(&self) -> Vec<FileId> {
+        let mut files: Vec<FileId> = self
+            .statements
+            .keys()
+            .chain(self.branches.keys())
+            .map(|span| span.file)
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// The counts of each line of `file` holding a statement, by line.
+    fn lines(&self, file: FileId) -> BTreeMap<usize, u64> {
+        let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+        for (span, count) in &self.statements {
+            if span.file == file {
+                let line = lines.entry(span.line).or_default();
+                *line = (*line).max(*count);
+            }
+        }
+        lines
+    }
+
+    /// The `if`s of `file` in source order.
+    fn ifs(&self, file: FileId) -> Vec<(&Span, &Branches)> {
+        let mut ifs: Vec<_> = self
+            .branches
+            .iter()
+            .filter(|(span, _)| span.file == file)
+            .collect();
+        ifs.sort_by_key(|(span, _)| (span.line, span.column));
+        ifs
+    }
+
+    fn totals(&self, file: FileId) -> Totals {
+        let statements: Vec<u64> = self
+            .statements
+            .iter()
+            .filter(|(span, _)| span.file == file)
+            .map(|(_, count)| *count)
+            .collect();
+        let branches: Vec<u64> = self
+            .ifs(file)
+            .into_iter()
+            .flat_map(|(_, branches)| branches.taken)
+            .collect();
+        Totals {
+            statements: hits(&statements),
+            branches: hits(&branches),
+        }
+    }
+
+    /// Writes the counts as an LCOV tracefile.
+    /// # Parameters
+    /// - `sources`: Where the files' paths come from
+    /// # Returns
+    /// One record per file, in the order the files were added to `sources`.
+    pub fn lcov(&self, sources: &SourceMap) -> String {
+        let mut out = String::new();
+        for file in self.files() {
+            let _ = writeln!(out, "TN:\nSF:{}", path(sources, file));
+            let ifs = self.ifs(file);
+            for (block, (span, branches)) in ifs.iter().enumerate() {
+                let ran = branches.taken.iter().any(|&count| count > 0);
+                for (branch, count) in branches.taken.iter().enumerate() {
+                    // `-` marks an `if` that was never reached
+                    let count = match ran {
+                        true => count.to_string(),
+                        false => "-".to_string(),
+                    };
+                    let _ = writeln!(out, "BRDA:{},{},{},{}", span.line, block, branch, count);
+                }
+            }
+            let totals = self.totals(file);
+            let _ = writeln!(out, "BRF:{}\nBRH:{}", totals.branches.1, totals.branches.0);
+            let lines = self.lines(file);
+            for (line, count) in &lines {
+                let _ = writeln!(out, "DA:{},{}", line, count);
+            }
+            let hit = lines.values().filter(|&&count| count > 0).count();
+            let _ = writeln!(out, "LF:{}\nLH:{}\nend_of_record", lines.len(), hit);
+        }
+        out
+    }
+
+    /// Formats the statement and branch coverage of each file as a table,
+    /// followed by a warning at each `else` arm that never ran.
+    /// # Parameters
+    /// - `sources`: Where the files' paths and lines come from
+    /// # Returns
+    /// The table, with a total row when there are several files.
+    pub fn summary(&self, sources: &SourceMap) -> String {
+        let files = self.files();
+        let mut rows: Vec<(String, Totals)> = files
+            .iter()
+            .map(|&file| (path(sources, file), self.totals(file)))
+            .collect();
+        if rows.len() > 1 {
+            let mut total = Totals::default();
+            for (_, totals) in &rows {
+                total.statements.0 += totals.statements.0;
+                total.statements.1 += totals.statements.1;
+                total.branches.0 += totals.branches.0;
+                total.branches.1 += totals.branches.1;
+            }
+            rows.push(("total".to_string(), total));
+        }
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("file".len());
+        let mut out = format!(
+            "{:<width$}  {:>18}  {:>18}\n",
+            "file", "statements", "branches"
+        );
+        for (name, totals) in &rows {
+            let _ = writeln!(
+                out,
+                "{:<width$}  {:>18}  {:>18}",
+                name,
+                ratio(totals.statements),
+                ratio(totals.branches)
+            );
+        }
+        for file in files {
+            for (_, branches) in self.ifs(file) {
+                if let Some(span) = &branches.alternative
+                    && branches.taken[1] == 0
+                {
+                    let warning = Diagnostic::warning(span.clone(), "else branch never taken");
+                    let _ = writeln!(out, "\n{}", warning.render(sources));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// How many of `counts` are not zero, and how many there are.
+fn hits(counts: &[u64]) -> (usize, usize) {
+    let hit = counts.iter().filter(|&&count| count > 0).count();
+    (hit, counts.len())
+}
+
+/// Formats hits out of a total with the percentage, e.g. `3/4 (75.0%)`.
+fn ratio((hit, total): (usize, usize)) -> String {
+    match total {
+        0 => "0/0 (-)".to_string(),
+        _ => format!(
+            "{}/{} ({:.1}%)",
+            hit,
+            total,
+            100.0 * hit as f64 / total as f64
+        ),
+    }
+}
+
+fn path(sources: &SourceMap, file: FileId) -> String {
+    sources
+        .path(file)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+impl Hook for Coverage {
+    fn statement(&mut self, stmt: &Statement, _env: &Env) -> Result<(), RuntimeError> {
+        *self
+            .statements
+            .entry(Span::from_token(stmt.token()))
+            .or_default() += 1;
+        Ok(())
+    }
+
+    fn branch(&mut self, expr: &IfExpression, consequence: bool) {
+        let branch = match consequence {
+            true => 0,
+            false => 1,
+        };
+        self.if_branches(expr).taken[branch] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Evaluator;
+    use crate::lexer::Lexer;
+    use crate::parser::{Parser, test_helper::check_parser_errors};
+    use std::io;
+    use std::path::Path;
+
+    const PROGRAM: &str = "let sign = fn(n) {
+  if (n < 0) {
+    -1;
+  } else {
+    if (n == 0) { 0; } else { 1; };
+  };
+};
+let check = fn(n) {
+  if (n > 100) { puts(\"big\"); };
+  n;
+};
+sign(-2);
+sign(0);
+check(1);
+let unused = fn() {
+  0;
+};";
+
+    /// Runs `source` as `test.monkey` `runs` times under one `Coverage`.
+    fn cover(source: &str, runs: usize) -> (Coverage, SourceMap) {
+        let mut sources = SourceMap::new();
+        let file = sources.add("test.monkey", source);
+        let mut parser = Parser::new(Lexer::new(source.to_string(), file));
+        let program = parser.parse_program();
+        check_parser_errors(&parser);
+        let mut coverage = Coverage::new();
+        coverage.add_program(&program);
+        for _ in 0..runs {
+            let _ = Evaluator::with_output(io::sink()).eval_program_with(&program, &mut coverage);
+        }
+        (coverage, sources)
+    }
+
+    #[test]
+    fn test_lcov() {
+        let (coverage, sources) = cover(PROGRAM, 1);
+        assert_eq!(
+            coverage.lcov(&sources),
+            "TN:
+SF:test.monkey
+BRDA:2,0,0,1
+BRDA:2,0,1,1
+BRDA:5,1,0,1
+BRDA:5,1,1,0
+BRDA:9,2,0,0
+BRDA:9,2,1,1
+BRF:6
+BRH:4
+DA:1,1
+DA:2,2
+DA:3,1
+DA:5,1
+DA:8,1
+DA:9,1
+DA:10,1
+DA:12,1
+DA:13,1
+DA:14,1
+DA:15,1
+DA:16,0
+LF:12
+LH:11
+end_of_record
+"
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let (coverage, sources) = cover(PROGRAM, 1);
+        assert_eq!(
+            coverage.summary(&sources),
+            "file                 statements            branches
+test.monkey       12/15 (80.0%)         4/6 (66.7%)
+
+warning: else branch never taken
+ --> test.monkey:5:29
+  |
+5 |     if (n == 0) { 0; } else { 1; };
+  |                             ^
+"
+        );
+    }
+
+    #[test]
+    fn test_counts_add_up() {
+        let (coverage, sources) = cover(PROGRAM, 3);
+        let file = sources.find(Path::new("test.monkey")).unwrap();
+        // The `if` in sign runs once per call, two calls per run
+        assert_eq!(
+            coverage.statement_count(&Span::in_file(file, 2, 3)),
+            Some(6)
+        );
+        assert_eq!(
+            coverage.branch_counts(&Span::in_file(file, 2, 3)),
+            Some([3, 3])
+        );
+        assert_eq!(
+            coverage.branch_counts(&Span::in_file(file, 9, 3)),
+            Some([0, 3])
+        );
+        // Never reached, so LCOV cannot say which way it would go
+        let (coverage, sources) = cover("let f = fn(x) { if (x) { 1; }; };", 1);
+        assert!(
+            coverage
+                .lcov(&sources)
+                .contains("BRDA:1,0,0,-\nBRDA:1,0,1,-\n")
+        );
+        assert!(coverage.summary(&sources).contains("0/2 (0.0%)"));
+    }
+}
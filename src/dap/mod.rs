@@ -0,0 +1,534 @@
+//! Debug Adapter Protocol server over stdin/stdout (`monkey dap`).
+//!
+//! The adapter runs one Monkey file on the AST interpreter under the
+//! `debugger`. A session goes:
+//!
+//! 1. `initialize`, then `launch` with the file as `program` (and optionally
+//!    `stopOnEntry`); the adapter answers with the `initialized` event
+//! 2. `setBreakpoints` and `setExceptionBreakpoints`, then
+//!    `configurationDone`, which starts the program
+//! 3. at each pause a `stopped` event (`entry`, `breakpoint`, `step` or
+//!    `exception`), after which the client may ask for `threads`,
+//!    `stackTrace`, `scopes`, `variables` and `evaluate` before resuming with
+//!    `continue`, `next`, `stepIn` or `stepOut`
+//! 4. `output` events with what the program printed and its error, then
+//!    `exited` and `terminated`
+//!
+//! `disconnect` or `terminate` stops the program at any point.
+//!
+//! A breakpoint is moved to the first line at or after the requested one
+//! where a statement starts; the response says where it ended up. The
+//! `runtime` exception filter, on by default, pauses where a runtime error
+//! is raised. There is one thread, and lines and columns are 1-based.
+//!
+//! Messages use the same framing as the language server (`lsp::rpc`), and
+//! `run` takes its streams explicitly, so a session can be replayed from a
+//! recorded transcript in tests.
+
+use crate::ast::Program;
+use crate::cli::EXIT_OK;
+use crate::debugger::{
+    Debugger, Frame, Frontend, ProgramOutput, QUIT_MESSAGE, Resume, Session, Stop, statement_lines,
+};
+use crate::diagnostic::Diagnostic;
+use crate::evaluator::Evaluator;
+use crate::lexer::Lexer;
+use crate::lsp::rpc;
+use crate::object::{Env, Object, RuntimeError};
+use crate::parser::Parser;
+use crate::source::SourceMap;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The only thread.
+const THREAD_ID: u64 = 1;
+
+/// The exception filter that pauses at runtime errors.
+const RUNTIME_FILTER: &str = "runtime";
+
+/// Serves one debug session until `disconnect` or the end of the input.
+/// # Parameters
+/// - `input`: Where client requests are read from
+/// - `output`: Where responses and events are written
+/// # Returns
+/// `EXIT_OK`, or an error if the streams failed or a message was malformed.
+pub fn run<R: BufRead, W: Write>(input: R, output: W) -> io::Result<i32> {
+    let mut adapter = Adapter::new(input, output);
+    let Some(program) = adapter.configure()? else {
+        return Ok(EXIT_OK);
+    };
+    let mut evaluator = Evaluator::with_output(adapter.printed.clone());
+    let breakpoints = adapter.breakpoints.clone();
+    let stop_on_entry = adapter.stop_on_entry;
+    let stop_on_error = adapter.stop_on_error;
+    let mut debugger = Debugger::new(adapter, breakpoints)
+        .stop_on_entry(stop_on_entry)
+        .stop_on_error(stop_on_error);
+    let result = evaluator.eval_program_with(&program, &mut debugger);
+    let mut adapter = debugger.into_frontend();
+    if let Some(error) = adapter.failure.take() {
+        return Err(error);
+    }
+    adapter.finish(result)?;
+    adapter.drain()?;
+    Ok(EXIT_OK)
+}
+
+/// What a request asks the adapter to do besides answering.
+enum Action {
+    Answer,
+    /// Send the `initialized` event: the program is loaded.
+    Launched,
+    /// Start the program, after `configurationDone`.
+    Start,
+    Resume(Resume),
+    /// Stop the program and end the session.
+    Disconnect,
+}
+
+/// Why a request could not be carried out; sent back as its `message`.
+type Failure = String;
+
+/// The file that was launched.
+struct Launched {
+    path: PathBuf,
+    /// The lines where a statement starts, which breakpoints move to.
+    lines: BTreeSet<usize>,
+}
+
+struct Adapter<R, W> {
+    input: R,
+    output: W,
+    /// The `seq` of the last message sent.
+    seq: u64,
+    sources: SourceMap,
+    launched: Option<Launched>,
+    /// The launched program until it starts.
+    program: Option<Program>,
+    /// The breakpoints and filters set before the program starts; after
+    /// that they are changed in the debugger's `Session` too.
+    breakpoints: BTreeSet<usize>,
+    stop_on_entry: bool,
+    stop_on_error: bool,
+    printed: ProgramOutput,
+    /// The scopes behind each `variablesReference` handed out during the
+    /// current pause; reference `n` lists the scopes `variables[n - 1]`.
+    variables: Vec<Vec<Env>>,
+    /// The first I/O error while paused; the program is stopped when it
+    /// happens.
+    failure: Option<io::Error>,
+    disconnected: bool,
+}
+
+impl<R: BufRead, W: Write> Frontend for Adapter<R, W> {
+    fn paused(&mut self, stop: &Stop, session: &mut Session) -> Resume {
+        match self.pause(stop, session) {
+            Ok(resume) => resume,
+            Err(error) => {
+                self.failure = Some(error);
+                Resume::Quit
+            }
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Adapter<R, W> {
+    fn new(input: R, output: W) -> Self {
+        Adapter {
+            input,
+            output,
+            seq: 0,
+            sources: SourceMap::new(),
+            launched: None,
+            program: None,
+            breakpoints: BTreeSet::new(),
+            stop_on_entry: false,
+            stop_on_error: true,
+            printed: ProgramOutput::default(),
+            variables: Vec::new(),
+            failure: None,
+            disconnected: false,
+        }
+    }
+
+    // ============ SESSION PHASES ============
+
+    /// Answers requests until the program is launched and configured.
+    /// # Returns
+    /// The program to run, or `None` if the client left first.
+    fn configure(&mut self) -> io::Result<Option<Program>> {
+        let mut configured = false;
+        while let Some(request) = self.next_request()? {
+            match self.handle(&request, None)? {
+                Action::Start => configured = true,
+                Action::Disconnect => return Ok(None),
+                _ => {}
+            }
+            if configured && self.program.is_some() {
+                return Ok(self.program.take());
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reports a pause and answers requests until one resumes the program.
+    fn pause(&mut self, stop: &Stop, session: &mut Session) -> io::Result<Resume> {
+        self.flush_program_output()?;
+        let reason = match stop {
+            Stop::Entry => "entry",
+            Stop::Breakpoint => "breakpoint",
+            Stop::Step => "step",
+            Stop::Error(_) => "exception",
+        };
+        let mut body =
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Stop::Error(error) = stop {
+            body["text"] = json!(error.message);
+        }
+        self.event("stopped", body)?;
+        let mut resume = Resume::Quit;
+        while let Some(request) = self.next_request()? {
+            match self.handle(&request, Some(session))? {
+                Action::Resume(chosen) => {
+                    resume = chosen;
+                    break;
+                }
+                Action::Disconnect => break,
+                _ => {}
+            }
+        }
+        self.variables.clear();
+        Ok(resume)
+    }
+
+    /// Reports how the program ended.
+    fn finish(&mut self, result: Result<Object, RuntimeError>) -> io::Result<()> {
+        self.flush_program_output()?;
+        let exit_code = match result {
+            Ok(_) => 0,
+            Err(error) if error.message == QUIT_MESSAGE => 0,
+            Err(error) => {
+                let output = error.render(&self.sources) + "\n";
+                self.event("output", json!({ "category": "stderr", "output": output }))?;
+                1
+            }
+        };
+        self.event("exited", json!({ "exitCode": exit_code }))?;
+        self.event("terminated", json!({}))
+    }
+
+    /// Answers requests after the program has ended, until the client
+    /// leaves.
+    fn drain(&mut self) -> io::Result<()> {
+        while !self.disconnected
+            && let Some(request) = self.next_request()?
+        {
+            self.handle(&request, None)?;
+        }
+        Ok(())
+    }
+
+    // ============ REQUESTS ============
+
+    /// Answers one request.
+    /// # Parameters
+    /// - `request`: The client's message
+    /// - `session`: The paused program, or `None` before it starts and
+    ///   after it ends
+    fn handle(&mut self, request: &Value, session: Option<&mut Session>) -> io::Result<Action> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        log::debug!("dap: {}", command);
+        let reply = match command {
+            "initialize" => Ok((Action::Answer, capabilities())),
+            "launch" => self.launch(args)?.map(|()| (Action::Launched, Value::Null)),
+            "setBreakpoints" => Ok((Action::Answer, self.set_breakpoints(args, session))),
+            "setExceptionBreakpoints" => {
+                let filters = args["filters"].as_array().cloned().unwrap_or_default();
+                self.stop_on_error = filters.iter().any(|filter| filter == RUNTIME_FILTER);
+                if let Some(session) = session {
+                    session.set_stop_on_error(self.stop_on_error);
+                }
+                Ok((Action::Answer, Value::Null))
+            }
+            "configurationDone" => Ok((Action::Start, Value::Null)),
+            "threads" => Ok((
+                Action::Answer,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            )),
+            "stackTrace" => Ok((Action::Answer, self.stack_trace(session.as_deref()))),
+            "scopes" => paused(session)
+                .and_then(|session| frame(session, args))
+                .map(|frame| (Action::Answer, self.scopes(frame))),
+            "variables" => self
+                .variables(args)
+                .map(|variables| (Action::Answer, json!({ "variables": variables }))),
+            "evaluate" => paused(session).and_then(|session| {
+                let frame = args["frameId"].as_u64().unwrap_or(0) as usize;
+                let expression = args["expression"].as_str().unwrap_or_default();
+                let value = session.evaluate(frame, expression)?;
+                Ok((Action::Answer, describe_result(&value)))
+            }),
+            "continue" => paused(session).map(|_| {
+                (
+                    Action::Resume(Resume::Continue),
+                    json!({ "allThreadsContinued": true }),
+                )
+            }),
+            "next" => paused(session).map(|_| (Action::Resume(Resume::StepOver), Value::Null)),
+            "stepIn" => paused(session).map(|_| (Action::Resume(Resume::StepIn), Value::Null)),
+            "stepOut" => paused(session).map(|_| (Action::Resume(Resume::StepOut), Value::Null)),
+            "disconnect" | "terminate" => {
+                self.disconnected = true;
+                Ok((Action::Disconnect, Value::Null))
+            }
+            other => Err(format!("unknown command '{}'", other)),
+        };
+        match reply {
+            Ok((action, body)) => {
+                self.respond(request, Ok(body))?;
+                if let Action::Launched = action {
+                    self.event("initialized", json!({}))?;
+                    return Ok(Action::Answer);
+                }
+                Ok(action)
+            }
+            Err(message) => {
+                self.respond(request, Err(message))?;
+                Ok(Action::Answer)
+            }
+        }
+    }
+
+    /// Loads the file named by `program`. Syntax errors are sent as output.
+    fn launch(&mut self, args: &Value) -> io::Result<Result<(), Failure>> {
+        if self.launched.is_some() {
+            return Ok(Err("a program is already launched".to_string()));
+        }
+        let Some(path) = args["program"].as_str() else {
+            return Ok(Err("missing 'program'".to_string()));
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) => return Ok(Err(format!("cannot read {}: {}", path, error))),
+        };
+        let file = self.sources.add(path, text.as_str());
+        let mut parser = Parser::new(Lexer::new(text, file));
+        let program = parser.parse_program();
+        if !parser.errors.is_empty() {
+            for error in &parser.errors {
+                let output = Diagnostic::from(error).render(&self.sources) + "\n";
+                self.event("output", json!({ "category": "stderr", "output": output }))?;
+            }
+            return Ok(Err(format!("{} has syntax errors", path)));
+        }
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.launched = Some(Launched {
+            path: PathBuf::from(path),
+            lines: statement_lines(&program),
+        });
+        self.program = Some(program);
+        Ok(Ok(()))
+    }
+
+    /// Replaces the breakpoints of the launched file, moving each to the
+    /// first line at or after it where a statement starts.
+    fn set_breakpoints(&mut self, args: &Value, session: Option<&mut Session>) -> Value {
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let path = args["source"]["path"].as_str().map(Path::new);
+        let launched = self
+            .launched
+            .as_ref()
+            .filter(|launched| path == Some(launched.path.as_path()));
+        let mut lines = BTreeSet::new();
+        let breakpoints: Vec<Value> = requested
+            .iter()
+            .map(|breakpoint| {
+                let line = breakpoint["line"].as_u64().unwrap_or(0) as usize;
+                let Some(launched) = launched else {
+                    return unverified(line, "not the launched program");
+                };
+                match launched.lines.range(line..).next() {
+                    Some(&actual) => {
+                        lines.insert(actual);
+                        json!({ "verified": true, "line": actual })
+                    }
+                    None => unverified(line, "no statement at or after this line"),
+                }
+            })
+            .collect();
+        if let Some(session) = session {
+            session.replace_breakpoints(lines.iter().copied());
+        }
+        self.breakpoints = lines;
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn stack_trace(&self, session: Option<&Session>) -> Value {
+        let frames: Vec<Value> = session
+            .into_iter()
+            .flat_map(Session::frames)
+            .enumerate()
+            .map(|(id, frame)| {
+                let path = self.sources.path(frame.span.file).unwrap_or(Path::new(""));
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                json!({
+                    "id": id,
+                    "name": frame.name,
+                    "line": frame.span.line,
+                    "column": frame.span.column,
+                    "source": { "name": name, "path": path },
+                })
+            })
+            .collect();
+        json!({ "totalFrames": frames.len(), "stackFrames": frames })
+    }
+
+    /// The scopes of a frame: its locals, then the globals.
+    fn scopes(&mut self, frame: &Frame) -> Value {
+        let mut locals = Vec::new();
+        let mut globals = None;
+        let mut scope = Some(Rc::clone(&frame.env));
+        while let Some(env) = scope {
+            scope = env.borrow().outer();
+            match scope {
+                Some(_) => locals.push(env),
+                None => globals = Some(env),
+            }
+        }
+        let mut scopes = Vec::new();
+        if !locals.is_empty() {
+            scopes.push(self.scope("Locals", locals));
+        }
+        scopes.extend(globals.map(|globals| self.scope("Globals", vec![globals])));
+        json!({ "scopes": scopes })
+    }
+
+    fn scope(&mut self, name: &str, envs: Vec<Env>) -> Value {
+        self.variables.push(envs);
+        json!({
+            "name": name,
+            "variablesReference": self.variables.len(),
+            "expensive": false,
+        })
+    }
+
+    /// The variables of a scope reference, sorted by name; an inner binding
+    /// hides an outer one of the same name.
+    fn variables(&self, args: &Value) -> Result<Vec<Value>, Failure> {
+        let reference = args["variablesReference"].as_u64().unwrap_or(0) as usize;
+        let envs = reference
+            .checked_sub(1)
+            .and_then(|index| self.variables.get(index))
+            .ok_or_else(|| format!("unknown variablesReference {}", reference))?;
+        let mut variables = BTreeMap::new();
+        for env in envs {
+            for (name, value) in env.borrow().bindings() {
+                variables.entry(name).or_insert(value);
+            }
+        }
+        Ok(variables
+            .into_iter()
+            .map(|(name, value)| {
+                json!({
+                    "name": name,
+                    "value": value.to_string(),
+                    "type": value.type_name(),
+                    "variablesReference": 0,
+                })
+            })
+            .collect())
+    }
+
+    // ============ MESSAGES ============
+
+    /// Reads the next request, skipping anything else.
+    /// # Returns
+    /// The request, or `None` at the end of the input.
+    fn next_request(&mut self) -> io::Result<Option<Value>> {
+        while let Some(body) = rpc::read_message(&mut self.input)? {
+            match serde_json::from_str::<Value>(&body) {
+                Ok(message) if message["type"] == "request" => return Ok(Some(message)),
+                Ok(_) => {}
+                Err(error) => log::debug!("dap: ignoring malformed message: {}", error),
+            }
+        }
+        Ok(None)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, Failure>) -> io::Result<()> {
+        self.seq += 1;
+        let mut response = json!({
+            "seq": self.seq,
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        rpc::write_message(&mut self.output, &response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.seq += 1;
+        let message = json!({ "seq": self.seq, "type": "event", "event": event, "body": body });
+        rpc::write_message(&mut self.output, &message)
+    }
+
+    /// Sends what the program printed since the last call as an `output`
+    /// event.
+    fn flush_program_output(&mut self) -> io::Result<()> {
+        let printed = self.printed.take();
+        if printed.is_empty() {
+            return Ok(());
+        }
+        let output = String::from_utf8_lossy(&printed);
+        self.event("output", json!({ "category": "stdout", "output": output }))
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsTerminateRequest": true,
+        "exceptionBreakpointFilters": [{
+            "filter": RUNTIME_FILTER,
+            "label": "Runtime errors",
+            "default": true,
+        }],
+    })
+}
+
+/// The session of a paused program, or why a request needs one.
+fn paused(session: Option<&mut Session>) -> Result<&mut Session, Failure> {
+    session.ok_or_else(|| "the program is not paused".to_string())
+}
+
+/// The frame named by `frameId`.
+fn frame<'a>(session: &'a Session, args: &Value) -> Result<&'a Frame, Failure> {
+    let id = args["frameId"].as_u64().unwrap_or(0);
+    session
+        .frame(id as usize)
+        .ok_or_else(|| format!("unknown frame {}", id))
+}
+
+fn unverified(line: usize, message: &str) -> Value {
+    json!({ "verified": false, "line": line, "message": message })
+}
+
+/// The body of an `evaluate` response.
+fn describe_result(value: &Object) -> Value {
+    json!({
+        "result": value.to_string(),
+        "type": value.type_name(),
+        "variablesReference": 0,
+    })
+}
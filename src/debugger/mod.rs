@@ -0,0 +1,477 @@
+//! A step debugger for the AST evaluator.
+//!
+//! `Debugger` is an evaluator `Hook`. It follows the running program's call
+//! stack and pauses it on entry, at line breakpoints, after a step and where
+//! a runtime error is raised. At each pause it hands a `Session` to its
+//! `Frontend`, which shows the program's state, may change breakpoints and
+//! variables, and says how to go on. The REPL's `:debug` command is one
+//! frontend.
+//!
+//! Pauses happen before statements. Stepping is by statement and counts
+//! depth in calls, not blocks:
+//!
+//! - step in stops at the next statement anywhere;
+//! - step over stops at the next statement of the current call or a caller;
+//! - step out stops at the next statement of a caller.
+//!
+//! A breakpoint on a line pauses the first statement on that line each time
+//! execution arrives at it, so a line holding several statements pauses once.
+//! `statement_lines` lists the lines a breakpoint can pause at.
+
+use crate::ast::expression::Expression;
+use crate::ast::statement::Statement;
+use crate::ast::{Program, Visitor, visit};
+use crate::evaluator::{Evaluator, Hook};
+use crate::lexer::{Lexer, token::Token};
+use crate::object::{Env, Function, Object, RuntimeError};
+use crate::parser::{Parser, error::Span};
+use crate::source::FileId;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
+
+/// The message of the error a program stops with when the frontend quits.
+pub const QUIT_MESSAGE: &str = "stopped by the debugger";
+
+/// Why the program paused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    /// Before the first statement.
+    Entry,
+    /// At a statement on a line with a breakpoint.
+    Breakpoint,
+    /// After a step in, over or out.
+    Step,
+    /// Where a runtime error was raised, before it unwinds.
+    Error(RuntimeError),
+}
+
+/// How the frontend wants the program to go on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+    /// Stops the program with `QUIT_MESSAGE`.
+    Quit,
+}
+
+/// The part of a debugger that talks to the user.
+pub trait Frontend {
+    /// Called each time the program pauses.
+    /// # Parameters
+    /// - `stop`: Why it paused
+    /// - `session`: The paused program's call stack and breakpoints
+    /// # Returns
+    /// How to go on.
+    fn paused(&mut self, stop: &Stop, session: &mut Session) -> Resume;
+}
+
+/// One call on the paused program's stack.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The function's name, `<anonymous>`, or `<main>` for the program.
+    pub name: String,
+    /// The statement the frame is running, or for a caller, its call.
+    pub span: Span,
+    /// The innermost scope of the frame.
+    pub env: Env,
+}
+
+/// What a frontend can see and change while the program is paused.
+#[derive(Debug)]
+pub struct Session {
+    breakpoints: BTreeSet<usize>,
+    frames: Vec<Frame>,
+    stop_on_error: bool,
+}
+
+impl Session {
+    /// The call stack, innermost frame first.
+    pub fn frames(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter().rev()
+    }
+
+    /// The innermost frame, where the program is paused.
+    pub fn current(&self) -> Option<&Frame> {
+        self.frames.last()
+    }
+
+    /// A frame by its position in `frames`; 0 is the innermost.
+    pub fn frame(&self, index: usize) -> Option<&Frame> {
+        self.frames.iter().rev().nth(index)
+    }
+
+    /// The lines with a breakpoint, in order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Pauses the program at `line` from now on.
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    /// # Returns
+    /// False if `line` had no breakpoint.
+    pub fn clear_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.remove(&line)
+    }
+
+    /// Replaces every breakpoint with the ones at `lines`.
+    pub fn replace_breakpoints(&mut self, lines: impl IntoIterator<Item = usize>) {
+        self.breakpoints = lines.into_iter().collect();
+    }
+
+    /// Chooses whether the program pauses where a runtime error is raised.
+    pub fn set_stop_on_error(&mut self, stop: bool) {
+        self.stop_on_error = stop;
+    }
+
+    /// Evaluates a Monkey expression in a frame. Its `puts` output is
+    /// discarded.
+    /// # Parameters
+    /// - `frame`: The frame's position in `frames`; 0 is the innermost
+    /// - `source`: The expression
+    /// # Returns
+    /// The value, or a message saying why the source did not parse or run.
+    pub fn evaluate(&self, frame: usize, source: &str) -> Result<Object, String> {
+        let frame = self.frame(frame).ok_or("no such frame")?;
+        let expr = parse_expression(source)?;
+        Evaluator::with_output(io::sink())
+            .eval_in(&expr, &frame.env)
+            .map_err(|error| error.message)
+    }
+
+    /// Evaluates `source` like `evaluate` and stores it in the variable
+    /// `name` of the frame's scope chain.
+    pub fn assign(&self, frame: usize, name: &str, source: &str) -> Result<Object, String> {
+        let value = self.evaluate(frame, source)?;
+        let frame = self.frame(frame).ok_or("no such frame")?;
+        if frame.env.borrow_mut().assign(name, value.clone()) {
+            Ok(value)
+        } else {
+            Err(format!("no variable named {}", name))
+        }
+    }
+}
+
+/// Parses source text that must hold exactly one expression; its closing
+/// `;` may be left out.
+fn parse_expression(source: &str) -> Result<Expression, String> {
+    let source = source.trim_end();
+    let terminated = match source.ends_with(';') {
+        true => source.to_string(),
+        false => format!("{};", source),
+    };
+    let mut parser = Parser::new(Lexer::new(terminated, FileId::DETACHED));
+    let mut program = parser.parse_program();
+    if let Some(error) = parser.errors().first() {
+        return Err(error.to_string());
+    }
+    match program.statements.pop() {
+        Some(Statement::Expression(stmt)) if program.statements.is_empty() => Ok(stmt.value),
+        _ => Err(format!("not an expression: {}", source)),
+    }
+}
+
+/// The lines where a statement starts, including statements in function
+/// bodies and blocks: the lines a breakpoint can pause at.
+pub fn statement_lines(program: &Program) -> BTreeSet<usize> {
+    struct Lines(BTreeSet<usize>);
+
+    impl<'ast> Visitor<'ast> for Lines {
+        fn visit_statement(&mut self, stmt: &'ast Statement) {
+            self.0.insert(stmt.token().line);
+            visit::walk_statement(self, stmt);
+        }
+    }
+
+    let mut lines = Lines(BTreeSet::new());
+    lines.visit_program(program);
+    lines.0
+}
+
+/// Collects what the program prints with `puts` until a frontend shows it;
+/// the evaluator needs a `'static` writer.
+#[derive(Debug, Clone, Default)]
+pub struct ProgramOutput(Rc<RefCell<Vec<u8>>>);
+
+impl ProgramOutput {
+    /// Takes what was printed since the last call.
+    pub fn take(&self) -> Vec<u8> {
+        mem::take(&mut *self.0.borrow_mut())
+    }
+}
+
+impl Write for ProgramOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How far the program runs before the next pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Entry,
+    Run,
+    StepIn,
+    /// Until a statement at most this many calls deep.
+    StepOver(usize),
+    /// Until a statement fewer than this many calls deep.
+    StepOut(usize),
+    Quit,
+}
+
+/// An evaluator hook that pauses the program for a `Frontend`.
+pub struct Debugger<F> {
+    frontend: F,
+    session: Session,
+    mode: Mode,
+    /// The line and call depth of the last statement, so a breakpoint does
+    /// not pause every statement of its line.
+    last: Option<(usize, usize)>,
+    stop_on_entry: bool,
+}
+
+impl<F: Frontend> Debugger<F> {
+    /// A debugger that pauses on entry, at `breakpoints` and at errors.
+    pub fn new(frontend: F, breakpoints: impl IntoIterator<Item = usize>) -> Self {
+        Debugger {
+            frontend,
+            session: Session {
+                breakpoints: breakpoints.into_iter().collect(),
+                frames: Vec::new(),
+                stop_on_error: true,
+            },
+            mode: Mode::Entry,
+            last: None,
+            stop_on_entry: true,
+        }
+    }
+
+    /// Chooses whether the program pauses before its first statement.
+    pub fn stop_on_entry(mut self, stop: bool) -> Self {
+        self.stop_on_entry = stop;
+        self
+    }
+
+    /// Chooses whether the program pauses where a runtime error is raised.
+    pub fn stop_on_error(mut self, stop: bool) -> Self {
+        self.session.stop_on_error = stop;
+        self
+    }
+
+    /// Gives the frontend back once the program has finished.
+    pub fn into_frontend(self) -> F {
+        self.frontend
+    }
+
+    fn pause(&mut self, stop: Stop) {
+        let depth = self.session.frames.len();
+        self.mode = match self.frontend.paused(&stop, &mut self.session) {
+            Resume::Continue => Mode::Run,
+            Resume::StepIn => Mode::StepIn,
+            Resume::StepOver => Mode::StepOver(depth),
+            Resume::StepOut => Mode::StepOut(depth),
+            Resume::Quit => Mode::Quit,
+        };
+    }
+}
+
+impl<F: Frontend> Hook for Debugger<F> {
+    fn statement(&mut self, stmt: &Statement, env: &Env) -> Result<(), RuntimeError> {
+        if self.mode == Mode::Quit {
+            return Err(RuntimeError::new(QUIT_MESSAGE));
+        }
+        let span = Span::from_token(stmt.token());
+        match self.session.frames.last_mut() {
+            Some(frame) => {
+                frame.span = span.clone();
+                frame.env = env.clone();
+            }
+            None => self.session.frames.push(Frame {
+                name: "<main>".to_string(),
+                span: span.clone(),
+                env: env.clone(),
+            }),
+        }
+        let depth = self.session.frames.len();
+        let here = (span.line, depth);
+        let stop = if self.session.breakpoints.contains(&span.line) && self.last != Some(here) {
+            Some(Stop::Breakpoint)
+        } else {
+            match self.mode {
+                Mode::Entry => self.stop_on_entry.then_some(Stop::Entry),
+                Mode::StepIn => Some(Stop::Step),
+                Mode::StepOver(from) if depth <= from => Some(Stop::Step),
+                Mode::StepOut(from) if depth < from => Some(Stop::Step),
+                _ => None,
+            }
+        };
+        self.last = Some(here);
+        if self.mode == Mode::Entry {
+            self.mode = Mode::Run;
+        }
+        if let Some(stop) = stop {
+            self.pause(stop);
+        }
+        match self.mode {
+            Mode::Quit => Err(RuntimeError::new(QUIT_MESSAGE)),
+            _ => Ok(()),
+        }
+    }
+
+    fn enter(&mut self, function: &Function, call: &Token, env: &Env) {
+        let span = Span::from_token(call);
+        if let Some(caller) = self.session.frames.last_mut() {
+            caller.span = span.clone();
+        }
+        self.session.frames.push(Frame {
+            name: function
+                .name
+                .clone()
+                .unwrap_or_else(|| "<anonymous>".to_string()),
+            span,
+            env: env.clone(),
+        });
+    }
+
+    fn exit(&mut self, _function: &Function) {
+        self.session.frames.pop();
+    }
+
+    fn error(&mut self, error: &RuntimeError, env: &Env) {
+        if !self.session.stop_on_error || self.mode == Mode::Quit {
+            return;
+        }
+        if let Some(frame) = self.session.frames.last_mut() {
+            if let Some(span) = &error.span {
+                frame.span = span.clone();
+            }
+            frame.env = env.clone();
+        }
+        self.pause(Stop::Error(error.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::test_helper::check_parser_errors;
+
+    /// Records every pause as `reason line:frames` and replies from a script.
+    struct Scripted {
+        replies: Vec<Resume>,
+        pauses: Vec<String>,
+    }
+
+    impl Frontend for Scripted {
+        fn paused(&mut self, stop: &Stop, session: &mut Session) -> Resume {
+            let reason = match stop {
+                Stop::Entry => "entry".to_string(),
+                Stop::Breakpoint => "breakpoint".to_string(),
+                Stop::Step => "step".to_string(),
+                Stop::Error(error) => format!("error({})", error.message),
+            };
+            let frames: Vec<_> = session.frames().map(|f| f.name.as_str()).collect();
+            let line = session.current().unwrap().span.line;
+            self.pauses
+                .push(format!("{} {}:{}", reason, line, frames.join(",")));
+            if self.replies.is_empty() {
+                Resume::Continue
+            } else {
+                self.replies.remove(0)
+            }
+        }
+    }
+
+    const PROGRAM: &str = "let add = fn(a, b) {
+  let sum = a + b;
+  sum;
+};
+let x = add(1, 2);
+let y = add(x, 3);
+y;";
+
+    fn debug(source: &str, breakpoints: &[usize], replies: Vec<Resume>) -> Vec<String> {
+        let mut parser = Parser::new(Lexer::new(source.to_string(), FileId::DETACHED));
+        let program = parser.parse_program();
+        check_parser_errors(&parser);
+        let frontend = Scripted {
+            replies,
+            pauses: Vec::new(),
+        };
+        let mut debugger = Debugger::new(frontend, breakpoints.iter().copied());
+        let _ = Evaluator::with_output(io::sink()).eval_program_with(&program, &mut debugger);
+        debugger.into_frontend().pauses
+    }
+
+    #[test]
+    fn test_steps() {
+        use Resume::*;
+        assert_eq!(
+            debug(
+                PROGRAM,
+                &[],
+                vec![StepOver, StepIn, StepIn, StepOut, StepOver]
+            ),
+            [
+                "entry 1:<main>",
+                "step 5:<main>",
+                "step 2:add,<main>",
+                "step 3:add,<main>",
+                "step 6:<main>",
+                "step 7:<main>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        assert_eq!(
+            debug(PROGRAM, &[2, 7], vec![]),
+            [
+                "entry 1:<main>",
+                "breakpoint 2:add,<main>",
+                "breakpoint 2:add,<main>",
+                "breakpoint 7:<main>"
+            ]
+        );
+        let recursive =
+            "let count = fn(n) { if (n > 0) { count(n - 1); } else { n; }; };\ncount(2);";
+        assert_eq!(
+            debug(recursive, &[1], vec![Resume::Continue]).len(),
+            // The `let`, then the body of each of the three calls
+            4
+        );
+    }
+
+    #[test]
+    fn test_statement_lines() {
+        let mut parser = Parser::new(Lexer::new(PROGRAM.to_string(), FileId::DETACHED));
+        let program = parser.parse_program();
+        assert_eq!(
+            statement_lines(&program).into_iter().collect::<Vec<_>>(),
+            [1, 2, 3, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn test_errors_and_quit() {
+        let failing = "let f = fn(n) {\n  n / 0;\n};\nf(1);";
+        assert_eq!(
+            debug(failing, &[], vec![]),
+            ["entry 1:<main>", "error(division by zero) 2:f,<main>"]
+        );
+        assert_eq!(debug(PROGRAM, &[6], vec![Resume::Quit]), ["entry 1:<main>"]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Source-annotated diagnostics.
+//!
+//! A `Diagnostic` pairs a message with the `Span` it refers to. Rendering it
+//! against a `SourceMap` prints the `path:line:col` location followed by the
+//! offending source line and a caret under the column:
+//!
+//! ```text
+//! error: expected token to be ASSIGN, got INT
+//!  --> main.monkey:1:7
+//!   |
+//! 1 | let x 5;
+//!   |       ^
+//! ```
+//!
+//! Labels point at other places the message is about, such as an earlier
+//! binding; each is rendered as a note with its own location and snippet.
+
+use crate::parser::error::{ParserError, Span};
+use crate::source::SourceMap;
+use std::fmt;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Context for a preceding error, such as a frame of a stack trace.
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A message attached to a source location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+    /// Extra lines printed after the snippet, e.g. hints.
+    pub notes: Vec<String>,
+    /// Other locations the message refers to, with what each one is.
+    pub labels: Vec<(Span, String)>,
+}
+
+impl Diagnostic {
+    /// Create an error diagnostic at the given span.
+    pub fn error(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            span,
+            message: message.into(),
+            notes: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Create a warning diagnostic at the given span.
+    pub fn warning(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(span, message)
+        }
+    }
+
+    /// Create a note diagnostic at the given span.
+    pub fn note(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Note,
+            ..Self::error(span, message)
+        }
+    }
+
+    /// Returns the diagnostic with an extra note appended.
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Returns the diagnostic with a label at another location appended.
+    pub fn with_label(mut self, span: Span, label: impl Into<String>) -> Self {
+        self.labels.push((span, label.into()));
+        self
+    }
+
+    /// Renders the diagnostic with its location and source snippet, then
+    /// each label as a note at its own location.
+    ///
+    /// If the span's file or line is not available in `sources`, only the
+    /// message and location are printed.
+    /// # Parameters
+    /// - `sources`: The source map that owns the span's file
+    /// # Returns
+    /// The rendered diagnostic, without a trailing newline.
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut out = format!("{}: {}\n", self.severity, self.message);
+        out.push_str(&format!(" --> {}", sources.location(&self.span)));
+
+        let line_text = sources
+            .get(self.span.file)
+            .and_then(|file| file.line(self.span.line));
+        if let Some(text) = line_text {
+            let number = self.span.line.to_string();
+            let gutter = " ".repeat(number.len());
+            // Keep tabs so the caret lines up with the source line
+            let indent: String = text
+                .chars()
+                .take(self.span.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            out.push_str(&format!("\n{} |", gutter));
+            out.push_str(&format!("\n{} | {}", number, text));
+            out.push_str(&format!("\n{} | {}^", gutter, indent));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("\n  = note: {}", note));
+        }
+        for (span, label) in &self.labels {
+            out.push('\n');
+            out.push_str(&Diagnostic::note(span.clone(), label.clone()).render(sources));
+        }
+        out
+    }
+}
+
+impl From<&ParserError> for Diagnostic {
+    fn from(error: &ParserError) -> Self {
+        Diagnostic::error(error.span.clone(), error.message.clone())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// Formats the diagnostic on one line, without source text.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {}", self.severity, self.span, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_render_parser_error_with_snippet() {
+        let mut sources = SourceMap::new();
+        sources.add("main.monkey", "let a = 1;\n");
+        let input = "let ok = 1;\nlet x 5;\n";
+        let file = sources.add("lib/math.monkey", input);
+
+        let mut parser = Parser::new(Lexer::new(input.to_string(), file));
+        parser.parse_program();
+        let diagnostic = Diagnostic::from(&parser.errors[0]);
+
+        assert_eq!(
+            diagnostic.render(&sources),
+            "error: expected token to be ASSIGN, got INT\n \
+             --> lib/math.monkey:2:7\n  \
+              |\n\
+             2 | let x 5;\n  \
+              |       ^"
+        );
+    }
+
+    #[test]
+    fn test_render_without_source_text() {
+        let sources = SourceMap::new();
+        let diagnostic = Diagnostic::warning(Span::new(3, 4), "unused").with_note("remove it");
+
+        assert_eq!(
+            diagnostic.render(&sources),
+            "warning: unused\n --> <unknown>:3:4\n  = note: remove it"
+        );
+    }
+
+    #[test]
+    fn test_render_labels() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.monkey", "let x = 1;\nlet x = 2;\n");
+        let diagnostic = Diagnostic::warning(Span::in_file(file, 2, 5), "shadowed")
+            .with_note("rename it")
+            .with_label(Span::in_file(file, 1, 5), "previous binding here");
+
+        assert_eq!(
+            diagnostic.render(&sources),
+            "warning: shadowed\n --> main.monkey:2:5\n  |\n2 | let x = 2;\n  |     ^\n  \
+             = note: rename it\n\
+             note: previous binding here\n --> main.monkey:1:5\n  |\n1 | let x = 1;\n  |     ^"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! A disassembler for compiled bytecode.
+//!
+//! `disassemble` prints the constant pool, the top-level instructions and
+//! then the body of every compiled function in the pool:
+//!
+//! ```text
+//! == constants ==
+//! 0 INTEGER 1
+//! 1 FUNCTION <fn inc> parameters=1 locals=1
+//!
+//! == main ==
+//! 0000 Closure 1 0          ; <fn inc>
+//! 0004 SetGlobal 0
+//! 0007 Null
+//! 0008 ReturnValue
+//!
+//! == constant 1: <fn inc> ==
+//! 0000 GetLocal 0
+//! 0002 Constant 0           ; 1
+//! 0005 Add
+//! 0006 ReturnValue
+//! ```
+//!
+//! Each instruction line is its offset, the opcode name and the decoded
+//! operands, followed by a comment naming the constant or builtin it refers
+//! to. The format is meant to be compared in tests, so it only changes when
+//! the instruction set does. Malformed instructions are shown as such rather
+//! than rejected, since the disassembler is how bad bytecode gets inspected.
+
+use crate::code::{Opcode, read_operands};
+use crate::compiler::Bytecode;
+use crate::object::{BUILTINS, Object};
+use std::fmt::Write;
+
+/// The column at which operand comments start, after the offset.
+const COMMENT_COLUMN: usize = 20;
+
+/// Prints a whole program: constants, main instructions and function bodies.
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let constants = &bytecode.constants;
+    let mut out = String::from("== constants ==\n");
+    for (index, constant) in constants.iter().enumerate() {
+        writeln!(out, "{} {}", index, describe(constant)).unwrap();
+    }
+    out.push_str("\n== main ==\n");
+    out.push_str(&disassemble_instructions(&bytecode.instructions, constants));
+    for (index, constant) in constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant {
+            writeln!(out, "\n== constant {}: {} ==", index, constant).unwrap();
+            out.push_str(&disassemble_instructions(&function.instructions, constants));
+        }
+    }
+    out
+}
+
+/// Prints one instruction per line.
+/// # Parameters
+/// - `instructions`: The instruction stream
+/// - `constants`: The constant pool, used to comment `Constant` and `Closure`
+///   operands
+pub fn disassemble_instructions(instructions: &[u8], constants: &[Object]) -> String {
+    let mut out = String::new();
+    let mut offset = 0;
+    while offset < instructions.len() {
+        let byte = instructions[offset];
+        let Some(op) = Opcode::from_byte(byte) else {
+            writeln!(out, "{:04} <invalid opcode {}>", offset, byte).unwrap();
+            break;
+        };
+        let Some((operands, read)) = read_operands(op, &instructions[offset + 1..]) else {
+            writeln!(out, "{:04} {} <truncated>", offset, op).unwrap();
+            break;
+        };
+        let mut text = op.to_string();
+        for operand in &operands {
+            write!(text, " {}", operand).unwrap();
+        }
+        match comment(op, &operands, constants) {
+            Some(comment) => writeln!(
+                out,
+                "{:04} {:<width$} ; {}",
+                offset,
+                text,
+                comment,
+                width = COMMENT_COLUMN
+            ),
+            None => writeln!(out, "{:04} {}", offset, text),
+        }
+        .unwrap();
+        offset += 1 + read;
+    }
+    out
+}
+
+/// What an instruction's first operand refers to, if it is an index.
+fn comment(op: Opcode, operands: &[usize], constants: &[Object]) -> Option<String> {
+    let &index = operands.first()?;
+    match op {
+        Opcode::Constant | Opcode::Closure => Some(match constants.get(index) {
+            Some(constant) => literal(constant),
+            None => "<missing constant>".to_string(),
+        }),
+        Opcode::GetBuiltin => Some(match BUILTINS.get(index) {
+            Some(builtin) => builtin.name.to_string(),
+            None => "<missing builtin>".to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// A constant pool entry: its type, value and, for functions, their sizes.
+fn describe(constant: &Object) -> String {
+    match constant {
+        Object::CompiledFunction(function) => format!(
+            "FUNCTION {} parameters={} locals={}",
+            constant, function.num_parameters, function.num_locals
+        ),
+        _ => format!("{} {}", constant.type_name(), literal(constant)),
+    }
+}
+
+/// A value as it would be written in source; strings are quoted.
+fn literal(value: &Object) -> String {
+    match value {
+        Object::String(value) => format!("{:?}", value),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::make;
+
+    #[test]
+    fn test_malformed_instructions() {
+        let mut instructions = make(Opcode::Constant, &[7]);
+        instructions.push(Opcode::Closure as u8);
+        assert_eq!(
+            disassemble_instructions(&instructions, &[]),
+            "0000 Constant 7           ; <missing constant>\n0003 Closure <truncated>\n"
+        );
+        assert_eq!(
+            disassemble_instructions(&[Opcode::Pop as u8, 200], &[]),
+            "0000 Pop\n0001 <invalid opcode 200>\n"
+        );
+    }
+}
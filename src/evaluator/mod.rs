@@ -0,0 +1,687 @@
+//! A tree-walking interpreter for Monkey programs.
+//!
+//! The evaluator runs an `ast::Program` directly. It is the reference the
+//! bytecode `vm` is checked against, so the two follow the same rules:
+//!
+//! - Scoping is lexical, as in `resolve`: the program, every function literal
+//!   and every block statement open a scope.
+//! - A block's value is the value of its last statement when that is an
+//!   expression statement, and `null` otherwise. A program's value is
+//!   computed the same way, unless a top-level `return` ends it early.
+//! - A `let` that reuses a name of its own scope creates a new binding, so
+//!   functions created earlier keep seeing the old one.
+//! - Operators are the ones in `object`.
+//! - A nested function may use a global defined after it, but not a local
+//!   of an enclosing function: the `vm` copies captured locals into the
+//!   closure when it is created, so such a program is rejected before it
+//!   runs, with the error `resolve` reports for it.
+//!
+//! Imports need the modules they name to have run first, so only
+//! `eval_modules` runs them: it takes a `ModuleGraph` from the module loader
+//! and runs each module in its own global scope, dependencies first. An
+//! import binds its alias to an `Object::Module` holding the exports, which
+//! `alias.name` reads. `eval_program` rejects imports, and member access
+//! works on modules only.
+//!
+//! Runtime errors carry the span of the expression that failed and a trace
+//! of the calls they unwound through, each with its call site.
+//!
+//! A run can be bounded with `Limits`: evaluation steps, call depth, bytes
+//! allocated and wall-clock time. Reaching one stops the program with a
+//! `RuntimeError` whose `limit` says which, at the span of the expression
+//! being evaluated. The `vm` honours the same limits.
+//!
+//! `eval_program_with` runs a program under a `Hook`, which sees every
+//! statement before it runs, every call of a Monkey function, every branch
+//! an `if` takes and every runtime error where it is raised. The debugger,
+//! the profiler and coverage are built on it; `eval_program` passes `()`,
+//! whose methods do nothing and compile away.
+
+use crate::ast::{
+    Program,
+    expression::{BlockStatement, CallExpression, Expression, FunctionLiteral, IfExpression},
+    statement::{LetStatement, Statement},
+};
+use crate::lexer::token::Token;
+use crate::module::ModuleGraph;
+use crate::object::{
+    self, Closure, Env, Environment, Function, HashKey, Limit, MAX_CALL_DEPTH, Namespace, Object,
+    RuntimeError, TraceFrame, lookup_builtin,
+};
+use crate::parser::error::Span;
+use crate::resolve::{self, ResolveError};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How many steps pass between two checks of the clock.
+pub(crate) const CLOCK_INTERVAL: u64 = 256;
+
+/// Bounds on the resources a single run of a program may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The most expressions evaluated; `None` for no limit.
+    pub max_steps: Option<u64>,
+    /// The deepest nesting of function calls. Values far above
+    /// `MAX_CALL_DEPTH` can overflow the Rust stack instead.
+    pub max_call_depth: usize,
+    /// The most bytes of strings, arrays, hashes and functions created,
+    /// counted as they are created and never given back; `None` for no limit.
+    pub max_allocated_bytes: Option<usize>,
+    /// The longest a run may take; `None` for no limit.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    /// No limits except the default call depth.
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            max_call_depth: MAX_CALL_DEPTH,
+            max_allocated_bytes: None,
+            timeout: None,
+        }
+    }
+}
+
+/// How evaluation leaves an expression early.
+enum Unwind {
+    /// A `return` statement, caught by the enclosing call or the program.
+    Return(Object),
+    /// Boxed to keep the evaluator's deeply recursive frames small.
+    Error(Box<RuntimeError>),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(Box::new(error))
+    }
+}
+
+type Eval<T> = Result<T, Unwind>;
+
+/// Observes a run; see `Evaluator::eval_program_with`. Every method does
+/// nothing by default.
+pub trait Hook {
+    /// Called before each statement runs, with the scope it runs in.
+    /// # Returns
+    /// An error to stop the program with instead of running the statement;
+    /// it is placed at the statement.
+    fn statement(&mut self, _stmt: &Statement, _env: &Env) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
+    /// Called when a call of a Monkey function starts.
+    /// # Parameters
+    /// - `function`: The function being called
+    /// - `call`: The token of the call expression
+    /// - `env`: The scope of the body, holding the arguments
+    fn enter(&mut self, _function: &Function, _call: &Token, _env: &Env) {}
+
+    /// Called when the call `enter` reported ends, however it ends.
+    fn exit(&mut self, _function: &Function) {}
+
+    /// Called when an `if` has evaluated its condition.
+    /// # Parameters
+    /// - `expr`: The `if` expression
+    /// - `consequence`: Whether the consequence runs; false when the
+    ///   alternative runs, or nothing does because there is none
+    fn branch(&mut self, _expr: &IfExpression, _consequence: bool) {}
+
+    /// Called once for each runtime error, in the scope of the expression
+    /// that raised it, before it unwinds.
+    fn error(&mut self, _error: &RuntimeError, _env: &Env) {}
+}
+
+impl Hook for () {}
+
+/// Runs programs against a global scope that persists between them.
+pub struct Evaluator {
+    env: Env,
+    /// Where `puts` writes.
+    output: Box<dyn Write>,
+    depth: usize,
+    limits: Limits,
+    /// What the current run has used so far.
+    steps: u64,
+    allocated: usize,
+    deadline: Option<Instant>,
+    /// The modules the running module imports, by alias.
+    imports: HashMap<String, Object>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Evaluator {
+    /// An evaluator whose `puts` writes to stdout.
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// An evaluator whose `puts` writes to `output`.
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        Evaluator {
+            env: Environment::new(),
+            output: Box::new(output),
+            depth: 0,
+            limits: Limits::default(),
+            steps: 0,
+            allocated: 0,
+            deadline: None,
+            imports: HashMap::new(),
+        }
+    }
+
+    /// Bounds every later run by `limits`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// The global scope.
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// Runs a program. Its bindings stay visible to later programs.
+    /// # Returns
+    /// The value of the program, or the error that stopped it.
+    pub fn eval_program(&mut self, program: &Program) -> Result<Object, RuntimeError> {
+        self.eval_program_with(program, &mut ())
+    }
+
+    /// Runs a program like `eval_program`, reporting its progress to `hook`.
+    pub fn eval_program_with<H: Hook>(
+        &mut self,
+        program: &Program,
+        hook: &mut H,
+    ) -> Result<Object, RuntimeError> {
+        self.start_run();
+        self.run(program, hook)
+    }
+
+    /// Runs every module of `graph`, each in a fresh global scope and after
+    /// the modules it imports. The entry module runs last, in the global
+    /// scope, so its bindings stay visible to later programs. The limits
+    /// bound the modules together.
+    /// # Returns
+    /// The value of the entry module, or the error that stopped a module.
+    pub fn eval_modules(&mut self, graph: &ModuleGraph) -> Result<Object, RuntimeError> {
+        self.eval_modules_with(graph, &mut ())
+    }
+
+    /// Runs a module graph like `eval_modules`, reporting its progress to
+    /// `hook`.
+    pub fn eval_modules_with<H: Hook>(
+        &mut self,
+        graph: &ModuleGraph,
+        hook: &mut H,
+    ) -> Result<Object, RuntimeError> {
+        self.start_run();
+        // Modules come after their imports, so every namespace an import
+        // needs is made before it
+        let mut namespaces: Vec<Object> = Vec::with_capacity(graph.modules.len());
+        for (id, module) in graph.modules.iter().enumerate() {
+            self.imports = module
+                .imports
+                .iter()
+                .map(|(alias, imported)| (alias.clone(), namespaces[*imported].clone()))
+                .collect();
+            if id == graph.entry {
+                break;
+            }
+            let global = mem::replace(&mut self.env, Environment::new());
+            let result = self.run(&module.program, hook);
+            let env = mem::replace(&mut self.env, global);
+            if let Err(error) = result {
+                self.imports.clear();
+                return Err(error);
+            }
+            let exports = module
+                .exports
+                .iter()
+                .map(|name| (name.clone(), env.borrow().get(name).unwrap_or(Object::Null)))
+                .collect();
+            namespaces.push(Object::Module(Rc::new(Namespace {
+                path: module.path.display().to_string(),
+                exports,
+            })));
+        }
+        let result = self.run(&graph.entry().program, hook);
+        self.imports.clear();
+        result
+    }
+
+    /// Resets what the run has used before a new run starts.
+    fn start_run(&mut self) {
+        self.steps = 0;
+        self.allocated = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Runs a program in the global scope, keeping its bindings.
+    fn run<H: Hook>(&mut self, program: &Program, hook: &mut H) -> Result<Object, RuntimeError> {
+        check_captures(program)?;
+        let env = Rc::clone(&self.env);
+        match self.eval_statements(&program.statements, env, hook) {
+            Ok((value, env)) => {
+                self.env = env;
+                Ok(value)
+            }
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(*error),
+        }
+    }
+
+    /// Evaluates an expression in `env`, which may be any scope of a
+    /// running program; the debugger uses it to print and set variables.
+    pub fn eval_in(&mut self, expr: &Expression, env: &Env) -> Result<Object, RuntimeError> {
+        match self.eval_expression(expr, env, &mut ()) {
+            Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(*error),
+        }
+    }
+
+    // ============ STATEMENTS ============
+
+    /// Runs statements in `env`.
+    /// # Returns
+    /// The value of the last statement and the scope after the last `let`.
+    fn eval_statements<H: Hook>(
+        &mut self,
+        statements: &[Statement],
+        mut env: Env,
+        hook: &mut H,
+    ) -> Eval<(Object, Env)> {
+        let mut value = Object::Null;
+        for stmt in statements {
+            value = Object::Null;
+            if let Err(mut error) = hook.statement(stmt, &env) {
+                error.span = Some(Span::from_token(stmt.token()));
+                return Err(error.into());
+            }
+            match stmt {
+                Statement::Let(let_stmt) => env = self.eval_let(let_stmt, env, hook)?,
+                Statement::Export(export) => env = self.eval_let(&export.statement, env, hook)?,
+                Statement::Import(import) => match self.imports.get(&import.alias.value) {
+                    Some(module) => env.borrow_mut().set(&import.alias.value, module.clone()),
+                    None => {
+                        let mut error = RuntimeError::new("import is not supported");
+                        error.span = Some(Span::from_token(&import.token));
+                        hook.error(&error, &env);
+                        return Err(error.into());
+                    }
+                },
+                Statement::Return(ret) => {
+                    let value = match &ret.value {
+                        Some(value) => self.eval_expression(value, &env, hook)?,
+                        None => Object::Null,
+                    };
+                    return Err(Unwind::Return(value));
+                }
+                Statement::Expression(stmt) => {
+                    value = self.eval_expression(&stmt.value, &env, hook)?
+                }
+            }
+        }
+        Ok((value, env))
+    }
+
+    /// Binds a `let`.
+    /// # Returns
+    /// The scope holding the new binding: `env`, or a scope nested in it if
+    /// `env` already binds the name.
+    fn eval_let<H: Hook>(&mut self, stmt: &LetStatement, env: Env, hook: &mut H) -> Eval<Env> {
+        let name = &stmt.name.value;
+        let new_scope = |env: Env| {
+            if env.borrow().contains(name) {
+                Environment::enclosed(&env)
+            } else {
+                env
+            }
+        };
+        let (env, value) = match &stmt.value {
+            // A function sees its own name, so the binding comes first
+            Some(literal @ Expression::FunctionLiteral(function)) => {
+                let env = new_scope(env);
+                let value = self.function(function, Some(name), &env);
+                if let Err(Unwind::Error(error)) = self.step().and_then(|()| self.allocate(&value))
+                {
+                    return Err(raise(error, literal, &env, hook));
+                }
+                (env, value)
+            }
+            Some(value) => {
+                let value = self.eval_expression(value, &env, hook)?;
+                (new_scope(env), value)
+            }
+            None => (new_scope(env), Object::Null),
+        };
+        env.borrow_mut().set(name, value);
+        Ok(env)
+    }
+
+    // ============ EXPRESSIONS ============
+
+    /// Evaluates an expression. An error raised here rather than in a
+    /// subexpression is placed at `expr` and reported to the hook.
+    fn eval_expression<H: Hook>(
+        &mut self,
+        expr: &Expression,
+        env: &Env,
+        hook: &mut H,
+    ) -> Eval<Object> {
+        let result = self.step().and_then(|()| match expr {
+            Expression::Identifier(ident) => self.eval_identifier(&ident.value, env),
+            Expression::IntegerLiteral(literal) => Ok(Object::Integer(literal.value)),
+            Expression::BooleanLiteral(literal) => Ok(Object::Boolean(literal.value)),
+            Expression::StringLiteral(literal) => {
+                let value = Object::String(literal.value.as_str().into());
+                self.allocate(&value)?;
+                Ok(value)
+            }
+            Expression::PrefixExpression(prefix) => {
+                let right = self.eval_expression(&prefix.right, env, hook)?;
+                self.operate(object::prefix(&prefix.operator, &right))
+            }
+            Expression::InfixExpression(infix) => {
+                let left = self.eval_expression(&infix.left, env, hook)?;
+                let right = self.eval_expression(&infix.right, env, hook)?;
+                self.operate(object::infix(&infix.operator, &left, &right))
+            }
+            Expression::IfExpression(if_expr) => self.eval_if(if_expr, env, hook),
+            Expression::BlockStatement(block) => self.eval_block(block, env, hook),
+            Expression::FunctionLiteral(function) => {
+                let value = self.function(function, None, env);
+                self.allocate(&value)?;
+                Ok(value)
+            }
+            Expression::CallExpression(call) => self.eval_call(call, env, hook),
+            Expression::MemberExpression(member) => {
+                match self.eval_expression(&member.object, env, hook)? {
+                    Object::Module(module) => {
+                        let name = &member.property.value;
+                        module.exports.get(name).cloned().ok_or_else(|| {
+                            RuntimeError::new(format!(
+                                "'{}' is not exported by {}",
+                                name, module.path
+                            ))
+                            .into()
+                        })
+                    }
+                    _ => Err(RuntimeError::new("member access is not supported").into()),
+                }
+            }
+        });
+        // Errors from subexpressions already have a span
+        match result {
+            Err(Unwind::Error(error)) if error.span.is_none() => Err(raise(error, expr, env, hook)),
+            result => result,
+        }
+    }
+
+    fn eval_identifier(&mut self, name: &str, env: &Env) -> Eval<Object> {
+        if let Some(value) = env.borrow().get(name) {
+            return Ok(value);
+        }
+        match lookup_builtin(name) {
+            Some(builtin) => Ok(Object::Builtin(builtin)),
+            None => Err(RuntimeError::new(format!("identifier not found: {}", name)).into()),
+        }
+    }
+
+    /// Takes the result of an operator, counting the value it created.
+    fn operate(&mut self, result: Result<Object, RuntimeError>) -> Eval<Object> {
+        let value = result?;
+        self.allocate(&value)?;
+        Ok(value)
+    }
+
+    fn eval_if<H: Hook>(
+        &mut self,
+        if_expr: &IfExpression,
+        env: &Env,
+        hook: &mut H,
+    ) -> Eval<Object> {
+        let condition = self
+            .eval_expression(&if_expr.condition, env, hook)?
+            .is_truthy();
+        hook.branch(if_expr, condition);
+        if condition {
+            self.eval_expression(&if_expr.consequence, env, hook)
+        } else if let Some(alternative) = &if_expr.alternative {
+            self.eval_expression(alternative, env, hook)
+        } else {
+            Ok(Object::Null)
+        }
+    }
+
+    fn eval_block<H: Hook>(
+        &mut self,
+        block: &BlockStatement,
+        env: &Env,
+        hook: &mut H,
+    ) -> Eval<Object> {
+        let (value, _) =
+            self.eval_statements(&block.statements, Environment::enclosed(env), hook)?;
+        Ok(value)
+    }
+
+    fn function(&self, function: &FunctionLiteral, name: Option<&str>, env: &Env) -> Object {
+        Object::Function(Rc::new(Function {
+            name: name.map(str::to_string),
+            span: Span::from_token(&function.token),
+            parameters: function.parameters.clone(),
+            body: function.body.clone(),
+            env: Rc::clone(env),
+        }))
+    }
+
+    fn eval_call<H: Hook>(
+        &mut self,
+        call: &CallExpression,
+        env: &Env,
+        hook: &mut H,
+    ) -> Eval<Object> {
+        let function = self.eval_expression(&call.function, env, hook)?;
+        let args = call
+            .arguments
+            .iter()
+            .map(|arg| self.eval_expression(arg, env, hook))
+            .collect::<Eval<Vec<Object>>>()?;
+        // Monkey functions count their own allocations; Rust ones cannot
+        let native = matches!(function, Object::Builtin(_) | Object::Native(_));
+        let value = self.apply(function, &args, &call.token, hook)?;
+        if native {
+            self.allocate(&value)?;
+        }
+        Ok(value)
+    }
+
+    /// Calls a function value with evaluated arguments.
+    /// # Parameters
+    /// - `function`: The value being called
+    /// - `args`: The evaluated arguments
+    /// - `token`: The call's token, the call site of the trace frame an
+    ///   error adds
+    /// - `hook`: Told when a Monkey function is entered and left
+    fn apply<H: Hook>(
+        &mut self,
+        function: Object,
+        args: &[Object],
+        token: &Token,
+        hook: &mut H,
+    ) -> Eval<Object> {
+        match function {
+            Object::Function(function) => {
+                if args.len() != function.parameters.len() {
+                    return Err(wrong_arguments(function.parameters.len(), args.len()));
+                }
+                if self.depth >= self.limits.max_call_depth {
+                    return Err(stack_overflow());
+                }
+                let env = Environment::enclosed(&function.env);
+                for (param, arg) in function.parameters.iter().zip(args) {
+                    env.borrow_mut().set(&param.value, arg.clone());
+                }
+                hook.enter(&function, token, &env);
+                self.depth += 1;
+                let result = self.eval_block(&function.body, &env, hook);
+                self.depth -= 1;
+                hook.exit(&function);
+                match result {
+                    Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+                    Err(Unwind::Error(mut error)) => {
+                        add_frame(&mut error, &function, token);
+                        Err(Unwind::Error(error))
+                    }
+                }
+            }
+            Object::Builtin(builtin) => Ok((builtin.func)(args, &mut *self.output)?),
+            Object::Native(native) => Ok(native.call(args)?),
+            other => {
+                Err(RuntimeError::new(format!("not a function: {}", other.type_name())).into())
+            }
+        }
+    }
+
+    // ============ LIMITS ============
+
+    /// Counts a step and checks the step and time limits.
+    fn step(&mut self) -> Eval<()> {
+        self.steps += 1;
+        if let Some(max) = self.limits.max_steps
+            && self.steps > max
+        {
+            return Err(RuntimeError::limit_exceeded(
+                Limit::Steps,
+                format!("step limit of {} exceeded", max),
+                None,
+            )
+            .into());
+        }
+        if let Some(deadline) = self.deadline
+            && self.steps.is_multiple_of(CLOCK_INTERVAL)
+            && Instant::now() >= deadline
+        {
+            return Err(RuntimeError::limit_exceeded(
+                Limit::Time,
+                format!(
+                    "time limit of {:?} exceeded",
+                    self.limits.timeout.unwrap_or_default()
+                ),
+                None,
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Counts the memory a newly created value holds against the limit.
+    fn allocate(&mut self, value: &Object) -> Eval<()> {
+        self.allocated = self.allocated.saturating_add(allocation_size(value));
+        match self.limits.max_allocated_bytes {
+            Some(max) if self.allocated > max => Err(RuntimeError::limit_exceeded(
+                Limit::Memory,
+                format!("memory limit of {} bytes exceeded", max),
+                None,
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rejects a program in which a nested function captures a local before
+/// the `let` that defines it has run.
+fn check_captures(program: &Program) -> Result<(), RuntimeError> {
+    let capture = resolve::resolve(program)
+        .errors
+        .into_iter()
+        .find(|error| matches!(error, ResolveError::CapturedBeforeDefinition { .. }));
+    match capture {
+        Some(capture) => {
+            let mut error = RuntimeError::new(capture.to_string());
+            error.span = Some(capture.span().clone());
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+// The error paths below are kept out of line: the evaluator recurses once
+// per nested expression, so its frames must stay small.
+
+#[cold]
+fn wrong_arguments(want: usize, got: usize) -> Unwind {
+    RuntimeError::new(format!(
+        "wrong number of arguments: want={}, got={}",
+        want, got
+    ))
+    .into()
+}
+
+#[cold]
+fn stack_overflow() -> Unwind {
+    RuntimeError::limit_exceeded(Limit::CallDepth, "stack overflow", None).into()
+}
+
+/// Places an error at the expression that raised it and reports it.
+#[cold]
+fn raise<H: Hook>(
+    mut error: Box<RuntimeError>,
+    expr: &Expression,
+    env: &Env,
+    hook: &mut H,
+) -> Unwind {
+    error.span = Some(Span::from_token(own_token(expr)));
+    hook.error(&error, env);
+    Unwind::Error(error)
+}
+
+/// Records that an error unwound through a call of `function`.
+#[cold]
+fn add_frame(error: &mut RuntimeError, function: &Function, call: &Token) {
+    error.trace.push(TraceFrame {
+        function: function.name.clone(),
+        call_site: Some(Span::from_token(call)),
+    });
+}
+
+/// The bytes a value owns on the heap, excluding values it shares. The VM
+/// counts its allocations with it too.
+pub(crate) fn allocation_size(value: &Object) -> usize {
+    match value {
+        Object::String(value) => value.len(),
+        Object::Array(elements) => elements.len() * mem::size_of::<Object>(),
+        Object::Hash(pairs) => pairs.len() * mem::size_of::<(HashKey, Object)>(),
+        Object::Function(_) => mem::size_of::<Function>(),
+        Object::Closure(closure) => {
+            mem::size_of::<Closure>() + closure.free.len() * mem::size_of::<Object>()
+        }
+        _ => 0,
+    }
+}
+
+/// The token an expression is reported at: its operator for infix
+/// expressions and its parenthesis for calls, as in the compiler's spans.
+fn own_token(expr: &Expression) -> &Token {
+    match expr {
+        Expression::Identifier(e) => &e.token,
+        Expression::IntegerLiteral(e) => &e.token,
+        Expression::BooleanLiteral(e) => &e.token,
+        Expression::StringLiteral(e) => &e.token,
+        Expression::PrefixExpression(e) => &e.token,
+        Expression::InfixExpression(e) => &e.token,
+        Expression::IfExpression(e) => &e.token,
+        Expression::BlockStatement(e) => &e.token,
+        Expression::FunctionLiteral(e) => &e.token,
+        Expression::CallExpression(e) => &e.token,
+        Expression::MemberExpression(e) => &e.token,
+    }
+}
@@ -0,0 +1,192 @@
+//! A small Wadler-style pretty-printing document.
+//!
+//! The formatter describes the layout it wants as a `Doc`; `Doc::render`
+//! then decides, group by group, whether each one fits on the current line
+//! or has to break. A group breaks as a whole: every `Line` directly inside it
+//! becomes a newline.
+
+/// A layout document.
+#[derive(Debug, Clone)]
+pub enum Doc {
+    /// Literal text; must not contain newlines.
+    Text(String),
+    /// A space, or a newline when the enclosing group breaks.
+    Line,
+    /// Nothing, or a newline when the enclosing group breaks.
+    SoftLine,
+    /// Always a newline; forces every enclosing group to break.
+    HardLine,
+    /// Prints nothing but forces every enclosing group to break.
+    BreakParent,
+    /// Indents the newlines inside it by one level.
+    Nest(Box<Doc>),
+    /// Prints its contents flat if they fit, broken otherwise.
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+/// Whether a group is being printed on one line or broken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    pub fn nest(doc: Doc) -> Doc {
+        Doc::Nest(Box::new(doc))
+    }
+
+    pub fn group(doc: Doc) -> Doc {
+        Doc::Group(Box::new(doc))
+    }
+
+    /// Joins `docs` with `separator` between each pair.
+    pub fn join(docs: Vec<Doc>, separator: Doc) -> Doc {
+        let mut parts = Vec::with_capacity(docs.len() * 2);
+        for (i, doc) in docs.into_iter().enumerate() {
+            if i > 0 {
+                parts.push(separator.clone());
+            }
+            parts.push(doc);
+        }
+        Doc::Concat(parts)
+    }
+
+    /// True if printing this document flat is impossible.
+    fn must_break(&self) -> bool {
+        match self {
+            Doc::HardLine | Doc::BreakParent => true,
+            Doc::Nest(doc) | Doc::Group(doc) => doc.must_break(),
+            Doc::Concat(docs) => docs.iter().any(Doc::must_break),
+            Doc::Text(_) | Doc::Line | Doc::SoftLine => false,
+        }
+    }
+
+    /// Lays the document out.
+    /// # Parameters
+    /// - `indent_width`: Spaces added per `Nest` level
+    /// - `max_width`: The line width groups try to stay within
+    /// # Returns
+    /// The rendered text. Lines never end in spaces.
+    pub fn render(&self, indent_width: usize, max_width: usize) -> String {
+        let mut out = String::new();
+        let mut column = 0;
+        let mut stack = vec![(0, Mode::Break, self)];
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    out.push_str(text);
+                    column += text.len();
+                }
+                Doc::Line if mode == Mode::Flat => {
+                    out.push(' ');
+                    column += 1;
+                }
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                    let trimmed = out.trim_end_matches(' ').len();
+                    out.truncate(trimmed);
+                    out.push('\n');
+                    out.push_str(&" ".repeat(indent));
+                    column = indent;
+                }
+                Doc::BreakParent => {}
+                Doc::Nest(inner) => stack.push((indent + indent_width, mode, inner)),
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+                }
+                Doc::Group(inner) => {
+                    let flat = mode == Mode::Flat
+                        || (!inner.must_break()
+                            && fits(
+                                max_width as isize - column as isize,
+                                (indent, Mode::Flat, inner),
+                                &stack,
+                            ));
+                    let mode = if flat { Mode::Flat } else { Mode::Break };
+                    stack.push((indent, mode, inner));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Checks whether `next`, followed by the rest of `stack`, fits in `remaining`
+/// columns up to the next line break.
+fn fits(mut remaining: isize, next: (usize, Mode, &Doc), stack: &[(usize, Mode, &Doc)]) -> bool {
+    let mut rest = stack.iter().rev();
+    let mut pending = vec![next];
+    while remaining >= 0 {
+        let (indent, mode, doc) = match pending.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(&item) => item,
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => remaining -= text.len() as isize,
+            Doc::Line if mode == Mode::Flat => remaining -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::BreakParent => {}
+            Doc::Nest(inner) => pending.push((indent, mode, inner)),
+            Doc::Group(inner) => pending.push((indent, mode, inner)),
+            Doc::Concat(docs) => pending.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(args: &[&str]) -> Doc {
+        Doc::group(Doc::Concat(vec![
+            Doc::text("f("),
+            Doc::nest(Doc::Concat(vec![
+                Doc::SoftLine,
+                Doc::join(
+                    args.iter().map(|a| Doc::text(*a)).collect(),
+                    Doc::Concat(vec![Doc::text(","), Doc::Line]),
+                ),
+            ])),
+            Doc::SoftLine,
+            Doc::text(");"),
+        ]))
+    }
+
+    #[test]
+    fn test_group_stays_flat_when_it_fits() {
+        assert_eq!(call(&["a", "b"]).render(4, 10), "f(a, b);");
+    }
+
+    #[test]
+    fn test_group_breaks_when_too_wide() {
+        // `f(a, b);` is 8 columns; the trailing `);` counts against the width
+        assert_eq!(call(&["a", "b"]).render(2, 7), "f(\n  a,\n  b\n);");
+    }
+
+    #[test]
+    fn test_hard_line_breaks_enclosing_group() {
+        let doc = Doc::group(Doc::Concat(vec![
+            Doc::text("{"),
+            Doc::nest(Doc::Concat(vec![
+                Doc::Line,
+                Doc::text("x;"),
+                Doc::HardLine,
+                Doc::text("y;"),
+            ])),
+            Doc::Line,
+            Doc::text("}"),
+        ]));
+        assert_eq!(doc.render(4, 80), "{\n    x;\n    y;\n}");
+    }
+}
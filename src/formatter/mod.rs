@@ -0,0 +1,411 @@
+//! Canonical source formatter (`monkey fmt`).
+//!
+//! The formatter parses a file, builds a `Doc` describing the layout, and
+//! renders it within `FormatOptions::max_width`:
+//!
+//! - one statement per line; a single blank line between statements is kept
+//! - operators are spaced and only parenthesized where precedence requires it
+//! - argument and parameter lists go one per line when they do not fit
+//! - blocks with one statement stay on one line if they fit; longer ones are
+//!   broken and indented by `FormatOptions::indent_width`
+//!
+//! `//` comments are re-attached by position: comments before a statement are
+//! printed above it, comments on the line where a statement ends are printed
+//! after it, and comments inside an expression move to the end of its
+//! statement. Formatting already formatted source returns it unchanged.
+
+mod doc;
+
+use crate::ast::{
+    expression::{BlockStatement, Expression, FunctionLiteral},
+    statement::{LetStatement, Statement},
+    types::TypeExpr,
+};
+use crate::lexer::{
+    Comment, Lexer,
+    token::{Token, TokenType},
+};
+use crate::parser::{Parser, error::ParserError, precedence::Precedence};
+use crate::source::FileId;
+use doc::Doc;
+
+/// Layout settings for the formatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Spaces per indentation level
+    pub indent_width: usize,
+    /// The line width the formatter tries to stay within
+    pub max_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent_width: 4,
+            max_width: 80,
+        }
+    }
+}
+
+/// Formats Monkey source text.
+/// # Parameters
+/// - `source`: The source text
+/// - `file`: The file the source belongs to, used in error spans
+/// - `options`: Indentation and line width
+/// # Returns
+/// - `Ok(text)` with the formatted source, ending in a newline unless empty
+/// - `Err(errors)` if the source does not parse
+pub fn format_source(
+    source: &str,
+    file: FileId,
+    options: &FormatOptions,
+) -> Result<String, Vec<ParserError>> {
+    let mut parser = Parser::new(Lexer::new(source.to_string(), file));
+    let program = parser.parse_program();
+    if !parser.errors.is_empty() {
+        return Err(parser.errors);
+    }
+
+    let mut lexer = Lexer::new(source.to_string(), file);
+    let tokens = std::iter::from_fn(|| {
+        let token = lexer.next_token();
+        (token.token_type != TokenType::EOF).then_some(token)
+    })
+    .collect();
+
+    let mut formatter = Formatter {
+        source_lines: source.lines().collect(),
+        tokens,
+        comments: parser.comments().to_vec(),
+        next_comment: 0,
+    };
+    let doc = formatter.statements(&program.statements, None);
+    let mut out = doc.render(options.indent_width, options.max_width);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// A `(line, column)` source position; tuples order like source text.
+type Position = (usize, usize);
+
+fn position(token: &Token) -> Position {
+    (token.line, token.column)
+}
+
+/// Builds the layout of a parsed program, pulling comments in source order.
+struct Formatter<'a> {
+    source_lines: Vec<&'a str>,
+    /// Every token of the source, used to find where statements and blocks end
+    tokens: Vec<Token>,
+    comments: Vec<Comment>,
+    /// Index of the first comment not yet printed
+    next_comment: usize,
+}
+
+impl Formatter<'_> {
+    // ============ COMMENTS AND POSITIONS ============
+
+    /// Takes the unprinted comments that start before `end` (all if `None`).
+    fn comments_before(&mut self, end: Option<Position>) -> Vec<Comment> {
+        let start = self.next_comment;
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if end.is_some_and(|end| (comment.line, comment.column) >= end) {
+                break;
+            }
+            self.next_comment += 1;
+        }
+        self.comments[start..self.next_comment].to_vec()
+    }
+
+    /// True if the source line before `line` is blank.
+    fn blank_line_before(&self, line: usize) -> bool {
+        line >= 2
+            && self
+                .source_lines
+                .get(line - 2)
+                .is_some_and(|text| text.trim().is_empty())
+    }
+
+    /// Finds the token at `start` and returns the index of the first token
+    /// after it that closes back to depth zero and satisfies `is_end`.
+    fn matching_token(&self, start: Position, is_end: fn(TokenType) -> bool) -> Option<usize> {
+        let first = self.tokens.binary_search_by_key(&start, position).ok()?;
+        let mut depth = 0i32;
+        for (i, token) in self.tokens.iter().enumerate().skip(first) {
+            match token.token_type {
+                TokenType::LPAREN | TokenType::LBRACE | TokenType::LBRACKET => depth += 1,
+                TokenType::RPAREN | TokenType::RBRACE | TokenType::RBRACKET => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 && is_end(token.token_type) {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    /// Index of the `;` ending the statement that starts at `start`.
+    fn statement_end(&self, start: Position) -> Option<usize> {
+        self.matching_token(start, |t| t == TokenType::SEMICOLON)
+    }
+
+    /// Position of the `}` closing the block that opens at `start`.
+    fn block_end(&self, start: Position) -> Option<Position> {
+        self.matching_token(start, |t| t == TokenType::RBRACE)
+            .map(|i| position(&self.tokens[i]))
+    }
+
+    // ============ STATEMENTS ============
+
+    /// Lays out a statement list with the comments that belong to it.
+    /// # Parameters
+    /// - `statements`: The statements of a program or block
+    /// - `end`: Where the list ends (a block's `}`); `None` for the whole file
+    fn statements(&mut self, statements: &[Statement], end: Option<Position>) -> Doc {
+        let mut parts = Vec::new();
+        for stmt in statements {
+            let start = position(stmt.token());
+            for comment in self.comments_before(Some(start)) {
+                self.separate(&mut parts, comment.line);
+                parts.push(comment_doc(comment));
+            }
+            self.separate(&mut parts, start.0);
+            parts.push(self.statement(stmt));
+            parts.push(self.trailing_comments(start));
+        }
+        for comment in self.comments_before(end) {
+            self.separate(&mut parts, comment.line);
+            parts.push(comment_doc(comment));
+        }
+        Doc::Concat(parts)
+    }
+
+    /// Starts a new line before the item at `line`, keeping one blank line
+    /// if the source had one. Nothing is added before the first item.
+    fn separate(&self, parts: &mut Vec<Doc>, line: usize) {
+        if parts.is_empty() {
+            return;
+        }
+        parts.push(Doc::HardLine);
+        if self.blank_line_before(line) {
+            parts.push(Doc::HardLine);
+        }
+    }
+
+    /// Prints the comments left inside the statement starting at `start`, and
+    /// those on the line it ends on, after the statement.
+    fn trailing_comments(&mut self, start: Position) -> Doc {
+        let Some(end) = self.statement_end(start) else {
+            return Doc::Concat(Vec::new());
+        };
+        let semicolon = position(&self.tokens[end]);
+        let mut comments = self.comments_before(Some(semicolon));
+        // A comment on the `;` line belongs to this statement only if no other
+        // code follows on that line.
+        let next_line = self.tokens.get(end + 1).map(|t| t.line);
+        if next_line != Some(semicolon.0)
+            && let Some(comment) = self.comments.get(self.next_comment)
+            && comment.line == semicolon.0
+        {
+            comments.extend(self.comments_before(Some((comment.line, comment.column + 1))));
+        }
+
+        let mut parts = Vec::new();
+        for (i, comment) in comments.into_iter().enumerate() {
+            parts.push(if i == 0 {
+                Doc::text(" ")
+            } else {
+                Doc::HardLine
+            });
+            parts.push(comment_doc(comment));
+        }
+        Doc::Concat(parts)
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Doc {
+        match stmt {
+            Statement::Let(s) => self.let_statement(s),
+            Statement::Return(s) => match &s.value {
+                Some(value) => Doc::Concat(vec![
+                    Doc::text("return "),
+                    self.expression(value),
+                    Doc::text(";"),
+                ]),
+                None => Doc::text("return;"),
+            },
+            Statement::Expression(s) => {
+                Doc::Concat(vec![self.expression(&s.value), Doc::text(";")])
+            }
+            Statement::Import(s) => {
+                Doc::text(format!("import \"{}\" as {};", s.path.value, s.alias.value))
+            }
+            Statement::Export(s) => {
+                Doc::Concat(vec![Doc::text("export "), self.let_statement(&s.statement)])
+            }
+        }
+    }
+
+    fn let_statement(&mut self, stmt: &LetStatement) -> Doc {
+        let mut parts = vec![Doc::text(format!(
+            "let {}{}",
+            stmt.name.value,
+            annotation(stmt.type_annotation.as_ref())
+        ))];
+        if let Some(value) = &stmt.value {
+            parts.push(Doc::text(" = "));
+            parts.push(self.expression(value));
+        }
+        parts.push(Doc::text(";"));
+        Doc::Concat(parts)
+    }
+
+    /// `{ ... }` on one line if it holds one short statement, broken otherwise.
+    fn block(&mut self, block: &BlockStatement) -> Doc {
+        Doc::group(self.block_body(block))
+    }
+
+    /// The ungrouped layout of a block; its lines break with the enclosing group.
+    fn block_body(&mut self, block: &BlockStatement) -> Doc {
+        let end = self.block_end(position(&block.token));
+        let body = self.statements(&block.statements, end);
+        if matches!(&body, Doc::Concat(parts) if parts.is_empty()) {
+            return Doc::text("{}");
+        }
+        Doc::Concat(vec![
+            Doc::text("{"),
+            Doc::nest(Doc::Concat(vec![Doc::Line, body])),
+            Doc::Line,
+            Doc::text("}"),
+        ])
+    }
+
+    /// A branch of an `if`; always a block when it comes from the parser.
+    fn branch(&mut self, expr: &Expression) -> Doc {
+        match expr {
+            Expression::BlockStatement(block) => self.block_body(block),
+            expr => self.expression(expr),
+        }
+    }
+
+    // ============ EXPRESSIONS ============
+
+    fn expression(&mut self, expr: &Expression) -> Doc {
+        match expr {
+            Expression::Identifier(e) => Doc::text(&e.value),
+            Expression::IntegerLiteral(e) => Doc::text(e.value.to_string()),
+            Expression::BooleanLiteral(e) => Doc::text(e.value.to_string()),
+            Expression::StringLiteral(e) => Doc::text(format!("\"{}\"", e.value)),
+            Expression::PrefixExpression(e) => {
+                Doc::Concat(vec![Doc::text(&e.operator), self.operand(&e.right, PREFIX)])
+            }
+            Expression::InfixExpression(e) => {
+                let precedence = infix_precedence(&e.token);
+                Doc::Concat(vec![
+                    self.operand(&e.left, precedence),
+                    Doc::text(format!(" {} ", e.operator)),
+                    // Operators are left-associative, so an equal right operand
+                    // was grouped explicitly.
+                    self.operand(&e.right, precedence + 1),
+                ])
+            }
+            Expression::IfExpression(e) => {
+                // Both branches share one group so they break together.
+                let mut parts = vec![
+                    Doc::text("if ("),
+                    self.expression(&e.condition),
+                    Doc::text(") "),
+                    self.branch(&e.consequence),
+                ];
+                if let Some(alternative) = &e.alternative {
+                    parts.push(Doc::text(" else "));
+                    parts.push(self.branch(alternative));
+                }
+                Doc::group(Doc::Concat(parts))
+            }
+            Expression::BlockStatement(block) => self.block(block),
+            Expression::FunctionLiteral(e) => self.function(e),
+            Expression::CallExpression(e) => {
+                let arguments = e.arguments.iter().map(|a| self.expression(a)).collect();
+                Doc::Concat(vec![self.operand(&e.function, POSTFIX), list(arguments)])
+            }
+            Expression::MemberExpression(e) => Doc::Concat(vec![
+                self.operand(&e.object, POSTFIX),
+                Doc::text(format!(".{}", e.property.value)),
+            ]),
+        }
+    }
+
+    fn function(&mut self, function: &FunctionLiteral) -> Doc {
+        let parameters = function
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let ty = function.parameter_types.get(i).and_then(Option::as_ref);
+                Doc::text(format!("{}{}", param.value, annotation(ty)))
+            })
+            .collect();
+        let mut parts = vec![Doc::text("fn"), list(parameters), Doc::text(" ")];
+        if let Some(ty) = &function.return_type {
+            parts.insert(2, Doc::text(format!(" -> {}", ty)));
+        }
+        parts.push(self.block(&function.body));
+        Doc::Concat(parts)
+    }
+
+    /// Formats `expr`, parenthesized if it binds looser than `min_precedence`.
+    fn operand(&mut self, expr: &Expression, min_precedence: i32) -> Doc {
+        let doc = self.expression(expr);
+        if expression_precedence(expr) < min_precedence {
+            Doc::Concat(vec![Doc::text("("), doc, Doc::text(")")])
+        } else {
+            doc
+        }
+    }
+}
+
+/// Binding strength of prefix operators.
+const PREFIX: i32 = Precedence::PREFIX as i32;
+/// Binding strength of calls and member access, which chain left to right.
+const POSTFIX: i32 = Precedence::CALL as i32;
+
+fn infix_precedence(token: &Token) -> i32 {
+    Precedence::from_token_type(&token.token_type)
+}
+
+/// A comment; nothing may follow it on its line.
+fn comment_doc(comment: Comment) -> Doc {
+    Doc::Concat(vec![Doc::Text(comment.text), Doc::BreakParent])
+}
+
+/// How tightly an expression binds when it appears as an operand.
+fn expression_precedence(expr: &Expression) -> i32 {
+    match expr {
+        Expression::InfixExpression(e) => infix_precedence(&e.token),
+        Expression::PrefixExpression(_) => PREFIX,
+        _ => POSTFIX,
+    }
+}
+
+/// `(a, b)` on one line, or one item per line when it does not fit.
+fn list(items: Vec<Doc>) -> Doc {
+    if items.is_empty() {
+        return Doc::text("()");
+    }
+    Doc::group(Doc::Concat(vec![
+        Doc::text("("),
+        Doc::nest(Doc::Concat(vec![
+            Doc::SoftLine,
+            Doc::join(items, Doc::Concat(vec![Doc::text(","), Doc::Line])),
+        ])),
+        Doc::SoftLine,
+        Doc::text(")"),
+    ]))
+}
+
+/// `: type`, or nothing.
+fn annotation(ty: Option<&TypeExpr>) -> String {
+    ty.map(|ty| format!(": {}", ty)).unwrap_or_default()
+}
@@ -0,0 +1,282 @@
+//! Semantic highlighting from the real lexer and parser.
+//!
+//! `classify` splits a source text into ranges with a `Category`. Token kinds
+//! come straight from the `Lexer` (through the syntax tree), so keywords and
+//! operators never drift from what the language accepts. Identifiers are
+//! classified with name resolution: a name is a `Parameter`, a `Function` (a
+//! `let` bound to a function literal) or a `Local`, wherever it appears.
+//! Names without a binding, such as builtins, are a `Function` when called
+//! and a `Local` otherwise.
+//!
+//! Delimiters, whitespace and type names are left unclassified.
+//!
+//! `to_ansi` and `to_html` render a classified text for terminals and web
+//! pages; the REPL, `monkey highlight` and the language server all use the
+//! same classification.
+
+use crate::lexer::token::TokenType;
+use crate::parser::error::Span;
+use crate::resolve::{BindingKind, Resolution, resolve};
+use crate::syntax::{
+    Parse, SyntaxKind, SyntaxNode, SyntaxToken,
+    ast::{AstNode, CallExpr, Expr, LetStmt},
+    parse,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+/// What a highlighted range is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Keyword,
+    Operator,
+    Number,
+    String,
+    Comment,
+    /// A name bound to a function literal, or any called name without a binding.
+    Function,
+    Parameter,
+    /// Any other name.
+    Local,
+    /// Text the lexer could not tokenize (`ILLEGAL`).
+    Unknown,
+}
+
+impl Category {
+    /// The lowercase name, used as the HTML class.
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Keyword => "keyword",
+            Category::Operator => "operator",
+            Category::Number => "number",
+            Category::String => "string",
+            Category::Comment => "comment",
+            Category::Function => "function",
+            Category::Parameter => "parameter",
+            Category::Local => "local",
+            Category::Unknown => "unknown",
+        }
+    }
+
+    /// The ANSI escape sequence that starts the category's style.
+    fn ansi(self) -> &'static str {
+        match self {
+            Category::Keyword => "\x1b[35m",
+            Category::Operator => "\x1b[36m",
+            Category::Number => "\x1b[33m",
+            Category::String => "\x1b[32m",
+            Category::Comment => "\x1b[90m",
+            Category::Function => "\x1b[34m",
+            Category::Parameter => "\x1b[3m",
+            Category::Local => "",
+            Category::Unknown => "\x1b[31;4m",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A classified range of the source, in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub range: Range<usize>,
+    pub category: Category,
+}
+
+const RESET: &str = "\x1b[0m";
+
+/// Classifies `source`, parsed as the default file.
+/// # Returns
+/// The classified ranges in source order. They never overlap.
+pub fn classify(source: &str) -> Vec<Highlight> {
+    let parse = parse(source);
+    let resolution = resolve(parse.program());
+    classify_parse(&parse, &resolution)
+}
+
+/// Classifies an existing parse, given the resolution of its program.
+pub fn classify_parse(parse: &Parse, resolution: &Resolution) -> Vec<Highlight> {
+    let root = parse.syntax();
+    let text = root.to_string();
+    let lines = LineStarts::new(&text);
+    let span_at = |offset: usize| {
+        let (line, column) = lines.position(offset);
+        Span::in_file(parse.file(), line, column)
+    };
+    // Bindings of function literals, so uses before the `let` are known too
+    let functions: HashSet<Span> = root
+        .descendants()
+        .filter_map(LetStmt::cast)
+        .filter(|stmt| matches!(stmt.value(), Some(Expr::Fn(_))))
+        .filter_map(|stmt| Some(span_at(stmt.name()?.text_range().start)))
+        .collect();
+
+    root.descendant_tokens()
+        .filter_map(|token| {
+            let category = match token.kind() {
+                SyntaxKind::Comment => Category::Comment,
+                SyntaxKind::Token(TokenType::IDENT) => {
+                    let span = span_at(token.text_range().start);
+                    name_category(&token, &span, resolution, &functions)?
+                }
+                SyntaxKind::Token(token_type) => token_category(token_type)?,
+                _ => return None,
+            };
+            Some(Highlight {
+                range: token.text_range(),
+                category,
+            })
+        })
+        .collect()
+}
+
+/// The category of a non-identifier token.
+fn token_category(token_type: TokenType) -> Option<Category> {
+    Some(match token_type {
+        TokenType::FUNCTION
+        | TokenType::LET
+        | TokenType::IF
+        | TokenType::ELSE
+        | TokenType::RETURN
+        | TokenType::TRUE
+        | TokenType::FALSE
+        | TokenType::IMPORT
+        | TokenType::EXPORT
+        | TokenType::AS => Category::Keyword,
+        TokenType::ASSIGN
+        | TokenType::PLUS
+        | TokenType::MINUS
+        | TokenType::BANG
+        | TokenType::SLASH
+        | TokenType::ASTERISK
+        | TokenType::LT
+        | TokenType::GT
+        | TokenType::NOTEQ
+        | TokenType::EQ
+        | TokenType::ARROW
+        | TokenType::QUESTION => Category::Operator,
+        TokenType::INT => Category::Number,
+        TokenType::STRING => Category::String,
+        TokenType::ILLEGAL => Category::Unknown,
+        _ => return None,
+    })
+}
+
+/// The category of an identifier, or `None` for a type name.
+fn name_category(
+    token: &SyntaxToken,
+    span: &Span,
+    resolution: &Resolution,
+    functions: &HashSet<Span>,
+) -> Option<Category> {
+    let parent = token.parent();
+    if parent.kind() == SyntaxKind::Type {
+        return None;
+    }
+    let binding = resolution
+        .binding_of(span)
+        .or_else(|| resolution.bindings.iter().find(|b| &b.span == span));
+    Some(match binding {
+        Some(binding) if binding.kind == BindingKind::Parameter => Category::Parameter,
+        Some(binding) if functions.contains(&binding.span) => Category::Function,
+        Some(_) => Category::Local,
+        None if is_callee(&parent) => Category::Function,
+        None => Category::Local,
+    })
+}
+
+/// Whether `node` (a name or member access) is the function of a call.
+fn is_callee(node: &SyntaxNode) -> bool {
+    node.parent()
+        .and_then(CallExpr::cast)
+        .and_then(|call| call.callee())
+        .is_some_and(|callee| callee.syntax() == node)
+}
+
+/// Renders `source` with ANSI colors.
+pub fn to_ansi(source: &str) -> String {
+    render(source, &classify(source), |text, category| match category {
+        Some(category) if !category.ansi().is_empty() => {
+            format!("{}{}{}", category.ansi(), text, RESET)
+        }
+        _ => text.to_string(),
+    })
+}
+
+/// Renders `source` as HTML: escaped text with each classified range in a
+/// `<span>` whose class is the category's name, e.g.
+/// `<span class="keyword">let</span>`. Wrap it in a `<pre>` to show it.
+pub fn to_html(source: &str) -> String {
+    render(source, &classify(source), |text, category| {
+        let text = escape_html(text);
+        match category {
+            Some(category) => format!("<span class=\"{}\">{}</span>", category, text),
+            None => text,
+        }
+    })
+}
+
+/// Concatenates `paint(text, category)` over the classified and unclassified
+/// pieces of `source`.
+fn render(
+    source: &str,
+    highlights: &[Highlight],
+    paint: impl Fn(&str, Option<Category>) -> String,
+) -> String {
+    let mut out = String::new();
+    let mut end = 0;
+    for highlight in highlights {
+        if end < highlight.range.start {
+            out.push_str(&paint(&source[end..highlight.range.start], None));
+        }
+        out.push_str(&paint(
+            &source[highlight.range.clone()],
+            Some(highlight.category),
+        ));
+        end = highlight.range.end;
+    }
+    if end < source.len() {
+        out.push_str(&paint(&source[end..], None));
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Converts byte offsets to the lexer's 1-based lines and char columns.
+struct LineStarts<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineStarts<'a> {
+    fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineStarts { text, starts }
+    }
+
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|&start| start <= offset);
+        let start = self.starts[line - 1];
+        (line, self.text[start..offset].chars().count() + 1)
+    }
+}
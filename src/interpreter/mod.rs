@@ -0,0 +1,182 @@
+//! An interpreter handle for embedding Monkey in a Rust program.
+//!
+//! `Interpreter` wraps the AST `evaluator` behind a small API: evaluate
+//! source text, read and write globals, and expose Rust closures to scripts
+//! as native functions. Globals persist between evaluations, so a host can
+//! load a script once and call into it later:
+//!
+//! ```
+//! use monkey_lang::interpreter::Interpreter;
+//! use monkey_lang::object::{FromValue, IntoValue};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.register("double", 1, |args| {
+//!     let n = i64::from_value(&args[0])?;
+//!     Ok((n * 2).into_value())
+//! });
+//! interpreter.set_global("limit", 20);
+//! interpreter.eval_str("let over = fn(n) { double(n) > limit; };").unwrap();
+//! let over: bool = interpreter.eval("over(11);").unwrap();
+//! assert!(over);
+//! ```
+//!
+//! Values cross the boundary as `Value`s; `IntoValue` and `FromValue`
+//! convert them to and from Rust types.
+//!
+//! Untrusted scripts should run under `Limits`. A script that reaches one
+//! fails with an `Error::Runtime` whose `limit` names it, and the
+//! interpreter stays usable:
+//!
+//! ```
+//! use monkey_lang::interpreter::{Interpreter, Limit, Limits};
+//!
+//! let mut interpreter = Interpreter::new();
+//! interpreter.set_limits(Limits {
+//!     max_steps: Some(10_000),
+//!     ..Limits::default()
+//! });
+//! let error = interpreter
+//!     .eval_str("let f = fn(n) { if (n > 0) { f(n - 1) + f(n - 1); } else { 1; }; }; f(30);")
+//!     .unwrap_err();
+//! assert_eq!(error.limit(), Some(Limit::Steps));
+//! ```
+
+pub use crate::evaluator::Limits;
+pub use crate::object::Limit;
+
+use crate::evaluator::Evaluator;
+use crate::lexer::Lexer;
+use crate::object::{FromValue, IntoValue, NativeFunction, Object, RuntimeError};
+use crate::parser::{Parser, error::ParserError};
+use crate::source::FileId;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// A value passed between the host and a script.
+pub type Value = Object;
+
+/// Why an evaluation failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The source did not parse; nothing was run.
+    Parse(Vec<ParserError>),
+    /// The program stopped, or its value had the wrong type for `eval`.
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+            Error::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error {
+    /// The resource limit that stopped the program, if that is why it failed.
+    pub fn limit(&self) -> Option<Limit> {
+        match self {
+            Error::Runtime(error) => error.limit,
+            Error::Parse(_) => None,
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        Error::Runtime(error)
+    }
+}
+
+/// A Monkey interpreter with its own globals.
+pub struct Interpreter {
+    evaluator: Evaluator,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// An interpreter whose `puts` writes to stdout.
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// An interpreter whose `puts` writes to `output`.
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        Interpreter {
+            evaluator: Evaluator::with_output(output),
+        }
+    }
+
+    /// Bounds every later evaluation by `limits`; each evaluation gets the
+    /// full budget.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.evaluator.set_limits(limits);
+    }
+
+    /// Parses and runs `source`. Its `let`s become globals.
+    /// # Returns
+    /// The value of the program, or why it could not be run or finished.
+    pub fn eval_str(&mut self, source: &str) -> Result<Value, Error> {
+        let mut parser = Parser::new(Lexer::new(source.to_string(), FileId::DETACHED));
+        let program = parser.parse_program();
+        if !parser.errors().is_empty() {
+            return Err(Error::Parse(parser.errors().clone()));
+        }
+        Ok(self.evaluator.eval_program(&program)?)
+    }
+
+    /// Runs `source` like `eval_str` and converts its value.
+    pub fn eval<T: FromValue>(&mut self, source: &str) -> Result<T, Error> {
+        let value = self.eval_str(source)?;
+        Ok(T::from_value(&value)?)
+    }
+
+    /// Binds a global, replacing any existing binding of the name.
+    pub fn set_global(&mut self, name: &str, value: impl IntoValue) {
+        self.evaluator
+            .env()
+            .borrow_mut()
+            .set(name, value.into_value());
+    }
+
+    /// The value of a global, if it is bound.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.evaluator.env().borrow().get(name)
+    }
+
+    /// Makes a Rust closure callable from scripts as the global `name`.
+    /// # Parameters
+    /// - `name`: The global the function is bound to; it shadows a builtin
+    ///   of the same name
+    /// - `arity`: The number of arguments; calls with any other number fail
+    ///   before `func` runs
+    /// - `func`: The function body, given exactly `arity` arguments
+    pub fn register<F>(&mut self, name: &str, arity: usize, func: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, RuntimeError> + 'static,
+    {
+        let native = NativeFunction {
+            name: name.to_string(),
+            arity,
+            func: Box::new(func),
+        };
+        self.set_global(name, Object::Native(Rc::new(native)));
+    }
+}
@@ -1,31 +1,54 @@
 pub mod token;
 
+use crate::source::FileId;
+use std::ops::Range;
 use token::{Token, TokenType, lookup_identifier};
 
+/// A `//` line comment. Comments are skipped like whitespace, but the lexer
+/// records them so tools such as the formatter can put them back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// The comment text including the leading `//`, without the newline
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Lexer {
     input: String,
+    file: FileId,
     curr_position: usize,
     next_read_position: usize,
     curr_char: char, // We currently supports ASCII character only
     line: usize,
     column: usize,
+    comments: Vec<Comment>,
+    /// Byte range of the whitespace and comments skipped before the last token
+    last_trivia: Range<usize>,
+    /// Byte range of the last token's source text
+    last_text: Range<usize>,
 }
 
 impl Lexer {
     /// Creates a new Lexer instance with the given input string.
     /// ## Arguments
     /// * `input` - The source code string to be tokenized
+    /// * `file` - The source file the input belongs to; every token carries it
     /// ## Returns
     /// A new Lexer instance initialized with the input string.
-    pub fn new(input: String) -> Self {
+    pub fn new(input: String, file: FileId) -> Self {
         let mut l = Lexer {
             input,
+            file,
             curr_position: 0,
             next_read_position: 0,
             curr_char: '\0', // \0 => Null
             line: 1,
             column: 0, // Will be 1 after first read_char
+            comments: Vec::new(),
+            last_trivia: 0..0,
+            last_text: 0..0,
         };
         l.read_char();
         l
@@ -83,15 +106,76 @@ impl Lexer {
         }
     }
 
-    /// Skips all whitespace characters from the current position.
+    /// Skips all whitespace characters and `//` comments from the current position.
     ///
     /// This method advances the lexer position past any whitespace characters
     /// (spaces, tabs, newlines, etc.) until it encounters a non-whitespace character.
+    /// Comments are recorded in `comments` as they are skipped.
     /// Line/column tracking is handled by read_char.
     fn skip_white_space(&mut self) {
-        while self.curr_char.is_ascii_whitespace() {
+        loop {
+            while self.curr_char.is_ascii_whitespace() {
+                self.read_char();
+            }
+            if self.curr_char == '/' && self.peek_char() == '/' {
+                self.read_comment();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Reads a `//` comment up to (but not including) the end of the line.
+    fn read_comment(&mut self) {
+        let (line, column) = (self.line, self.column);
+        let start_position = self.curr_position;
+        while self.curr_char != '\n' && !self.at_end() {
             self.read_char();
         }
+        let end_position = self.offset();
+        let text = self.input[start_position..end_position]
+            .trim_end()
+            .to_string();
+        self.comments.push(Comment { text, line, column });
+    }
+
+    /// Returns the comments skipped so far, in source order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Returns the whole input being tokenized.
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// Returns the whitespace and comments skipped before the last token.
+    pub fn last_trivia(&self) -> &str {
+        &self.input[self.last_trivia.clone()]
+    }
+
+    /// Returns the exact source text of the last token, including the quotes
+    /// of a string literal. Empty for `EOF`.
+    ///
+    /// Every byte of the input belongs to exactly one token's trivia or text.
+    pub fn last_text(&self) -> &str {
+        &self.input[self.last_text.clone()]
+    }
+
+    /// True once the lexer has read past the last character.
+    ///
+    /// A `'\0'` inside the input is an ordinary (illegal) character, not the end.
+    fn at_end(&self) -> bool {
+        self.next_read_position > self.input.len()
+    }
+
+    /// Byte offset of the current character (the input length at the end).
+    fn offset(&self) -> usize {
+        if self.at_end() {
+            self.input.len()
+        } else {
+            self.curr_position
+        }
     }
 
     /// Checks if the current character is a letter (alphabetic or underscore).
@@ -125,11 +209,26 @@ impl Lexer {
         while self.is_letter() {
             self.read_char();
         }
-        let end_position = if self.curr_char == '\0' {
-            self.input.len()
-        } else {
-            self.curr_position
-        };
+        let end_position = self.offset();
+        self.input[start_position..end_position].to_string()
+    }
+
+    /// Reads a string literal from the current position.
+    ///
+    /// Expects the current character to be the opening double quote. Reads until
+    /// the closing double quote or the end of input, leaving the lexer on the
+    /// closing quote.
+    /// ## Returns
+    /// A String containing the characters between the quotes.
+    fn read_string(&mut self) -> String {
+        let start_position = self.curr_position + 1;
+        loop {
+            self.read_char();
+            if self.curr_char == '"' || self.at_end() {
+                break;
+            }
+        }
+        let end_position = self.offset();
         self.input[start_position..end_position].to_string()
     }
 
@@ -150,11 +249,7 @@ impl Lexer {
         while self.is_digit() {
             self.read_char();
         }
-        let end_position = if self.curr_char == '\0' {
-            self.input.len()
-        } else {
-            self.curr_position
-        };
+        let end_position = self.offset();
         self.input[start_position..end_position].to_string()
     }
 
@@ -165,10 +260,20 @@ impl Lexer {
     /// The lexer position is advanced as tokens are consumed.
     ///
     /// ## Returns
-    /// A Token representing the next lexical element in the input.
+    /// A Token representing the next lexical element in the input, tagged with the
+    /// lexer's source file.
     pub fn next_token(&mut self) -> Token {
+        let trivia_start = self.offset();
         self.skip_white_space();
+        let text_start = self.offset();
+        let token = self.read_token().in_file(self.file);
+        self.last_trivia = trivia_start..text_start;
+        self.last_text = text_start..self.offset();
+        token
+    }
 
+    /// Reads the token at the current character, positioned in the default file.
+    fn read_token(&mut self) -> Token {
         // Capture position before reading token
         let line = self.line;
         let column = self.column;
@@ -186,7 +291,17 @@ impl Lexer {
                     Token::new(TokenType::ASSIGN, self.curr_char.to_string(), line, column)
                 }
             }
-            '-' => Token::new(TokenType::MINUS, self.curr_char.to_string(), line, column),
+            '-' => {
+                // Handling '->' case : used by return type annotations
+                if self.peek_char() == '>' {
+                    let ch = self.curr_char;
+                    self.read_char();
+                    let literal = format!("{}{}", ch, self.curr_char);
+                    Token::new(TokenType::ARROW, literal, line, column)
+                } else {
+                    Token::new(TokenType::MINUS, self.curr_char.to_string(), line, column)
+                }
+            }
             '!' => {
                 // Here we have two cases '!' or '!=' they both are separate tokens so need to check
                 if self.peek_char() == '=' {
@@ -232,7 +347,15 @@ impl Lexer {
                 column,
             ),
             ':' => Token::new(TokenType::COLON, self.curr_char.to_string(), line, column),
-            '\0' => Token::new(TokenType::EOF, "".to_string(), line, column),
+            '.' => Token::new(TokenType::DOT, self.curr_char.to_string(), line, column),
+            '?' => Token::new(
+                TokenType::QUESTION,
+                self.curr_char.to_string(),
+                line,
+                column,
+            ),
+            '"' => Token::new(TokenType::STRING, self.read_string(), line, column),
+            '\0' if self.at_end() => Token::new(TokenType::EOF, "".to_string(), line, column),
             _ => {
                 // Handling identifiers and numbers
                 if self.is_letter() {
@@ -346,7 +469,7 @@ mod tests {
             (TokenType::NOTEQ, "!=".to_string()),
             (TokenType::EOF, "".to_string()),
         ];
-        let mut lex = Lexer::new(input);
+        let mut lex = Lexer::new(input, FileId::DETACHED);
         for (i, (expected_type, expected_literal)) in tests.into_iter().enumerate() {
             let token = lex.next_token();
             // Assert that token type matches the expected type
@@ -363,4 +486,161 @@ mod tests {
             )
         }
     }
+
+    #[test]
+    fn test_next_token_modules() {
+        let input = r#"import "lib/math.monkey" as math;
+        export let two = math.add(1, 1);
+        "foo bar"
+        "unterminated"#
+            .to_string();
+        let tests = vec![
+            (TokenType::IMPORT, "import"),
+            (TokenType::STRING, "lib/math.monkey"),
+            (TokenType::AS, "as"),
+            (TokenType::IDENT, "math"),
+            (TokenType::SEMICOLON, ";"),
+            (TokenType::EXPORT, "export"),
+            (TokenType::LET, "let"),
+            (TokenType::IDENT, "two"),
+            (TokenType::ASSIGN, "="),
+            (TokenType::IDENT, "math"),
+            (TokenType::DOT, "."),
+            (TokenType::IDENT, "add"),
+            (TokenType::LPAREN, "("),
+            (TokenType::INT, "1"),
+            (TokenType::COMMA, ","),
+            (TokenType::INT, "1"),
+            (TokenType::RPAREN, ")"),
+            (TokenType::SEMICOLON, ";"),
+            (TokenType::STRING, "foo bar"),
+            (TokenType::STRING, "unterminated"),
+            (TokenType::EOF, ""),
+        ];
+        let mut lex = Lexer::new(input, FileId::DETACHED);
+        for (i, (expected_type, expected_literal)) in tests.into_iter().enumerate() {
+            let token = lex.next_token();
+            assert_eq!(
+                token.token_type, expected_type,
+                "tests[{}] - tokentype wrong. expected={:?}, got={:?}",
+                i, expected_type, token.token_type
+            );
+            assert_eq!(
+                token.literal, expected_literal,
+                "tests[{}] - literal wrong. expected={:?}, got={:?}",
+                i, expected_literal, token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_tokens_carry_file_id() {
+        let mut sources = crate::source::SourceMap::new();
+        sources.add("main.monkey", "");
+        let file = sources.add("lib.monkey", "let x;");
+
+        let mut lex = Lexer::new("let x;".to_string(), file);
+        loop {
+            let token = lex.next_token();
+            assert_eq!(token.file, file, "token {:?} lost its file id", token);
+            if token.token_type == TokenType::EOF {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_token_type_annotations() {
+        let input = "let f: fn(int?) -> [bool] = fn(a: int) -> bool { a - 1; };".to_string();
+        let tests = vec![
+            (TokenType::LET, "let"),
+            (TokenType::IDENT, "f"),
+            (TokenType::COLON, ":"),
+            (TokenType::FUNCTION, "fn"),
+            (TokenType::LPAREN, "("),
+            (TokenType::IDENT, "int"),
+            (TokenType::QUESTION, "?"),
+            (TokenType::RPAREN, ")"),
+            (TokenType::ARROW, "->"),
+            (TokenType::LBRACKET, "["),
+            (TokenType::IDENT, "bool"),
+            (TokenType::RBRACKET, "]"),
+            (TokenType::ASSIGN, "="),
+            (TokenType::FUNCTION, "fn"),
+            (TokenType::LPAREN, "("),
+            (TokenType::IDENT, "a"),
+            (TokenType::COLON, ":"),
+            (TokenType::IDENT, "int"),
+            (TokenType::RPAREN, ")"),
+            (TokenType::ARROW, "->"),
+            (TokenType::IDENT, "bool"),
+            (TokenType::LBRACE, "{"),
+            (TokenType::IDENT, "a"),
+            (TokenType::MINUS, "-"),
+            (TokenType::INT, "1"),
+            (TokenType::SEMICOLON, ";"),
+            (TokenType::RBRACE, "}"),
+            (TokenType::SEMICOLON, ";"),
+            (TokenType::EOF, ""),
+        ];
+        let mut lex = Lexer::new(input, FileId::DETACHED);
+        for (i, (expected_type, expected_literal)) in tests.into_iter().enumerate() {
+            let token = lex.next_token();
+            assert_eq!(
+                token.token_type, expected_type,
+                "tests[{}] - tokentype wrong. expected={:?}, got={:?}",
+                i, expected_type, token.token_type
+            );
+            assert_eq!(
+                token.literal, expected_literal,
+                "tests[{}] - literal wrong. expected={:?}, got={:?}",
+                i, expected_literal, token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_comments_are_skipped_and_recorded() {
+        let input = "// header\nlet x = 10 / 2; // half\n//\n".to_string();
+        let mut lex = Lexer::new(input, FileId::DETACHED);
+        let types: Vec<TokenType> = std::iter::from_fn(|| {
+            let token = lex.next_token();
+            (token.token_type != TokenType::EOF).then_some(token.token_type)
+        })
+        .collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::LET,
+                TokenType::IDENT,
+                TokenType::ASSIGN,
+                TokenType::INT,
+                TokenType::SLASH,
+                TokenType::INT,
+                TokenType::SEMICOLON,
+            ]
+        );
+        let comments: Vec<(&str, usize, usize)> = lex
+            .comments()
+            .iter()
+            .map(|c| (c.text.as_str(), c.line, c.column))
+            .collect();
+        assert_eq!(
+            comments,
+            [("// header", 1, 1), ("// half", 2, 17), ("//", 3, 1)]
+        );
+    }
+
+    #[test]
+    fn test_keywords_match_lookup_identifier() {
+        for keyword in token::KEYWORDS {
+            assert_ne!(
+                token::lookup_identifier(keyword),
+                TokenType::IDENT,
+                "{}",
+                keyword
+            );
+        }
+        assert_eq!(token::lookup_identifier("fun"), TokenType::IDENT);
+    }
 }
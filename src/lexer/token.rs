@@ -1,11 +1,15 @@
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+use crate::source::FileId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
 pub enum TokenType {
     ILLEGAL, // Invalid token
     EOF,     // End of file
 
     // Identifiers and literals
-    IDENT, // Variable names, function names, etc.
-    INT,   // Integer literals
+    IDENT,  // Variable names, function names, etc.
+    INT,    // Integer literals
+    STRING, // String literals
 
     // Operators
     ASSIGN,   // "="
@@ -18,6 +22,8 @@ pub enum TokenType {
     GT,       // ">"
     NOTEQ,    // "!="
     EQ,       // "=="
+    ARROW,    // "->"
+    QUESTION, // "?"
 
     // Delimiters
     COMMA,     // ","
@@ -29,6 +35,7 @@ pub enum TokenType {
     LBRACKET,  // "["
     RBRACKET,  // "]"
     COLON,     // ":"
+    DOT,       // "."
     // Keywords
     FUNCTION, // "fn"
     LET,      // "let"
@@ -37,14 +44,18 @@ pub enum TokenType {
     RETURN,   // "return"
     TRUE,     // "true"
     FALSE,    // "false"
+    IMPORT,   // "import"
+    EXPORT,   // "export"
+    AS,       // "as"
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
     pub line: usize,
     pub column: usize,
+    pub file: FileId,
 }
 
 impl Token {
@@ -60,15 +71,24 @@ impl Token {
     /// ## Returns
     ///
     /// A new Token instance with the provided type, literal, and position.
+    /// The token belongs to no file (`FileId::DETACHED`); use `in_file` to
+    /// attach one.
     pub fn new(token_type: TokenType, literal: String, line: usize, column: usize) -> Self {
         Token {
             token_type,
             literal,
             line,
             column,
+            file: FileId::DETACHED,
         }
     }
 
+    /// Returns the token moved into the given source file.
+    pub fn in_file(mut self, file: FileId) -> Self {
+        self.file = file;
+        self
+    }
+
     /// Converts the token to a Monkey-Lang friendly string representation.
     ///
     /// ## Returns
@@ -78,6 +98,7 @@ impl Token {
         match self.token_type {
             TokenType::IDENT => self.literal.to_string(),
             TokenType::INT => self.literal.to_string(),
+            TokenType::STRING => format!("\"{}\"", self.literal),
             TokenType::ASSIGN => "=".to_string(),
             TokenType::PLUS => "+".to_string(),
             TokenType::MINUS => "-".to_string(),
@@ -88,12 +109,15 @@ impl Token {
             TokenType::GT => ">".to_string(),
             TokenType::NOTEQ => "!=".to_string(),
             TokenType::EQ => "==".to_string(),
+            TokenType::ARROW => "->".to_string(),
+            TokenType::QUESTION => "?".to_string(),
             TokenType::COMMA => ",".to_string(),
             TokenType::SEMICOLON => ";".to_string(),
             TokenType::LPAREN => "(".to_string(),
             TokenType::RPAREN => ")".to_string(),
             TokenType::LBRACE => "{".to_string(),
             TokenType::RBRACE => "}".to_string(),
+            TokenType::DOT => ".".to_string(),
             TokenType::FUNCTION => "fn".to_string(),
             TokenType::LET => "let".to_string(),
             TokenType::IF => "if".to_string(),
@@ -101,11 +125,19 @@ impl Token {
             TokenType::RETURN => "return".to_string(),
             TokenType::TRUE => "true".to_string(),
             TokenType::FALSE => "false".to_string(),
+            TokenType::IMPORT => "import".to_string(),
+            TokenType::EXPORT => "export".to_string(),
+            TokenType::AS => "as".to_string(),
             _ => "ILLEGAL".to_string(),
         }
     }
 }
 
+/// Every keyword, in the order `lookup_identifier` checks them.
+pub const KEYWORDS: [&str; 10] = [
+    "fn", "let", "if", "else", "return", "true", "false", "import", "export", "as",
+];
+
 /// Looks up the token type for a given identifier string.
 ///
 /// This function checks if the identifier matches any of the predefined keywords
@@ -125,6 +157,9 @@ pub fn lookup_identifier(ident: &str) -> TokenType {
         "return" => TokenType::RETURN,
         "true" => TokenType::TRUE,
         "false" => TokenType::FALSE,
+        "import" => TokenType::IMPORT,
+        "export" => TokenType::EXPORT,
+        "as" => TokenType::AS,
         _ => TokenType::IDENT,
     }
 }
@@ -142,6 +177,8 @@ pub fn lookup_operator(op: &str) -> TokenType {
     match op {
         "==" => TokenType::EQ,
         "!=" => TokenType::NOTEQ,
+        "->" => TokenType::ARROW,
+        "?" => TokenType::QUESTION,
         "<" => TokenType::LT,
         ">" => TokenType::GT,
         "(" => TokenType::LPAREN,
@@ -153,6 +190,7 @@ pub fn lookup_operator(op: &str) -> TokenType {
         ":" => TokenType::COLON,
         "[" => TokenType::LBRACKET,
         "]" => TokenType::RBRACKET,
+        "." => TokenType::DOT,
         _ => TokenType::ILLEGAL,
     }
 }
@@ -1,7 +1,30 @@
 pub mod ast;
+pub mod cli;
+pub mod code;
+pub mod compiler;
+pub mod coverage;
+pub mod dap;
+pub mod debugger;
+pub mod diagnostic;
+pub mod disasm;
+pub mod evaluator;
+pub mod formatter;
+pub mod highlight;
+pub mod interpreter;
 pub mod lexer;
+pub mod lsp;
+pub mod mbc;
+pub mod module;
+pub mod object;
 pub mod parser;
+pub mod profiler;
+pub mod rename;
 pub mod repl;
+pub mod resolve;
+pub mod source;
+pub mod syntax;
+pub mod typeck;
+pub mod vm;
 
 #[cfg(test)]
 mod tests;
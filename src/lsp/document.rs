@@ -0,0 +1,168 @@
+//! An open text document and conversions between LSP and source positions.
+//!
+//! LSP positions are a 0-based line and a 0-based column counted in UTF-16
+//! code units. The CST uses byte offsets, and tokens and `Span`s use a 1-based
+//! line and a 1-based column counted in chars.
+
+use crate::lexer::token::TokenType;
+use crate::parser::error::Span;
+use crate::resolve::{Resolution, resolve};
+use crate::syntax::{Parse, SyntaxKind, SyntaxToken, TextEdit, parse};
+use serde::{Deserialize, Serialize};
+use std::ops::Range as ByteRange;
+
+/// A position in LSP coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// A range in LSP coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// The text of an open document with its parse and name resolution.
+#[derive(Debug, Clone)]
+pub struct Document {
+    text: String,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    parse: Parse,
+    resolution: Resolution,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let parse = parse(&text);
+        Document::with_parse(text, parse)
+    }
+
+    fn with_parse(text: String, parse: Parse) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let resolution = resolve(parse.program());
+        Document {
+            text,
+            line_starts,
+            parse,
+            resolution,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn parse(&self) -> &Parse {
+        &self.parse
+    }
+
+    pub fn resolution(&self) -> &Resolution {
+        &self.resolution
+    }
+
+    /// Replaces `range` (or the whole text when `None`) with `text`,
+    /// reparsing only what the change touches.
+    pub fn apply_change(&mut self, range: Option<Range>, text: &str) {
+        let range = match range {
+            Some(range) => self.offset(range.start)..self.offset(range.end),
+            None => 0..self.text.len(),
+        };
+        let range = range.start.min(range.end)..range.end;
+        let edit = TextEdit::new(range, text);
+        let parse = self.parse.reparse(&edit);
+        *self = Document::with_parse(edit.apply(&self.text), parse);
+    }
+
+    /// The byte offset of `position`, clamped to the text.
+    pub fn offset(&self, position: Position) -> usize {
+        let Some(&start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+        let line = &self.text[start..self.line_end(start)];
+        let mut units = 0;
+        for (offset, c) in line.char_indices() {
+            if units >= position.character as usize {
+                return start + offset;
+            }
+            units += c.len_utf16();
+        }
+        start + line.len()
+    }
+
+    /// The LSP position of a byte offset.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        Position {
+            line: line as u32,
+            character: self.text[start..offset].encode_utf16().count() as u32,
+        }
+    }
+
+    /// The LSP range of a byte range.
+    pub fn range(&self, range: ByteRange<usize>) -> Range {
+        Range {
+            start: self.position(range.start),
+            end: self.position(range.end),
+        }
+    }
+
+    /// The byte offset of a 1-based line and char column, as in a `Span`.
+    pub fn span_offset(&self, span: &Span) -> usize {
+        let Some(&start) = self.line_starts.get(span.line.saturating_sub(1)) else {
+            return self.text.len();
+        };
+        let line = &self.text[start..self.line_end(start)];
+        start
+            + line
+                .char_indices()
+                .nth(span.column.saturating_sub(1))
+                .map_or(line.len(), |(offset, _)| offset)
+    }
+
+    /// The `Span` of the token starting at `offset`.
+    pub fn span_at(&self, offset: usize) -> Span {
+        let position = self.position(offset);
+        let start = self.line_starts[position.line as usize];
+        Span::new(
+            position.line as usize + 1,
+            self.text[start..offset].chars().count() + 1,
+        )
+    }
+
+    /// The non-trivia token at `offset`, or the one ending there.
+    pub fn token_at(&self, offset: usize) -> Option<SyntaxToken> {
+        self.tokens_around(offset)
+            .find(|token| !token.kind().is_trivia())
+    }
+
+    /// The identifier at `offset`, or the one ending there, so a cursor just
+    /// after a name still finds it.
+    pub fn ident_at(&self, offset: usize) -> Option<SyntaxToken> {
+        self.tokens_around(offset)
+            .find(|token| token.kind() == SyntaxKind::Token(TokenType::IDENT))
+    }
+
+    /// The token at `offset`, then the one before it.
+    fn tokens_around(&self, offset: usize) -> impl Iterator<Item = SyntaxToken> + '_ {
+        let root = self.parse.syntax();
+        [Some(offset), offset.checked_sub(1)]
+            .into_iter()
+            .flatten()
+            .filter_map(|offset| root.token_at_offset(offset))
+    }
+
+    /// Where the line starting at `start` ends, excluding the newline.
+    fn line_end(&self, start: usize) -> usize {
+        self.text[start..]
+            .find('\n')
+            .map_or(self.text.len(), |i| start + i)
+    }
+}
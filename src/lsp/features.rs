@@ -0,0 +1,315 @@
+//! The language features: diagnostics, symbols, hover, definition,
+//! completion, semantic tokens and rename. Each takes a `Document` and returns the LSP
+//! result as JSON.
+
+use super::document::Document;
+use crate::highlight::{Category, classify_parse};
+use crate::lexer::token::{KEYWORDS, TokenType};
+use crate::rename::{self, RenameError};
+use crate::resolve::Binding;
+use crate::syntax::{
+    SyntaxKind, SyntaxNode, SyntaxToken,
+    ast::{AstNode, BlockExpr, Expr, FnExpr, LetStmt, Program, Stmt},
+};
+use serde_json::{Value, json};
+
+/// `SymbolKind.Function` and `SymbolKind.Variable`.
+const SYMBOL_FUNCTION: u32 = 12;
+const SYMBOL_VARIABLE: u32 = 13;
+/// `CompletionItemKind.Function`, `.Variable` and `.Keyword`.
+const COMPLETION_FUNCTION: u32 = 3;
+const COMPLETION_VARIABLE: u32 = 6;
+const COMPLETION_KEYWORD: u32 = 14;
+/// `DiagnosticSeverity.Error`.
+const SEVERITY_ERROR: u32 = 1;
+
+/// The semantic token types the server reports, in legend order.
+pub const SEMANTIC_TOKEN_TYPES: [&str; 8] = [
+    "keyword",
+    "operator",
+    "number",
+    "string",
+    "comment",
+    "function",
+    "parameter",
+    "variable",
+];
+
+/// The parser errors of the document as LSP diagnostics. Each covers the
+/// token the error points at.
+pub fn diagnostics(doc: &Document) -> Value {
+    let diagnostics: Vec<Value> = doc
+        .parse()
+        .errors()
+        .iter()
+        .map(|error| {
+            let start = doc.span_offset(&error.span);
+            let end = doc
+                .parse()
+                .syntax()
+                .token_at_offset(start)
+                .filter(|token| token.text_range().start == start)
+                .map_or(start, |token| token.text_range().end);
+            json!({
+                "range": doc.range(start..end),
+                "severity": SEVERITY_ERROR,
+                "source": "monkey",
+                "message": error.message,
+            })
+        })
+        .collect();
+    json!(diagnostics)
+}
+
+/// A `DocumentSymbol` for every `let` binding, nested under the function
+/// whose body contains it.
+pub fn document_symbols(doc: &Document) -> Value {
+    json!(symbols_in(doc, doc.parse().root().statements()))
+}
+
+fn symbols_in(doc: &Document, statements: impl Iterator<Item = Stmt>) -> Vec<Value> {
+    statements
+        .filter_map(|stmt| match stmt {
+            Stmt::Let(let_stmt) => Some(let_stmt),
+            Stmt::Export(export) => export.let_stmt(),
+            _ => None,
+        })
+        .filter_map(|let_stmt| symbol(doc, &let_stmt))
+        .collect()
+}
+
+fn symbol(doc: &Document, let_stmt: &LetStmt) -> Option<Value> {
+    let name = let_stmt.name()?;
+    let value = let_stmt.value();
+    let kind = match value {
+        Some(Expr::Fn(_)) => SYMBOL_FUNCTION,
+        _ => SYMBOL_VARIABLE,
+    };
+    // Bindings inside function bodies in the value, outermost blocks only
+    let children: Vec<Value> = value
+        .map(|value| {
+            blocks_below(value.syntax())
+                .iter()
+                .flat_map(|block| symbols_in(doc, block.statements()))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(json!({
+        "name": name.text(),
+        "kind": kind,
+        "range": doc.range(let_stmt.syntax().text_range()),
+        "selectionRange": doc.range(name.text_range()),
+        "children": children,
+    }))
+}
+
+/// The blocks below `node` that are not inside another block below it.
+fn blocks_below(node: &SyntaxNode) -> Vec<BlockExpr> {
+    node.children()
+        .flat_map(|child| match BlockExpr::cast(child.clone()) {
+            Some(block) => vec![block],
+            None => blocks_below(&child),
+        })
+        .collect()
+}
+
+/// Shows the kind of AST node under the cursor and, for a name, what
+/// introduced it.
+pub fn hover(doc: &Document, offset: usize) -> Option<Value> {
+    let token = doc.token_at(offset)?;
+    let kind = ast_kind(&token)?;
+    let mut value = format!("`{}`", kind);
+    if let Some(binding) = binding(doc, &token) {
+        value.push_str(&format!("\n\n{} `{}`", binding.kind, binding.name));
+    }
+    Some(json!({
+        "contents": { "kind": "markdown", "value": value },
+        "range": doc.range(token.text_range()),
+    }))
+}
+
+/// The name of the AST node a token belongs to, as in the JSON dump.
+fn ast_kind(token: &SyntaxToken) -> Option<&'static str> {
+    if token.kind() == SyntaxKind::Token(TokenType::IDENT) {
+        return Some("Identifier");
+    }
+    std::iter::once(token.parent())
+        .chain(token.parent().ancestors())
+        .find_map(|node| {
+            Some(match node.kind() {
+                SyntaxKind::Program => "Program",
+                SyntaxKind::LetStmt => "LetStatement",
+                SyntaxKind::ReturnStmt => "ReturnStatement",
+                SyntaxKind::ExprStmt => "ExpressionStatement",
+                SyntaxKind::ImportStmt => "ImportStatement",
+                SyntaxKind::ExportStmt => "ExportStatement",
+                SyntaxKind::NameRef | SyntaxKind::Param => "Identifier",
+                SyntaxKind::Literal => match token.kind() {
+                    SyntaxKind::Token(TokenType::INT) => "IntegerLiteral",
+                    SyntaxKind::Token(TokenType::STRING) => "StringLiteral",
+                    _ => "BooleanLiteral",
+                },
+                SyntaxKind::PrefixExpr => "PrefixExpression",
+                SyntaxKind::InfixExpr => "InfixExpression",
+                SyntaxKind::IfExpr => "IfExpression",
+                SyntaxKind::BlockExpr => "BlockStatement",
+                SyntaxKind::FnExpr => "FunctionLiteral",
+                SyntaxKind::CallExpr => "CallExpression",
+                SyntaxKind::MemberExpr => "MemberExpression",
+                SyntaxKind::Type => "TypeExpr",
+                _ => return None,
+            })
+        })
+}
+
+/// The range of the binding the identifier at `offset` refers to. A binding
+/// site is its own definition.
+pub fn definition(doc: &Document, offset: usize) -> Option<Value> {
+    let binding = binding(doc, &doc.ident_at(offset)?)?;
+    let start = doc.span_offset(&binding.span);
+    Some(json!(doc.range(start..start + binding.name.len())))
+}
+
+/// The binding an identifier token refers to or defines.
+fn binding<'a>(doc: &'a Document, token: &SyntaxToken) -> Option<&'a Binding> {
+    if token.kind() != SyntaxKind::Token(TokenType::IDENT) {
+        return None;
+    }
+    let span = doc.span_at(token.text_range().start);
+    let resolution = doc.resolution();
+    resolution.binding_of(&span).or_else(|| {
+        resolution
+            .bindings
+            .iter()
+            .find(|binding| binding.span == span)
+    })
+}
+
+/// Keywords and the names visible at `offset`.
+pub fn completion(doc: &Document, offset: usize) -> Value {
+    let root = doc.parse().syntax();
+    let scope = root
+        .token_at_offset(offset.saturating_sub(1))
+        .map_or(root.clone(), |token| token.parent());
+    let mut items: Vec<Value> = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    for node in std::iter::once(scope.clone()).chain(scope.ancestors()) {
+        for (name, is_function) in names_in_scope(&node) {
+            if !seen.contains(&name) {
+                let kind = if is_function {
+                    COMPLETION_FUNCTION
+                } else {
+                    COMPLETION_VARIABLE
+                };
+                items.push(json!({ "label": name, "kind": kind }));
+                seen.push(name);
+            }
+        }
+    }
+    items.extend(
+        KEYWORDS
+            .iter()
+            .map(|keyword| json!({ "label": keyword, "kind": COMPLETION_KEYWORD })),
+    );
+    json!(items)
+}
+
+/// The names a node binds for the code inside it, with whether each is bound
+/// to a function: the parameters of a function, and the `let` bindings and
+/// import aliases of a block or the program.
+fn names_in_scope(node: &SyntaxNode) -> Vec<(String, bool)> {
+    if let Some(function) = FnExpr::cast(node.clone()) {
+        let Some(params) = function.param_list() else {
+            return Vec::new();
+        };
+        return params
+            .params()
+            .filter_map(|param| Some((param.name()?.text().to_string(), false)))
+            .collect();
+    }
+    let statements: Vec<Stmt> = match BlockExpr::cast(node.clone()) {
+        Some(block) => block.statements().collect(),
+        None => match Program::cast(node.clone()) {
+            Some(program) => program.statements().collect(),
+            None => return Vec::new(),
+        },
+    };
+    statements
+        .into_iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Let(let_stmt) => let_binding(&let_stmt),
+            Stmt::Export(export) => let_binding(&export.let_stmt()?),
+            Stmt::Import(import) => Some((import.alias()?.text().to_string(), false)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn let_binding(let_stmt: &LetStmt) -> Option<(String, bool)> {
+    Some((
+        let_stmt.name()?.text().to_string(),
+        matches!(let_stmt.value(), Some(Expr::Fn(_))),
+    ))
+}
+
+/// The whole document as LSP semantic tokens: five integers per token
+/// (line and start delta from the previous token, length, legend index and
+/// modifiers), with positions in UTF-16 units. A token spanning lines is
+/// split into one token per line; unknown text is left out.
+pub fn semantic_tokens(doc: &Document) -> Value {
+    let mut data: Vec<u32> = Vec::new();
+    let mut previous = (0, 0);
+    for highlight in classify_parse(doc.parse(), doc.resolution()) {
+        let Some(index) = legend_index(highlight.category) else {
+            continue;
+        };
+        let mut start = highlight.range.start;
+        for piece in doc.text()[highlight.range].split('\n') {
+            let end = start + piece.len();
+            if !piece.is_empty() {
+                let position = doc.position(start);
+                let (line, character) = (position.line, position.character);
+                let delta_start = if line == previous.0 {
+                    character - previous.1
+                } else {
+                    character
+                };
+                let length = piece.encode_utf16().count() as u32;
+                data.extend([line - previous.0, delta_start, length, index, 0]);
+                previous = (line, character);
+            }
+            start = end + 1;
+        }
+    }
+    json!({ "data": data })
+}
+
+/// The index of a category in `SEMANTIC_TOKEN_TYPES`.
+fn legend_index(category: Category) -> Option<u32> {
+    Some(match category {
+        Category::Keyword => 0,
+        Category::Operator => 1,
+        Category::Number => 2,
+        Category::String => 3,
+        Category::Comment => 4,
+        Category::Function => 5,
+        Category::Parameter => 6,
+        Category::Local => 7,
+        Category::Unknown => return None,
+    })
+}
+
+/// The `WorkspaceEdit` that renames the binding at `offset` in the document
+/// named `uri`.
+pub fn rename(
+    doc: &Document,
+    uri: &str,
+    offset: usize,
+    new_name: &str,
+) -> Result<Value, RenameError> {
+    let edits: Vec<Value> = rename::rename(doc.text(), offset, new_name)?
+        .into_iter()
+        .map(|edit| json!({ "range": doc.range(edit.range), "newText": edit.replacement }))
+        .collect();
+    Ok(json!({ "changes": { uri: edits } }))
+}
@@ -0,0 +1,234 @@
+//! Language Server Protocol server over stdin/stdout (`monkey lsp`).
+//!
+//! The server keeps every open document parsed and answers:
+//!
+//! - `textDocument/publishDiagnostics` with the parser errors, sent after
+//!   every open and change
+//! - `textDocument/documentSymbol` with the `let` bindings, nested under the
+//!   functions that contain them
+//! - `textDocument/hover` with the kind of AST node under the cursor
+//! - `textDocument/definition` for identifiers, using name resolution
+//! - `textDocument/completion` with keywords and the names in scope
+//! - `textDocument/semanticTokens/full` with the classification from
+//!   `highlight`
+//! - `textDocument/rename` for bindings, refused (`RequestFailed`) when the
+//!   new name would change what a name refers to
+//!
+//! Changes are synced incrementally and applied with `Parse::reparse`.
+//!
+//! `run` takes its streams explicitly, so a session can be replayed from a
+//! scripted transcript in tests.
+
+mod document;
+mod features;
+pub mod rpc;
+
+pub use document::{Document, Position, Range};
+
+use crate::cli::{EXIT_FAILURE, EXIT_OK};
+use rpc::RpcError;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// `TextDocumentSyncKind.Incremental`.
+const SYNC_INCREMENTAL: u32 = 2;
+
+/// Serves one LSP session until `exit` or the end of the input.
+/// # Parameters
+/// - `input`: Where client messages are read from
+/// - `output`: Where responses and notifications are written
+/// # Returns
+/// `EXIT_OK` if the client sent `shutdown` before `exit`, `EXIT_FAILURE`
+/// otherwise, or an error if the streams failed or a message was malformed.
+pub fn run<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<i32> {
+    let mut server = Server::default();
+    while let Some(body) = rpc::read_message(&mut input)? {
+        let message: Value = match serde_json::from_str(&body) {
+            Ok(message) => message,
+            Err(error) => {
+                let error = RpcError::new(rpc::PARSE_ERROR, error.to_string());
+                rpc::write_message(&mut output, &rpc::error_response(Value::Null, error))?;
+                continue;
+            }
+        };
+        if message["method"] == "exit" {
+            break;
+        }
+        for reply in server.handle(&message) {
+            rpc::write_message(&mut output, &reply)?;
+        }
+    }
+    Ok(if server.shut_down {
+        EXIT_OK
+    } else {
+        EXIT_FAILURE
+    })
+}
+
+/// The state of a session: the open documents by URI.
+#[derive(Debug, Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+impl Server {
+    /// Handles one client message.
+    /// # Returns
+    /// The messages to send back: the response to a request and any
+    /// notifications it caused.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let Some(method) = message["method"].as_str() else {
+            if message.get("result").is_some() || message.get("error").is_some() {
+                // A response to a request we never send
+                return Vec::new();
+            }
+            let id = message.get("id").cloned().unwrap_or(Value::Null);
+            let error = RpcError::new(rpc::INVALID_REQUEST, "message without a method");
+            return vec![rpc::error_response(id, error)];
+        };
+        let params = &message["params"];
+        log::debug!("lsp: {}", method);
+        let Some(id) = message.get("id").cloned() else {
+            return self.notification(method, params).unwrap_or_else(|error| {
+                log::debug!("lsp: ignoring {}: {}", method, error.message);
+                Vec::new()
+            });
+        };
+        let reply = match self.request(method, params) {
+            Ok(result) => rpc::response(id, result),
+            Err(error) => rpc::error_response(id, error),
+        };
+        vec![reply]
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": SYNC_INCREMENTAL,
+                    "documentSymbolProvider": true,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                    "renameProvider": true,
+                    "semanticTokensProvider": {
+                        "legend": {
+                            "tokenTypes": features::SEMANTIC_TOKEN_TYPES,
+                            "tokenModifiers": [],
+                        },
+                        "full": true,
+                    },
+                },
+                "serverInfo": { "name": "monkey-lsp", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/documentSymbol" => Ok(features::document_symbols(self.document(params)?)),
+            "textDocument/hover" => {
+                let (doc, offset) = self.cursor(params)?;
+                Ok(features::hover(doc, offset).unwrap_or(Value::Null))
+            }
+            "textDocument/definition" => {
+                let (doc, offset) = self.cursor(params)?;
+                let uri = &params["textDocument"]["uri"];
+                Ok(features::definition(doc, offset)
+                    .map_or(Value::Null, |range| json!({ "uri": uri, "range": range })))
+            }
+            "textDocument/completion" => {
+                let (doc, offset) = self.cursor(params)?;
+                Ok(features::completion(doc, offset))
+            }
+            "textDocument/semanticTokens/full" => {
+                Ok(features::semantic_tokens(self.document(params)?))
+            }
+            "textDocument/rename" => {
+                let (doc, offset) = self.cursor(params)?;
+                let uri: String = field(params, &["textDocument", "uri"])?;
+                let new_name: String = field(params, &["newName"])?;
+                features::rename(doc, &uri, offset, &new_name)
+                    .map_err(|error| RpcError::new(rpc::REQUEST_FAILED, error.to_string()))
+            }
+            _ => Err(RpcError::new(
+                rpc::METHOD_NOT_FOUND,
+                format!("unknown method '{}'", method),
+            )),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Result<Vec<Value>, RpcError> {
+        match method {
+            "textDocument/didOpen" => {
+                let uri: String = field(params, &["textDocument", "uri"])?;
+                let text: String = field(params, &["textDocument", "text"])?;
+                self.documents.insert(uri.clone(), Document::new(text));
+                Ok(vec![self.diagnostics(&uri)])
+            }
+            "textDocument/didChange" => {
+                let uri: String = field(params, &["textDocument", "uri"])?;
+                let changes: Vec<Value> = field(params, &["contentChanges"])?;
+                let doc = self.documents.get_mut(&uri).ok_or_else(|| unknown(&uri))?;
+                for change in &changes {
+                    let range: Option<Range> = field(change, &["range"]).ok();
+                    let text: String = field(change, &["text"])?;
+                    doc.apply_change(range, &text);
+                }
+                Ok(vec![self.diagnostics(&uri)])
+            }
+            "textDocument/didClose" => {
+                let uri: String = field(params, &["textDocument", "uri"])?;
+                self.documents.remove(&uri);
+                Ok(vec![rpc::notification(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                )])
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn diagnostics(&self, uri: &str) -> Value {
+        rpc::notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": features::diagnostics(&self.documents[uri]) }),
+        )
+    }
+
+    /// The document named by `params.textDocument.uri`.
+    fn document(&self, params: &Value) -> Result<&Document, RpcError> {
+        let uri: String = field(params, &["textDocument", "uri"])?;
+        self.documents.get(&uri).ok_or_else(|| unknown(&uri))
+    }
+
+    /// The document and byte offset of a `TextDocumentPositionParams`.
+    fn cursor(&self, params: &Value) -> Result<(&Document, usize), RpcError> {
+        let doc = self.document(params)?;
+        let position: Position = field(params, &["position"])?;
+        Ok((doc, doc.offset(position)))
+    }
+}
+
+/// Reads the value at `path` in `value`.
+fn field<T: DeserializeOwned>(value: &Value, path: &[&str]) -> Result<T, RpcError> {
+    let found = path.iter().try_fold(value, |value, key| value.get(key));
+    let found = found.ok_or_else(|| {
+        RpcError::new(rpc::INVALID_PARAMS, format!("missing '{}'", path.join(".")))
+    })?;
+    serde_json::from_value(found.clone()).map_err(|error| {
+        RpcError::new(
+            rpc::INVALID_PARAMS,
+            format!("invalid '{}': {}", path.join("."), error),
+        )
+    })
+}
+
+fn unknown(uri: &str) -> RpcError {
+    RpcError::new(
+        rpc::INVALID_PARAMS,
+        format!("document '{}' is not open", uri),
+    )
+}
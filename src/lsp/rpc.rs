@@ -0,0 +1,126 @@
+//! JSON-RPC 2.0 messages with the LSP base protocol's framing.
+//!
+//! Each message is a JSON body preceded by headers:
+//!
+//! ```text
+//! Content-Length: 52\r\n
+//! \r\n
+//! {"jsonrpc":"2.0","id":1,"method":"shutdown"}
+//! ```
+
+use serde_json::{Value, json};
+use std::io::{self, BufRead, Write};
+
+/// Invalid JSON was received.
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON is not a valid request.
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The method's parameters are invalid.
+pub const INVALID_PARAMS: i64 = -32602;
+/// The request was valid but could not be carried out (LSP `RequestFailed`).
+pub const REQUEST_FAILED: i64 = -32803;
+
+/// An error to send back in a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Reads the body of the next message.
+/// # Returns
+/// - `Ok(Some(body))` for a message
+/// - `Ok(None)` at the end of the input
+/// - `Err(_)` if reading failed or the headers are malformed
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message without a valid Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Writes `message` with its `Content-Length` header.
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// A successful response to the request with `id`.
+pub fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+/// A failed response to the request with `id`.
+pub fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+/// A notification from the server.
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_framing_round_trip() {
+        let mut out = Vec::new();
+        write_message(&mut out, &json!({ "a": "é" })).unwrap();
+        write_message(&mut out, &json!([1, 2])).unwrap();
+        let mut input = Cursor::new(out);
+        assert_eq!(
+            read_message(&mut input).unwrap().as_deref(),
+            Some(r#"{"a":"é"}"#)
+        );
+        assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("[1,2]"));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn test_missing_length_is_an_error() {
+        let mut input = Cursor::new("Content-Type: x\r\n\r\n{}");
+        assert!(read_message(&mut input).is_err());
+    }
+}
@@ -1,15 +1,18 @@
 use env_logger::Builder;
-use monkey_lang::repl::repl;
+use monkey_lang::cli;
 use std::io;
+use std::process;
 
 fn main() -> io::Result<()> {
     // Initialize logger (can be controlled via RUST_LOG environment variable)
     // Examples: RUST_LOG=debug, RUST_LOG=monkey_lang::parser=debug
     Builder::from_default_env().init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
     let stdin = io::stdin().lock();
     let stdout = io::stdout().lock();
+    let stderr = io::stderr().lock();
 
-    repl(stdin, stdout)?;
-    Ok(())
+    let code = cli::run(&args, stdin, stdout, stderr)?;
+    process::exit(code);
 }
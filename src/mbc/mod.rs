@@ -0,0 +1,388 @@
+//! The on-disk format for compiled programs (`.mbc` files).
+//!
+//! A file holds one `Bytecode`, so scripts can ship without their source.
+//! All integers are big-endian, like instruction operands.
+//!
+//! ```text
+//! header     magic "\x7fMBC", version: u16, flags: u16
+//! constants  count: u32, then per constant a tag: u8 and its payload
+//!              0 integer   value: i64
+//!              1 string    length: u32, UTF-8 bytes
+//!              2 function  has name: u8, [name as a string], parameters: u8,
+//!                          locals: u8, code, [spans]
+//! main       code, [spans]
+//!
+//! code       length: u32, instruction bytes
+//! spans      count: u32, then per entry offset: u32, line: u32, column: u32
+//! ```
+//!
+//! The span tables are only present when the `FLAG_SPANS` bit is set; they
+//! map instructions back to source lines and columns for error messages.
+//! Spans read from a file belong to `FileId::DETACHED`: the file does not
+//! say which source they came from.
+//!
+//! `decode` checks everything the VM relies on, so a file that loads cannot
+//! make it index out of bounds: every instruction decodes, constant,
+//! builtin and local indexes are in range, `Closure` names a function, jumps
+//! land on instruction boundaries and every body ends with `ReturnValue`.
+//! Truncated or corrupt input is reported as a `LoadError`, never a panic.
+
+use crate::code::{Instructions, Opcode, SpanTable, read_operands};
+use crate::compiler::Bytecode;
+use crate::object::{BUILTINS, CompiledFunction, Object};
+use crate::parser::error::Span;
+use std::fmt;
+use std::rc::Rc;
+
+/// The first bytes of every `.mbc` file.
+pub const MAGIC: [u8; 4] = *b"\x7fMBC";
+/// The format version written by `encode`, the only one `decode` accepts.
+pub const VERSION: u16 = 1;
+/// Header flag: the file has span tables.
+pub const FLAG_SPANS: u16 = 1;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+/// Why a file could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The file does not start with `MAGIC`.
+    NotBytecode,
+    UnsupportedVersion(u16),
+    /// The file ends before the data at `offset` is complete.
+    Truncated {
+        offset: usize,
+    },
+    /// The data at byte `offset` is malformed.
+    Corrupt {
+        offset: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::NotBytecode => write!(f, "not a compiled Monkey file (bad magic number)"),
+            LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported bytecode format version {} (expected {})",
+                version, VERSION
+            ),
+            LoadError::Truncated { offset } => {
+                write!(f, "truncated file: data at byte {} is incomplete", offset)
+            }
+            LoadError::Corrupt { offset, message } => {
+                write!(f, "corrupt file at byte {}: {}", offset, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Whether `bytes` look like an `.mbc` file rather than source text.
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+// ============ ENCODING ============
+
+/// Serializes a program.
+/// # Parameters
+/// - `bytecode`: The compiled program
+/// - `spans`: Whether to include the span tables
+///
+/// # Panics
+/// If the constant pool holds a value other than an integer, a string or a
+/// compiled function. The compiler produces no others.
+pub fn encode(bytecode: &Bytecode, spans: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&VERSION.to_be_bytes());
+    out.extend_from_slice(&(if spans { FLAG_SPANS } else { 0 }).to_be_bytes());
+    put_u32(&mut out, bytecode.constants.len());
+    for constant in &bytecode.constants {
+        match constant {
+            Object::Integer(value) => {
+                out.push(TAG_INTEGER);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            Object::String(value) => {
+                out.push(TAG_STRING);
+                put_bytes(&mut out, value.as_bytes());
+            }
+            Object::CompiledFunction(function) => {
+                out.push(TAG_FUNCTION);
+                match &function.name {
+                    Some(name) => {
+                        out.push(1);
+                        put_bytes(&mut out, name.as_bytes());
+                    }
+                    None => out.push(0),
+                }
+                out.push(function.num_parameters as u8);
+                out.push(function.num_locals as u8);
+                put_code(&mut out, &function.instructions, &function.spans, spans);
+            }
+            other => panic!("a {} cannot be stored as a constant", other.type_name()),
+        }
+    }
+    put_code(&mut out, &bytecode.instructions, &bytecode.spans, spans);
+    out
+}
+
+fn put_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_be_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn put_code(out: &mut Vec<u8>, instructions: &[u8], spans: &[(usize, Span)], with_spans: bool) {
+    put_bytes(out, instructions);
+    if with_spans {
+        put_u32(out, spans.len());
+        for (offset, span) in spans {
+            put_u32(out, *offset);
+            put_u32(out, span.line);
+            put_u32(out, span.column);
+        }
+    }
+}
+
+// ============ DECODING ============
+
+/// Instructions read from a file and where they start in it.
+struct Code {
+    instructions: Instructions,
+    spans: SpanTable,
+    offset: usize,
+}
+
+/// Reads values from the file, tracking the offset for errors.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], LoadError> {
+        let end = self
+            .offset
+            .checked_add(count)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(LoadError::Truncated {
+                offset: self.offset,
+            })?;
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], LoadError> {
+        Ok(self.take(N)?.try_into().expect("take returns N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, LoadError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<usize, LoadError> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], LoadError> {
+        let length = self.u32()?;
+        self.take(length)
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        let offset = self.offset;
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupt(offset, "string is not UTF-8"))
+    }
+
+    fn code(&mut self, spans: bool) -> Result<Code, LoadError> {
+        let offset = self.offset + 4;
+        let instructions = self.bytes()?.to_vec();
+        let mut table = SpanTable::new();
+        if spans {
+            let count = self.u32()?;
+            for _ in 0..count {
+                let entry = self.offset;
+                let start = self.u32()?;
+                let span = Span::new(self.u32()?, self.u32()?);
+                if start >= instructions.len()
+                    || table.last().is_some_and(|(last, _)| *last >= start)
+                {
+                    return Err(corrupt(entry, "span table is out of order"));
+                }
+                table.push((start, span));
+            }
+        }
+        Ok(Code {
+            instructions,
+            spans: table,
+            offset,
+        })
+    }
+}
+
+/// Loads a program, validating it for the VM.
+pub fn decode(bytes: &[u8]) -> Result<Bytecode, LoadError> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if !is_bytecode(bytes) {
+        return Err(LoadError::NotBytecode);
+    }
+    reader.take(MAGIC.len())?;
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+    let flags_offset = reader.offset;
+    let flags = reader.u16()?;
+    if flags & !FLAG_SPANS != 0 {
+        return Err(corrupt(
+            flags_offset,
+            format!("unknown flags {:#06x}", flags),
+        ));
+    }
+    let spans = flags & FLAG_SPANS != 0;
+
+    let count = reader.u32()?;
+    let mut constants = Vec::new();
+    // Bodies are checked once every constant they may refer to is known
+    let mut bodies = Vec::new();
+    for _ in 0..count {
+        let tag_offset = reader.offset;
+        match reader.u8()? {
+            TAG_INTEGER => constants.push(Object::Integer(i64::from_be_bytes(reader.array()?))),
+            TAG_STRING => constants.push(Object::String(reader.string()?.into())),
+            TAG_FUNCTION => {
+                let name = match reader.u8()? {
+                    0 => None,
+                    1 => Some(reader.string()?),
+                    _ => return Err(corrupt(tag_offset + 1, "invalid function name flag")),
+                };
+                let num_parameters = reader.u8()? as usize;
+                let num_locals = reader.u8()? as usize;
+                if num_parameters > num_locals {
+                    return Err(corrupt(
+                        tag_offset,
+                        "function has more parameters than locals",
+                    ));
+                }
+                let code = reader.code(spans)?;
+                bodies.push((constants.len(), code.offset));
+                constants.push(Object::CompiledFunction(Rc::new(CompiledFunction {
+                    name,
+                    instructions: code.instructions,
+                    num_locals,
+                    num_parameters,
+                    spans: code.spans,
+                })));
+            }
+            tag => return Err(corrupt(tag_offset, format!("unknown constant tag {}", tag))),
+        }
+    }
+    let main = reader.code(spans)?;
+    if reader.offset != bytes.len() {
+        return Err(corrupt(reader.offset, "unexpected data after the program"));
+    }
+
+    for (index, offset) in bodies {
+        let Object::CompiledFunction(function) = &constants[index] else {
+            unreachable!("bodies only lists functions");
+        };
+        validate(
+            &function.instructions,
+            offset,
+            function.num_locals,
+            &constants,
+        )?;
+    }
+    validate(&main.instructions, main.offset, 0, &constants)?;
+    Ok(Bytecode {
+        instructions: main.instructions,
+        constants,
+        spans: main.spans,
+    })
+}
+
+/// Checks that the VM can run `instructions` without reading out of bounds.
+/// # Parameters
+/// - `instructions`: A function body or the main program
+/// - `base`: Where the instructions start in the file, for error offsets
+/// - `num_locals`: The slots the function has (0 for the main program)
+/// - `constants`: The whole constant pool
+fn validate(
+    instructions: &[u8],
+    base: usize,
+    num_locals: usize,
+    constants: &[Object],
+) -> Result<(), LoadError> {
+    let mut starts = Vec::new();
+    let mut jumps = Vec::new();
+    let mut last = None;
+    let mut offset = 0;
+    while offset < instructions.len() {
+        let error = |message: String| corrupt(base + offset, message);
+        let op = Opcode::from_byte(instructions[offset])
+            .ok_or_else(|| error(format!("unknown opcode {}", instructions[offset])))?;
+        let (operands, read) = read_operands(op, &instructions[offset + 1..])
+            .ok_or_else(|| error(format!("{} runs past the end of the code", op)))?;
+        let operand = operands.first().copied().unwrap_or(0);
+        match op {
+            Opcode::Constant if operand >= constants.len() => {
+                return Err(error(format!("constant {} does not exist", operand)));
+            }
+            Opcode::Closure
+                if !matches!(constants.get(operand), Some(Object::CompiledFunction(_))) =>
+            {
+                return Err(error(format!("constant {} is not a function", operand)));
+            }
+            Opcode::GetLocal | Opcode::SetLocal if operand >= num_locals => {
+                return Err(error(format!("local {} does not exist", operand)));
+            }
+            Opcode::GetBuiltin if operand >= BUILTINS.len() => {
+                return Err(error(format!("builtin {} does not exist", operand)));
+            }
+            Opcode::Jump | Opcode::JumpNotTruthy => jumps.push((offset, operand)),
+            _ => {}
+        }
+        starts.push(offset);
+        last = Some(op);
+        offset += 1 + read;
+    }
+    if last != Some(Opcode::ReturnValue) {
+        return Err(corrupt(
+            base + instructions.len(),
+            "code does not end with ReturnValue",
+        ));
+    }
+    for (offset, target) in jumps {
+        if starts.binary_search(&target).is_err() {
+            return Err(corrupt(
+                base + offset,
+                format!("jump to {} is not the start of an instruction", target),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn corrupt(offset: usize, message: impl Into<String>) -> LoadError {
+    LoadError::Corrupt {
+        offset,
+        message: message.into(),
+    }
+}
@@ -0,0 +1,590 @@
+//! Module loading for multi-file Monkey programs.
+//!
+//! A module is a single Monkey source file. Modules pull in other modules with
+//! `import "<path>" as <alias>;` and make bindings visible to importers with
+//! `export let <name> = <value>;`. The importer reaches those bindings through
+//! its own alias (`alias.name`), so every module keeps its own namespace.
+//!
+//! The `ModuleLoader` resolves import paths relative to the importing file,
+//! parses every reachable file once, rejects import cycles, and checks that
+//! every `alias.name` refers to an exported binding. `Evaluator::eval_modules`
+//! runs the graph it returns.
+
+use crate::ast::{
+    Program, Visitor,
+    expression::{Expression, MemberExpression},
+    statement::Statement,
+    visit,
+};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Lexer;
+use crate::parser::{
+    Parser,
+    error::{ParserError, Span},
+};
+use crate::source::{FileId, SourceMap};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Index of a module inside a `ModuleGraph`.
+pub type ModuleId = usize;
+
+/// Source of module text, abstracted so tests can load from memory.
+pub trait SourceLoader {
+    /// Returns the contents of the file at `path`.
+    fn load(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Loads module sources from the file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystemLoader;
+
+impl SourceLoader for FileSystemLoader {
+    fn load(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+impl SourceLoader for HashMap<PathBuf, String> {
+    fn load(&self, path: &Path) -> io::Result<String> {
+        self.get(path).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such file: {}", path.display()),
+            )
+        })
+    }
+}
+
+/// A parsed module together with its resolved imports and exported names.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub path: PathBuf,
+    /// The module's entry in the loader's `SourceMap`.
+    pub file: FileId,
+    pub program: Program,
+    /// Import aliases in source order, each mapped to the imported module.
+    pub imports: Vec<(String, ModuleId)>,
+    /// Names bound by `export let` statements, in source order.
+    pub exports: Vec<String>,
+}
+
+impl Module {
+    /// Returns the module imported under `alias`, if any.
+    pub fn import(&self, alias: &str) -> Option<ModuleId> {
+        self.imports
+            .iter()
+            .find(|(name, _)| name == alias)
+            .map(|(_, id)| *id)
+    }
+
+    /// Returns true if the module exports a binding called `name`.
+    pub fn exports(&self, name: &str) -> bool {
+        self.exports.iter().any(|export| export == name)
+    }
+}
+
+/// All modules reachable from an entry file.
+///
+/// Modules are stored in dependency order: every module comes after all of
+/// the modules it imports, so evaluating them front to back is always valid.
+#[derive(Debug, Clone)]
+pub struct ModuleGraph {
+    pub modules: Vec<Module>,
+    pub entry: ModuleId,
+}
+
+impl ModuleGraph {
+    /// Returns the module with the given id.
+    pub fn module(&self, id: ModuleId) -> &Module {
+        &self.modules[id]
+    }
+
+    /// Returns the entry module.
+    pub fn entry(&self) -> &Module {
+        &self.modules[self.entry]
+    }
+}
+
+/// An error raised while loading a module graph.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleError {
+    /// The file could not be read.
+    Io { path: PathBuf, message: String },
+    /// The file was read but did not parse. Spans point into `path`.
+    Parse {
+        path: PathBuf,
+        errors: Vec<ParserError>,
+    },
+    /// The files import each other; the first path is repeated at the end.
+    Cycle { cycle: Vec<PathBuf> },
+    /// `alias.name` was used but the imported module does not export `name`.
+    NotExported {
+        path: PathBuf,
+        span: Span,
+        module: PathBuf,
+        name: String,
+    },
+    /// The same name is exported twice from one module.
+    DuplicateExport {
+        path: PathBuf,
+        span: Span,
+        name: String,
+    },
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::Io { path, message } => {
+                write!(f, "{}: cannot read module: {}", path.display(), message)
+            }
+            ModuleError::Parse { path, errors } => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(
+                        f,
+                        "{}:{}:{}: {}",
+                        path.display(),
+                        error.span.line,
+                        error.span.column,
+                        error.message
+                    )?;
+                }
+                Ok(())
+            }
+            ModuleError::Cycle { cycle } => {
+                let chain: Vec<String> = cycle.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "import cycle: {}", chain.join(" -> "))
+            }
+            ModuleError::NotExported {
+                path,
+                span,
+                module,
+                name,
+            } => write!(
+                f,
+                "{}:{}:{}: '{}' is not exported by {}",
+                path.display(),
+                span.line,
+                span.column,
+                name,
+                module.display()
+            ),
+            ModuleError::DuplicateExport { path, span, name } => write!(
+                f,
+                "{}:{}:{}: '{}' is exported more than once",
+                path.display(),
+                span.line,
+                span.column,
+                name
+            ),
+        }
+    }
+}
+
+impl ModuleError {
+    /// Converts the error into renderable diagnostics, one per parser error.
+    /// # Returns
+    /// `None` for errors that have no position in a file: unreadable files
+    /// and cycles.
+    pub fn to_diagnostics(&self) -> Option<Vec<Diagnostic>> {
+        match self {
+            ModuleError::Io { .. } | ModuleError::Cycle { .. } => None,
+            ModuleError::Parse { errors, .. } => {
+                Some(errors.iter().map(Diagnostic::from).collect())
+            }
+            ModuleError::NotExported {
+                span, module, name, ..
+            } => Some(vec![Diagnostic::error(
+                span.clone(),
+                format!("'{}' is not exported by {}", name, module.display()),
+            )]),
+            ModuleError::DuplicateExport { span, name, .. } => Some(vec![Diagnostic::error(
+                span.clone(),
+                format!("'{}' is exported more than once", name),
+            )]),
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
+
+/// Loads an entry module and everything it imports.
+///
+/// Every file read is registered in the loader's `SourceMap`, which outlives
+/// individual loads so diagnostics can be rendered against the right text.
+#[derive(Debug)]
+pub struct ModuleLoader<L: SourceLoader> {
+    loader: L,
+    sources: SourceMap,
+    modules: Vec<Module>,
+    by_path: HashMap<PathBuf, ModuleId>,
+    /// Paths currently being loaded, used to detect cycles.
+    stack: Vec<PathBuf>,
+}
+
+impl ModuleLoader<FileSystemLoader> {
+    /// Creates a loader that reads modules from the file system.
+    pub fn from_file_system() -> Self {
+        Self::new(FileSystemLoader)
+    }
+}
+
+impl<L: SourceLoader> ModuleLoader<L> {
+    /// Creates a loader that reads module sources through `loader`.
+    pub fn new(loader: L) -> Self {
+        Self {
+            loader,
+            sources: SourceMap::new(),
+            modules: Vec::new(),
+            by_path: HashMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Returns the sources of every file read so far.
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    /// Consumes the loader and returns the sources of every file read.
+    pub fn into_sources(self) -> SourceMap {
+        self.sources
+    }
+
+    /// Loads `entry` and all modules it transitively imports.
+    ///
+    /// # Returns
+    /// - `Ok(ModuleGraph)` with the modules in dependency order
+    /// - `Err(ModuleError)` for the first unreadable, unparsable or cyclic import
+    pub fn load(&mut self, entry: impl AsRef<Path>) -> Result<ModuleGraph, ModuleError> {
+        self.modules.clear();
+        self.by_path.clear();
+        self.stack.clear();
+
+        let entry = self.load_module(normalize(entry.as_ref()))?;
+        for module in &self.modules {
+            check_member_access(module, &self.modules)?;
+        }
+        Ok(ModuleGraph {
+            modules: std::mem::take(&mut self.modules),
+            entry,
+        })
+    }
+
+    /// Loads a single module, recursively loading its imports first.
+    fn load_module(&mut self, path: PathBuf) -> Result<ModuleId, ModuleError> {
+        if let Some(id) = self.by_path.get(&path) {
+            return Ok(*id);
+        }
+        if let Some(start) = self.stack.iter().position(|p| *p == path) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(path);
+            return Err(ModuleError::Cycle { cycle });
+        }
+        log::debug!("Loading module {}", path.display());
+
+        let source = self.loader.load(&path).map_err(|e| ModuleError::Io {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        let file = self.sources.add(path.clone(), source.clone());
+        let mut parser = Parser::new(Lexer::new(source, file));
+        let program = parser.parse_program();
+        if !parser.errors.is_empty() {
+            return Err(ModuleError::Parse {
+                path,
+                errors: parser.errors,
+            });
+        }
+
+        self.stack.push(path.clone());
+        let mut imports = Vec::new();
+        let mut exports: Vec<String> = Vec::new();
+        for stmt in &program.statements {
+            match stmt {
+                Statement::Import(import) => {
+                    let target = resolve_import(&path, &import.path.value);
+                    let id = self.load_module(target)?;
+                    imports.push((import.alias.value.clone(), id));
+                }
+                Statement::Export(export) => {
+                    let name = &export.statement.name;
+                    if exports.contains(&name.value) {
+                        return Err(ModuleError::DuplicateExport {
+                            path,
+                            span: Span::from_token(&name.token),
+                            name: name.value.clone(),
+                        });
+                    }
+                    exports.push(name.value.clone());
+                }
+                _ => {}
+            }
+        }
+        self.stack.pop();
+
+        let id = self.modules.len();
+        self.modules.push(Module {
+            path: path.clone(),
+            file,
+            program,
+            imports,
+            exports,
+        });
+        self.by_path.insert(path, id);
+        Ok(id)
+    }
+}
+
+/// Resolves an import path relative to the directory of the importing file.
+pub fn resolve_import(importer: &Path, target: &str) -> PathBuf {
+    let base = importer.parent().unwrap_or_else(|| Path::new(""));
+    normalize(&base.join(target))
+}
+
+/// Lexically normalizes a path by dropping `.` and folding `..` components,
+/// so the same file reached through different relative paths is loaded once.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Checks that every `alias.name` in `module` names an export of the aliased module.
+fn check_member_access(module: &Module, modules: &[Module]) -> Result<(), ModuleError> {
+    let mut collector = MemberCollector::default();
+    collector.visit_program(&module.program);
+    for member in collector.members {
+        let Expression::Identifier(alias) = member.object.as_ref() else {
+            continue;
+        };
+        let Some(id) = module.import(&alias.value) else {
+            continue;
+        };
+        let imported = &modules[id];
+        if !imported.exports(&member.property.value) {
+            return Err(ModuleError::NotExported {
+                path: module.path.clone(),
+                span: Span::from_token(&member.property.token),
+                module: imported.path.clone(),
+                name: member.property.value.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Collects every member expression in a program, including nested ones.
+#[derive(Default)]
+struct MemberCollector<'ast> {
+    members: Vec<&'ast MemberExpression>,
+}
+
+impl<'ast> Visitor<'ast> for MemberCollector<'ast> {
+    fn visit_member_expression(&mut self, member: &'ast MemberExpression) {
+        self.members.push(member);
+        visit::walk_member_expression(self, member);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Evaluator;
+    use crate::object::Object;
+
+    fn sources(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, text)| (PathBuf::from(path), text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_load_resolves_imports_relative_to_importer() {
+        let files = sources(&[
+            (
+                "app/main.monkey",
+                r#"import "lib/math.monkey" as math; math.add(1, 2);"#,
+            ),
+            (
+                "app/lib/math.monkey",
+                r#"import "../util.monkey" as util; export let add = fn(a, b) { util.id(a) + b; };"#,
+            ),
+            ("app/util.monkey", "export let id = fn(x) { x; };"),
+        ]);
+        let graph = ModuleLoader::new(files).load("app/main.monkey").unwrap();
+
+        let paths: Vec<&Path> = graph.modules.iter().map(|m| m.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("app/util.monkey"),
+                Path::new("app/lib/math.monkey"),
+                Path::new("app/main.monkey"),
+            ]
+        );
+        assert_eq!(graph.entry().path, Path::new("app/main.monkey"));
+        let math = graph.entry().import("math").unwrap();
+        assert_eq!(graph.module(math).exports, vec!["add".to_string()]);
+    }
+
+    #[test]
+    fn test_load_shares_diamond_imports() {
+        let files = sources(&[
+            (
+                "main.monkey",
+                r#"import "a.monkey" as a; import "b.monkey" as b;"#,
+            ),
+            ("a.monkey", r#"import "c.monkey" as c;"#),
+            ("b.monkey", r#"import "./c.monkey" as c;"#),
+            ("c.monkey", "export let x = 1;"),
+        ]);
+        let graph = ModuleLoader::new(files).load("main.monkey").unwrap();
+        assert_eq!(graph.modules.len(), 4);
+    }
+
+    #[test]
+    fn test_load_detects_cycles() {
+        let files = sources(&[
+            ("a.monkey", r#"import "b.monkey" as b;"#),
+            ("b.monkey", r#"import "a.monkey" as a;"#),
+        ]);
+        let err = ModuleLoader::new(files).load("a.monkey").unwrap_err();
+        assert_eq!(
+            err,
+            ModuleError::Cycle {
+                cycle: vec![
+                    PathBuf::from("a.monkey"),
+                    PathBuf::from("b.monkey"),
+                    PathBuf::from("a.monkey"),
+                ]
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "import cycle: a.monkey -> b.monkey -> a.monkey"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_imported_file() {
+        let files = sources(&[
+            ("main.monkey", r#"import "bad.monkey" as bad;"#),
+            ("bad.monkey", "let x 5;"),
+        ]);
+        let mut loader = ModuleLoader::new(files);
+        let err = loader.load("main.monkey").unwrap_err();
+        let bad = loader.sources().find(Path::new("bad.monkey")).unwrap();
+        match &err {
+            ModuleError::Parse { path, errors } => {
+                assert_eq!(path, Path::new("bad.monkey"));
+                assert_eq!(errors[0].span, Span::in_file(bad, 1, 7));
+                assert_eq!(loader.sources().location(&errors[0].span), "bad.monkey:1:7");
+            }
+            other => panic!("expected parse error. got={:?}", other),
+        }
+        assert!(err.to_string().starts_with("bad.monkey:1:7: "));
+    }
+
+    #[test]
+    fn test_missing_module_is_io_error() {
+        let files = sources(&[("main.monkey", r#"import "nope.monkey" as nope;"#)]);
+        let err = ModuleLoader::new(files).load("main.monkey").unwrap_err();
+        assert!(matches!(err, ModuleError::Io { path, .. } if path == Path::new("nope.monkey")));
+    }
+
+    #[test]
+    fn test_member_access_requires_export() {
+        let files = sources(&[
+            (
+                "main.monkey",
+                r#"import "math.monkey" as math; let f = fn() { math.secret; };"#,
+            ),
+            (
+                "math.monkey",
+                "let secret = 42; export let answer = secret;",
+            ),
+        ]);
+        let mut loader = ModuleLoader::new(files);
+        let err = loader.load("main.monkey").unwrap_err();
+        let main = loader.sources().find(Path::new("main.monkey")).unwrap();
+        assert_eq!(
+            err,
+            ModuleError::NotExported {
+                path: PathBuf::from("main.monkey"),
+                span: Span::in_file(main, 1, 51),
+                module: PathBuf::from("math.monkey"),
+                name: "secret".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_duplicate_export() {
+        let files = sources(&[("main.monkey", "export let a = 1; export let a = 2;")]);
+        let err = ModuleLoader::new(files).load("main.monkey").unwrap_err();
+        assert!(matches!(err, ModuleError::DuplicateExport { name, .. } if name == "a"));
+    }
+
+    #[test]
+    fn test_evaluate_module_graph() {
+        let files = sources(&[
+            (
+                "main.monkey",
+                r#"import "a.monkey" as a; import "b.monkey" as b; let m = a.shared; a.total + b.total + m.count;"#,
+            ),
+            (
+                "a.monkey",
+                r#"import "shared.monkey" as shared; export let shared = shared; export let total = shared.count + 1;"#,
+            ),
+            (
+                "b.monkey",
+                r#"import "shared.monkey" as s; let count = 100; export let total = s.count + count;"#,
+            ),
+            ("shared.monkey", "export let count = 10;"),
+        ]);
+        let graph = ModuleLoader::new(files).load("main.monkey").unwrap();
+        let mut evaluator = Evaluator::with_output(io::sink());
+        // Each module has its own scope, so b's `count` does not clash
+        // with the one it imports, and a namespace is an ordinary value
+        assert_eq!(
+            evaluator.eval_modules(&graph).unwrap(),
+            Object::Integer(11 + 110 + 10)
+        );
+        assert!(evaluator.env().borrow().get("count").is_none());
+
+        let files = sources(&[
+            (
+                "main.monkey",
+                r#"import "lib.monkey" as lib; let m = lib; m.hidden;"#,
+            ),
+            (
+                "lib.monkey",
+                "let hidden = 1; export let fail = fn() { 1 / 0; };",
+            ),
+        ]);
+        let mut loader = ModuleLoader::new(files);
+        let graph = loader.load("main.monkey").unwrap();
+        let error = evaluator.eval_modules(&graph).unwrap_err();
+        assert_eq!(error.message, "'hidden' is not exported by lib.monkey");
+        let main = loader.sources().find(Path::new("main.monkey")).unwrap();
+        assert_eq!(error.span, Some(Span::in_file(main, 1, 43)));
+    }
+}
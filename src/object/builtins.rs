@@ -0,0 +1,127 @@
+//! Functions available in every program without a binding.
+
+use super::{Object, RuntimeError};
+use std::io::Write;
+use std::rc::Rc;
+
+/// The signature of a builtin: the arguments and where `puts` writes.
+pub type BuiltinFn = fn(&[Object], &mut dyn Write) -> Result<Object, RuntimeError>;
+
+/// A function implemented in Rust.
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFn,
+}
+
+/// Every builtin. The VM refers to them by their index in this list.
+pub static BUILTINS: [Builtin; 6] = [
+    Builtin {
+        name: "len",
+        func: len,
+    },
+    Builtin {
+        name: "puts",
+        func: puts,
+    },
+    Builtin {
+        name: "first",
+        func: first,
+    },
+    Builtin {
+        name: "last",
+        func: last,
+    },
+    Builtin {
+        name: "rest",
+        func: rest,
+    },
+    Builtin {
+        name: "push",
+        func: push,
+    },
+];
+
+/// Finds a builtin by name.
+pub fn lookup_builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+/// `len(x)`: the number of characters in a string or elements in an array.
+fn len(args: &[Object], _: &mut dyn Write) -> Result<Object, RuntimeError> {
+    match args {
+        [Object::String(value)] => Ok(Object::Integer(value.chars().count() as i64)),
+        [Object::Array(elements)] => Ok(Object::Integer(elements.len() as i64)),
+        [other] => Err(RuntimeError::new(format!(
+            "argument to `len` not supported, got {}",
+            other.type_name()
+        ))),
+        _ => Err(RuntimeError::new(format!(
+            "wrong number of arguments: want=1, got={}",
+            args.len()
+        ))),
+    }
+}
+
+/// `puts(a, b, ...)`: writes each argument on its own line.
+fn puts(args: &[Object], out: &mut dyn Write) -> Result<Object, RuntimeError> {
+    for arg in args {
+        writeln!(out, "{}", arg).map_err(|error| RuntimeError::new(error.to_string()))?;
+    }
+    Ok(Object::Null)
+}
+
+/// `first(a)`: the first element of an array, or `null` if it is empty.
+fn first(args: &[Object], _: &mut dyn Write) -> Result<Object, RuntimeError> {
+    let elements = array_argument("first", args, 1)?;
+    Ok(elements.first().cloned().unwrap_or(Object::Null))
+}
+
+/// `last(a)`: the last element of an array, or `null` if it is empty.
+fn last(args: &[Object], _: &mut dyn Write) -> Result<Object, RuntimeError> {
+    let elements = array_argument("last", args, 1)?;
+    Ok(elements.last().cloned().unwrap_or(Object::Null))
+}
+
+/// `rest(a)`: a new array without the first element, or `null` if `a` is
+/// empty.
+fn rest(args: &[Object], _: &mut dyn Write) -> Result<Object, RuntimeError> {
+    let elements = array_argument("rest", args, 1)?;
+    Ok(match elements.split_first() {
+        Some((_, rest)) => Object::Array(Rc::new(rest.to_vec())),
+        None => Object::Null,
+    })
+}
+
+/// `push(a, x)`: a new array with `x` appended; `a` is unchanged.
+fn push(args: &[Object], _: &mut dyn Write) -> Result<Object, RuntimeError> {
+    let elements = array_argument("push", args, 2)?;
+    let mut elements = elements.to_vec();
+    elements.push(args[1].clone());
+    Ok(Object::Array(Rc::new(elements)))
+}
+
+/// Checks the argument count of an array builtin.
+/// # Returns
+/// The elements of the first argument, which must be an array.
+fn array_argument<'a>(
+    name: &str,
+    args: &'a [Object],
+    want: usize,
+) -> Result<&'a [Object], RuntimeError> {
+    if args.len() != want {
+        return Err(RuntimeError::new(format!(
+            "wrong number of arguments: want={}, got={}",
+            want,
+            args.len()
+        )));
+    }
+    match &args[0] {
+        Object::Array(elements) => Ok(elements),
+        other => Err(RuntimeError::new(format!(
+            "argument to `{}` must be ARRAY, got {}",
+            name,
+            other.type_name()
+        ))),
+    }
+}
@@ -0,0 +1,234 @@
+//! Conversions between runtime values and Rust types.
+//!
+//! `IntoValue` turns a Rust value into an `Object`; `FromValue` goes the
+//! other way and fails with a `RuntimeError` naming the expected type, so a
+//! native function can convert its arguments with `?`. `Option` maps to and
+//! from `null`, `Vec` to arrays and `HashMap` to hashes. Only the types
+//! that implement `IntoHashKey` can key a `HashMap` given to a program.
+
+use super::{HashKey, Object, RuntimeError};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::rc::Rc;
+
+/// A Rust value that can be given to a program.
+pub trait IntoValue {
+    fn into_value(self) -> Object;
+}
+
+/// A Rust value that can be read back from a program.
+pub trait FromValue: Sized {
+    /// # Returns
+    /// The converted value, or an error naming the type that was expected.
+    fn from_value(value: &Object) -> Result<Self, RuntimeError>;
+}
+
+/// A Rust value that can key a hash.
+pub trait IntoHashKey {
+    fn into_hash_key(self) -> HashKey;
+}
+
+fn expected(type_name: &str, value: &Object) -> RuntimeError {
+    RuntimeError::new(format!("expected {}, got {}", type_name, value.type_name()))
+}
+
+impl IntoValue for Object {
+    fn into_value(self) -> Object {
+        self
+    }
+}
+
+impl FromValue for Object {
+    fn from_value(value: &Object) -> Result<Self, RuntimeError> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Object {
+        Object::Null
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Object {
+        Object::Integer(self)
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Integer(value) => Ok(*value),
+            other => Err(expected("INTEGER", other)),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Object {
+        Object::Boolean(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Boolean(value) => Ok(*value),
+            other => Err(expected("BOOLEAN", other)),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Object {
+        Object::String(self.into())
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Object {
+        Object::String(self.into())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::String(value) => Ok(value.to_string()),
+            other => Err(expected("STRING", other)),
+        }
+    }
+}
+
+impl IntoHashKey for i64 {
+    fn into_hash_key(self) -> HashKey {
+        HashKey::Integer(self)
+    }
+}
+
+impl IntoHashKey for bool {
+    fn into_hash_key(self) -> HashKey {
+        HashKey::Boolean(self)
+    }
+}
+
+impl IntoHashKey for String {
+    fn into_hash_key(self) -> HashKey {
+        HashKey::String(self.into())
+    }
+}
+
+impl IntoHashKey for &str {
+    fn into_hash_key(self) -> HashKey {
+        HashKey::String(self.into())
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Object {
+        self.map_or(Object::Null, IntoValue::into_value)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Null => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Object {
+        Object::Array(Rc::new(
+            self.into_iter().map(IntoValue::into_value).collect(),
+        ))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Array(elements) => elements.iter().map(T::from_value).collect(),
+            other => Err(expected("ARRAY", other)),
+        }
+    }
+}
+
+impl<K, V, S> IntoValue for HashMap<K, V, S>
+where
+    K: IntoHashKey,
+    V: IntoValue,
+{
+    fn into_value(self) -> Object {
+        let pairs = self
+            .into_iter()
+            .map(|(key, value)| (key.into_hash_key(), value.into_value()))
+            .collect();
+        Object::Hash(Rc::new(pairs))
+    }
+}
+
+impl<K, V, S> FromValue for HashMap<K, V, S>
+where
+    K: FromValue + Eq + Hash,
+    V: FromValue,
+    S: BuildHasher + Default,
+{
+    fn from_value(value: &Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Hash(pairs) => pairs
+                .iter()
+                .map(|(key, value)| {
+                    Ok((K::from_value(&key.clone().into())?, V::from_value(value)?))
+                })
+                .collect(),
+            other => Err(expected("HASH", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        let numbers = vec![1i64, -2, 3];
+        assert_eq!(
+            Vec::<i64>::from_value(&numbers.clone().into_value()),
+            Ok(numbers)
+        );
+        let words: HashMap<String, Vec<bool>> =
+            HashMap::from([("a".to_string(), vec![true]), ("b".to_string(), vec![])]);
+        assert_eq!(HashMap::from_value(&words.clone().into_value()), Ok(words));
+        assert_eq!(Option::<String>::from_value(&().into_value()), Ok(None));
+        assert_eq!(
+            Option::<String>::from_value(&"x".into_value()),
+            Ok(Some("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_mismatches() {
+        assert_eq!(
+            i64::from_value(&Object::Boolean(true)).unwrap_err().message,
+            "expected INTEGER, got BOOLEAN"
+        );
+        let mixed = vec![Object::Integer(1), Object::Null].into_value();
+        assert_eq!(
+            Vec::<i64>::from_value(&mixed).unwrap_err().message,
+            "expected INTEGER, got NULL"
+        );
+        let keyed = HashMap::from([(1i64, true)]).into_value();
+        assert_eq!(keyed.to_string(), "{1: true}");
+        assert_eq!(
+            HashMap::<String, bool>::from_value(&keyed)
+                .unwrap_err()
+                .message,
+            "expected STRING, got INTEGER"
+        );
+    }
+}
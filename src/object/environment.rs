@@ -0,0 +1,107 @@
+//! Scopes of the AST interpreter.
+
+use super::Object;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A shared, mutable scope.
+pub type Env = Rc<RefCell<Environment>>;
+
+/// The bindings of one scope and the scope enclosing it.
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+}
+
+impl Environment {
+    /// A scope with no enclosing scope.
+    pub fn new() -> Env {
+        Rc::new(RefCell::new(Environment::default()))
+    }
+
+    /// A scope nested in `outer`.
+    pub fn enclosed(outer: &Env) -> Env {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            outer: Some(Rc::clone(outer)),
+        }))
+    }
+
+    /// Looks `name` up here and then in the enclosing scopes.
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// Binds `name` in this scope, replacing an existing binding.
+    pub fn set(&mut self, name: impl Into<String>, value: Object) {
+        self.store.insert(name.into(), value);
+    }
+
+    /// True if this scope itself binds `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.store.contains_key(name)
+    }
+
+    /// Rebinds `name` in the nearest scope that binds it.
+    /// # Returns
+    /// False, changing nothing, if no scope binds `name`.
+    pub fn assign(&mut self, name: &str, value: Object) -> bool {
+        if let Some(slot) = self.store.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(name, value),
+            None => false,
+        }
+    }
+
+    /// The bindings of this scope itself, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings: Vec<_> = self
+            .store
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /// The enclosing scope, if any.
+    pub fn outer(&self) -> Option<Env> {
+        self.outer.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assign_finds_the_nearest_binding() {
+        let global = Environment::new();
+        global.borrow_mut().set("x", Object::Integer(1));
+        global.borrow_mut().set("y", Object::Integer(2));
+        let inner = Environment::enclosed(&global);
+        inner.borrow_mut().set("y", Object::Integer(3));
+
+        assert!(inner.borrow_mut().assign("x", Object::Integer(10)));
+        assert!(inner.borrow_mut().assign("y", Object::Integer(30)));
+        assert!(!inner.borrow_mut().assign("z", Object::Null));
+        assert_eq!(
+            global.borrow().bindings(),
+            [
+                ("x".to_string(), Object::Integer(10)),
+                ("y".to_string(), Object::Integer(2))
+            ]
+        );
+        assert_eq!(inner.borrow().get("y"), Some(Object::Integer(30)));
+        assert!(inner.borrow().outer().is_some());
+        assert!(global.borrow().outer().is_none());
+    }
+}
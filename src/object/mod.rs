@@ -0,0 +1,562 @@
+//! Runtime values shared by the AST interpreter and the virtual machine.
+//!
+//! Both execution engines produce and consume `Object`s. The operators are
+//! defined once here (`prefix`, `infix`, `is_truthy`) so the evaluator and
+//! the VM cannot disagree on what `1 + 2` or `"a" == "b"` means.
+//!
+//! Functions have a representation per engine: the evaluator closes over an
+//! `Environment`, while the VM pairs a `CompiledFunction` with the values it
+//! captured in a `Closure`. Functions written in Rust by an embedder are
+//! `NativeFunction`s and work with both.
+//!
+//! Arrays and hashes have no literal syntax yet; they reach programs through
+//! native functions and the `interpreter`'s globals. `convert` maps them and
+//! the scalar values to and from Rust types.
+
+mod builtins;
+mod convert;
+mod environment;
+
+pub use builtins::{BUILTINS, Builtin, BuiltinFn, lookup_builtin};
+pub use convert::{FromValue, IntoHashKey, IntoValue};
+pub use environment::{Env, Environment};
+
+use crate::ast::{Identifier, expression::BlockStatement};
+use crate::code::{Instructions, SpanTable};
+use crate::diagnostic::Diagnostic;
+use crate::parser::error::Span;
+use crate::source::SourceMap;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A value at runtime.
+#[derive(Debug, Clone)]
+pub enum Object {
+    Null,
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+    Array(Rc<Vec<Object>>),
+    /// Pairs ordered by key, so hashes print the same way every time.
+    Hash(Rc<BTreeMap<HashKey, Object>>),
+    /// A function created by the AST interpreter.
+    Function(Rc<Function>),
+    Builtin(&'static Builtin),
+    /// A function registered by the embedder.
+    Native(Rc<NativeFunction>),
+    /// The compiled body of a function literal, stored in the constant pool.
+    CompiledFunction(Rc<CompiledFunction>),
+    /// A compiled function with the free variables it captured.
+    Closure(Rc<Closure>),
+    /// The exports of an imported module, bound to its alias.
+    Module(Rc<Namespace>),
+}
+
+/// A value that can key a hash.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+}
+
+impl HashKey {
+    /// The key for `value`, if its type can key a hash.
+    pub fn new(value: &Object) -> Option<Self> {
+        match value {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(Rc::clone(value))),
+            _ => None,
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Integer(value) => Object::Integer(value),
+            HashKey::Boolean(value) => Object::Boolean(value),
+            HashKey::String(value) => Object::String(value),
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Object::from(self.clone()).fmt(f)
+    }
+}
+
+/// The signature of a native function: it receives the arguments only.
+pub type NativeFn = dyn Fn(&[Object]) -> Result<Object, RuntimeError>;
+
+/// A Rust closure callable from Monkey with a fixed number of arguments.
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub func: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    /// Calls the function after checking the number of arguments.
+    pub fn call(&self, args: &[Object]) -> Result<Object, RuntimeError> {
+        if args.len() != self.arity {
+            return Err(RuntimeError::new(format!(
+                "wrong number of arguments: want={}, got={}",
+                self.arity,
+                args.len()
+            )));
+        }
+        (self.func)(args)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a module exports, as seen through `alias.name`.
+#[derive(Debug)]
+pub struct Namespace {
+    /// The path the module was loaded from.
+    pub path: String,
+    pub exports: BTreeMap<String, Object>,
+}
+
+/// A function literal evaluated by the AST interpreter.
+#[derive(Debug)]
+pub struct Function {
+    /// The name of the `let` the literal was bound to, if any.
+    pub name: Option<String>,
+    /// Where the literal starts: the `fn` keyword.
+    pub span: Span,
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    /// The scope the literal was evaluated in.
+    pub env: Env,
+}
+
+/// A function literal compiled to bytecode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    /// The name of the `let` the literal was bound to, if any.
+    pub name: Option<String>,
+    pub instructions: Instructions,
+    /// Slots for parameters and locals, parameters first.
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    /// Where the instructions came from; empty if unknown.
+    pub spans: SpanTable,
+}
+
+/// A compiled function together with its free variables.
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Object>,
+}
+
+/// A resource bound that can stop a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// Too many evaluation steps.
+    Steps,
+    /// Calls nested too deeply.
+    CallDepth,
+    /// Too many bytes allocated.
+    Memory,
+    /// The run took longer than its time limit.
+    Time,
+}
+
+/// A function call in progress when an error happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    /// The called function's name; `None` if it is anonymous.
+    pub function: Option<String>,
+    /// Where it was called, if known.
+    pub call_site: Option<Span>,
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.function {
+            Some(name) => write!(f, "in {}", name),
+            None => write!(f, "in <anonymous>"),
+        }
+    }
+}
+
+/// An error raised while running a program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    /// The limit that stopped the program, if that is why it failed.
+    pub limit: Option<Limit>,
+    /// The expression that failed, if known.
+    pub span: Option<Span>,
+    /// The calls the error unwound through, innermost first.
+    pub trace: Vec<TraceFrame>,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>) -> Self {
+        RuntimeError {
+            message: message.into(),
+            limit: None,
+            span: None,
+            trace: Vec::new(),
+        }
+    }
+
+    /// An error raised because the program reached `limit`.
+    /// # Parameters
+    /// - `limit`: The limit that was reached
+    /// - `message`: The error message
+    /// - `span`: Where execution stopped, if known
+    pub fn limit_exceeded(limit: Limit, message: impl Into<String>, span: Option<Span>) -> Self {
+        RuntimeError {
+            message: message.into(),
+            limit: Some(limit),
+            span,
+            trace: Vec::new(),
+        }
+    }
+
+    /// Renders the error like a parse error, with the failing expression's
+    /// source line, followed by a note at each call site of the trace.
+    /// Frames that repeat the one before them are counted, not repeated.
+    /// # Parameters
+    /// - `sources`: The source map that owns the spans' files
+    /// # Returns
+    /// The rendered error, without a trailing newline.
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut out = match &self.span {
+            Some(span) => Diagnostic::error(span.clone(), self.message.clone()).render(sources),
+            None => format!("error: {}", self.message),
+        };
+        let mut frames = self.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            let mut repeats = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeats += 1;
+            }
+            let repeated =
+                (repeats > 0).then(|| format!("the call above repeats {} more times", repeats));
+            match &frame.call_site {
+                Some(span) => {
+                    let mut note =
+                        Diagnostic::note(span.clone(), format!("{}, called here", frame));
+                    note.notes.extend(repeated);
+                    out.push('\n');
+                    out.push_str(&note.render(sources));
+                }
+                None => {
+                    out.push_str(&format!("\nnote: {}", frame));
+                    if let Some(repeated) = repeated {
+                        out.push_str(&format!("\n  = note: {}", repeated));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// The deepest nesting of function calls the VM allows before it reports a
+/// stack overflow, and the evaluator's default limit.
+pub const MAX_CALL_DEPTH: usize = 256;
+
+impl Object {
+    /// The name of the value's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Null => "NULL",
+            Object::Integer(_) => "INTEGER",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Function(_) | Object::CompiledFunction(_) | Object::Closure(_) => "FUNCTION",
+            Object::Builtin(_) | Object::Native(_) => "BUILTIN",
+            Object::Module(_) => "MODULE",
+        }
+    }
+
+    /// `null` and `false` are falsy; every other value is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Null | Object::Boolean(false))
+    }
+}
+
+impl PartialEq for Object {
+    /// Values, arrays and hashes compare by content; functions and modules
+    /// only equal themselves.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Null, Object::Null) => true,
+            (Object::Integer(a), Object::Integer(b)) => a == b,
+            (Object::Boolean(a), Object::Boolean(b)) => a == b,
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Array(a), Object::Array(b)) => a == b,
+            (Object::Hash(a), Object::Hash(b)) => a == b,
+            (Object::Function(a), Object::Function(b)) => Rc::ptr_eq(a, b),
+            (Object::Builtin(a), Object::Builtin(b)) => std::ptr::eq(*a, *b),
+            (Object::Native(a), Object::Native(b)) => Rc::ptr_eq(a, b),
+            (Object::CompiledFunction(a), Object::CompiledFunction(b)) => Rc::ptr_eq(a, b),
+            (Object::Closure(a), Object::Closure(b)) => Rc::ptr_eq(a, b),
+            (Object::Module(a), Object::Module(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Object::Null => return write!(f, "null"),
+            Object::Integer(value) => return write!(f, "{}", value),
+            Object::Boolean(value) => return write!(f, "{}", value),
+            Object::String(value) => return write!(f, "{}", value),
+            Object::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                return write!(f, "]");
+            }
+            Object::Hash(pairs) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                return write!(f, "}}");
+            }
+            Object::Builtin(builtin) => return write!(f, "<builtin {}>", builtin.name),
+            Object::Native(native) => return write!(f, "<builtin {}>", native.name),
+            Object::Module(module) => return write!(f, "<module {}>", module.path),
+            Object::Function(function) => &function.name,
+            Object::CompiledFunction(function) => &function.name,
+            Object::Closure(closure) => &closure.function.name,
+        };
+        match name {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<fn>"),
+        }
+    }
+}
+
+/// Applies a prefix operator (`!` or `-`).
+pub fn prefix(operator: &str, right: &Object) -> Result<Object, RuntimeError> {
+    match (operator, right) {
+        ("!", right) => Ok(Object::Boolean(!right.is_truthy())),
+        ("-", Object::Integer(value)) => value
+            .checked_neg()
+            .map(Object::Integer)
+            .ok_or_else(|| RuntimeError::new("integer overflow")),
+        _ => Err(RuntimeError::new(format!(
+            "unknown operator: {}{}",
+            operator,
+            right.type_name()
+        ))),
+    }
+}
+
+/// Applies an infix operator. Integers support arithmetic and comparison,
+/// strings support `+`, and any two values can be compared with `==` and
+/// `!=`.
+pub fn infix(operator: &str, left: &Object, right: &Object) -> Result<Object, RuntimeError> {
+    match (left, right) {
+        (Object::Integer(a), Object::Integer(b)) => integer_infix(operator, *a, *b),
+        (Object::String(a), Object::String(b)) if operator == "+" => {
+            Ok(Object::String(format!("{}{}", a, b).into()))
+        }
+        _ if operator == "==" => Ok(Object::Boolean(left == right)),
+        _ if operator == "!=" => Ok(Object::Boolean(left != right)),
+        _ if left.type_name() != right.type_name() => Err(RuntimeError::new(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        ))),
+        _ => Err(unknown_infix(operator, left, right)),
+    }
+}
+
+fn integer_infix(operator: &str, a: i64, b: i64) -> Result<Object, RuntimeError> {
+    let arithmetic = match operator {
+        "+" => a.checked_add(b),
+        "-" => a.checked_sub(b),
+        "*" => a.checked_mul(b),
+        "/" if b == 0 => return Err(RuntimeError::new("division by zero")),
+        "/" => a.checked_div(b),
+        "<" => return Ok(Object::Boolean(a < b)),
+        ">" => return Ok(Object::Boolean(a > b)),
+        "==" => return Ok(Object::Boolean(a == b)),
+        "!=" => return Ok(Object::Boolean(a != b)),
+        _ => {
+            return Err(unknown_infix(
+                operator,
+                &Object::Integer(a),
+                &Object::Integer(b),
+            ));
+        }
+    };
+    arithmetic
+        .map(Object::Integer)
+        .ok_or_else(|| RuntimeError::new("integer overflow"))
+}
+
+fn unknown_infix(operator: &str, left: &Object, right: &Object) -> RuntimeError {
+    RuntimeError::new(format!(
+        "unknown operator: {} {} {}",
+        left.type_name(),
+        operator,
+        right.type_name()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i64) -> Object {
+        Object::Integer(value)
+    }
+
+    #[test]
+    fn test_infix() {
+        assert_eq!(infix("+", &int(2), &int(3)), Ok(int(5)));
+        assert_eq!(infix("<", &int(2), &int(3)), Ok(Object::Boolean(true)));
+        let (a, b) = (Object::String("a".into()), Object::String("b".into()));
+        assert_eq!(infix("+", &a, &b), Ok(Object::String("ab".into())));
+        assert_eq!(infix("==", &a, &b), Ok(Object::Boolean(false)));
+        assert_eq!(
+            infix("==", &int(1), &Object::Null),
+            Ok(Object::Boolean(false))
+        );
+        assert_eq!(
+            infix("-", &a, &b).unwrap_err().message,
+            "unknown operator: STRING - STRING"
+        );
+        assert_eq!(
+            infix("+", &int(1), &Object::Boolean(true))
+                .unwrap_err()
+                .message,
+            "type mismatch: INTEGER + BOOLEAN"
+        );
+        assert_eq!(
+            infix("/", &int(1), &int(0)).unwrap_err().message,
+            "division by zero"
+        );
+        assert_eq!(
+            infix("*", &int(i64::MAX), &int(2)).unwrap_err().message,
+            "integer overflow"
+        );
+    }
+
+    #[test]
+    fn test_prefix_and_truthiness() {
+        assert_eq!(prefix("-", &int(4)), Ok(int(-4)));
+        assert_eq!(prefix("!", &int(0)), Ok(Object::Boolean(false)));
+        assert_eq!(prefix("!", &Object::Null), Ok(Object::Boolean(true)));
+        assert_eq!(
+            prefix("-", &Object::Boolean(true)).unwrap_err().message,
+            "unknown operator: -BOOLEAN"
+        );
+    }
+
+    #[test]
+    fn test_arrays_and_hashes() {
+        let array = Object::Array(Rc::new(vec![int(1), Object::String("a".into())]));
+        assert_eq!(array.to_string(), "[1, a]");
+        assert_eq!(
+            infix("==", &array, &array.clone()),
+            Ok(Object::Boolean(true))
+        );
+        let pairs = [
+            (HashKey::String("b".into()), int(2)),
+            (HashKey::Integer(1), Object::Boolean(true)),
+        ];
+        let hash = Object::Hash(Rc::new(pairs.into_iter().collect()));
+        assert_eq!(hash.to_string(), "{1: true, b: 2}");
+        assert_eq!(HashKey::new(&array), None);
+        assert_eq!(
+            infix("+", &array, &hash).unwrap_err().message,
+            "type mismatch: ARRAY + HASH"
+        );
+    }
+
+    #[test]
+    fn test_render_trace() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("loop.monkey", "let f = fn(n) { f(n + 1); };\nf(0);\n");
+        let frame = |line, column| TraceFrame {
+            function: Some("f".to_string()),
+            call_site: Some(Span::in_file(file, line, column)),
+        };
+        let mut error = RuntimeError::new("stack overflow");
+        error.span = Some(Span::in_file(file, 1, 18));
+        error.trace = vec![frame(1, 18), frame(1, 18), frame(1, 18), frame(2, 2)];
+        assert_eq!(
+            error.render(&sources),
+            "error: stack overflow\n --> loop.monkey:1:18\n  |\n\
+             1 | let f = fn(n) { f(n + 1); };\n  |                  ^\n\
+             note: in f, called here\n --> loop.monkey:1:18\n  |\n\
+             1 | let f = fn(n) { f(n + 1); };\n  |                  ^\n  \
+             = note: the call above repeats 2 more times\n\
+             note: in f, called here\n --> loop.monkey:2:2\n  |\n\
+             2 | f(0);\n  |  ^"
+        );
+
+        // Stripped bytecode knows the functions but not where they are
+        let mut error = RuntimeError::new("division by zero");
+        error.trace = vec![TraceFrame {
+            function: None,
+            call_site: None,
+        }];
+        assert_eq!(
+            error.render(&sources),
+            "error: division by zero\nnote: in <anonymous>"
+        );
+    }
+
+    #[test]
+    fn test_native_arity() {
+        let native = NativeFunction {
+            name: "twice".to_string(),
+            arity: 1,
+            func: Box::new(|args| infix("*", &args[0], &int(2))),
+        };
+        assert_eq!(native.call(&[int(4)]), Ok(int(8)));
+        assert_eq!(
+            native.call(&[]).unwrap_err().message,
+            "wrong number of arguments: want=1, got=0"
+        );
+        assert_eq!(
+            Object::Native(Rc::new(native)).to_string(),
+            "<builtin twice>"
+        );
+    }
+}
@@ -1,33 +1,53 @@
 //! Source location tracking for parser errors.
 
 use crate::lexer::token::Token;
+use crate::source::FileId;
 use std::fmt;
 
 /// Source location for error reporting.
 ///
-/// Represents where an error occurred in the source code,
-/// specifically the line and column position.
-#[derive(Debug, Clone, PartialEq, Default, Eq)]
+/// Represents where an error occurred in the source code: the file it
+/// belongs to and the line and column position inside that file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Span {
+    pub file: FileId,
     pub line: usize,
     pub column: usize,
 }
 
 impl Span {
-    /// Create a new span at the given line and column.
+    /// Create a new span at the given line and column of no file
+    /// (`FileId::DETACHED`).
     pub fn new(line: usize, column: usize) -> Self {
-        Self { line, column }
+        Self {
+            file: FileId::DETACHED,
+            line,
+            column,
+        }
+    }
+
+    /// Create a new span at the given line and column of `file`.
+    pub fn in_file(file: FileId, line: usize, column: usize) -> Self {
+        Self { file, line, column }
     }
 
     /// Create a span from a token's position.
     pub fn from_token(token: &Token) -> Self {
         Self {
+            file: token.file,
             line: token.line,
             column: token.column,
         }
     }
 }
 
+impl Default for Span {
+    /// Line 0, column 0 of no file.
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
 impl fmt::Display for Span {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "[line {}:{}]", self.line, self.column)
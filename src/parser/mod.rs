@@ -8,24 +8,32 @@
 //! - Maintains a two-token lookahead (`curr_token`, `peek_token`).
 //! - Provides helpers like `expect_peek`, `is_curr_token`, and `is_peek_token`.
 //! - Reports user-friendly errors via the `errors` vector.
+//! - Builds a lossless syntax tree alongside the AST (`syntax_tree`).
 
 pub mod error;
-mod precedence;
+pub(crate) mod precedence;
 pub mod test_helper;
+mod types;
 
 use crate::ast::{
-    Program,
+    Node, Program,
     expression::{
         BlockStatement, BooleanLiteral, CallExpression, Expression, FunctionLiteral, Identifier,
-        IfExpression, InfixExpression, IntegerLiteral, PrefixExpression,
+        IfExpression, InfixExpression, IntegerLiteral, MemberExpression, PrefixExpression,
+        StringLiteral,
     },
-    statement::{ExpressionStatement, LetStatement, ReturnStatement, Statement},
+    statement::{
+        ExportStatement, ExpressionStatement, ImportStatement, LetStatement, ReturnStatement,
+        Statement,
+    },
+    types::TypeExpr,
 };
 use crate::lexer::{
-    Lexer,
+    Comment, Lexer,
     token::{Token, TokenType},
 };
 use crate::parser::error::ParserError;
+use crate::syntax::{SyntaxBuilder, SyntaxKind, SyntaxNode};
 use precedence::Precedence;
 use std::collections::HashMap;
 
@@ -42,6 +50,12 @@ pub struct Parser {
     pub errors: Vec<ParserError>,
     prefix_parse_fns: HashMap<TokenType, PrefixParseFn>,
     infix_parse_fns: HashMap<TokenType, InfixParseFn>,
+    /// The concrete syntax tree built as tokens become `curr_token`
+    syntax: SyntaxBuilder,
+    /// Syntax checkpoint just before `curr_token`, where nodes starting at it begin
+    curr_checkpoint: usize,
+    /// Leading trivia and source text of `peek_token`, not yet in `syntax`
+    peek_source: Option<(String, String)>,
 }
 
 type PrefixParseFn = fn(&mut Parser) -> Option<Expression>;
@@ -61,6 +75,9 @@ impl Parser {
             errors: Vec::<ParserError>::new(),
             prefix_parse_fns: HashMap::new(),
             infix_parse_fns: HashMap::new(),
+            syntax: SyntaxBuilder::default(),
+            curr_checkpoint: 0,
+            peek_source: None,
         };
         // Register prefix parse functions
         p.register_prefix_parse_fn(TokenType::IDENT, Parser::parse_identifier);
@@ -74,6 +91,7 @@ impl Parser {
         p.register_prefix_parse_fn(TokenType::LBRACE, Parser::parse_block_statement);
         p.register_prefix_parse_fn(TokenType::ELSE, Parser::parse_if_expression);
         p.register_prefix_parse_fn(TokenType::FUNCTION, Parser::parse_function_literal);
+        p.register_prefix_parse_fn(TokenType::STRING, Parser::parse_string_literal);
         // Register Infix parse functions
         p.register_infix_parse_fn(TokenType::PLUS, Parser::parse_infix_expression);
         p.register_infix_parse_fn(TokenType::MINUS, Parser::parse_infix_expression);
@@ -84,6 +102,7 @@ impl Parser {
         p.register_infix_parse_fn(TokenType::LT, Parser::parse_infix_expression);
         p.register_infix_parse_fn(TokenType::GT, Parser::parse_infix_expression);
         p.register_infix_parse_fn(TokenType::LPAREN, Parser::parse_call_expression);
+        p.register_infix_parse_fn(TokenType::DOT, Parser::parse_member_expression);
         // Advance the token buffer to have a two-token lookahead
         p.next_token();
         p.next_token();
@@ -94,9 +113,47 @@ impl Parser {
     /// Moves the peek token to the current token position and reads
     /// the next token from the lexer into the peek position. This maintains
     /// the two-token lookahead buffer/window used for parsing decisions.
+    /// The new current token and its leading trivia are added to the syntax tree.
     fn next_token(&mut self) {
         self.curr_token = self.peek_token.clone();
+        if let Some((trivia, text)) = self.peek_source.take() {
+            self.syntax.trivia(&trivia);
+            self.curr_checkpoint = self.syntax.checkpoint();
+            if self.curr_token.token_type != TokenType::EOF {
+                self.syntax
+                    .token(SyntaxKind::Token(self.curr_token.token_type), &text);
+            }
+        }
         self.peek_token = self.l.next_token();
+        self.peek_source = Some((
+            self.l.last_trivia().to_string(),
+            self.l.last_text().to_string(),
+        ));
+    }
+
+    /// Wraps the syntax pushed since `checkpoint` into a `kind` node, or an
+    /// `Error` node if `result` is `None`, and passes `result` through.
+    fn wrap_syntax<T>(
+        &mut self,
+        checkpoint: usize,
+        kind: SyntaxKind,
+        result: Option<T>,
+    ) -> Option<T> {
+        let kind = if result.is_some() {
+            kind
+        } else {
+            SyntaxKind::Error
+        };
+        self.syntax.wrap(checkpoint, kind);
+        result
+    }
+
+    /// Returns the lossless syntax tree of the tokens consumed so far.
+    ///
+    /// After `parse_program`, `syntax_tree().to_string()` is exactly the input,
+    /// including whitespace, comments and any text the parser rejected.
+    pub fn syntax_tree(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.syntax.finish(SyntaxKind::Program))
     }
 
     /// Checks if the current token matches the expected token type.
@@ -175,6 +232,13 @@ impl Parser {
         &self.errors
     }
 
+    /// Returns the comments the lexer has skipped so far.
+    ///
+    /// After `parse_program` this is every comment in the input.
+    pub fn comments(&self) -> &[Comment] {
+        self.l.comments()
+    }
+
     /// Parses the entire program and returns the root AST node.
     ///
     /// Iterates through all tokens until EOF is reached, parsing each
@@ -208,18 +272,55 @@ impl Parser {
         program
     }
 
+    /// Parses the input as the statements between the braces of a block, with
+    /// the errors a block reports. Used to reparse part of a block on its own.
+    ///
+    /// # Returns
+    /// The parsed statements, or `None` if a `}` is reached at the top level,
+    /// where an enclosing block would have ended.
+    pub(crate) fn parse_block_contents(&mut self) -> Option<Vec<Statement>> {
+        let mut statements = Vec::new();
+        while self.curr_token.token_type != TokenType::EOF {
+            if self.is_curr_token(TokenType::RBRACE) {
+                return None;
+            }
+            self.parse_block_item(&mut statements);
+            self.next_token();
+        }
+        Some(statements)
+    }
+
     /// Parses a single statement based on the current token type.
     ///
     /// Uses the current token to determine what type of statement to parse.
-    /// Currently supports LET and RETURN statements. If an unsupported token type is
-    /// encountered, adds an error and returns None. Returns a Statement enum
-    /// variant for type-safe statement handling.
+    /// Supports LET, RETURN, IMPORT and EXPORT statements; anything else is parsed
+    /// as an expression statement. Returns a Statement enum variant for type-safe
+    /// statement handling.
     fn parse_statement(&mut self) -> Option<Statement> {
-        match self.curr_token.token_type {
-            TokenType::LET => self.parse_let_statement().map(Statement::Let),
-            TokenType::RETURN => self.parse_return_statement().map(Statement::Return),
-            _ => self.parse_expression_statement().map(Statement::Expression),
-        }
+        let checkpoint = self.curr_checkpoint;
+        let (kind, stmt) = match self.curr_token.token_type {
+            TokenType::LET => (
+                SyntaxKind::LetStmt,
+                self.parse_let_statement().map(Statement::Let),
+            ),
+            TokenType::RETURN => (
+                SyntaxKind::ReturnStmt,
+                self.parse_return_statement().map(Statement::Return),
+            ),
+            TokenType::IMPORT => (
+                SyntaxKind::ImportStmt,
+                self.parse_import_statement().map(Statement::Import),
+            ),
+            TokenType::EXPORT => (
+                SyntaxKind::ExportStmt,
+                self.parse_export_statement().map(Statement::Export),
+            ),
+            _ => (
+                SyntaxKind::ExprStmt,
+                self.parse_expression_statement().map(Statement::Expression),
+            ),
+        };
+        self.wrap_syntax(checkpoint, kind, stmt)
     }
 
     /// Parses a let statement with the format: let <identifier> = <expression>;
@@ -240,9 +341,13 @@ impl Parser {
             value: self.curr_token.literal.clone(),
         };
 
+        // Parse the optional type annotation (e.g., `let x: int = 5;`)
+        let type_annotation = self.parse_optional_annotation()?;
+
         let mut stmt = LetStatement {
             token,
             name,
+            type_annotation,
             value: None,
         };
 
@@ -300,6 +405,59 @@ impl Parser {
         Some(ReturnStatement { token, value })
     }
 
+    /// Parses an import statement with the format: import "<path>" as <alias>;
+    ///
+    /// Expects the current token to be IMPORT. The path is kept exactly as written;
+    /// resolving it against the importing file is left to the module loader.
+    fn parse_import_statement(&mut self) -> Option<ImportStatement> {
+        let token = self.curr_token.clone();
+
+        // Expect the module path after 'import'
+        if !self.expect_peek(TokenType::STRING) {
+            return None;
+        }
+        let path = StringLiteral {
+            token: self.curr_token.clone(),
+            value: self.curr_token.literal.clone(),
+        };
+
+        // Expect 'as' followed by the alias identifier
+        if !self.expect_peek(TokenType::AS) {
+            return None;
+        }
+        if !self.expect_peek(TokenType::IDENT) {
+            return None;
+        }
+        let alias = Identifier {
+            token: self.curr_token.clone(),
+            value: self.curr_token.literal.clone(),
+        };
+
+        // Require semicolon
+        if !self.expect_peek(TokenType::SEMICOLON) {
+            return None;
+        }
+
+        Some(ImportStatement { token, path, alias })
+    }
+
+    /// Parses an export statement with the format: export let <identifier> = <expression>;
+    ///
+    /// Expects the current token to be EXPORT. Only `let` bindings can be exported.
+    fn parse_export_statement(&mut self) -> Option<ExportStatement> {
+        let token = self.curr_token.clone();
+
+        // Expect 'let' after 'export'
+        if !self.expect_peek(TokenType::LET) {
+            return None;
+        }
+        let checkpoint = self.curr_checkpoint;
+        let statement = self.parse_let_statement();
+        let statement = self.wrap_syntax(checkpoint, SyntaxKind::LetStmt, statement)?;
+
+        Some(ExportStatement { token, statement })
+    }
+
     /// Parses an identifier expression.
     /// Expects the current token to be an identifier. Returns an Identifier expression.
     fn parse_identifier(&mut self) -> Option<Expression> {
@@ -365,12 +523,17 @@ impl Parser {
             self.curr_token.token_type
         );
         let token_type = self.curr_token.token_type;
-        let prefix = self.prefix_parse_fns.get(&token_type);
+        let checkpoint = self.curr_checkpoint;
+        let prefix = self.prefix_parse_fns.get(&token_type).copied();
         // If the prefix parse function is found, parse the left-hand side expression and returns an Expression
         let mut left = if let Some(prefix_parse_fn) = prefix {
             log::debug!("Found prefix parse function for {:?}", token_type);
 
-            prefix_parse_fn(self)?
+            let left = prefix_parse_fn(self);
+            match prefix_syntax_kind(token_type) {
+                Some(kind) => self.wrap_syntax(checkpoint, kind, left)?,
+                None => left?,
+            }
         } else {
             self.no_prefix_parse_function_error();
             return None;
@@ -400,7 +563,13 @@ impl Parser {
             self.next_token();
             // If the infix parse function is found, parse the right-hand side expression with the precedence level and returns an Expression
             if let Some(infix_parse_fn) = infix {
-                left = infix_parse_fn(self, left)?;
+                let kind = match peek_token_type {
+                    TokenType::LPAREN => SyntaxKind::CallExpr,
+                    TokenType::DOT => SyntaxKind::MemberExpr,
+                    _ => SyntaxKind::InfixExpr,
+                };
+                let expr = infix_parse_fn(self, left);
+                left = self.wrap_syntax(checkpoint, kind, expr)?;
             } else {
                 return Some(left);
             }
@@ -435,6 +604,16 @@ impl Parser {
             }
         }
     }
+    /// Parses a string literal expression from the current token.
+    ///
+    /// Expects the current token to be of type `STRING`. The lexer has already
+    /// stripped the surrounding quotes, so the literal is used as the value.
+    fn parse_string_literal(&mut self) -> Option<Expression> {
+        let token = self.curr_token.clone();
+        let value = self.curr_token.literal.clone();
+        Some(Expression::StringLiteral(StringLiteral { token, value }))
+    }
+
     /// Parses a boolean literal expression from the current token.
     ///
     /// Expects the current token to be of type `TRUE` or `FALSE`. Determines the boolean value
@@ -707,24 +886,20 @@ impl Parser {
     /// # Errors
     /// Adds an error to the parser's error list if the right brace is not found or statement parsing fails.
     fn parse_block_statement(&mut self) -> Option<Expression> {
+        let checkpoint = self.curr_checkpoint;
+        let block = self.parse_block_body();
+        self.wrap_syntax(checkpoint, SyntaxKind::BlockExpr, block)
+    }
+
+    /// Parses the statements of a block up to and including its `}`.
+    fn parse_block_body(&mut self) -> Option<Expression> {
         log::debug!("Parsing block statement");
         let token = self.curr_token.clone();
         let mut statements = Vec::new();
         // Parse the statements in the block until the right brace is found or EOF is reached
         while !self.is_peek_token(TokenType::RBRACE) && !self.is_peek_token(TokenType::EOF) {
             self.next_token();
-            match self.parse_statement() {
-                Some(stmt) => statements.push(stmt),
-                None => {
-                    let error = ParserError::at_token(
-                        &self.curr_token,
-                        "failed to parse statement in block".to_string(),
-                    );
-                    log::debug!("{}", error);
-                    self.errors.push(error);
-                    // Continue parsing to collect more errors
-                }
-            }
+            self.parse_block_item(&mut statements);
         }
         if !self.expect_peek(TokenType::RBRACE) {
             // Error already added by expect_peek
@@ -736,6 +911,34 @@ impl Parser {
         }))
     }
 
+    /// Parses the statement at the current token as part of a block, adding
+    /// it to `statements` or reporting why it cannot be there.
+    fn parse_block_item(&mut self, statements: &mut Vec<Statement>) {
+        match self.parse_statement() {
+            Some(stmt @ (Statement::Import(_) | Statement::Export(_))) => {
+                let error = ParserError::at_token(
+                    &self.curr_token,
+                    format!(
+                        "{} statements are only allowed at the top level",
+                        stmt.token_literal()
+                    ),
+                );
+                log::debug!("{}", error);
+                self.errors.push(error);
+            }
+            Some(stmt) => statements.push(stmt),
+            None => {
+                let error = ParserError::at_token(
+                    &self.curr_token,
+                    "failed to parse statement in block".to_string(),
+                );
+                log::debug!("{}", error);
+                self.errors.push(error);
+                // Continue parsing to collect more errors
+            }
+        }
+    }
+
     /// Parses a function literal expression (e.g., `fn(<parameters>) <body>`).
     ///
     /// Expects the current token to be a function keyword. Parses the parameters and body.
@@ -750,17 +953,28 @@ impl Parser {
             // Error already added by expect_peek
             return None;
         }
-        let parameters = match self.parse_function_parameters() {
-            Some(p) => p,
-            None => {
-                let error = ParserError::at_token(
-                    &self.curr_token,
-                    "failed to parse function parameters".to_string(),
-                );
-                log::debug!("{}", error);
-                self.errors.push(error);
-                return None;
-            }
+        let checkpoint = self.curr_checkpoint;
+        let parameters = self.parse_function_parameters();
+        let (parameters, parameter_types) =
+            match self.wrap_syntax(checkpoint, SyntaxKind::ParamList, parameters) {
+                Some(p) => p,
+                None => {
+                    let error = ParserError::at_token(
+                        &self.curr_token,
+                        "failed to parse function parameters".to_string(),
+                    );
+                    log::debug!("{}", error);
+                    self.errors.push(error);
+                    return None;
+                }
+            };
+        // Parse the optional return type (e.g., `fn(x) -> int { ... }`)
+        let return_type = if self.is_peek_token(TokenType::ARROW) {
+            self.next_token();
+            self.next_token();
+            Some(self.parse_type()?)
+        } else {
+            None
         };
         if !self.expect_peek(TokenType::LBRACE) {
             // Error already added by expect_peek
@@ -790,26 +1004,30 @@ impl Parser {
         Some(Expression::FunctionLiteral(FunctionLiteral {
             token,
             parameters,
+            parameter_types,
+            return_type,
             body,
         }))
     }
 
-    /// Parses the function parameters (e.g., `x, y`).
+    /// Parses the function parameters (e.g., `x, y` or `x: int, y`).
     ///
     /// Expects the current token to be a left parenthesis. Parses the parameters until the right parenthesis is found.
-    /// Returns a Vec<Identifier> containing the parsed parameters.
+    /// Returns the parsed parameters together with their optional type annotations.
     ///
     /// # Returns
-    /// An `Option<Vec<Identifier>>` containing a `Vec<Identifier>` variant if parsing succeeds.
-    fn parse_function_parameters(&mut self) -> Option<Vec<Identifier>> {
+    /// An `Option<(Vec<Identifier>, Vec<Option<TypeExpr>>)>` with index-aligned vectors if parsing succeeds.
+    fn parse_function_parameters(&mut self) -> Option<(Vec<Identifier>, Vec<Option<TypeExpr>>)> {
         log::debug!("Parsing function parameters");
         let mut parameters = Vec::new();
+        let mut parameter_types = Vec::new();
         if self.is_peek_token(TokenType::RPAREN) {
             self.next_token();
-            return Some(parameters);
+            return Some((parameters, parameter_types));
         }
         // Advance to the next token to point to the first parameter
         self.next_token();
+        let checkpoint = self.curr_checkpoint;
 
         // Parse first parameter
         let first_param = match self.parse_identifier() {
@@ -837,11 +1055,14 @@ impl Parser {
             }
         };
         parameters.push(first_param);
+        let annotation = self.parse_optional_annotation();
+        parameter_types.push(self.wrap_syntax(checkpoint, SyntaxKind::Param, annotation)?);
 
         // Parse remaining parameters
         while self.is_peek_token(TokenType::COMMA) {
             self.next_token();
             self.next_token();
+            let checkpoint = self.curr_checkpoint;
 
             let identifier = match self.parse_identifier() {
                 Some(Expression::Identifier(ident)) => ident,
@@ -868,6 +1089,8 @@ impl Parser {
                 }
             };
             parameters.push(identifier);
+            let annotation = self.parse_optional_annotation();
+            parameter_types.push(self.wrap_syntax(checkpoint, SyntaxKind::Param, annotation)?);
         }
 
         if !self.expect_peek(TokenType::RPAREN) {
@@ -875,13 +1098,15 @@ impl Parser {
             return None;
         }
 
-        Some(parameters)
+        Some((parameters, parameter_types))
     }
 
     fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
         log::debug!("Parsing call expression");
         let token = self.curr_token.clone();
-        let arguments = match self.parse_call_arguments() {
+        let checkpoint = self.curr_checkpoint;
+        let arguments = self.parse_call_arguments();
+        let arguments = match self.wrap_syntax(checkpoint, SyntaxKind::ArgList, arguments) {
             Some(args) => args,
             None => {
                 let error = ParserError::at_token(
@@ -957,4 +1182,45 @@ impl Parser {
 
         Some(arguments)
     }
+
+    /// Parses a member access expression (e.g., `math.add`).
+    ///
+    /// Expects the current token to be a dot. The property must be an identifier.
+    ///
+    /// # Returns
+    /// An `Option<Expression>` containing a `MemberExpression` variant if parsing succeeds.
+    fn parse_member_expression(&mut self, object: Expression) -> Option<Expression> {
+        log::debug!("Parsing member expression");
+        let token = self.curr_token.clone();
+        if !self.expect_peek(TokenType::IDENT) {
+            // Error already added by expect_peek
+            return None;
+        }
+        let property = Identifier {
+            token: self.curr_token.clone(),
+            value: self.curr_token.literal.clone(),
+        };
+        Some(Expression::MemberExpression(MemberExpression {
+            token,
+            object: Box::new(object),
+            property,
+        }))
+    }
+}
+
+/// The syntax node kind for an expression started by `token_type`, if the
+/// prefix parse function does not create its own node.
+fn prefix_syntax_kind(token_type: TokenType) -> Option<SyntaxKind> {
+    match token_type {
+        TokenType::IDENT => Some(SyntaxKind::NameRef),
+        TokenType::INT | TokenType::STRING | TokenType::TRUE | TokenType::FALSE => {
+            Some(SyntaxKind::Literal)
+        }
+        TokenType::BANG | TokenType::MINUS => Some(SyntaxKind::PrefixExpr),
+        TokenType::LPAREN => Some(SyntaxKind::ParenExpr),
+        TokenType::IF | TokenType::ELSE => Some(SyntaxKind::IfExpr),
+        TokenType::FUNCTION => Some(SyntaxKind::FnExpr),
+        // Blocks wrap themselves, since if and fn parse them directly
+        _ => None,
+    }
 }
@@ -25,6 +25,8 @@ pub enum Precedence {
     PREFIX = 6,
     /// Function calls: myFunction(X)
     CALL = 7,
+    /// Member access: module.name
+    MEMBER = 8,
 }
 
 impl Precedence {
@@ -40,6 +42,7 @@ impl Precedence {
             TokenType::SLASH => Precedence::PRODUCT as i32,
             TokenType::ASTERISK => Precedence::PRODUCT as i32,
             TokenType::LPAREN => Precedence::CALL as i32,
+            TokenType::DOT => Precedence::MEMBER as i32,
             _ => Precedence::LOWEST as i32,
         }
     }
@@ -102,7 +102,8 @@ pub fn test_let_statement(stmt: &Statement, name: &str) -> bool {
 /// early. If errors are present, it prints each error message and then
 /// panics with a summary of the error count. This is used to ensure that
 /// the parser correctly handles invalid input and reports any issues
-/// encountered during the parsing process.
+/// encountered during the parsing process. It also asserts that the parser's
+/// syntax tree reproduces the input exactly.
 ///
 /// # Parameters
 /// - `p`: A reference to the Parser instance to check for errors
@@ -112,6 +113,12 @@ pub fn test_let_statement(stmt: &Statement, name: &str) -> bool {
 /// - Panics with a summary of the error count if errors are present
 #[track_caller]
 pub fn check_parser_errors(p: &Parser) {
+    // Every parsed input must also round-trip through the lossless syntax tree
+    assert_eq!(
+        p.syntax_tree().to_string(),
+        p.l.input(),
+        "syntax tree does not reproduce the input"
+    );
     let errors = p.errors();
 
     if errors.is_empty() {
@@ -321,6 +328,7 @@ pub fn test_infix_expression_str(exp: Expression, left: &str, operator: &str, ri
 #[cfg(test)]
 mod tests {
     use crate::lexer::Lexer;
+    use crate::source::FileId;
 
     use super::*;
 
@@ -333,7 +341,7 @@ mod tests {
             ("5 / 5;", 5, "/", 5),
         ];
         for (input, left, operator, right) in tests {
-            let l = Lexer::new(input.to_string());
+            let l = Lexer::new(input.to_string(), FileId::DETACHED);
             let mut p = Parser::new(l);
             let program = p.parse_program();
 
@@ -351,7 +359,7 @@ mod tests {
 
         let str_tests: Vec<(&str, &str, &str, &str)> = vec![("alice * bob;", "alice", "*", "bob")];
         for (input, left, operator, right) in str_tests {
-            let l = Lexer::new(input.to_string());
+            let l = Lexer::new(input.to_string(), FileId::DETACHED);
             let mut p = Parser::new(l);
             let program = p.parse_program();
 
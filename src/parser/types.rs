@@ -0,0 +1,162 @@
+//! Parsing of type annotations.
+//!
+//! Type expressions follow this grammar:
+//!
+//! ```text
+//! type     := primary "?"*
+//! primary  := IDENT                         (named:    int)
+//!           | "[" type "]"                  (array:    [int])
+//!           | "{" type ":" type "}"         (hash:     {string: int})
+//!           | "fn" "(" types? ")" "->" type (function: fn(int) -> bool)
+//!           | "(" type ")"                  (grouping: (fn() -> int)?)
+//! ```
+
+use super::Parser;
+use crate::ast::types::{ArrayType, FunctionType, HashType, NamedType, OptionalType, TypeExpr};
+use crate::lexer::token::TokenType;
+use crate::parser::error::ParserError;
+use crate::syntax::SyntaxKind;
+
+impl Parser {
+    /// Parses an optional `: <type>` annotation following the current token.
+    ///
+    /// # Returns
+    /// - `Some(None)` if the peek token is not a colon (no annotation)
+    /// - `Some(Some(TypeExpr))` if an annotation was parsed
+    /// - `None` if an annotation was started but failed to parse
+    pub(super) fn parse_optional_annotation(&mut self) -> Option<Option<TypeExpr>> {
+        if !self.is_peek_token(TokenType::COLON) {
+            return Some(None);
+        }
+        self.next_token();
+        self.next_token();
+        self.parse_type().map(Some)
+    }
+
+    /// Parses a type expression starting at the current token.
+    ///
+    /// Leaves the current token on the last token of the type.
+    ///
+    /// # Errors
+    /// Adds an error to the parser's error list if the current token cannot start a type.
+    pub(super) fn parse_type(&mut self) -> Option<TypeExpr> {
+        let checkpoint = self.curr_checkpoint;
+        let ty = self.parse_type_inner();
+        self.wrap_syntax(checkpoint, SyntaxKind::Type, ty)
+    }
+
+    /// Parses a type expression; `parse_type` wraps it in a `Type` syntax node.
+    fn parse_type_inner(&mut self) -> Option<TypeExpr> {
+        log::debug!("Parsing type {:?}", self.curr_token.token_type);
+        let mut ty = match self.curr_token.token_type {
+            TokenType::IDENT => TypeExpr::Named(NamedType {
+                token: self.curr_token.clone(),
+                name: self.curr_token.literal.clone(),
+            }),
+            TokenType::LBRACKET => self.parse_array_type()?,
+            TokenType::LBRACE => self.parse_hash_type()?,
+            TokenType::FUNCTION => self.parse_function_type()?,
+            TokenType::LPAREN => {
+                self.next_token();
+                let inner = self.parse_type()?;
+                if !self.expect_peek(TokenType::RPAREN) {
+                    // Error already added by expect_peek
+                    return None;
+                }
+                inner
+            }
+            _ => {
+                let error = ParserError::at_token(
+                    &self.curr_token,
+                    format!("expected type, got {:?}", self.curr_token.token_type),
+                );
+                log::debug!("{}", error);
+                self.errors.push(error);
+                return None;
+            }
+        };
+
+        // Any number of trailing '?' wrap the type as optional
+        while self.is_peek_token(TokenType::QUESTION) {
+            self.next_token();
+            ty = TypeExpr::Optional(OptionalType {
+                token: self.curr_token.clone(),
+                inner: Box::new(ty),
+            });
+        }
+        Some(ty)
+    }
+
+    /// Parses an array type: `[<element>]`.
+    fn parse_array_type(&mut self) -> Option<TypeExpr> {
+        let token = self.curr_token.clone();
+        self.next_token();
+        let element = self.parse_type()?;
+        if !self.expect_peek(TokenType::RBRACKET) {
+            // Error already added by expect_peek
+            return None;
+        }
+        Some(TypeExpr::Array(ArrayType {
+            token,
+            element: Box::new(element),
+        }))
+    }
+
+    /// Parses a hash type: `{<key>: <value>}`.
+    fn parse_hash_type(&mut self) -> Option<TypeExpr> {
+        let token = self.curr_token.clone();
+        self.next_token();
+        let key = self.parse_type()?;
+        if !self.expect_peek(TokenType::COLON) {
+            // Error already added by expect_peek
+            return None;
+        }
+        self.next_token();
+        let value = self.parse_type()?;
+        if !self.expect_peek(TokenType::RBRACE) {
+            // Error already added by expect_peek
+            return None;
+        }
+        Some(TypeExpr::Hash(HashType {
+            token,
+            key: Box::new(key),
+            value: Box::new(value),
+        }))
+    }
+
+    /// Parses a function type: `fn(<parameters>) -> <return_type>`.
+    fn parse_function_type(&mut self) -> Option<TypeExpr> {
+        let token = self.curr_token.clone();
+        if !self.expect_peek(TokenType::LPAREN) {
+            // Error already added by expect_peek
+            return None;
+        }
+        let mut parameters = Vec::new();
+        if self.is_peek_token(TokenType::RPAREN) {
+            self.next_token();
+        } else {
+            self.next_token();
+            parameters.push(self.parse_type()?);
+            while self.is_peek_token(TokenType::COMMA) {
+                self.next_token();
+                self.next_token();
+                parameters.push(self.parse_type()?);
+            }
+            if !self.expect_peek(TokenType::RPAREN) {
+                // Error already added by expect_peek
+                return None;
+            }
+        }
+        if !self.expect_peek(TokenType::ARROW) {
+            // Error already added by expect_peek
+            return None;
+        }
+        self.next_token();
+        let return_type = self.parse_type()?;
+        Some(TypeExpr::Function(FunctionType {
+            token,
+            parameters,
+            return_type: Box::new(return_type),
+        }))
+    }
+}
@@ -0,0 +1,346 @@
+//! An instrumenting profiler for the AST evaluator.
+//!
+//! `Profiler` is an evaluator `Hook` that times every call of a Monkey
+//! function. Functions are told apart by where their literal is defined, so
+//! two closures made by the same `fn` share one entry, while two functions
+//! bound to the same name do not. For each it counts:
+//!
+//! - calls;
+//! - inclusive time: from entering to leaving the function, callees
+//!   included. A recursive call adds to it only when it is the outermost one
+//!   on the stack, so time is never counted twice;
+//! - exclusive time: inclusive time minus the time spent in callees.
+//!
+//! `Profile::report` lists the functions by exclusive time, and
+//! `Profile::folded` writes the exclusive time of every distinct call stack
+//! in the folded format that `flamegraph.pl` and `inferno` read, in
+//! microseconds. Time outside any function is charged to `<main>`.
+//!
+//! Only runs passed a `Profiler` pay for it: `Evaluator::eval_program` runs
+//! with the no-op `()` hook, which compiles away.
+
+use crate::evaluator::Hook;
+use crate::lexer::token::Token;
+use crate::object::{Env, Function};
+use crate::parser::error::Span;
+use crate::source::{FileId, SourceMap};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// The root frame of every folded stack.
+const MAIN: &str = "<main>";
+
+/// What one function cost over a run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionProfile {
+    /// The name of the `let` the literal was bound to, or `<anonymous>`.
+    pub name: String,
+    /// Where the literal is defined; the key functions are told apart by.
+    pub span: Span,
+    pub calls: u64,
+    pub inclusive: Duration,
+    pub exclusive: Duration,
+}
+
+/// The result of a profiled run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// The time from creating the profiler to finishing it.
+    pub total: Duration,
+    /// Every function called, by exclusive time, longest first.
+    pub functions: Vec<FunctionProfile>,
+    /// The exclusive time of each call stack, outermost function first; the
+    /// empty stack is the time outside any function.
+    stacks: Vec<(Vec<Span>, Duration)>,
+}
+
+impl Profile {
+    /// Formats the functions as a table, longest exclusive time first.
+    /// # Parameters
+    /// - `sources`: Where definition spans are shown from
+    /// # Returns
+    /// One line per function and a line with the total, times in
+    /// milliseconds.
+    pub fn report(&self, sources: &SourceMap) -> String {
+        let mut report = format!(
+            "{:>8} {:>14} {:>14}  function\n",
+            "calls", "inclusive ms", "exclusive ms"
+        );
+        for function in &self.functions {
+            let _ = writeln!(
+                report,
+                "{:>8} {:>14.3} {:>14.3}  {} at {}",
+                function.calls,
+                millis(function.inclusive),
+                millis(function.exclusive),
+                function.name,
+                sources.location(&function.span)
+            );
+        }
+        let _ = writeln!(report, "total {:.3} ms", millis(self.total));
+        report
+    }
+
+    /// Formats the call stacks in the folded format flamegraph tools read:
+    /// one `frame;frame;... count` line per stack, with its exclusive time
+    /// in microseconds as the count.
+    /// # Parameters
+    /// - `sources`: Where definition spans are shown from
+    /// # Returns
+    /// The lines sorted by stack; every stack starts at `<main>`.
+    pub fn folded(&self, sources: &SourceMap) -> String {
+        let names: HashMap<&Span, &str> = self
+            .functions
+            .iter()
+            .map(|function| (&function.span, function.name.as_str()))
+            .collect();
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, time)| {
+                let mut line = MAIN.to_string();
+                for span in stack {
+                    let name = names.get(span).copied().unwrap_or_default();
+                    let _ = write!(line, ";{} ({})", name, sources.location(span));
+                }
+                let _ = write!(line, " {}", time.as_micros());
+                line
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+fn position(span: &Span) -> (FileId, usize, usize) {
+    (span.file, span.line, span.column)
+}
+
+fn millis(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
+
+/// A call that has not returned yet.
+#[derive(Debug)]
+struct Call {
+    function: Span,
+    started: Duration,
+    /// The inclusive time of the calls it made that have returned.
+    callees: Duration,
+}
+
+/// An evaluator hook that times calls of Monkey functions.
+pub struct Profiler {
+    clock: Box<dyn FnMut() -> Duration>,
+    started: Duration,
+    calls: Vec<Call>,
+    /// The inclusive time of the returned calls made outside any function.
+    main_callees: Duration,
+    functions: HashMap<Span, FunctionProfile>,
+    stacks: HashMap<Vec<Span>, Duration>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    /// A profiler that measures wall-clock time from now.
+    pub fn new() -> Self {
+        let start = Instant::now();
+        Self::with_clock(move || start.elapsed())
+    }
+
+    /// A profiler that reads the time from `clock`, which must never go
+    /// backwards. Tests use it to make timings exact.
+    pub fn with_clock(mut clock: impl FnMut() -> Duration + 'static) -> Self {
+        let started = clock();
+        Profiler {
+            clock: Box::new(clock),
+            started,
+            calls: Vec::new(),
+            main_callees: Duration::ZERO,
+            functions: HashMap::new(),
+            stacks: HashMap::new(),
+        }
+    }
+
+    /// Stops measuring; call it once the program has finished.
+    /// # Returns
+    /// What the run cost.
+    pub fn finish(mut self) -> Profile {
+        let total = (self.clock)().saturating_sub(self.started);
+        *self.stacks.entry(Vec::new()).or_default() += total.saturating_sub(self.main_callees);
+        let mut functions: Vec<FunctionProfile> = self.functions.into_values().collect();
+        functions.sort_by(|a, b| {
+            b.exclusive
+                .cmp(&a.exclusive)
+                .then(b.calls.cmp(&a.calls))
+                .then_with(|| position(&a.span).cmp(&position(&b.span)))
+        });
+        Profile {
+            total,
+            functions,
+            stacks: self.stacks.into_iter().collect(),
+        }
+    }
+}
+
+impl Hook for Profiler {
+    fn enter(&mut self, function: &Function, _call: &Token, _env: &Env) {
+        let profile = self
+            .functions
+            .entry(function.span.clone())
+            .or_insert_with(|| FunctionProfile {
+                name: function
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| "<anonymous>".to_string()),
+                span: function.span.clone(),
+                calls: 0,
+                inclusive: Duration::ZERO,
+                exclusive: Duration::ZERO,
+            });
+        profile.calls += 1;
+        let started = (self.clock)();
+        self.calls.push(Call {
+            function: function.span.clone(),
+            started,
+            callees: Duration::ZERO,
+        });
+    }
+
+    fn exit(&mut self, _function: &Function) {
+        let now = (self.clock)();
+        let stack: Vec<Span> = self
+            .calls
+            .iter()
+            .map(|call| call.function.clone())
+            .collect();
+        let Some(call) = self.calls.pop() else {
+            return;
+        };
+        let elapsed = now.saturating_sub(call.started);
+        let exclusive = elapsed.saturating_sub(call.callees);
+        let outermost = !self
+            .calls
+            .iter()
+            .any(|outer| outer.function == call.function);
+        if let Some(profile) = self.functions.get_mut(&call.function) {
+            profile.exclusive += exclusive;
+            if outermost {
+                profile.inclusive += elapsed;
+            }
+        }
+        match self.calls.last_mut() {
+            Some(caller) => caller.callees += elapsed,
+            None => self.main_callees += elapsed,
+        }
+        *self.stacks.entry(stack).or_default() += exclusive;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Evaluator;
+    use crate::lexer::Lexer;
+    use crate::parser::{Parser, test_helper::check_parser_errors};
+    use std::io;
+
+    /// Profiles `source` as `test.monkey` with a clock that moves 1ms each
+    /// time it is read.
+    fn profile(source: &str) -> (Profile, SourceMap) {
+        let mut sources = SourceMap::new();
+        let file = sources.add("test.monkey", source);
+        let mut parser = Parser::new(Lexer::new(source.to_string(), file));
+        let program = parser.parse_program();
+        check_parser_errors(&parser);
+        let mut now = Duration::ZERO;
+        let mut profiler = Profiler::with_clock(move || {
+            now += Duration::from_millis(1);
+            now
+        });
+        let _ = Evaluator::with_output(io::sink()).eval_program_with(&program, &mut profiler);
+        (profiler.finish(), sources)
+    }
+
+    #[test]
+    fn test_inclusive_and_exclusive_time() {
+        let (profile, sources) = profile(
+            "let double = fn(x) { x * 2; };
+let quad = fn(x) { double(double(x)); };
+quad(1);
+quad(2);",
+        );
+        // Each call of quad takes 5ms, 1ms in each call of double; the
+        // profiler's first and last reads add 3ms outside any function
+        assert_eq!(profile.total, Duration::from_millis(13));
+        assert_eq!(
+            profile.report(&sources),
+            "   calls   inclusive ms   exclusive ms  function
+       2         10.000          6.000  quad at test.monkey:2:12
+       4          4.000          4.000  double at test.monkey:1:14
+total 13.000 ms
+"
+        );
+        assert_eq!(
+            profile.folded(&sources),
+            "<main> 3000
+<main>;quad (test.monkey:2:12) 6000
+<main>;quad (test.monkey:2:12);double (test.monkey:1:14) 4000
+"
+        );
+    }
+
+    #[test]
+    fn test_recursion_is_counted_once() {
+        let (profile, sources) =
+            profile("let count = fn(n) { if (n > 0) { count(n - 1); } else { 0; }; };\ncount(2);");
+        let count = &profile.functions[0];
+        assert_eq!(count.calls, 3);
+        assert_eq!(count.inclusive, Duration::from_millis(5));
+        assert_eq!(count.exclusive, Duration::from_millis(5));
+        let folded = profile.folded(&sources);
+        let stacks: Vec<&str> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().1)
+            .collect();
+        // `<main>`, then count one, two and three calls deep
+        assert_eq!(stacks, ["2000", "2000", "2000", "1000"]);
+    }
+
+    #[test]
+    fn test_functions_are_keyed_by_definition() {
+        let (profile, sources) = profile(
+            "let adder = fn(n) { fn(x) { x + n; }; };
+let f = adder(1);
+let g = adder(2);
+f(1) + g(2);
+let f = fn(x) { x; };
+f(3);
+let fail = fn(x) { x / 0; };
+fail(1);",
+        );
+        let mut entries: Vec<String> = profile
+            .functions
+            .iter()
+            .map(|f| format!("{} {} {}", f.name, sources.location(&f.span), f.calls))
+            .collect();
+        entries.sort();
+        // Both closures made by adder share one entry; the two `f`s do not,
+        // and a call that fails is still timed
+        assert_eq!(
+            entries,
+            [
+                "<anonymous> test.monkey:1:21 2",
+                "adder test.monkey:1:13 2",
+                "f test.monkey:5:9 1",
+                "fail test.monkey:7:12 1",
+            ]
+        );
+    }
+}
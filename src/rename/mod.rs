@@ -0,0 +1,211 @@
+//! Scope-aware renaming of bindings.
+//!
+//! `rename` finds the binding under a position (a `let`, a parameter or an
+//! import alias, either where it is defined or where it is used) and returns
+//! the text edits that rename it and every use of it. Uses are taken from
+//! name resolution, so a binding of the same name in a nested
+//! `FunctionLiteral` or `BlockStatement` scope is left alone.
+//!
+//! A rename is refused when the result would mean something else: when the
+//! new name would capture a use of another binding (or of a builtin), or when
+//! a use of the renamed binding would end up referring to another binding of
+//! the new name. This is checked by resolving the renamed program and
+//! comparing every identifier with what it referred to before.
+
+use crate::lexer::Lexer;
+use crate::lexer::token::{TokenType, lookup_identifier};
+use crate::parser::error::Span;
+use crate::resolve::{BindingId, Resolution, resolve};
+use crate::source::FileId;
+use crate::syntax::{self, Parse, SyntaxKind, SyntaxToken, TextEdit};
+use std::fmt;
+
+/// Why a rename cannot be done.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenameError {
+    /// The new name is not an identifier.
+    InvalidName(String),
+    /// The new name is a keyword.
+    Keyword(String),
+    /// The source does not parse, so its bindings are not reliable.
+    ParseErrors(usize),
+    /// There is no binding at the position.
+    NoBinding,
+    /// The binding is exported, so other modules may refer to it by name.
+    Exported(String),
+    /// After the rename, the identifier at `span` would refer to a different
+    /// binding than before.
+    Capture { span: Span, name: String },
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameError::InvalidName(name) => write!(f, "'{}' is not a valid name", name),
+            RenameError::Keyword(name) => write!(f, "'{}' is a keyword", name),
+            RenameError::ParseErrors(count) => {
+                write!(f, "cannot rename in a file with {} parser error(s)", count)
+            }
+            RenameError::NoBinding => write!(f, "no binding to rename at this position"),
+            RenameError::Exported(name) => {
+                write!(f, "'{}' is exported and may be used by other modules", name)
+            }
+            RenameError::Capture { span, name } => write!(
+                f,
+                "the rename would change what '{}' at {} refers to",
+                name, span
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenameError {}
+
+/// Renames the binding at `offset` in `source` to `new_name`.
+/// # Parameters
+/// - `source`: The text of the file
+/// - `offset`: A byte offset on or just after a name of the binding
+/// - `new_name`: The name to give it
+/// # Returns
+/// The edits, one per occurrence in source order (none when the name does not
+/// change), or why the rename is refused.
+pub fn rename(source: &str, offset: usize, new_name: &str) -> Result<Vec<TextEdit>, RenameError> {
+    check_name(new_name)?;
+    let parse = syntax::parse(source);
+    if !parse.errors().is_empty() {
+        return Err(RenameError::ParseErrors(parse.errors().len()));
+    }
+    let resolution = resolve(parse.program());
+    let idents = identifiers(&parse);
+
+    let target = [Some(offset), offset.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .find_map(|offset| {
+            idents
+                .iter()
+                .find(|ident| ident.token.text_range().contains(&offset))
+        })
+        .and_then(|ident| binding_at(&resolution, &ident.span))
+        .ok_or(RenameError::NoBinding)?;
+    let binding = &resolution.bindings[target];
+    if binding.name == new_name {
+        return Ok(Vec::new());
+    }
+    if is_exported(&idents, &binding.span) {
+        return Err(RenameError::Exported(binding.name.clone()));
+    }
+
+    let edits: Vec<TextEdit> = idents
+        .iter()
+        .filter(|ident| binding_at(&resolution, &ident.span) == Some(target))
+        .map(|ident| TextEdit::new(ident.token.text_range(), new_name))
+        .collect();
+
+    // Every identifier must refer to the same binding as before. The program
+    // has the same shape, so bindings are declared in the same order.
+    let renamed = syntax::parse(&apply_edits(source, &edits));
+    let renamed_resolution = resolve(renamed.program());
+    let renamed_idents = identifiers(&renamed);
+    for (before, after) in idents.iter().zip(&renamed_idents) {
+        if resolution.uses.get(&before.span) != renamed_resolution.uses.get(&after.span) {
+            return Err(RenameError::Capture {
+                span: before.span.clone(),
+                name: before.token.text().to_string(),
+            });
+        }
+    }
+    Ok(edits)
+}
+
+/// Applies non-overlapping edits, such as those from `rename`, to `source`.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|edit| edit.range.start);
+    let mut text = source.to_string();
+    for edit in edits.into_iter().rev() {
+        text = edit.apply(&text);
+    }
+    text
+}
+
+/// Accepts exactly the names the lexer reads as a single identifier.
+fn check_name(name: &str) -> Result<(), RenameError> {
+    if lookup_identifier(name) != TokenType::IDENT {
+        return Err(RenameError::Keyword(name.to_string()));
+    }
+    let mut lexer = Lexer::new(name.to_string(), FileId::DETACHED);
+    let token = lexer.next_token();
+    if token.token_type != TokenType::IDENT
+        || token.literal != name
+        || lexer.next_token().token_type != TokenType::EOF
+    {
+        return Err(RenameError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// An identifier token with its position as the lexer reports it.
+struct Ident {
+    token: SyntaxToken,
+    span: Span,
+}
+
+/// Every identifier token of the tree, in source order.
+fn identifiers(parse: &Parse) -> Vec<Ident> {
+    let mut idents = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    for token in parse.syntax().descendant_tokens() {
+        let span = Span::in_file(parse.file(), line, column);
+        let text = token.text();
+        match text.rfind('\n') {
+            Some(newline) => {
+                line += text.matches('\n').count();
+                column = text[newline + 1..].chars().count() + 1;
+            }
+            None => column += text.chars().count(),
+        }
+        if token.kind() == SyntaxKind::Token(TokenType::IDENT) {
+            idents.push(Ident { token, span });
+        }
+    }
+    idents
+}
+
+/// The binding an identifier at `span` uses or defines.
+fn binding_at(resolution: &Resolution, span: &Span) -> Option<BindingId> {
+    resolution.uses.get(span).copied().or_else(|| {
+        resolution
+            .bindings
+            .iter()
+            .position(|binding| &binding.span == span)
+    })
+}
+
+/// Whether the binding defined at `span` is an `export let`.
+fn is_exported(idents: &[Ident], span: &Span) -> bool {
+    idents
+        .iter()
+        .find(|ident| &ident.span == span)
+        .and_then(|ident| ident.token.parent().parent())
+        .is_some_and(|node| node.kind() == SyntaxKind::ExportStmt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        assert_eq!(check_name("total_count"), Ok(()));
+        assert_eq!(check_name("fn"), Err(RenameError::Keyword("fn".into())));
+        for name in ["", "a b", "x1", "1x", "a-b", "\"s\""] {
+            assert_eq!(
+                check_name(name),
+                Err(RenameError::InvalidName(name.into())),
+                "{:?}",
+                name
+            );
+        }
+    }
+}
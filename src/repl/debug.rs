@@ -0,0 +1,220 @@
+//! The REPL's `:debug` command: a line-oriented frontend for `debugger`.
+//!
+//! The program runs until it pauses, then commands are read from the REPL's
+//! input until one resumes it:
+//!
+//! - `break N` / `delete N`: set or remove a breakpoint on line N
+//! - `continue`, `step`, `next`, `out`: resume, step in, step over, step out
+//! - `list`: print the current statement again
+//! - `print EXPR`: evaluate an expression in the current scope
+//! - `set NAME = EXPR`: change a variable in the current scope chain
+//! - `vars`: print every variable in the current scope chain
+//! - `backtrace`: print the call stack
+//! - `quit`: stop the program
+//!
+//! What the program prints with `puts` is written to the same output, in
+//! order with the debugger's own messages.
+use crate::ast::Program;
+use crate::debugger::{Debugger, Frontend, ProgramOutput, QUIT_MESSAGE, Resume, Session, Stop};
+use crate::evaluator::Evaluator;
+use crate::object::Env;
+use crate::parser::error::Span;
+use crate::source::SourceMap;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+const PROMPT: &str = "(debug)";
+
+const HELP: &str = "\
+break N        pause at line N
+delete N       remove the breakpoint at line N
+continue, c    run to the next breakpoint
+step, s        step into calls
+next, n        step over calls
+out, o         step out of the current call
+list, l        print the current statement
+print, p EXPR  evaluate EXPR in the current scope
+set NAME = EXPR
+               change a variable
+vars           print the variables in scope
+backtrace, bt  print the call stack
+quit, q        stop the program";
+
+/// Runs `program` under the debugger, reading commands from `input`. It
+/// pauses before the first statement.
+/// # Parameters
+/// - `program`: The program to run, parsed from a file in `sources`
+/// - `sources`: Where statements and errors are shown from
+/// - `input`: The reader commands are read from
+/// - `output`: The writer for the program's output and the debugger's
+/// # Returns
+/// - `Ok(())` once the program has finished, failed or been stopped
+/// - `Err(e)` if reading or writing failed
+pub fn debug<R: BufRead, W: Write>(
+    program: &Program,
+    sources: &SourceMap,
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<()> {
+    let printed = ProgramOutput::default();
+    let mut evaluator = Evaluator::with_output(printed.clone());
+    let console = Console {
+        input,
+        output,
+        sources,
+        printed,
+        failure: None,
+    };
+    let mut debugger = Debugger::new(console, []);
+    let result = evaluator.eval_program_with(program, &mut debugger);
+    let mut console = debugger.into_frontend();
+    if let Some(error) = console.failure {
+        return Err(error);
+    }
+    console.flush_program_output()?;
+    match result {
+        Ok(value) => writeln!(console.output, "program finished: {}", value),
+        Err(error) if error.message == QUIT_MESSAGE => {
+            writeln!(console.output, "program stopped")
+        }
+        Err(error) => writeln!(console.output, "{}", error.render(sources)),
+    }
+}
+
+struct Console<'a, R, W> {
+    input: &'a mut R,
+    output: &'a mut W,
+    sources: &'a SourceMap,
+    printed: ProgramOutput,
+    /// The first I/O error; the program is stopped when it happens.
+    failure: Option<io::Error>,
+}
+
+impl<R: BufRead, W: Write> Frontend for Console<'_, R, W> {
+    fn paused(&mut self, stop: &Stop, session: &mut Session) -> Resume {
+        match self.interact(stop, session) {
+            Ok(resume) => resume,
+            Err(error) => {
+                self.failure = Some(error);
+                Resume::Quit
+            }
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Console<'_, R, W> {
+    fn flush_program_output(&mut self) -> io::Result<()> {
+        self.output.write_all(&self.printed.take())
+    }
+
+    /// Reports a pause and runs commands until one resumes the program.
+    fn interact(&mut self, stop: &Stop, session: &mut Session) -> io::Result<Resume> {
+        self.flush_program_output()?;
+        let Some(span) = session.current().map(|frame| frame.span.clone()) else {
+            return Ok(Resume::Continue);
+        };
+        let location = self.sources.location(&span);
+        match stop {
+            Stop::Entry => writeln!(self.output, "paused at {}", location)?,
+            Stop::Breakpoint => writeln!(self.output, "breakpoint at {}", location)?,
+            Stop::Step => writeln!(self.output, "step to {}", location)?,
+            Stop::Error(error) => writeln!(self.output, "error at {}: {}", location, error)?,
+        }
+        self.list(&span)?;
+
+        let mut line = String::new();
+        loop {
+            write!(self.output, "{} ", PROMPT)?;
+            self.output.flush()?;
+            line.clear();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(Resume::Quit);
+            }
+            let line = line.trim();
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let argument = argument.trim();
+            match command {
+                "" => {}
+                "continue" | "c" => return Ok(Resume::Continue),
+                "step" | "s" => return Ok(Resume::StepIn),
+                "next" | "n" => return Ok(Resume::StepOver),
+                "out" | "o" => return Ok(Resume::StepOut),
+                "quit" | "q" => return Ok(Resume::Quit),
+                "break" | "b" => match argument.parse() {
+                    Ok(line) => {
+                        session.set_breakpoint(line);
+                        writeln!(self.output, "breakpoint set at line {}", line)?;
+                    }
+                    Err(_) => writeln!(self.output, "usage: break LINE")?,
+                },
+                "delete" | "d" => match argument.parse() {
+                    Ok(line) if session.clear_breakpoint(line) => {
+                        writeln!(self.output, "breakpoint at line {} removed", line)?
+                    }
+                    Ok(line) => writeln!(self.output, "no breakpoint at line {}", line)?,
+                    Err(_) => writeln!(self.output, "usage: delete LINE")?,
+                },
+                "list" | "l" => self.list(&span)?,
+                "print" | "p" => match session.evaluate(0, argument) {
+                    Ok(value) => writeln!(self.output, "{}", value)?,
+                    Err(message) => writeln!(self.output, "error: {}", message)?,
+                },
+                "set" => match argument.split_once('=') {
+                    Some((name, value)) => match session.assign(0, name.trim(), value.trim()) {
+                        Ok(value) => writeln!(self.output, "{} = {}", name.trim(), value)?,
+                        Err(message) => writeln!(self.output, "error: {}", message)?,
+                    },
+                    None => writeln!(self.output, "usage: set NAME = EXPR")?,
+                },
+                "vars" => {
+                    if let Some(frame) = session.current() {
+                        self.vars(&frame.env)?;
+                    }
+                }
+                "backtrace" | "bt" => {
+                    for (i, frame) in session.frames().enumerate() {
+                        let location = self.sources.location(&frame.span);
+                        writeln!(self.output, "#{} {} at {}", i, frame.name, location)?;
+                    }
+                }
+                "help" | "h" => writeln!(self.output, "{}", HELP)?,
+                _ => writeln!(self.output, "unknown command: {} (try help)", command)?,
+            }
+        }
+    }
+
+    /// Prints the source line of `span`.
+    fn list(&mut self, span: &Span) -> io::Result<()> {
+        let text = self
+            .sources
+            .get(span.file)
+            .and_then(|file| file.line(span.line))
+            .unwrap_or_default();
+        writeln!(self.output, "{:>4} | {}", span.line, text)
+    }
+
+    /// Prints every binding from `env` out to the global scope.
+    fn vars(&mut self, env: &Env) -> io::Result<()> {
+        let mut scope = Some(Rc::clone(env));
+        let mut first = true;
+        while let Some(env) = scope {
+            let env = env.borrow();
+            let bindings = env.bindings();
+            scope = env.outer();
+            if bindings.is_empty() {
+                continue;
+            }
+            let title = match (&scope, first) {
+                (None, _) => "globals",
+                (Some(_), true) => "locals",
+                (Some(_), false) => "enclosing",
+            };
+            first = false;
+            writeln!(self.output, "{}:", title)?;
+            for (name, value) in bindings {
+                writeln!(self.output, "  {} = {}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -7,12 +7,20 @@
 //!
 //! - `print_welcome`: Prints the welcome message to the output
 //! - `print_parser_errors`: Prints the parser errors to the output
+//! - `print_type_errors`: Prints the type errors to the output
+//! - `print_resolve_errors`: Prints undefined and shadowed names to the output
+//! - `print_compile_error`: Prints an error from compiling to bytecode
 //!
 //! # Types
 //!
 //! - `MONKEY_LOGO`: The Monkey logo
 //! - `RESET`: The reset color code
+use crate::compiler::CompileError;
+use crate::diagnostic::Diagnostic;
 use crate::parser::error::ParserError;
+use crate::resolve::ResolveError;
+use crate::source::SourceMap;
+use crate::typeck::TypeError;
 use colored::Colorize;
 use std::io::{Result, Write};
 
@@ -71,18 +79,88 @@ pub fn print_welcome() {
 /// # Parameters
 /// - `output`: The output writer to write the errors to
 /// - `errors`: The vector of errors to print
+/// - `sources`: The sources the errors point into, used to render snippets
 /// # Returns
 /// - `Ok(())` if the errors were printed successfully
 /// - `Err(e)` if an error occurred while printing the errors
-pub fn print_parser_errors<W: Write>(output: &mut W, errors: &Vec<ParserError>) -> Result<()> {
+pub fn print_parser_errors<W: Write>(
+    output: &mut W,
+    errors: &[ParserError],
+    sources: &SourceMap,
+) -> Result<()> {
     writeln!(
         output,
         "{}",
         "Woops! We ran into some monkey business here!".red().bold()
     )?;
     writeln!(output, " parser errors:")?;
-    for error in errors {
-        writeln!(output, "  {}{}{}", GRAY, error, RESET)?;
+    let diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+    print_diagnostics(output, &diagnostics, sources)
+}
+
+/// Prints type errors to the output
+/// # Parameters
+/// - `output`: The output writer to write the errors to
+/// - `errors`: The type errors to print
+/// - `sources`: The sources the errors point into, used to render snippets
+/// # Returns
+/// - `Ok(())` if the errors were printed successfully
+/// - `Err(e)` if an error occurred while printing the errors
+pub fn print_type_errors<W: Write>(
+    output: &mut W,
+    errors: &[TypeError],
+    sources: &SourceMap,
+) -> Result<()> {
+    writeln!(output, " type errors:")?;
+    let diagnostics: Vec<Diagnostic> = errors.iter().map(TypeError::to_diagnostic).collect();
+    print_diagnostics(output, &diagnostics, sources)
+}
+
+/// Prints name resolution errors and warnings to the output
+/// # Parameters
+/// - `output`: The output writer to write the errors to
+/// - `errors`: The resolution problems to print
+/// - `sources`: The sources the errors point into, used to render snippets
+/// # Returns
+/// - `Ok(())` if the errors were printed successfully
+/// - `Err(e)` if an error occurred while printing the errors
+pub fn print_resolve_errors<W: Write>(
+    output: &mut W,
+    errors: &[ResolveError],
+    sources: &SourceMap,
+) -> Result<()> {
+    writeln!(output, " name errors:")?;
+    let diagnostics: Vec<Diagnostic> = errors.iter().map(ResolveError::to_diagnostic).collect();
+    print_diagnostics(output, &diagnostics, sources)
+}
+
+/// Prints an error from compiling the input to bytecode
+/// # Parameters
+/// - `output`: The output writer to write the error to
+/// - `error`: The compile error to print
+/// - `sources`: The sources the error points into, used to render snippets
+/// # Returns
+/// - `Ok(())` if the error was printed successfully
+/// - `Err(e)` if an error occurred while printing the error
+pub fn print_compile_error<W: Write>(
+    output: &mut W,
+    error: &CompileError,
+    sources: &SourceMap,
+) -> Result<()> {
+    writeln!(output, " compile errors:")?;
+    print_diagnostics(output, &[error.to_diagnostic()], sources)
+}
+
+/// Prints rendered diagnostics, indented and grayed out
+fn print_diagnostics<W: Write>(
+    output: &mut W,
+    diagnostics: &[Diagnostic],
+    sources: &SourceMap,
+) -> Result<()> {
+    for diagnostic in diagnostics {
+        for line in diagnostic.render(sources).lines() {
+            writeln!(output, "  {}{}{}", GRAY, line, RESET)?;
+        }
     }
     Ok(())
 }
@@ -9,13 +9,66 @@
 //! let x = 10;
 //! println!("x is {}", x);
 //! ```
-use crate::{lexer::Lexer, parser::Parser};
+//!
+//! Prefixing an input with `:type` prints its inferred type instead, using the
+//! bindings from every earlier line (e.g. `:type fn(x) { x + 1; }`).
+//!
+//! `:view source|sexpr|tree` chooses how each parsed input is echoed: as
+//! re-sugared source (the default), as S-expressions, or as an indented tree.
+//! The source view is colored with `highlight::to_ansi`.
+//!
+//! `:disasm` prints the bytecode an input compiles to (e.g. `:disasm 1 + 2;`),
+//! with the globals and constant pool built up by the earlier lines.
+//!
+//! `:debug path` runs a file under the step debugger; see `debug` for its
+//! commands, which are read from the same input as REPL lines.
+use crate::{
+    ast::Program, compiler::Compiler, disasm::disassemble, highlight, lexer::Lexer, parser::Parser,
+    resolve::Resolver, source::SourceMap, typeck::TypeChecker,
+};
+pub use debug::debug;
 pub use display::MONKEY_LOGO;
-use display::{CYAN, GRAY, RESET, print_parser_errors, print_welcome};
+use display::{
+    CYAN, GRAY, RESET, print_compile_error, print_parser_errors, print_resolve_errors,
+    print_type_errors, print_welcome,
+};
+use std::fs;
 use std::io::{self, BufRead, Write};
+mod debug;
 mod display;
 
 const PROMPT: &str = ">>";
+const TYPE_COMMAND: &str = ":type";
+const VIEW_COMMAND: &str = ":view";
+const DISASM_COMMAND: &str = ":disasm";
+const DEBUG_COMMAND: &str = ":debug";
+
+/// How the REPL echoes a parsed program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Source,
+    Sexpr,
+    Tree,
+}
+
+impl View {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "source" => Some(View::Source),
+            "sexpr" => Some(View::Sexpr),
+            "tree" => Some(View::Tree),
+            _ => None,
+        }
+    }
+
+    fn render(self, program: &Program) -> String {
+        match self {
+            View::Source => highlight::to_ansi(&program.to_string()),
+            View::Sexpr => program.to_sexpr(),
+            View::Tree => program.to_tree(),
+        }
+    }
+}
 
 /// Starts the REPL
 /// # Parameters
@@ -29,6 +82,15 @@ pub fn repl<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
 
     let mut reader = input;
     let mut line = String::new();
+    // Every input line is kept so errors can be rendered with their source text
+    let mut sources = SourceMap::new();
+    // Checks every line so `:type` knows the bindings made so far
+    let mut checker = TypeChecker::new();
+    // Reports undefined and shadowed names against the bindings of earlier lines
+    let mut resolver = Resolver::new();
+    // Compiles every line so `:disasm` sees the globals defined so far
+    let mut compiler = Compiler::new();
+    let mut view = View::Source;
 
     loop {
         // Print prompt
@@ -48,17 +110,80 @@ pub fn repl<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
             continue;
         }
 
+        if let Some(name) = trimmed_line.strip_prefix(VIEW_COMMAND) {
+            match View::parse(name.trim()) {
+                Some(selected) => view = selected,
+                None => writeln!(output, "usage: {} source|sexpr|tree", VIEW_COMMAND)?,
+            }
+            continue;
+        }
+
+        if let Some(path) = trimmed_line.strip_prefix(DEBUG_COMMAND) {
+            let path = path.trim();
+            match fs::read_to_string(path) {
+                Ok(text) => {
+                    let file = sources.add(path, text.as_str());
+                    let mut parser = Parser::new(Lexer::new(text, file));
+                    let program = parser.parse_program();
+                    if parser.errors.is_empty() {
+                        debug(&program, &sources, &mut reader, &mut output)?;
+                    } else {
+                        print_parser_errors(&mut output, &parser.errors, &sources)?;
+                    }
+                }
+                Err(error) => writeln!(output, "cannot read {}: {}", path, error)?,
+            }
+            continue;
+        }
+
+        let (show_type, source) = match trimmed_line.strip_prefix(TYPE_COMMAND) {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, trimmed_line),
+        };
+        let (show_bytecode, source) = match source.strip_prefix(DISASM_COMMAND) {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, source),
+        };
+
         // Create lexer and parse tokens
-        let lexer = Lexer::new(trimmed_line.to_string());
+        let file = sources.add("<repl>", source);
+        let lexer = Lexer::new(source.to_string(), file);
         let mut parser = Parser::new(lexer);
         let program = parser.parse_program();
 
         // If there are parser errors, print them and continue to next iteration
         if !parser.errors.is_empty() {
-            print_parser_errors(&mut output, &parser.errors)?;
+            print_parser_errors(&mut output, &parser.errors, &sources)?;
+            continue;
+        }
+
+        // Every pass sees every line so later lines know earlier bindings
+        resolver.resolve_program(&program);
+        let name_errors = resolver.take_errors();
+        let ty = checker.check_program(&program);
+        let type_errors = checker.take_errors();
+        let compiled = compiler.compile_program(&program);
+        if show_type {
+            if type_errors.is_empty() {
+                writeln!(output, "{}", ty)?;
+            } else {
+                print_type_errors(&mut output, &type_errors, &sources)?;
+            }
             continue;
         }
 
-        println!("{}", program);
+        if show_bytecode {
+            match compiled {
+                Ok(()) => write!(output, "{}", disassemble(&compiler.bytecode()))?,
+                Err(error) => print_compile_error(&mut output, &error, &sources)?,
+            }
+            continue;
+        }
+
+        if !name_errors.is_empty() {
+            print_resolve_errors(&mut output, &name_errors, &sources)?;
+        }
+
+        writeln!(output, "{}", view.render(&program))?;
     }
 }
@@ -0,0 +1,528 @@
+//! Name resolution for Monkey programs.
+//!
+//! The resolver walks a `Program` before it runs and links every identifier
+//! use to the binding it refers to. Bindings come from `let` (and `export let`)
+//! statements, `import ... as` aliases and function parameters; lexical scopes
+//! are opened by the program, each `FunctionLiteral` and each `BlockStatement`.
+//! The builtins from `object::BUILTINS` (`puts`, `len`, ...) form a scope
+//! around the global one: they are visible everywhere unless a binding hides
+//! them, and their uses are not linked to any binding.
+//!
+//! Problems found on the way are reported as `ResolveError`s:
+//!
+//! - undefined names, with a suggestion when a similar name is in scope
+//! - names used before the `let` that defines them in the same function
+//! - locals captured by a nested function before their `let` has run
+//! - bindings that shadow another visible binding (a warning)
+//!
+//! A use inside a function body may refer to a binding defined later in an
+//! enclosing scope, since the body only runs once the function is called:
+//!
+//! ```text
+//! let isEven = fn(n) { if (n == 0) { true; } else { isOdd(n - 1); }; };
+//! let isOdd = fn(n) { if (n == 0) { false; } else { isEven(n - 1); }; };
+//! ```
+//!
+//! That only holds for globals. A closure keeps the values of the locals it
+//! captures from when it is created, so a local of an enclosing function
+//! defined after the nested function is an error.
+
+use crate::ast::{
+    Program,
+    expression::{BlockStatement, Expression, FunctionLiteral, Identifier},
+    statement::{LetStatement, Statement},
+};
+use crate::diagnostic::Diagnostic;
+use crate::object::{BUILTINS, lookup_builtin};
+use crate::parser::error::Span;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Index of a binding in `Resolution::bindings`.
+pub type BindingId = usize;
+
+/// What introduced a binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    Let,
+    Parameter,
+    Import,
+}
+
+impl fmt::Display for BindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingKind::Let => write!(f, "let binding"),
+            BindingKind::Parameter => write!(f, "parameter"),
+            BindingKind::Import => write!(f, "import"),
+        }
+    }
+}
+
+/// A place where a name is defined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub kind: BindingKind,
+    /// The span of the defining identifier.
+    pub span: Span,
+}
+
+/// A problem found while resolving names.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    /// A name with no binding in scope.
+    Undefined {
+        span: Span,
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// A name used before the `let` that defines it has run.
+    UseBeforeDefinition {
+        span: Span,
+        name: String,
+        definition: Span,
+    },
+    /// A local of an enclosing function used by a nested function created
+    /// before the `let` that defines it.
+    CapturedBeforeDefinition {
+        span: Span,
+        name: String,
+        definition: Span,
+    },
+    /// A binding that hides another visible binding. Reported as a warning.
+    Shadowed {
+        span: Span,
+        name: String,
+        previous: Span,
+    },
+}
+
+impl ResolveError {
+    /// Returns the span of the offending identifier.
+    pub fn span(&self) -> &Span {
+        match self {
+            ResolveError::Undefined { span, .. }
+            | ResolveError::UseBeforeDefinition { span, .. }
+            | ResolveError::CapturedBeforeDefinition { span, .. }
+            | ResolveError::Shadowed { span, .. } => span,
+        }
+    }
+
+    /// Returns true for problems that do not stop the program from running.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ResolveError::Shadowed { .. })
+    }
+
+    /// Converts the problem into a renderable diagnostic.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let message = self.to_string();
+        match self {
+            ResolveError::Undefined { suggestion, .. } => {
+                let diagnostic = Diagnostic::error(self.span().clone(), message);
+                match suggestion {
+                    Some(name) => diagnostic.with_note(format!("did you mean '{}'?", name)),
+                    None => diagnostic,
+                }
+            }
+            ResolveError::UseBeforeDefinition { definition, .. }
+            | ResolveError::CapturedBeforeDefinition { definition, .. } => {
+                Diagnostic::error(self.span().clone(), message)
+                    .with_label(definition.clone(), "defined later here")
+            }
+            ResolveError::Shadowed { previous, .. } => {
+                Diagnostic::warning(self.span().clone(), message)
+                    .with_label(previous.clone(), "previous binding here")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::Undefined { name, .. } => write!(f, "undefined name '{}'", name),
+            ResolveError::UseBeforeDefinition { name, .. } => {
+                write!(f, "'{}' is used before its definition", name)
+            }
+            ResolveError::CapturedBeforeDefinition { name, .. } => {
+                write!(f, "'{}' is captured before its definition", name)
+            }
+            ResolveError::Shadowed { name, .. } => {
+                write!(f, "'{}' shadows an earlier binding", name)
+            }
+        }
+    }
+}
+
+/// The result of resolving a program: its bindings and the side table of uses.
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    /// Every binding, in the order it was declared.
+    pub bindings: Vec<Binding>,
+    /// Maps the span of each resolved identifier use to its binding.
+    pub uses: HashMap<Span, BindingId>,
+    /// Problems found, in source order.
+    pub errors: Vec<ResolveError>,
+}
+
+impl Resolution {
+    /// Returns the binding a use at `span` refers to.
+    pub fn binding_of(&self, span: &Span) -> Option<&Binding> {
+        self.uses.get(span).map(|id| &self.bindings[*id])
+    }
+
+    /// Returns the spans of every use of a binding, sorted by position.
+    pub fn uses_of(&self, id: BindingId) -> Vec<Span> {
+        let mut spans: Vec<Span> = self
+            .uses
+            .iter()
+            .filter(|(_, binding)| **binding == id)
+            .map(|(span, _)| span.clone())
+            .collect();
+        spans.sort_by_key(|span| (span.file, span.line, span.column));
+        spans
+    }
+
+    /// Returns true if any problem other than a warning was found.
+    pub fn has_errors(&self) -> bool {
+        self.errors.iter().any(|error| !error.is_warning())
+    }
+}
+
+/// Resolves a whole program with a fresh resolver.
+pub fn resolve(program: &Program) -> Resolution {
+    let mut resolver = Resolver::new();
+    resolver.resolve_program(program);
+    resolver.into_resolution()
+}
+
+/// A lexical scope.
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    /// Bindings whose definition has been reached, in order.
+    defined: Vec<BindingId>,
+    /// `let` bindings of this scope whose statement has not been reached yet.
+    pending: Vec<BindingId>,
+    /// True for the parameter scope of a function literal.
+    is_function: bool,
+}
+
+/// Resolves names across one or more programs.
+///
+/// The global scope persists between calls to `resolve_program`, so one
+/// resolver can be fed successive REPL inputs.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    scopes: Vec<Scope>,
+    resolution: Resolution,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    /// Creates a resolver with an empty global scope.
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![Scope::default()],
+            resolution: Resolution::default(),
+        }
+    }
+
+    /// Returns everything resolved so far.
+    pub fn resolution(&self) -> &Resolution {
+        &self.resolution
+    }
+
+    /// Consumes the resolver and returns everything resolved so far.
+    pub fn into_resolution(self) -> Resolution {
+        self.resolution
+    }
+
+    /// Removes and returns the problems found so far.
+    pub fn take_errors(&mut self) -> Vec<ResolveError> {
+        std::mem::take(&mut self.resolution.errors)
+    }
+
+    /// Resolves the statements of a program in the global scope.
+    pub fn resolve_program(&mut self, program: &Program) {
+        self.declare_statements(&program.statements);
+        for stmt in &program.statements {
+            self.resolve_statement(stmt);
+        }
+    }
+
+    // ============ STATEMENTS ============
+
+    /// Declares the `let` bindings of a statement list as pending in the current scope.
+    fn declare_statements(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            let (name, kind) = match stmt {
+                Statement::Let(let_stmt) => (&let_stmt.name, BindingKind::Let),
+                Statement::Export(export) => (&export.statement.name, BindingKind::Let),
+                Statement::Import(import) => (&import.alias, BindingKind::Import),
+                Statement::Return(_) | Statement::Expression(_) => continue,
+            };
+            let id = self.new_binding(name, kind);
+            self.current_scope().pending.push(id);
+        }
+    }
+
+    fn resolve_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let(let_stmt) => self.resolve_let(let_stmt),
+            Statement::Export(export) => self.resolve_let(&export.statement),
+            Statement::Import(import) => self.define_pending(&import.alias),
+            Statement::Return(ret) => {
+                if let Some(value) = &ret.value {
+                    self.resolve_expression(value);
+                }
+            }
+            Statement::Expression(stmt) => self.resolve_expression(&stmt.value),
+        }
+    }
+
+    fn resolve_let(&mut self, let_stmt: &LetStatement) {
+        match &let_stmt.value {
+            // A function may call itself, so its name is visible in its own body
+            Some(value @ Expression::FunctionLiteral(_)) => {
+                self.define_pending(&let_stmt.name);
+                self.resolve_expression(value);
+            }
+            Some(value) => {
+                self.resolve_expression(value);
+                self.define_pending(&let_stmt.name);
+            }
+            None => self.define_pending(&let_stmt.name),
+        }
+    }
+
+    // ============ EXPRESSIONS ============
+
+    fn resolve_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Identifier(ident) => self.resolve_use(ident),
+            Expression::IntegerLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::StringLiteral(_) => {}
+            Expression::PrefixExpression(prefix) => self.resolve_expression(&prefix.right),
+            Expression::InfixExpression(infix) => {
+                self.resolve_expression(&infix.left);
+                self.resolve_expression(&infix.right);
+            }
+            Expression::IfExpression(if_expr) => {
+                self.resolve_expression(&if_expr.condition);
+                self.resolve_expression(&if_expr.consequence);
+                if let Some(alternative) = &if_expr.alternative {
+                    self.resolve_expression(alternative);
+                }
+            }
+            Expression::BlockStatement(block) => self.resolve_block(block),
+            Expression::FunctionLiteral(function) => self.resolve_function(function),
+            Expression::CallExpression(call) => {
+                self.resolve_expression(&call.function);
+                for arg in &call.arguments {
+                    self.resolve_expression(arg);
+                }
+            }
+            // The property names a member of the module, not a binding in scope
+            Expression::MemberExpression(member) => self.resolve_expression(&member.object),
+        }
+    }
+
+    fn resolve_block(&mut self, block: &BlockStatement) {
+        self.scopes.push(Scope::default());
+        self.declare_statements(&block.statements);
+        for stmt in &block.statements {
+            self.resolve_statement(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_function(&mut self, function: &FunctionLiteral) {
+        self.scopes.push(Scope {
+            is_function: true,
+            ..Scope::default()
+        });
+        for param in &function.parameters {
+            let id = self.new_binding(param, BindingKind::Parameter);
+            self.define(id);
+        }
+        self.resolve_block(&function.body);
+        self.scopes.pop();
+    }
+
+    /// Links an identifier use to its binding, or reports why it cannot.
+    fn resolve_use(&mut self, ident: &Identifier) {
+        let span = Span::from_token(&ident.token);
+        let mut crossed_function = false;
+        for (depth, scope) in self.scopes.iter().enumerate().rev() {
+            if let Some(id) = find(&self.resolution.bindings, &scope.defined, &ident.value) {
+                self.resolution.uses.insert(span, id);
+                return;
+            }
+            if let Some(id) = find(&self.resolution.bindings, &scope.pending, &ident.value) {
+                let is_local = self.scopes[..=depth].iter().any(|scope| scope.is_function);
+                if crossed_function && !is_local {
+                    // The function body can only run after the definition
+                    self.resolution.uses.insert(span, id);
+                    return;
+                }
+                let name = ident.value.clone();
+                let definition = self.resolution.bindings[id].span.clone();
+                let error = if crossed_function {
+                    ResolveError::CapturedBeforeDefinition {
+                        span,
+                        name,
+                        definition,
+                    }
+                } else {
+                    ResolveError::UseBeforeDefinition {
+                        span,
+                        name,
+                        definition,
+                    }
+                };
+                self.resolution.errors.push(error);
+                return;
+            }
+            crossed_function |= scope.is_function;
+        }
+        if lookup_builtin(&ident.value).is_some() {
+            return;
+        }
+
+        let suggestion = self.suggest(&ident.value);
+        self.resolution.errors.push(ResolveError::Undefined {
+            span,
+            name: ident.value.clone(),
+            suggestion,
+        });
+    }
+
+    // ============ BINDINGS ============
+
+    fn current_scope(&mut self) -> &mut Scope {
+        self.scopes
+            .last_mut()
+            .expect("the global scope is never popped")
+    }
+
+    fn new_binding(&mut self, name: &Identifier, kind: BindingKind) -> BindingId {
+        self.resolution.bindings.push(Binding {
+            name: name.value.clone(),
+            kind,
+            span: Span::from_token(&name.token),
+        });
+        self.resolution.bindings.len() - 1
+    }
+
+    /// Moves the pending binding declared by `name` into the defined set.
+    fn define_pending(&mut self, name: &Identifier) {
+        let span = Span::from_token(&name.token);
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("the global scope is never popped");
+        let bindings = &self.resolution.bindings;
+        if let Some(position) = scope
+            .pending
+            .iter()
+            .position(|id| bindings[*id].span == span)
+        {
+            let id = scope.pending.remove(position);
+            self.define(id);
+        }
+    }
+
+    /// Makes a binding visible, warning if it hides another visible one.
+    fn define(&mut self, id: BindingId) {
+        let binding = &self.resolution.bindings[id];
+        let previous = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| find(&self.resolution.bindings, &scope.defined, &binding.name));
+        if let Some(previous) = previous {
+            let error = ResolveError::Shadowed {
+                span: binding.span.clone(),
+                name: binding.name.clone(),
+                previous: self.resolution.bindings[previous].span.clone(),
+            };
+            self.resolution.errors.push(error);
+        }
+        self.current_scope().defined.push(id);
+    }
+
+    /// Returns the visible name closest to `name`, if one is close enough to be a typo.
+    ///
+    /// Single-character names get no suggestion: any other one-letter name
+    /// would be one edit away.
+    fn suggest(&self, name: &str) -> Option<String> {
+        let length = name.chars().count();
+        if length < 2 {
+            return None;
+        }
+        let max_distance = (length / 3).max(1);
+        self.scopes
+            .iter()
+            .flat_map(|scope| &scope.defined)
+            .map(|id| self.resolution.bindings[*id].name.as_str())
+            .chain(BUILTINS.iter().map(|builtin| builtin.name))
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance && *distance < length)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate.to_string())
+    }
+}
+
+/// Returns the latest binding in `ids` named `name`.
+fn find(bindings: &[Binding], ids: &[BindingId], name: &str) -> Option<BindingId> {
+    ids.iter()
+        .rev()
+        .copied()
+        .find(|id| bindings[*id].name == name)
+}
+
+/// Edit distance between two strings, counting a swap of adjacent characters as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j] is the distance between the first i chars of a and the first j chars of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("count", "count"), 0);
+        assert_eq!(edit_distance("cuont", "count"), 1);
+        assert_eq!(edit_distance("add", "adder"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,170 @@
+//! Source file bookkeeping for multi-file diagnostics.
+//!
+//! The `SourceMap` owns the text of every loaded input and hands out a
+//! `FileId` for each one. Tokens and spans carry that id, so an error can be
+//! traced back to the file it came from and rendered against the right text.
+
+use crate::parser::error::Span;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Identifies a source file registered in a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// The file of input that never went through a source map, such as
+    /// ad-hoc strings in tests. No `SourceMap` hands it out, so its spans
+    /// are never rendered against another file's text.
+    pub const DETACHED: FileId = FileId(u32::MAX);
+
+    /// Returns the index of the file inside its `SourceMap`.
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// A single loaded source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    path: PathBuf,
+    text: String,
+    /// Byte offset at which each line starts.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(path: PathBuf, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            path,
+            text,
+            line_starts,
+        }
+    }
+
+    /// Returns the path (or display name) the file was registered with.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the full text of the file.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the number of lines in the file.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the text of a 1-indexed line without its line terminator.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.text.len());
+        Some(self.text[start..end].trim_end_matches(['\n', '\r']))
+    }
+}
+
+/// Owns every loaded source and maps `FileId`s back to paths and text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    /// Creates an empty source map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a source and returns its id.
+    /// # Parameters
+    /// - `path`: The file path, or a display name such as `<repl>`
+    /// - `text`: The full source text
+    pub fn add(&mut self, path: impl Into<PathBuf>, text: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        assert_ne!(id, FileId::DETACHED, "too many source files");
+        self.files.push(SourceFile::new(path.into(), text.into()));
+        id
+    }
+
+    /// Returns the file with the given id, if it was registered here.
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.index())
+    }
+
+    /// Returns the id of the most recently added file with the given path.
+    pub fn find(&self, path: &Path) -> Option<FileId> {
+        self.files
+            .iter()
+            .rposition(|file| file.path == path)
+            .map(|i| FileId(i as u32))
+    }
+
+    /// Returns the path of the file with the given id.
+    pub fn path(&self, id: FileId) -> Option<&Path> {
+        self.get(id).map(SourceFile::path)
+    }
+
+    /// Formats a span as `path:line:col`.
+    ///
+    /// Spans whose file is unknown to this map are printed as `<unknown>:line:col`.
+    pub fn location(&self, span: &Span) -> String {
+        let path = self
+            .path(span.file)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "<unknown>".to_string());
+        format!("{}:{}:{}", path, span.line, span.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map_ids_and_lines() {
+        let mut sources = SourceMap::new();
+        let a = sources.add("a.monkey", "let x = 1;\nlet y = 2;\n");
+        let b = sources.add("lib/b.monkey", "b;");
+
+        assert_ne!(a, b);
+        assert_ne!(a, FileId::DETACHED);
+        assert_eq!(sources.get(FileId::DETACHED), None);
+        assert_eq!(sources.path(b), Some(Path::new("lib/b.monkey")));
+        assert_eq!(sources.find(Path::new("a.monkey")), Some(a));
+
+        let file = sources.get(a).unwrap();
+        assert_eq!(file.line(1), Some("let x = 1;"));
+        assert_eq!(file.line(2), Some("let y = 2;"));
+        assert_eq!(file.line(3), Some(""));
+        assert_eq!(file.line(4), None);
+        assert_eq!(file.line(0), None);
+    }
+
+    #[test]
+    fn test_source_map_location() {
+        let mut sources = SourceMap::new();
+        sources.add("main.monkey", "1;");
+        let lib = sources.add("lib/math.monkey", "2;");
+
+        assert_eq!(
+            sources.location(&Span::in_file(lib, 3, 14)),
+            "lib/math.monkey:3:14"
+        );
+    }
+}
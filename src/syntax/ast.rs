@@ -0,0 +1,349 @@
+//! Typed view over the concrete syntax tree.
+//!
+//! Each type wraps a `SyntaxNode` of one kind and offers accessors for its
+//! parts. Accessors return `Option` because the tree may come from invalid
+//! input: a construct that failed to parse is an `Error` node, which no typed
+//! wrapper accepts, so the part it stood for is missing.
+//!
+//! ```
+//! use monkey_lang::syntax::ast::{AstNode, Expr, Stmt};
+//!
+//! let root = monkey_lang::syntax::parse("let x = (1 + 2) * 3;").root();
+//! let Some(Stmt::Let(stmt)) = root.statements().next() else { panic!() };
+//! assert_eq!(stmt.name().unwrap().text(), "x");
+//! let Some(Expr::Infix(mul)) = stmt.value() else { panic!() };
+//! assert_eq!(mul.lhs().unwrap().syntax().to_string(), "(1 + 2)");
+//! ```
+
+use super::{SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::lexer::token::TokenType;
+
+/// A typed wrapper around a `SyntaxNode`.
+pub trait AstNode: Sized {
+    /// Wraps `node` if it has the right kind.
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    /// The underlying syntax node.
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+/// Defines a struct wrapping nodes of one `SyntaxKind`.
+macro_rules! ast_node {
+    ($(#[$meta:meta])* $name:ident, $kind:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct $name(SyntaxNode);
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                (node.kind() == SyntaxKind::$kind).then_some($name(node))
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+ast_node!(
+    /// A whole file.
+    Program,
+    Program
+);
+ast_node!(LetStmt, LetStmt);
+ast_node!(ReturnStmt, ReturnStmt);
+ast_node!(ExprStmt, ExprStmt);
+ast_node!(ImportStmt, ImportStmt);
+ast_node!(ExportStmt, ExportStmt);
+ast_node!(NameRef, NameRef);
+ast_node!(Literal, Literal);
+ast_node!(PrefixExpr, PrefixExpr);
+ast_node!(InfixExpr, InfixExpr);
+ast_node!(ParenExpr, ParenExpr);
+ast_node!(IfExpr, IfExpr);
+ast_node!(BlockExpr, BlockExpr);
+ast_node!(FnExpr, FnExpr);
+ast_node!(ParamList, ParamList);
+ast_node!(Param, Param);
+ast_node!(CallExpr, CallExpr);
+ast_node!(ArgList, ArgList);
+ast_node!(MemberExpr, MemberExpr);
+ast_node!(
+    /// A type annotation; its text is the type as written.
+    Type,
+    Type
+);
+
+/// Any statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    Let(LetStmt),
+    Return(ReturnStmt),
+    Expr(ExprStmt),
+    Import(ImportStmt),
+    Export(ExportStmt),
+}
+
+/// Any expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    NameRef(NameRef),
+    Literal(Literal),
+    Prefix(PrefixExpr),
+    Infix(InfixExpr),
+    Paren(ParenExpr),
+    If(IfExpr),
+    Block(BlockExpr),
+    Fn(FnExpr),
+    Call(CallExpr),
+    Member(MemberExpr),
+}
+
+impl AstNode for Stmt {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        Some(match node.kind() {
+            SyntaxKind::LetStmt => Stmt::Let(LetStmt(node)),
+            SyntaxKind::ReturnStmt => Stmt::Return(ReturnStmt(node)),
+            SyntaxKind::ExprStmt => Stmt::Expr(ExprStmt(node)),
+            SyntaxKind::ImportStmt => Stmt::Import(ImportStmt(node)),
+            SyntaxKind::ExportStmt => Stmt::Export(ExportStmt(node)),
+            _ => return None,
+        })
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Stmt::Let(s) => s.syntax(),
+            Stmt::Return(s) => s.syntax(),
+            Stmt::Expr(s) => s.syntax(),
+            Stmt::Import(s) => s.syntax(),
+            Stmt::Export(s) => s.syntax(),
+        }
+    }
+}
+
+impl AstNode for Expr {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        Some(match node.kind() {
+            SyntaxKind::NameRef => Expr::NameRef(NameRef(node)),
+            SyntaxKind::Literal => Expr::Literal(Literal(node)),
+            SyntaxKind::PrefixExpr => Expr::Prefix(PrefixExpr(node)),
+            SyntaxKind::InfixExpr => Expr::Infix(InfixExpr(node)),
+            SyntaxKind::ParenExpr => Expr::Paren(ParenExpr(node)),
+            SyntaxKind::IfExpr => Expr::If(IfExpr(node)),
+            SyntaxKind::BlockExpr => Expr::Block(BlockExpr(node)),
+            SyntaxKind::FnExpr => Expr::Fn(FnExpr(node)),
+            SyntaxKind::CallExpr => Expr::Call(CallExpr(node)),
+            SyntaxKind::MemberExpr => Expr::Member(MemberExpr(node)),
+            _ => return None,
+        })
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Expr::NameRef(e) => e.syntax(),
+            Expr::Literal(e) => e.syntax(),
+            Expr::Prefix(e) => e.syntax(),
+            Expr::Infix(e) => e.syntax(),
+            Expr::Paren(e) => e.syntax(),
+            Expr::If(e) => e.syntax(),
+            Expr::Block(e) => e.syntax(),
+            Expr::Fn(e) => e.syntax(),
+            Expr::Call(e) => e.syntax(),
+            Expr::Member(e) => e.syntax(),
+        }
+    }
+}
+
+// ============ HELPERS ============
+
+/// The `n`th child node that casts to `N`.
+fn nth_child<N: AstNode>(node: &SyntaxNode, n: usize) -> Option<N> {
+    node.children().filter_map(N::cast).nth(n)
+}
+
+/// The first direct child token of the given type.
+fn token(node: &SyntaxNode, token_type: TokenType) -> Option<SyntaxToken> {
+    node.child_tokens()
+        .find(|t| t.kind() == SyntaxKind::Token(token_type))
+}
+
+/// The first direct child token that is not trivia.
+fn first_token(node: &SyntaxNode) -> Option<SyntaxToken> {
+    node.child_tokens().find(|t| !t.kind().is_trivia())
+}
+
+// ============ STATEMENTS ============
+
+impl Program {
+    pub fn statements(&self) -> impl Iterator<Item = Stmt> + '_ {
+        self.0.children().filter_map(Stmt::cast)
+    }
+}
+
+impl LetStmt {
+    /// The bound name.
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenType::IDENT)
+    }
+
+    pub fn type_annotation(&self) -> Option<Type> {
+        nth_child(&self.0, 0)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+}
+
+impl ReturnStmt {
+    pub fn value(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+}
+
+impl ExprStmt {
+    pub fn expr(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+}
+
+impl ImportStmt {
+    /// The path string, including its quotes.
+    pub fn path(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenType::STRING)
+    }
+
+    pub fn alias(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenType::IDENT)
+    }
+}
+
+impl ExportStmt {
+    pub fn let_stmt(&self) -> Option<LetStmt> {
+        nth_child(&self.0, 0)
+    }
+}
+
+// ============ EXPRESSIONS ============
+
+impl NameRef {
+    pub fn ident(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenType::IDENT)
+    }
+}
+
+impl Literal {
+    /// The literal's token (`INT`, `STRING`, `TRUE` or `FALSE`).
+    pub fn token(&self) -> Option<SyntaxToken> {
+        first_token(&self.0)
+    }
+}
+
+impl PrefixExpr {
+    pub fn op(&self) -> Option<SyntaxToken> {
+        first_token(&self.0)
+    }
+
+    pub fn operand(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+}
+
+impl InfixExpr {
+    pub fn lhs(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+
+    /// The operator token between the operands.
+    pub fn op(&self) -> Option<SyntaxToken> {
+        first_token(&self.0)
+    }
+
+    pub fn rhs(&self) -> Option<Expr> {
+        nth_child(&self.0, 1)
+    }
+}
+
+impl ParenExpr {
+    pub fn expr(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+}
+
+impl IfExpr {
+    pub fn condition(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+
+    pub fn then_branch(&self) -> Option<BlockExpr> {
+        self.0.children().skip(1).filter_map(BlockExpr::cast).next()
+    }
+
+    pub fn else_branch(&self) -> Option<BlockExpr> {
+        self.0.children().skip(1).filter_map(BlockExpr::cast).nth(1)
+    }
+}
+
+impl BlockExpr {
+    pub fn statements(&self) -> impl Iterator<Item = Stmt> + '_ {
+        self.0.children().filter_map(Stmt::cast)
+    }
+}
+
+impl FnExpr {
+    pub fn param_list(&self) -> Option<ParamList> {
+        nth_child(&self.0, 0)
+    }
+
+    pub fn return_type(&self) -> Option<Type> {
+        nth_child(&self.0, 0)
+    }
+
+    pub fn body(&self) -> Option<BlockExpr> {
+        nth_child(&self.0, 0)
+    }
+}
+
+impl ParamList {
+    pub fn params(&self) -> impl Iterator<Item = Param> + '_ {
+        self.0.children().filter_map(Param::cast)
+    }
+}
+
+impl Param {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenType::IDENT)
+    }
+
+    pub fn type_annotation(&self) -> Option<Type> {
+        nth_child(&self.0, 0)
+    }
+}
+
+impl CallExpr {
+    pub fn callee(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+
+    pub fn arg_list(&self) -> Option<ArgList> {
+        nth_child(&self.0, 0)
+    }
+}
+
+impl ArgList {
+    pub fn args(&self) -> impl Iterator<Item = Expr> + '_ {
+        self.0.children().filter_map(Expr::cast)
+    }
+}
+
+impl MemberExpr {
+    pub fn object(&self) -> Option<Expr> {
+        nth_child(&self.0, 0)
+    }
+
+    pub fn property(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenType::IDENT)
+    }
+}
@@ -0,0 +1,59 @@
+//! Builds a green tree while the parser consumes tokens.
+
+use super::{
+    SyntaxKind,
+    green::{GreenElement, GreenNode, GreenToken},
+};
+use std::sync::Arc;
+
+/// Collects tokens as the parser advances and groups them into nodes.
+///
+/// Nodes are created after the fact: the parser remembers a `checkpoint`
+/// where a construct starts and, once it is parsed, `wrap`s everything pushed
+/// since then into one node. This suits a Pratt parser, which only learns that
+/// `a` is the left operand of `a + b` after it has parsed `a`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct SyntaxBuilder {
+    children: Vec<GreenElement>,
+}
+
+impl SyntaxBuilder {
+    pub fn token(&mut self, kind: SyntaxKind, text: &str) {
+        self.children
+            .push(GreenElement::Token(Arc::new(GreenToken::new(kind, text))));
+    }
+
+    /// Pushes whitespace and `//` comments as separate trivia tokens.
+    pub fn trivia(&mut self, mut text: &str) {
+        while !text.is_empty() {
+            let (kind, len) = if text.starts_with("//") {
+                (SyntaxKind::Comment, text.find('\n').unwrap_or(text.len()))
+            } else {
+                (
+                    SyntaxKind::Whitespace,
+                    text.find("//").unwrap_or(text.len()),
+                )
+            };
+            self.token(kind, &text[..len]);
+            text = &text[len..];
+        }
+    }
+
+    /// Marks the current position; pass it to `wrap` once the construct ends.
+    pub fn checkpoint(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Turns everything pushed since `checkpoint` into one `kind` node.
+    pub fn wrap(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        let checkpoint = checkpoint.min(self.children.len());
+        let children = self.children.split_off(checkpoint);
+        self.children
+            .push(GreenElement::Node(Arc::new(GreenNode::new(kind, children))));
+    }
+
+    /// Returns a `kind` node holding everything pushed so far.
+    pub fn finish(&self, kind: SyntaxKind) -> GreenNode {
+        GreenNode::new(kind, self.children.clone())
+    }
+}
@@ -0,0 +1,112 @@
+//! The green tree: immutable, position-free syntax.
+//!
+//! Green nodes only know their kind, their children and their text length, so
+//! identical subtrees can be shared and a tree can be cloned cheaply. Positions
+//! and parent links live in the red tree (`SyntaxNode`) built on top.
+
+use super::SyntaxKind;
+use std::fmt::{Display, Formatter, Result};
+use std::sync::Arc;
+
+/// A leaf of the green tree: a token or trivia with its exact source text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: impl Into<String>) -> Self {
+        GreenToken {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Length of the text in bytes.
+    pub fn text_len(&self) -> usize {
+        self.text.len()
+    }
+}
+
+/// An interior node of the green tree.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    text_len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let text_len = children.iter().map(GreenElement::text_len).sum();
+        GreenNode {
+            kind,
+            text_len,
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// Length of the text covered by the node, in bytes.
+    pub fn text_len(&self) -> usize {
+        self.text_len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+}
+
+/// A child of a green node.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            GreenElement::Node(node) => node.kind(),
+            GreenElement::Token(token) => token.kind(),
+        }
+    }
+
+    pub fn text_len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.text_len(),
+            GreenElement::Token(token) => token.text_len(),
+        }
+    }
+}
+
+impl Display for GreenToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Prints the exact source text the node covers.
+impl Display for GreenNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => write!(f, "{}", node)?,
+                GreenElement::Token(token) => write!(f, "{}", token)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+//! Kinds of CST nodes and tokens.
+
+use crate::lexer::token::TokenType;
+use std::fmt::{Display, Formatter, Result};
+
+/// What a node or token in the concrete syntax tree is.
+///
+/// Tokens reuse the lexer's `TokenType`; trivia (whitespace and comments) and
+/// interior nodes have kinds of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    /// A token produced by the lexer
+    Token(TokenType),
+    /// Spaces, tabs and newlines between tokens
+    Whitespace,
+    /// A `//` comment up to the end of its line
+    Comment,
+
+    /// The root node of a file
+    Program,
+    LetStmt,
+    ReturnStmt,
+    ExprStmt,
+    ImportStmt,
+    ExportStmt,
+    /// An identifier used as an expression
+    NameRef,
+    /// An integer, string or boolean literal
+    Literal,
+    PrefixExpr,
+    InfixExpr,
+    /// A parenthesized expression, which the AST drops
+    ParenExpr,
+    IfExpr,
+    BlockExpr,
+    FnExpr,
+    /// `(a, b: int)` of a function literal
+    ParamList,
+    Param,
+    CallExpr,
+    /// `(x, y)` of a call
+    ArgList,
+    MemberExpr,
+    /// A type annotation such as `int` or `fn(int) -> [bool]`
+    Type,
+    /// A construct the parser could not parse
+    Error,
+}
+
+impl SyntaxKind {
+    /// True for whitespace and comments.
+    pub fn is_trivia(self) -> bool {
+        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment)
+    }
+}
+
+impl Display for SyntaxKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            SyntaxKind::Token(token_type) => write!(f, "{:?}", token_type),
+            kind => write!(f, "{:?}", kind),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Lossless concrete syntax tree (CST).
+//!
+//! The AST drops whitespace, comments and parentheses; the CST keeps every
+//! byte of the input, so `tree.to_string() == source` holds even when the
+//! source has errors. The parser builds both at once (see
+//! `Parser::syntax_tree`).
+//!
+//! The tree has two layers:
+//!
+//! - the **green** tree (`GreenNode`, `GreenToken`) is immutable and stores
+//!   only kinds, text and lengths, so it can be shared and cloned cheaply
+//! - the **red** tree (`SyntaxNode`, `SyntaxToken`) is a cursor over it that
+//!   adds byte offsets and parent links
+//!
+//! `ast` adds typed wrappers (`LetStmt`, `CallExpr`, ...) on top of the red
+//! tree. Whitespace and comments are `Whitespace` and `Comment` tokens attached
+//! before the token they precede, as siblings of the nodes around them.
+//!
+//! `Parse::reparse` applies a `TextEdit` by reparsing only the statements it
+//! touches (see `reparse`).
+
+pub mod ast;
+mod builder;
+mod green;
+mod kind;
+mod red;
+mod reparse;
+
+pub(crate) use builder::SyntaxBuilder;
+pub use green::{GreenElement, GreenNode, GreenToken};
+pub use kind::SyntaxKind;
+pub use red::{SyntaxElement, SyntaxNode, SyntaxToken};
+pub use reparse::TextEdit;
+
+use crate::ast::Program;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, error::ParserError};
+use crate::source::FileId;
+use ast::AstNode;
+
+/// The result of parsing a source text: both trees and the errors.
+#[derive(Debug, Clone)]
+pub struct Parse {
+    syntax: SyntaxNode,
+    program: Program,
+    errors: Vec<ParserError>,
+    file: FileId,
+}
+
+impl Parse {
+    /// The root of the lossless syntax tree.
+    pub fn syntax(&self) -> &SyntaxNode {
+        &self.syntax
+    }
+
+    /// The typed view of the root.
+    pub fn root(&self) -> ast::Program {
+        ast::Program::cast(self.syntax.clone()).expect("the root is a Program node")
+    }
+
+    /// The AST of the same source.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn errors(&self) -> &[ParserError] {
+        &self.errors
+    }
+
+    /// The file the source was parsed as.
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+}
+
+/// Parses `source` (as the default file) into a syntax tree and an AST.
+pub fn parse(source: &str) -> Parse {
+    parse_file(source, FileId::DETACHED)
+}
+
+/// Parses `source` as `file`; tokens and errors carry that file id.
+pub fn parse_file(source: &str, file: FileId) -> Parse {
+    let mut parser = Parser::new(Lexer::new(source.to_string(), file));
+    let program = parser.parse_program();
+    Parse {
+        syntax: parser.syntax_tree(),
+        program,
+        errors: parser.errors,
+        file,
+    }
+}
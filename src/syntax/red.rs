@@ -0,0 +1,249 @@
+//! The red tree: a cursor over a green tree with positions and parents.
+//!
+//! Red nodes are created on the fly while walking down from the root; each
+//! knows its byte offset in the file and the node it was reached from.
+
+use super::{
+    SyntaxKind,
+    green::{GreenElement, GreenNode, GreenToken},
+};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// A node of the concrete syntax tree.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    offset: usize,
+}
+
+/// A token (or trivia) of the concrete syntax tree.
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    parent: SyntaxNode,
+    offset: usize,
+}
+
+/// Either a node or a token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    /// Makes `green` the root of a new tree, starting at offset 0.
+    pub fn new_root(green: GreenNode) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green: Arc::new(green),
+            parent: None,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind()
+    }
+
+    pub fn green(&self) -> &GreenNode {
+        &self.0.green
+    }
+
+    /// Byte range of the node's text in the file.
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.text_len()
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    /// The node's parent, its parent's parent, and so on up to the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> {
+        std::iter::successors(self.parent(), SyntaxNode::parent)
+    }
+
+    /// Child nodes and tokens in source order.
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0.green.children().iter().map(move |child| {
+            let start = offset;
+            offset += child.text_len();
+            match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    parent: Some(self.clone()),
+                    offset: start,
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: green.clone(),
+                    parent: self.clone(),
+                    offset: start,
+                }),
+            }
+        })
+    }
+
+    /// Child nodes in source order.
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// Direct child tokens, including trivia, in source order.
+    pub fn child_tokens(&self) -> impl Iterator<Item = SyntaxToken> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+    }
+
+    /// The node and every node below it, in preorder.
+    pub fn descendants(&self) -> impl Iterator<Item = SyntaxNode> {
+        let mut stack = vec![self.clone()];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            let children: Vec<SyntaxNode> = node.children().collect();
+            stack.extend(children.into_iter().rev());
+            Some(node)
+        })
+    }
+
+    /// Every token below the node, including trivia, in source order.
+    pub fn descendant_tokens(&self) -> impl Iterator<Item = SyntaxToken> {
+        let mut stack = vec![SyntaxElement::Node(self.clone())];
+        std::iter::from_fn(move || {
+            loop {
+                match stack.pop()? {
+                    SyntaxElement::Token(token) => return Some(token),
+                    SyntaxElement::Node(node) => {
+                        let children: Vec<SyntaxElement> = node.children_with_tokens().collect();
+                        stack.extend(children.into_iter().rev());
+                    }
+                }
+            }
+        })
+    }
+
+    /// The token whose text contains `offset`, or the token starting there
+    /// when `offset` falls between two tokens.
+    pub fn token_at_offset(&self, offset: usize) -> Option<SyntaxToken> {
+        self.descendant_tokens()
+            .find(|token| token.text_range().contains(&offset))
+    }
+
+    /// Renders the tree one element per line, e.g. `LetStmt@0..10` and
+    /// `IDENT@4..5 "x"`, indented two spaces per level.
+    pub fn debug_tree(&self) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, 0);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        out.push_str(&format!("{}{:?}\n", "  ".repeat(depth), self));
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => node.write_tree(out, depth + 1),
+                SyntaxElement::Token(token) => {
+                    out.push_str(&format!("{}{:?}\n", "  ".repeat(depth + 1), token))
+                }
+            }
+        }
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind()
+    }
+
+    /// The exact source text of the token.
+    pub fn text(&self) -> &str {
+        self.green.text()
+    }
+
+    /// Byte range of the token in the file.
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text_len()
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+}
+
+impl SyntaxElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            SyntaxElement::Node(node) => node.kind(),
+            SyntaxElement::Token(token) => token.kind(),
+        }
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        match self {
+            SyntaxElement::Node(node) => node.text_range(),
+            SyntaxElement::Token(token) => token.text_range(),
+        }
+    }
+}
+
+/// Two nodes are equal if they are the same green node at the same offset.
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+impl PartialEq for SyntaxToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.green, &other.green) && self.offset == other.offset
+    }
+}
+
+impl Eq for SyntaxToken {}
+
+/// Prints the exact source text the node covers.
+impl Display for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+impl Display for SyntaxToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+impl Debug for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let range = self.text_range();
+        write!(f, "{}@{}..{}", self.kind(), range.start, range.end)
+    }
+}
+
+impl Debug for SyntaxToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let range = self.text_range();
+        write!(
+            f,
+            "{}@{}..{} {:?}",
+            self.kind(),
+            range.start,
+            range.end,
+            self.text()
+        )
+    }
+}
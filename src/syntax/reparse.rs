@@ -0,0 +1,500 @@
+//! Incremental reparsing for editors.
+//!
+//! An edit usually touches one statement. Instead of parsing the whole file
+//! again, `Parse::reparse` picks the innermost block (or the file itself) whose
+//! braces enclose the edit, widens the edited range to whole statements of that
+//! block, and parses just that text on its own. The new statements replace the
+//! old ones; every other green subtree is shared with the previous tree, and
+//! AST tokens and errors after the edit are moved to their new lines and
+//! columns.
+//!
+//! This gives the same result as a full parse because the parser starts each
+//! statement from scratch: once the statement before the region has ended at
+//! its `;`, nothing before it affects what follows. Statements of a block are
+//! parsed with the block's rules, so a half-typed statement such as `let x = ;`
+//! reports the same errors it would in a full parse. The region is only reused
+//! when that holds, i.e. when none of its errors is at its end (where the
+//! parser wanted the text after it), it does not reach a `}` that would close
+//! the block, and it ends on a statement boundary: its last token is a `;`
+//! and it does not end inside a comment. Otherwise the next enclosing block
+//! is tried, and as a last resort the whole file is parsed again.
+
+use super::{GreenElement, GreenNode, Parse, SyntaxElement, SyntaxKind, SyntaxNode, parse_file};
+use crate::ast::{
+    expression::{
+        BlockStatement, BooleanLiteral, CallExpression, FunctionLiteral, Identifier, IfExpression,
+        InfixExpression, IntegerLiteral, MemberExpression, PrefixExpression, StringLiteral,
+    },
+    statement::{
+        ExportStatement, ExpressionStatement, ImportStatement, LetStatement, ReturnStatement,
+        Statement,
+    },
+    types::TypeExpr,
+    visit_mut::{self, VisitorMut},
+};
+use crate::lexer::{
+    Lexer,
+    token::{Token, TokenType},
+};
+use crate::parser::{Parser, error::ParserError};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// A line and a column, both starting at 1, as the lexer counts them.
+type Position = (usize, usize);
+
+/// Replaces the text in `range` (byte offsets) with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, replacement: impl Into<String>) -> Self {
+        TextEdit {
+            range,
+            replacement: replacement.into(),
+        }
+    }
+
+    /// Returns `text` with the edit applied.
+    ///
+    /// # Panics
+    /// If the range is out of bounds or does not lie on char boundaries.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        text.replace_range(self.range.clone(), &self.replacement);
+        text
+    }
+}
+
+impl Parse {
+    /// Applies `edit` to the parsed source and returns the parse of the
+    /// result, reparsing only the statements the edit touches when possible.
+    ///
+    /// # Parameters
+    /// - `edit`: The change, in byte offsets of the current source
+    ///
+    /// # Returns
+    /// The same `Parse` that `parse_file` would return for the edited source.
+    ///
+    /// # Panics
+    /// If the edit's range is out of bounds or not on char boundaries.
+    pub fn reparse(&self, edit: &TextEdit) -> Parse {
+        let old_text = self.syntax.to_string();
+        let new_text = edit.apply(&old_text);
+        for container in containers(&self.syntax, &edit.range) {
+            if let Some(parse) = self.reparse_in(&container, edit, &old_text, &new_text) {
+                log::debug!("reparsed {:?} incrementally", container);
+                return parse;
+            }
+        }
+        log::debug!("reparsing the whole file");
+        parse_file(&new_text, self.file)
+    }
+
+    /// Reparses the statements of `container` (the root or a `BlockExpr`)
+    /// that the edit touches, or returns `None` if that would not match a full
+    /// parse.
+    fn reparse_in(
+        &self,
+        container: &SyntaxNode,
+        edit: &TextEdit,
+        old_text: &str,
+        new_text: &str,
+    ) -> Option<Parse> {
+        let in_block = container.kind() == SyntaxKind::BlockExpr;
+        let elems: Vec<SyntaxElement> = container.children_with_tokens().collect();
+        // Children between the braces of a block, or all of the root's
+        let (first, last, inner) = if in_block {
+            let last = elems.len() - 1;
+            let inner = elems[0].text_range().end..elems[last].text_range().start;
+            (1, last, inner)
+        } else {
+            (0, elems.len(), 0..old_text.len())
+        };
+
+        // The children the edit touches, including ones it merely borders
+        let Range { start: s, end: e } = edit.range;
+        let mut lo = (first..last)
+            .find(|&i| elems[i].text_range().end >= s)
+            .unwrap_or(last);
+        let mut hi = (first..last)
+            .rev()
+            .find(|&i| elems[i].text_range().start <= e)
+            .map_or(first, |i| i + 1)
+            .max(lo);
+        // Start after a complete statement and stop before the next one. A
+        // failed statement may have reported an error at the token after it,
+        // so the statement holding that token is reparsed too.
+        while lo > first && !is_complete_statement(&elems[lo - 1]) {
+            lo -= 1;
+        }
+        while hi < last
+            && (!matches!(elems[hi], SyntaxElement::Node(_))
+                || elems[lo..hi]
+                    .iter()
+                    .rfind(|elem| matches!(elem, SyntaxElement::Node(_)))
+                    .is_some_and(|elem| !is_complete_statement(elem)))
+        {
+            hi += 1;
+        }
+        let start = if lo == first {
+            inner.start
+        } else {
+            elems[lo - 1].text_range().end
+        };
+        let end = if hi == last {
+            inner.end
+        } else {
+            elems[hi].text_range().start
+        };
+        let new_end = end - (e - s) + edit.replacement.len();
+        let region = &new_text[start..new_end];
+
+        let mut parser = Parser::new(Lexer::new(region.to_string(), self.file));
+        let mut statements = if in_block {
+            parser.parse_block_contents()?
+        } else {
+            parser.parse_program().statements
+        };
+        let region_syntax = parser.syntax_tree();
+        // Unless the region runs to the end of the file, the parser must not
+        // have needed the text after it: no error may sit at the region's
+        // end, and its last token must be a `;` rather than, say, a string
+        // that would have run on
+        let to_eof = end == old_text.len();
+        let region_size = advance((1, 1), region);
+        if !to_eof
+            && (parser
+                .errors
+                .iter()
+                .any(|error| (error.span.line, error.span.column) >= region_size)
+                || region_syntax
+                    .descendant_tokens()
+                    .filter(|token| !token.kind().is_trivia())
+                    .last()
+                    .is_some_and(|token| token.kind() != SyntaxKind::Token(TokenType::SEMICOLON)))
+        {
+            return None;
+        }
+        // A trailing comment would swallow the text after the region
+        if !to_eof
+            && region_syntax
+                .descendant_tokens()
+                .last()
+                .is_some_and(|token| token.kind() == SyntaxKind::Comment)
+        {
+            return None;
+        }
+
+        // Where the region starts and ends, before and after the edit
+        let region_start = position(old_text, start);
+        let old_end = advance(region_start, &old_text[start..end]);
+        let region_end = advance(region_start, region);
+        let moved = |(line, column): Position| -> Position {
+            if line == old_end.0 {
+                (region_end.0, column - old_end.1 + region_end.1)
+            } else {
+                (line - old_end.0 + region_end.0, column)
+            }
+        };
+
+        // The AST: shift what follows the region, then swap its statements
+        let mut program = self.program.clone();
+        EachToken(|token: &mut Token| {
+            if (token.line, token.column) >= old_end {
+                (token.line, token.column) = moved((token.line, token.column));
+            }
+        })
+        .visit_program_mut(&mut program);
+        let placed = |(line, column): Position| -> Position {
+            if line == 1 {
+                (region_start.0, column + region_start.1 - 1)
+            } else {
+                (line + region_start.0 - 1, column)
+            }
+        };
+        for stmt in &mut statements {
+            EachToken(|token: &mut Token| {
+                (token.line, token.column) = placed((token.line, token.column));
+            })
+            .visit_statement_mut(stmt);
+        }
+        let skip = elems[first..lo]
+            .iter()
+            .filter(|elem| is_ast_statement(elem, in_block))
+            .count();
+        let removed = skip
+            + elems[lo..hi]
+                .iter()
+                .filter(|elem| is_ast_statement(elem, in_block))
+                .count();
+        if in_block {
+            let (line, column) = position(old_text, elems[0].text_range().start);
+            let mut splice = SpliceBlock {
+                line,
+                column,
+                range: skip..removed,
+                statements: Some(statements),
+            };
+            splice.visit_program_mut(&mut program);
+            // The block is missing from the AST when an enclosing construct failed
+            splice.statements.is_none().then_some(())?;
+        } else {
+            if removed > program.statements.len() {
+                return None;
+            }
+            program.statements.splice(skip..removed, statements);
+        }
+
+        // The errors: the region's own replace the old ones inside it. Errors
+        // at EOF sit past the region's end, but belong to it when it runs to
+        // the end of the file
+        let at = |error: &ParserError| (error.span.line, error.span.column);
+        let mut errors: Vec<ParserError> = self
+            .errors
+            .iter()
+            .filter(|error| at(error) < region_start)
+            .cloned()
+            .collect();
+        errors.extend(parser.errors.iter().map(|error| {
+            let mut error = error.clone();
+            (error.span.line, error.span.column) = placed(at(&error));
+            error
+        }));
+        errors.extend(
+            self.errors
+                .iter()
+                .filter(|error| at(error) >= old_end && !to_eof)
+                .map(|error| {
+                    let mut error = error.clone();
+                    (error.span.line, error.span.column) = moved(at(&error));
+                    error
+                }),
+        );
+
+        // The syntax tree: new children for the region, the rest shared
+        let children = container.green().children();
+        let mut new_children = children[..lo].to_vec();
+        new_children.extend(region_syntax.green().children().iter().cloned());
+        new_children.extend_from_slice(&children[hi..]);
+        let green = replace(container, GreenNode::new(container.kind(), new_children));
+
+        Some(Parse {
+            syntax: SyntaxNode::new_root(green),
+            program,
+            errors,
+            file: self.file,
+        })
+    }
+}
+
+// ============ HELPERS ============
+
+/// The nodes that may be reparsed for an edit of `range`: blocks whose braces
+/// enclose it, innermost first, then the root.
+fn containers(root: &SyntaxNode, range: &Range<usize>) -> Vec<SyntaxNode> {
+    let mut found = vec![root.clone()];
+    let mut node = root.clone();
+    loop {
+        let Some(child) = node.children().find(|child| {
+            let text = child.text_range();
+            text.start <= range.start && range.end <= text.end
+        }) else {
+            break;
+        };
+        if child.kind() == SyntaxKind::BlockExpr && encloses(&child, range) {
+            found.push(child.clone());
+        }
+        node = child;
+    }
+    found.reverse();
+    found
+}
+
+/// Whether `block` starts with `{`, ends with `}` and `range` lies between them.
+fn encloses(block: &SyntaxNode, range: &Range<usize>) -> bool {
+    let first = block.children_with_tokens().next();
+    let last = block.children_with_tokens().last();
+    match (first, last) {
+        (Some(SyntaxElement::Token(lbrace)), Some(SyntaxElement::Token(rbrace))) => {
+            lbrace.kind() == SyntaxKind::Token(TokenType::LBRACE)
+                && rbrace.kind() == SyntaxKind::Token(TokenType::RBRACE)
+                && lbrace.text_range().end <= range.start
+                && range.end <= rbrace.text_range().start
+        }
+        _ => false,
+    }
+}
+
+/// Whether `elem` is a statement that parsed and ends with its `;`.
+fn is_complete_statement(elem: &SyntaxElement) -> bool {
+    match elem {
+        SyntaxElement::Node(node) => {
+            node.kind() != SyntaxKind::Error
+                && node
+                    .descendant_tokens()
+                    .last()
+                    .is_some_and(|token| token.kind() == SyntaxKind::Token(TokenType::SEMICOLON))
+        }
+        SyntaxElement::Token(_) => false,
+    }
+}
+
+/// Whether `elem` has a statement in the AST. Blocks reject imports and
+/// exports, so those only count at the top level.
+fn is_ast_statement(elem: &SyntaxElement, in_block: bool) -> bool {
+    match elem.kind() {
+        SyntaxKind::LetStmt | SyntaxKind::ReturnStmt | SyntaxKind::ExprStmt => true,
+        SyntaxKind::ImportStmt | SyntaxKind::ExportStmt => !in_block,
+        _ => false,
+    }
+}
+
+/// Rebuilds the green nodes from `node` up to the root with `green` in place
+/// of `node`, sharing every other child.
+fn replace(node: &SyntaxNode, green: GreenNode) -> GreenNode {
+    let Some(parent) = node.parent() else {
+        return green;
+    };
+    let index = parent
+        .children_with_tokens()
+        .position(|child| child == SyntaxElement::Node(node.clone()))
+        .expect("a node is among its parent's children");
+    let mut children = parent.green().children().to_vec();
+    children[index] = GreenElement::Node(Arc::new(green));
+    replace(&parent, GreenNode::new(parent.kind(), children))
+}
+
+/// The line and column of the byte at `offset` in `text`.
+fn position(text: &str, offset: usize) -> Position {
+    advance((1, 1), &text[..offset])
+}
+
+/// The position just past `text` when it starts at `from`.
+fn advance(from: Position, text: &str) -> Position {
+    match text.rfind('\n') {
+        Some(newline) => (
+            from.0 + text.matches('\n').count(),
+            text[newline + 1..].chars().count() + 1,
+        ),
+        None => (from.0, from.1 + text.chars().count()),
+    }
+}
+
+/// Calls the closure on every token in the AST.
+struct EachToken<F: FnMut(&mut Token)>(F);
+
+impl<F: FnMut(&mut Token)> VisitorMut for EachToken<F> {
+    fn visit_let_statement_mut(&mut self, stmt: &mut LetStatement) {
+        (self.0)(&mut stmt.token);
+        visit_mut::walk_let_statement_mut(self, stmt);
+    }
+
+    fn visit_return_statement_mut(&mut self, stmt: &mut ReturnStatement) {
+        (self.0)(&mut stmt.token);
+        visit_mut::walk_return_statement_mut(self, stmt);
+    }
+
+    fn visit_expression_statement_mut(&mut self, stmt: &mut ExpressionStatement) {
+        (self.0)(&mut stmt.token);
+        visit_mut::walk_expression_statement_mut(self, stmt);
+    }
+
+    fn visit_import_statement_mut(&mut self, stmt: &mut ImportStatement) {
+        (self.0)(&mut stmt.token);
+        visit_mut::walk_import_statement_mut(self, stmt);
+    }
+
+    fn visit_export_statement_mut(&mut self, stmt: &mut ExportStatement) {
+        (self.0)(&mut stmt.token);
+        visit_mut::walk_export_statement_mut(self, stmt);
+    }
+
+    fn visit_block_statement_mut(&mut self, block: &mut BlockStatement) {
+        (self.0)(&mut block.token);
+        visit_mut::walk_block_statement_mut(self, block);
+    }
+
+    fn visit_identifier_mut(&mut self, ident: &mut Identifier) {
+        (self.0)(&mut ident.token);
+    }
+
+    fn visit_integer_literal_mut(&mut self, lit: &mut IntegerLiteral) {
+        (self.0)(&mut lit.token);
+    }
+
+    fn visit_boolean_literal_mut(&mut self, lit: &mut BooleanLiteral) {
+        (self.0)(&mut lit.token);
+    }
+
+    fn visit_string_literal_mut(&mut self, lit: &mut StringLiteral) {
+        (self.0)(&mut lit.token);
+    }
+
+    fn visit_prefix_expression_mut(&mut self, expr: &mut PrefixExpression) {
+        (self.0)(&mut expr.token);
+        visit_mut::walk_prefix_expression_mut(self, expr);
+    }
+
+    fn visit_infix_expression_mut(&mut self, expr: &mut InfixExpression) {
+        (self.0)(&mut expr.token);
+        visit_mut::walk_infix_expression_mut(self, expr);
+    }
+
+    fn visit_if_expression_mut(&mut self, expr: &mut IfExpression) {
+        (self.0)(&mut expr.token);
+        visit_mut::walk_if_expression_mut(self, expr);
+    }
+
+    fn visit_function_literal_mut(&mut self, function: &mut FunctionLiteral) {
+        (self.0)(&mut function.token);
+        visit_mut::walk_function_literal_mut(self, function);
+    }
+
+    fn visit_call_expression_mut(&mut self, call: &mut CallExpression) {
+        (self.0)(&mut call.token);
+        visit_mut::walk_call_expression_mut(self, call);
+    }
+
+    fn visit_member_expression_mut(&mut self, member: &mut MemberExpression) {
+        (self.0)(&mut member.token);
+        visit_mut::walk_member_expression_mut(self, member);
+        self.visit_identifier_mut(&mut member.property);
+    }
+
+    fn visit_type_expr_mut(&mut self, ty: &mut TypeExpr) {
+        (self.0)(match ty {
+            TypeExpr::Named(t) => &mut t.token,
+            TypeExpr::Array(t) => &mut t.token,
+            TypeExpr::Hash(t) => &mut t.token,
+            TypeExpr::Function(t) => &mut t.token,
+            TypeExpr::Optional(t) => &mut t.token,
+        });
+        visit_mut::walk_type_expr_mut(self, ty);
+    }
+}
+
+/// Replaces `range` of the statements of the block whose `{` is at `line`
+/// and `column`. `statements` is taken once the block is found.
+struct SpliceBlock {
+    line: usize,
+    column: usize,
+    range: Range<usize>,
+    statements: Option<Vec<Statement>>,
+}
+
+impl VisitorMut for SpliceBlock {
+    fn visit_block_statement_mut(&mut self, block: &mut BlockStatement) {
+        if (block.token.line, block.token.column) != (self.line, self.column) {
+            return visit_mut::walk_block_statement_mut(self, block);
+        }
+        if self.range.end <= block.statements.len()
+            && let Some(statements) = self.statements.take()
+        {
+            block.statements.splice(self.range.clone(), statements);
+        }
+    }
+}
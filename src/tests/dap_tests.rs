@@ -0,0 +1,415 @@
+use crate::dap;
+use crate::lsp::rpc;
+use serde_json::{Value, json};
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+const PROGRAM: &str = "let scale = 10;
+let add = fn(a, b) {
+  let scale = 2;
+  puts(a + b);
+  (a + b) * scale;
+};
+let x = add(1, 2);
+
+let y = add(x, 3);
+y;
+";
+
+/// Writes `text` to a fresh file in the temp directory and returns its path.
+fn temp_file(name: &str, text: &str) -> String {
+    let dir: PathBuf = std::env::temp_dir().join(format!("monkey-dap-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, text).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+/// Replays `requests` (command and arguments) as a client session, numbering
+/// them from 1, and returns every message the adapter sent.
+fn session(requests: &[(&str, Value)]) -> Vec<Value> {
+    let mut input = Vec::new();
+    for (seq, (command, arguments)) in requests.iter().enumerate() {
+        let request = json!({
+            "seq": seq + 1,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        });
+        rpc::write_message(&mut input, &request).unwrap();
+    }
+    let mut output = Vec::new();
+    dap::run(Cursor::new(input), &mut output).unwrap();
+    let mut output = Cursor::new(output);
+    let mut messages = Vec::new();
+    while let Some(body) = rpc::read_message(&mut output).unwrap() {
+        messages.push(serde_json::from_str(&body).unwrap());
+    }
+    messages
+}
+
+/// The requests that launch `path` and start it with breakpoints at `lines`.
+fn launch(path: &str, lines: &[u64], stop_on_entry: bool) -> Vec<(&'static str, Value)> {
+    let breakpoints: Vec<Value> = lines.iter().map(|line| json!({ "line": line })).collect();
+    vec![
+        ("initialize", json!({ "adapterID": "monkey" })),
+        (
+            "launch",
+            json!({ "program": path, "stopOnEntry": stop_on_entry }),
+        ),
+        (
+            "setBreakpoints",
+            json!({ "source": { "path": path }, "breakpoints": breakpoints }),
+        ),
+        ("configurationDone", json!({})),
+    ]
+}
+
+fn response(seq: u64, request_seq: u64, command: &str, body: Option<Value>) -> Value {
+    let mut response = json!({
+        "seq": seq,
+        "type": "response",
+        "request_seq": request_seq,
+        "command": command,
+        "success": true,
+    });
+    if let Some(body) = body {
+        response["body"] = body;
+    }
+    response
+}
+
+fn event(seq: u64, event: &str, body: Value) -> Value {
+    json!({ "seq": seq, "type": "event", "event": event, "body": body })
+}
+
+fn stopped(seq: u64, reason: &str) -> Value {
+    event(
+        seq,
+        "stopped",
+        json!({ "reason": reason, "threadId": 1, "allThreadsStopped": true }),
+    )
+}
+
+fn frame(id: u64, name: &str, path: &str, line: u64, column: u64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "line": line,
+        "column": column,
+        "source": { "name": "debuggee.monkey", "path": path },
+    })
+}
+
+fn variable(name: &str, value: &str, type_name: &str) -> Value {
+    json!({ "name": name, "value": value, "type": type_name, "variablesReference": 0 })
+}
+
+/// The events with their reason or category, and the lines of every
+/// successful `stackTrace`'s top frame.
+fn summary(messages: &[Value]) -> Vec<String> {
+    messages
+        .iter()
+        .filter_map(|message| match (&message["event"], &message["command"]) {
+            (Value::String(event), _) => {
+                let detail = message["body"]["reason"]
+                    .as_str()
+                    .or(message["body"]["category"].as_str())
+                    .unwrap_or_default();
+                Some(format!("{} {}", event, detail).trim_end().to_string())
+            }
+            (_, Value::String(command)) if command == "stackTrace" => {
+                let top = &message["body"]["stackFrames"][0];
+                Some(format!(
+                    "at {}:{} in {}",
+                    top["line"],
+                    top["column"],
+                    top["name"].as_str().unwrap_or_default()
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_recorded_breakpoint_session() {
+    let path = temp_file("debuggee.monkey", PROGRAM);
+    let mut requests = launch(&path, &[4, 8], false);
+    requests.extend([
+        ("threads", json!({})),
+        ("stackTrace", json!({ "threadId": 1 })),
+        ("scopes", json!({ "frameId": 0 })),
+        ("variables", json!({ "variablesReference": 1 })),
+        ("variables", json!({ "variablesReference": 2 })),
+        ("evaluate", json!({ "expression": "scale", "frameId": 1 })),
+        ("continue", json!({ "threadId": 1 })),
+        ("continue", json!({ "threadId": 1 })),
+        ("continue", json!({ "threadId": 1 })),
+        ("disconnect", json!({})),
+    ]);
+    let messages = session(&requests);
+    let expected = vec![
+        response(
+            1,
+            1,
+            "initialize",
+            Some(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsTerminateRequest": true,
+                "exceptionBreakpointFilters": [
+                    { "filter": "runtime", "label": "Runtime errors", "default": true },
+                ],
+            })),
+        ),
+        response(2, 2, "launch", None),
+        event(3, "initialized", json!({})),
+        // Line 8 is blank, so its breakpoint moves to the next statement
+        response(
+            4,
+            3,
+            "setBreakpoints",
+            Some(json!({ "breakpoints": [
+                { "verified": true, "line": 4 },
+                { "verified": true, "line": 9 },
+            ]})),
+        ),
+        response(5, 4, "configurationDone", None),
+        stopped(6, "breakpoint"),
+        response(
+            7,
+            5,
+            "threads",
+            Some(json!({ "threads": [{ "id": 1, "name": "main" }] })),
+        ),
+        response(
+            8,
+            6,
+            "stackTrace",
+            Some(json!({
+                "totalFrames": 2,
+                "stackFrames": [
+                    frame(0, "add", &path, 4, 3),
+                    frame(1, "<main>", &path, 7, 12),
+                ],
+            })),
+        ),
+        response(
+            9,
+            7,
+            "scopes",
+            Some(json!({ "scopes": [
+                { "name": "Locals", "variablesReference": 1, "expensive": false },
+                { "name": "Globals", "variablesReference": 2, "expensive": false },
+            ]})),
+        ),
+        response(
+            10,
+            8,
+            "variables",
+            Some(json!({ "variables": [
+                variable("a", "1", "INTEGER"),
+                variable("b", "2", "INTEGER"),
+                variable("scale", "2", "INTEGER"),
+            ]})),
+        ),
+        response(
+            11,
+            9,
+            "variables",
+            Some(json!({ "variables": [
+                variable("add", "<fn add>", "FUNCTION"),
+                variable("scale", "10", "INTEGER"),
+            ]})),
+        ),
+        response(
+            12,
+            10,
+            "evaluate",
+            Some(json!({ "result": "10", "type": "INTEGER", "variablesReference": 0 })),
+        ),
+        response(
+            13,
+            11,
+            "continue",
+            Some(json!({ "allThreadsContinued": true })),
+        ),
+        event(
+            14,
+            "output",
+            json!({ "category": "stdout", "output": "3\n" }),
+        ),
+        stopped(15, "breakpoint"),
+        response(
+            16,
+            12,
+            "continue",
+            Some(json!({ "allThreadsContinued": true })),
+        ),
+        stopped(17, "breakpoint"),
+        response(
+            18,
+            13,
+            "continue",
+            Some(json!({ "allThreadsContinued": true })),
+        ),
+        event(
+            19,
+            "output",
+            json!({ "category": "stdout", "output": "9\n" }),
+        ),
+        event(20, "exited", json!({ "exitCode": 0 })),
+        event(21, "terminated", json!({})),
+        response(22, 14, "disconnect", None),
+    ];
+    assert_eq!(messages, expected);
+}
+
+#[test]
+fn test_stepping() {
+    let path = temp_file("stepping.monkey", PROGRAM);
+    let mut requests = launch(&path, &[], true);
+    for command in ["next", "next", "stepIn", "next", "stepOut", "next"] {
+        requests.push((command, json!({ "threadId": 1 })));
+        requests.push(("stackTrace", json!({ "threadId": 1 })));
+    }
+    requests.push(("continue", json!({ "threadId": 1 })));
+    assert_eq!(
+        summary(&session(&requests)),
+        [
+            "initialized",
+            "stopped entry",
+            "stopped step",
+            "at 2:1 in <main>",
+            "stopped step",
+            "at 7:1 in <main>",
+            "stopped step",
+            "at 3:3 in add",
+            "stopped step",
+            "at 4:3 in add",
+            "output stdout",
+            "stopped step",
+            "at 9:1 in <main>",
+            "output stdout",
+            "stopped step",
+            "at 10:1 in <main>",
+            "exited",
+            "terminated",
+        ]
+    );
+}
+
+#[test]
+fn test_stop_on_runtime_error() {
+    let source = "let div = fn(n) {\n  n / 0;\n};\ndiv(7);\n";
+    let path = temp_file("failing.monkey", source);
+    let mut requests = launch(&path, &[], false);
+    requests.extend([
+        ("stackTrace", json!({ "threadId": 1 })),
+        ("evaluate", json!({ "expression": "n + 1", "frameId": 0 })),
+        ("continue", json!({ "threadId": 1 })),
+    ]);
+    let messages = session(&requests);
+    assert_eq!(
+        summary(&messages),
+        [
+            "initialized",
+            "stopped exception",
+            "at 2:5 in div",
+            "output stderr",
+            "exited",
+            "terminated",
+        ]
+    );
+    let stopped = messages
+        .iter()
+        .find(|message| message["event"] == "stopped")
+        .unwrap();
+    assert_eq!(stopped["body"]["text"], "division by zero");
+    let evaluated = messages
+        .iter()
+        .find(|message| message["command"] == "evaluate")
+        .unwrap();
+    assert_eq!(evaluated["body"]["result"], "8");
+    let error = messages
+        .iter()
+        .find(|message| message["body"]["category"] == "stderr")
+        .unwrap();
+    assert!(
+        error["body"]["output"]
+            .as_str()
+            .unwrap()
+            .starts_with("error: division by zero\n")
+    );
+    let exited = messages
+        .iter()
+        .find(|message| message["event"] == "exited")
+        .unwrap();
+    assert_eq!(exited["body"]["exitCode"], 1);
+
+    // Without the filter the error ends the program at once
+    let mut requests = launch(&path, &[], false);
+    requests.insert(3, ("setExceptionBreakpoints", json!({ "filters": [] })));
+    assert_eq!(
+        summary(&session(&requests)),
+        ["initialized", "output stderr", "exited", "terminated"]
+    );
+}
+
+#[test]
+fn test_failed_requests() {
+    let path = temp_file("requests.monkey", PROGRAM);
+    let broken = temp_file("broken.monkey", "let = 1;");
+    let messages = session(&[
+        ("launch", json!({ "program": broken })),
+        ("launch", json!({ "program": format!("{}.missing", path) })),
+        ("launch", json!({ "program": path })),
+        (
+            "setBreakpoints",
+            json!({ "source": { "path": "other.monkey" }, "breakpoints": [{ "line": 1 }] }),
+        ),
+        ("continue", json!({ "threadId": 1 })),
+        ("stackTrace", json!({ "threadId": 1 })),
+        ("restartFrame", json!({ "frameId": 0 })),
+        ("disconnect", json!({})),
+        ("configurationDone", json!({})),
+    ]);
+    let replies: Vec<(bool, String)> = messages
+        .iter()
+        .filter(|message| message["type"] == "response")
+        .map(|message| {
+            let detail = message
+                .get("message")
+                .map(|text| text.as_str().unwrap().to_string())
+                .unwrap_or_else(|| message["body"].to_string());
+            (message["success"].as_bool().unwrap(), detail)
+        })
+        .collect();
+    assert_eq!(
+        replies,
+        [
+            (false, format!("{} has syntax errors", broken)),
+            (
+                false,
+                format!(
+                    "cannot read {}.missing: No such file or directory (os error 2)",
+                    path
+                )
+            ),
+            (true, "null".to_string()),
+            (
+                true,
+                r#"{"breakpoints":[{"line":1,"message":"not the launched program","verified":false}]}"#
+                    .to_string()
+            ),
+            (false, "the program is not paused".to_string()),
+            (true, r#"{"stackFrames":[],"totalFrames":0}"#.to_string()),
+            (false, "unknown command 'restartFrame'".to_string()),
+            // The session ends at `disconnect`; the program never runs
+            (true, "null".to_string()),
+        ]
+    );
+    assert_eq!(messages[0]["event"], "output");
+    assert_eq!(messages[0]["body"]["category"], "stderr");
+}
@@ -0,0 +1,139 @@
+use crate::lexer::Lexer;
+use crate::parser::{Parser, test_helper::check_parser_errors};
+use crate::repl::{debug, repl};
+use crate::source::SourceMap;
+use std::fs;
+
+const PROGRAM: &str = "let add = fn(a, b) {
+  let sum = a + b;
+  puts(sum);
+  sum;
+};
+let x = add(1, 2);
+let y = add(x, 3);
+y;";
+
+/// Debugs `source` as `test.monkey`, typing `commands`, and returns
+/// everything written.
+fn session(source: &str, commands: &str) -> String {
+    let mut sources = SourceMap::new();
+    let file = sources.add("test.monkey", source);
+    let mut parser = Parser::new(Lexer::new(source.to_string(), file));
+    let program = parser.parse_program();
+    check_parser_errors(&parser);
+    let mut output = Vec::new();
+    debug(&program, &sources, &mut commands.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_breakpoints_vars_and_backtrace() {
+    let output = session(PROGRAM, "break 3\ncontinue\nvars\nbacktrace\ndelete 3\nc\n");
+    assert_eq!(
+        output,
+        "paused at test.monkey:1:1
+   1 | let add = fn(a, b) {
+(debug) breakpoint set at line 3
+(debug) breakpoint at test.monkey:3:3
+   3 |   puts(sum);
+(debug) locals:
+  sum = 3
+enclosing:
+  a = 1
+  b = 2
+globals:
+  add = <fn add>
+(debug) #0 add at test.monkey:3:3
+#1 <main> at test.monkey:6:12
+(debug) breakpoint at line 3 removed
+(debug) 3
+6
+program finished: 6
+"
+    );
+}
+
+#[test]
+fn test_stepping() {
+    let output = session(PROGRAM, "next\nstep\nlist\nnext\nout\nstep\nstep\nout\nc\n");
+    let stops: Vec<&str> = output
+        .lines()
+        .filter_map(|line| line.split_once(" to test.monkey:"))
+        .map(|(_, location)| location)
+        .collect();
+    // Stepping out of a call pauses at the caller's next statement
+    assert_eq!(stops, ["6:1", "2:3", "3:3", "7:1", "2:3", "3:3", "8:1"]);
+    assert!(output.contains("(debug)    2 |   let sum = a + b;\n"));
+    assert!(output.contains("(debug) 6\nstep to test.monkey:8:1\n"));
+    assert!(output.ends_with("(debug) program finished: 6\n"));
+}
+
+#[test]
+fn test_print_and_set() {
+    let output = session(
+        PROGRAM,
+        "b 4\nc\np sum * 10\np a + nope\np let z = 1\nset sum = sum + 100\nset z = 1\ndelete 4\nc\n",
+    );
+    assert!(output.contains("(debug) 30\n"));
+    assert!(output.contains("(debug) error: identifier not found: nope\n"));
+    assert!(output.contains("(debug) error: not an expression: let z = 1\n"));
+    assert!(output.contains("(debug) sum = 103\n"));
+    assert!(output.contains("(debug) error: no variable named z\n"));
+    // The first call now returns 103, so the second adds 3 to it
+    assert!(output.ends_with("106\nprogram finished: 106\n"));
+}
+
+#[test]
+fn test_pause_at_error() {
+    let source = "let div = fn(n) {\n  n / 0;\n};\ndiv(7);";
+    let output = session(source, "c\np n\nbt\nc\n");
+    assert_eq!(
+        output,
+        "paused at test.monkey:1:1
+   1 | let div = fn(n) {
+(debug) error at test.monkey:2:5: division by zero
+   2 |   n / 0;
+(debug) 7
+(debug) #0 div at test.monkey:2:5
+#1 <main> at test.monkey:4:4
+(debug) error: division by zero
+ --> test.monkey:2:5
+  |
+2 |   n / 0;
+  |     ^
+note: in div, called here
+ --> test.monkey:4:4
+  |
+4 | div(7);
+  |    ^
+"
+    );
+}
+
+#[test]
+fn test_quit() {
+    assert!(session(PROGRAM, "b 3\nc\nquit\n").ends_with("(debug) program stopped\n"));
+    // Running out of input stops the program too
+    assert!(
+        session(PROGRAM, "frobnicate\n")
+            .ends_with("(debug) unknown command: frobnicate (try help)\n(debug) program stopped\n")
+    );
+}
+
+#[test]
+fn test_repl_debug_command() {
+    let dir = std::env::temp_dir().join(format!("monkey-debug-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("add.monkey");
+    fs::write(&path, PROGRAM).unwrap();
+    let input = format!(
+        ":debug {}\nb 2\nc\np a\nc\nc\n:view sexpr\n",
+        path.display()
+    );
+    let mut output = Vec::new();
+    repl(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains(&format!("breakpoint at {}:2:3\n", path.display())));
+    assert!(output.contains("(debug) 1\n"));
+    assert!(output.contains("program finished: 6\n"));
+}
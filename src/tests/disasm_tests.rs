@@ -0,0 +1,91 @@
+use crate::compiler::compile;
+use crate::disasm::disassemble;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, test_helper::*};
+use crate::source::FileId;
+
+fn disassemble_source(input: &str) -> String {
+    let mut p = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    disassemble(&compile(&program).unwrap())
+}
+
+#[test]
+fn test_disassemble_function() {
+    assert_eq!(
+        disassemble_source("let inc = fn(x) { x + 1; };"),
+        "\
+== constants ==
+0 INTEGER 1
+1 FUNCTION <fn inc> parameters=1 locals=1
+
+== main ==
+0000 Closure 1 0          ; <fn inc>
+0004 SetGlobal 0
+0007 Null
+0008 ReturnValue
+
+== constant 1: <fn inc> ==
+0000 GetLocal 0
+0002 Constant 0           ; 1
+0005 Add
+0006 ReturnValue
+"
+    );
+}
+
+#[test]
+fn test_disassemble_jumps_builtins_and_strings() {
+    assert_eq!(
+        disassemble_source("if (len(\"abc\") > 2) { puts(\"long\"); } else { 0; };"),
+        "\
+== constants ==
+0 STRING \"abc\"
+1 INTEGER 2
+2 STRING \"long\"
+3 INTEGER 0
+
+== main ==
+0000 GetBuiltin 0         ; len
+0002 Constant 0           ; \"abc\"
+0005 Call 1
+0007 Constant 1           ; 2
+0010 GreaterThan
+0011 JumpNotTruthy 24
+0014 GetBuiltin 1         ; puts
+0016 Constant 2           ; \"long\"
+0019 Call 1
+0021 Jump 27
+0024 Constant 3           ; 0
+0027 ReturnValue
+"
+    );
+}
+
+#[test]
+fn test_disassemble_nested_closures() {
+    assert_eq!(
+        disassemble_source("fn(a) { fn(b) { a + b; }; };"),
+        "\
+== constants ==
+0 FUNCTION <fn> parameters=1 locals=1
+1 FUNCTION <fn> parameters=1 locals=1
+
+== main ==
+0000 Closure 1 0          ; <fn>
+0004 ReturnValue
+
+== constant 0: <fn> ==
+0000 GetFree 0
+0002 GetLocal 0
+0004 Add
+0005 ReturnValue
+
+== constant 1: <fn> ==
+0000 GetLocal 0
+0002 Closure 0 1          ; <fn>
+0006 ReturnValue
+"
+    );
+}
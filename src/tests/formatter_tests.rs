@@ -0,0 +1,143 @@
+use crate::ast::Program;
+use crate::formatter::{FormatOptions, format_source};
+use crate::lexer::Lexer;
+use crate::parser::{Parser, test_helper::*};
+use crate::source::FileId;
+
+fn format(input: &str) -> String {
+    format_with(input, FormatOptions::default())
+}
+
+fn format_with(input: &str, options: FormatOptions) -> String {
+    let formatted = format_source(input, FileId::DETACHED, &options)
+        .unwrap_or_else(|errors| panic!("parser errors for {:?}: {:?}", input, errors));
+    let again = format_source(&formatted, FileId::DETACHED, &options).unwrap();
+    assert_eq!(again, formatted, "formatting is not idempotent");
+    formatted
+}
+
+fn parse(input: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    program
+}
+
+#[test]
+fn test_format_spacing_and_statements() {
+    assert_eq!(
+        format("let   x=1+2*3;let y:int=-x;return x==y;"),
+        "let x = 1 + 2 * 3;\nlet y: int = -x;\nreturn x == y;\n"
+    );
+    assert_eq!(
+        format(r#"import "lib/math"   as math;export let two=math.add(1,1);"#),
+        "import \"lib/math\" as math;\nexport let two = math.add(1, 1);\n"
+    );
+    assert_eq!(format(""), "");
+}
+
+#[test]
+fn test_format_minimal_parentheses() {
+    let tests = [
+        ("(1 + 2) * 3;", "(1 + 2) * 3;\n"),
+        ("1 + (2 * 3);", "1 + 2 * 3;\n"),
+        ("a - (b - c);", "a - (b - c);\n"),
+        ("(a - b) - c;", "a - b - c;\n"),
+        ("-(a + b);", "-(a + b);\n"),
+        ("!(-a);", "!-a;\n"),
+        ("(a < b) == (c > d);", "a < b == c > d;\n"),
+        ("(f(x)).y(z);", "f(x).y(z);\n"),
+        ("(-f)(1);", "(-f)(1);\n"),
+    ];
+    for (input, expected) in tests {
+        let formatted = format(input);
+        assert_eq!(formatted, expected, "input: {}", input);
+        assert_eq!(
+            parse(&formatted).to_string(),
+            parse(input).to_string(),
+            "reparse changed meaning of {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_format_blocks() {
+    assert_eq!(
+        format("let id=fn(x){x;};if(a){b;}else{c;};let f = fn() {};"),
+        "let id = fn(x) { x; };\nif (a) { b; } else { c; };\nlet f = fn() {};\n"
+    );
+    assert_eq!(
+        format("let add=fn(a:int,b:int)->int{let c=a+b;return c;};"),
+        "\
+let add = fn(a: int, b: int) -> int {
+    let c = a + b;
+    return c;
+};
+"
+    );
+}
+
+#[test]
+fn test_format_breaks_long_calls() {
+    let input =
+        "let result = compute(first_argument, second_argument, third_argument, fourth_argument);";
+    assert_eq!(
+        format(input),
+        "\
+let result = compute(
+    first_argument,
+    second_argument,
+    third_argument,
+    fourth_argument
+);
+"
+    );
+    let options = FormatOptions {
+        indent_width: 2,
+        max_width: 20,
+    };
+    assert_eq!(
+        format_with("let f = fn(x) { g(x, 1); };", options),
+        "let f = fn(x) {\n  g(x, 1);\n};\n"
+    );
+}
+
+#[test]
+fn test_format_preserves_comments_and_blank_lines() {
+    let input = "\
+// Adds things.
+let add = fn(a, b) { // entry
+  // the sum
+  a + b;   // trailing
+
+
+  // before closing
+};
+let x = add(1, // one
+  2);
+// end of file
+";
+    assert_eq!(
+        format(input),
+        "\
+// Adds things.
+let add = fn(a, b) {
+    // entry
+    // the sum
+    a + b; // trailing
+
+    // before closing
+};
+let x = add(1, 2); // one
+// end of file
+"
+    );
+}
+
+#[test]
+fn test_format_reports_parser_errors() {
+    let errors =
+        format_source("let x 5;", FileId::DETACHED, &FormatOptions::default()).unwrap_err();
+    assert_eq!(errors.len(), 1);
+}
@@ -0,0 +1,140 @@
+use crate::highlight::{Category, classify, to_ansi, to_html};
+
+/// The classified pieces of `source` as `(text, category)` pairs.
+fn pieces(source: &str) -> Vec<(&str, Category)> {
+    classify(source)
+        .into_iter()
+        .map(|highlight| (&source[highlight.range], highlight.category))
+        .collect()
+}
+
+#[test]
+fn test_tokens_are_classified_by_the_lexer() {
+    use Category::*;
+    assert_eq!(
+        pieces("let x = !true == 10 - 2; // done"),
+        vec![
+            ("let", Keyword),
+            ("x", Local),
+            ("=", Operator),
+            ("!", Operator),
+            ("true", Keyword),
+            ("==", Operator),
+            ("10", Number),
+            ("-", Operator),
+            ("2", Number),
+            ("// done", Comment),
+        ]
+    );
+    assert_eq!(
+        pieces("import \"m\" as m; if (a) { return \"s\"; } else { x ? y; }")[..4],
+        [
+            ("import", Keyword),
+            ("\"m\"", String),
+            ("as", Keyword),
+            ("m", Local),
+        ]
+    );
+    // Delimiters are left out
+    assert!(pieces("(a, [b]);").iter().all(|(_, c)| *c == Local));
+}
+
+#[test]
+fn test_names_are_classified_by_their_binding() {
+    use Category::*;
+    let source = "let add = fn(a, b: int) -> int { let s = a + b; s; };
+let total = add(1, len(total));
+twice(add);";
+    let names: Vec<(&str, Category)> = pieces(source)
+        .into_iter()
+        .filter(|(text, _)| text.chars().all(|c| c.is_alphabetic()))
+        .filter(|(_, category)| *category != Keyword)
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            ("add", Function),
+            ("a", Parameter),
+            ("b", Parameter),
+            ("s", Local),
+            ("a", Parameter),
+            ("b", Parameter),
+            ("s", Local),
+            ("total", Local),
+            ("add", Function),
+            // Unbound names: called ones are functions
+            ("len", Function),
+            ("total", Local),
+            ("twice", Function),
+            ("add", Function),
+        ]
+    );
+}
+
+#[test]
+fn test_member_calls_and_type_names() {
+    use Category::*;
+    assert_eq!(
+        pieces("m.f(m.x);"),
+        vec![("m", Local), ("f", Function), ("m", Local), ("x", Local)]
+    );
+    // `int` and `bool` are not classified
+    assert_eq!(
+        pieces("let f = fn(x: int) -> bool { true; };")
+            .iter()
+            .map(|(text, _)| *text)
+            .collect::<Vec<_>>(),
+        vec!["let", "f", "=", "fn", "x", "->", "true"]
+    );
+}
+
+#[test]
+fn test_invalid_input_is_still_classified() {
+    use Category::*;
+    assert_eq!(
+        pieces("let = 5 @ x"),
+        vec![
+            ("let", Keyword),
+            ("=", Operator),
+            ("5", Number),
+            ("@", Unknown),
+            ("x", Local),
+        ]
+    );
+    // Highlights cover the source in order without overlapping
+    let source = "fn(a { let ; } \"open";
+    let highlights = classify(source);
+    assert!(
+        highlights
+            .windows(2)
+            .all(|pair| pair[0].range.end <= pair[1].range.start)
+    );
+    assert_eq!(highlights.last().unwrap().range.end, source.len());
+}
+
+#[test]
+fn test_to_html() {
+    assert_eq!(
+        to_html("a < \"&\";"),
+        "<span class=\"local\">a</span> <span class=\"operator\">&lt;</span> \
+         <span class=\"string\">&quot;&amp;&quot;</span>;"
+    );
+}
+
+#[test]
+fn test_to_ansi_keeps_the_text() {
+    let source = "let f = fn(x) { x * 2; }; // twice\nf(3);\n";
+    let colored = to_ansi(source);
+    assert!(colored.starts_with("\x1b[35mlet\x1b[0m "));
+    // Stripping the escapes gives the source back
+    let mut plain = std::string::String::new();
+    let mut chars = colored.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    assert_eq!(plain, source);
+}
@@ -0,0 +1,158 @@
+use super::vm_tests::SharedBuffer;
+use crate::interpreter::{Error, Interpreter, Value};
+use crate::object::{FromValue, IntoValue, RuntimeError};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[test]
+fn test_eval_str_keeps_globals() {
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    assert_eq!(
+        interpreter.eval_str("let add = fn(a, b) { a + b; };"),
+        Ok(Value::Null)
+    );
+    assert_eq!(interpreter.eval_str("add(2, 3);"), Ok(Value::Integer(5)));
+    assert_eq!(interpreter.eval::<i64>("puts(\"hi\"); add(1, 1);"), Ok(2));
+    assert_eq!(output.contents(), "hi\n");
+    assert_eq!(
+        interpreter.get_global("add").unwrap().to_string(),
+        "<fn add>"
+    );
+    assert_eq!(interpreter.get_global("missing"), None);
+}
+
+#[test]
+fn test_set_and_get_globals() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_global("name", "monkey");
+    interpreter.set_global("scores", vec![3i64, 4]);
+    interpreter.set_global("limits", HashMap::from([("max", 10i64)]));
+    assert_eq!(interpreter.eval::<i64>("len(name) + len(scores);"), Ok(8));
+    assert_eq!(
+        interpreter.get_global("limits").unwrap().to_string(),
+        "{max: 10}"
+    );
+    interpreter
+        .eval_str("let verdict = len(name) > 5;")
+        .unwrap();
+    let verdict = interpreter.get_global("verdict").unwrap();
+    assert_eq!(bool::from_value(&verdict), Ok(true));
+    assert_eq!(
+        HashMap::<String, i64>::from_value(&interpreter.get_global("limits").unwrap()),
+        Ok(HashMap::from([("max".to_string(), 10)]))
+    );
+}
+
+#[test]
+fn test_hash_keys() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_global("ints", HashMap::from([(1i64, "one"), (-2, "minus two")]));
+    interpreter.set_global("flags", HashMap::from([(true, 1i64), (false, 0)]));
+    interpreter.set_global("owned", HashMap::from([("a".to_string(), true)]));
+    interpreter.set_global("borrowed", HashMap::from([("b", false)]));
+    assert_eq!(
+        HashMap::<i64, String>::from_value(&interpreter.get_global("ints").unwrap()),
+        Ok(HashMap::from([
+            (1, "one".to_string()),
+            (-2, "minus two".to_string())
+        ]))
+    );
+    assert_eq!(
+        HashMap::<bool, i64>::from_value(&interpreter.get_global("flags").unwrap()),
+        Ok(HashMap::from([(true, 1), (false, 0)]))
+    );
+    assert_eq!(
+        HashMap::<String, bool>::from_value(&interpreter.get_global("owned").unwrap()),
+        Ok(HashMap::from([("a".to_string(), true)]))
+    );
+    assert_eq!(
+        HashMap::<String, bool>::from_value(&interpreter.get_global("borrowed").unwrap()),
+        Ok(HashMap::from([("b".to_string(), false)]))
+    );
+}
+
+#[test]
+fn test_native_functions() {
+    let mut interpreter = Interpreter::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let sink = Rc::clone(&log);
+    interpreter.register("record", 1, move |args| {
+        sink.borrow_mut().push(String::from_value(&args[0])?);
+        Ok(Value::Null)
+    });
+    interpreter.register("sum", 1, |args| {
+        let numbers = Vec::<i64>::from_value(&args[0])?;
+        Ok(numbers.iter().sum::<i64>().into_value())
+    });
+    interpreter.register("len", 1, |_| Ok(0.into_value()));
+    interpreter.set_global("numbers", vec![1i64, 2, 3]);
+
+    interpreter
+        .eval_str("record(\"a\"); let apply = fn(f, x) { f(x); }; apply(record, \"b\");")
+        .unwrap();
+    assert_eq!(*log.borrow(), ["a", "b"]);
+    assert_eq!(interpreter.eval::<i64>("sum(numbers);"), Ok(6));
+    assert_eq!(interpreter.eval::<i64>("len(\"shadowed\");"), Ok(0));
+    assert_eq!(
+        interpreter.eval_str("record;").unwrap().to_string(),
+        "<builtin record>"
+    );
+    assert_eq!(
+        interpreter
+            .eval_str("sum(numbers, 1);")
+            .unwrap_err()
+            .to_string(),
+        "wrong number of arguments: want=1, got=2"
+    );
+    assert_eq!(
+        interpreter.eval_str("sum(1);").unwrap_err().to_string(),
+        "expected ARRAY, got INTEGER"
+    );
+}
+
+#[test]
+fn test_errors() {
+    let mut interpreter = Interpreter::new();
+    let Err(Error::Parse(errors)) = interpreter.eval_str("let = 1;") else {
+        panic!("expected a parse error");
+    };
+    assert!(!errors.is_empty());
+    assert_eq!(
+        interpreter.eval_str("1 + true;").unwrap_err().to_string(),
+        "type mismatch: INTEGER + BOOLEAN"
+    );
+    assert_eq!(
+        interpreter.eval::<bool>("1;"),
+        Err(Error::Runtime(RuntimeError::new(
+            "expected BOOLEAN, got INTEGER"
+        )))
+    );
+    // A failed evaluation leaves earlier globals alone
+    interpreter.eval_str("let x = 1;").unwrap();
+    assert!(interpreter.eval_str("let y = x + nope;").is_err());
+    assert_eq!(interpreter.eval::<i64>("x;"), Ok(1));
+}
+
+#[test]
+fn test_array_builtins() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_global("empty", Vec::<i64>::new());
+    interpreter
+        .eval_str("let items = push(push(push(empty, 1), 2), 3);")
+        .unwrap();
+    assert_eq!(interpreter.eval::<Vec<i64>>("items;"), Ok(vec![1, 2, 3]));
+    assert_eq!(
+        interpreter.eval::<i64>("first(items) + last(items);"),
+        Ok(4)
+    );
+    assert_eq!(
+        interpreter.eval::<Vec<i64>>("rest(rest(items));"),
+        Ok(vec![3])
+    );
+    assert_eq!(interpreter.eval::<Option<i64>>("first(empty);"), Ok(None));
+    assert_eq!(interpreter.eval_str("rest(empty);"), Ok(Value::Null));
+    // `push` copies, so the original array is unchanged
+    assert_eq!(interpreter.eval::<i64>("len(empty);"), Ok(0));
+}
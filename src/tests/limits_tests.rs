@@ -0,0 +1,200 @@
+use crate::code::{Opcode, make};
+use crate::compiler::{Bytecode, compile};
+use crate::interpreter::{Interpreter, Limit, Limits};
+use crate::lexer::Lexer;
+use crate::object::RuntimeError;
+use crate::parser::{Parser, error::Span, test_helper::check_parser_errors};
+use crate::source::FileId;
+use crate::vm::Vm;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// Doubles its work at every level, so `f(30)` never finishes in a test.
+const EXPONENTIAL: &str = "let f = fn(n) {
+  if (n > 0) { f(n - 1) + f(n - 1); } else { 1; };
+};
+f(30);";
+
+fn limited(limits: Limits) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(limits);
+    interpreter
+}
+
+/// Runs `input` and returns the error it must stop with.
+fn run_error(interpreter: &mut Interpreter, input: &str) -> RuntimeError {
+    match interpreter.eval_str(input) {
+        Err(crate::interpreter::Error::Runtime(error)) => error,
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+/// Compiles `input` and runs it on a VM bounded by `limits`, returning the
+/// error it must stop with.
+fn vm_error(limits: Limits, input: &str) -> RuntimeError {
+    let mut parser = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+    let program = parser.parse_program();
+    check_parser_errors(&parser);
+    vm_run_error(limits, &compile(&program).unwrap())
+}
+
+fn vm_run_error(limits: Limits, bytecode: &Bytecode) -> RuntimeError {
+    let mut vm = Vm::with_output(io::sink());
+    vm.set_limits(limits);
+    match vm.run(bytecode) {
+        Err(error) => error,
+        Ok(value) => panic!("expected a runtime error, got {}", value),
+    }
+}
+
+fn at(line: usize, column: usize) -> Option<Span> {
+    Some(Span::new(line, column))
+}
+
+#[test]
+fn test_infinite_recursion() {
+    let input = "let f = fn(n) {
+  f(n + 1);
+};
+f(0);";
+    let error = run_error(&mut Interpreter::new(), input);
+    assert_eq!(error.limit, Some(Limit::CallDepth));
+    assert_eq!(error.message, "stack overflow");
+    assert_eq!(error.span, at(2, 4));
+
+    let mut interpreter = limited(Limits {
+        max_call_depth: 10,
+        ..Limits::default()
+    });
+    interpreter
+        .eval_str("let depth = fn(n) { if (n > 0) { depth(n - 1); } else { 0; }; };")
+        .unwrap();
+    assert_eq!(interpreter.eval::<i64>("depth(9);"), Ok(0));
+    let error = run_error(&mut interpreter, "depth(10);");
+    assert_eq!(error.limit, Some(Limit::CallDepth));
+    assert_eq!(error.span, at(1, 39));
+}
+
+#[test]
+fn test_unbounded_array_growth() {
+    let mut interpreter = limited(Limits {
+        max_allocated_bytes: Some(4096),
+        ..Limits::default()
+    });
+    interpreter.set_global("empty", Vec::<i64>::new());
+    let input = "let grow = fn(items) {
+  grow(push(items, len(items)));
+};
+grow(empty);";
+    let error = run_error(&mut interpreter, input);
+    assert_eq!(error.limit, Some(Limit::Memory));
+    assert_eq!(error.message, "memory limit of 4096 bytes exceeded");
+    assert_eq!(error.span, at(2, 12));
+    // Every run starts with a fresh budget
+    assert_eq!(interpreter.eval::<i64>("len(push(empty, 1));"), Ok(1));
+}
+
+#[test]
+fn test_string_growth() {
+    let mut interpreter = limited(Limits {
+        max_allocated_bytes: Some(1 << 16),
+        ..Limits::default()
+    });
+    let input = "let double = fn(s) { double(s + s); };\ndouble(\"ab\");";
+    let error = run_error(&mut interpreter, input);
+    assert_eq!(error.limit, Some(Limit::Memory));
+    assert_eq!(error.span, at(1, 31));
+}
+
+#[test]
+fn test_step_limit() {
+    let mut interpreter = limited(Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    });
+    let error = run_error(&mut interpreter, EXPONENTIAL);
+    assert_eq!(error.limit, Some(Limit::Steps));
+    assert_eq!(error.message, "step limit of 1000 exceeded");
+    assert!(error.span.is_some());
+    assert_eq!(interpreter.eval::<i64>("f(3);"), Ok(8));
+}
+
+#[test]
+fn test_time_limit() {
+    let mut interpreter = limited(Limits {
+        timeout: Some(Duration::from_millis(20)),
+        ..Limits::default()
+    });
+    let start = Instant::now();
+    let error = run_error(&mut interpreter, EXPONENTIAL);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(error.limit, Some(Limit::Time));
+    assert_eq!(error.message, "time limit of 20ms exceeded");
+    assert!(error.span.is_some());
+}
+
+#[test]
+fn test_ordinary_errors_have_no_limit() {
+    let mut interpreter = limited(Limits {
+        max_steps: Some(100),
+        ..Limits::default()
+    });
+    let error = run_error(&mut interpreter, "1 / 0;");
+    assert_eq!(error.limit, None);
+    assert_eq!(error.message, "division by zero");
+}
+
+#[test]
+fn test_vm_limits() {
+    let error = vm_error(
+        Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        },
+        EXPONENTIAL,
+    );
+    assert_eq!(error.limit, Some(Limit::Steps));
+    assert_eq!(error.message, "step limit of 1000 exceeded");
+    assert!(error.span.is_some());
+
+    let error = vm_error(
+        Limits {
+            max_call_depth: 10,
+            ..Limits::default()
+        },
+        "let f = fn(n) {\n  f(n + 1);\n};\nf(0);",
+    );
+    assert_eq!(error.limit, Some(Limit::CallDepth));
+    assert_eq!(error.span, at(2, 4));
+
+    let error = vm_error(
+        Limits {
+            max_allocated_bytes: Some(1 << 16),
+            ..Limits::default()
+        },
+        "let double = fn(s) { double(s + s); };\ndouble(\"ab\");",
+    );
+    assert_eq!(error.limit, Some(Limit::Memory));
+    assert_eq!(error.span, at(1, 31));
+
+    // The compiler never jumps backwards, but bytecode built by hand can
+    let mut instructions = make(Opcode::Null, &[]);
+    instructions.extend(make(Opcode::Pop, &[]));
+    instructions.extend(make(Opcode::Jump, &[0]));
+    let endless = Bytecode {
+        instructions,
+        constants: Vec::new(),
+        spans: Vec::new(),
+    };
+    let start = Instant::now();
+    let error = vm_run_error(
+        Limits {
+            timeout: Some(Duration::from_millis(20)),
+            ..Limits::default()
+        },
+        &endless,
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(error.limit, Some(Limit::Time));
+    assert_eq!(error.message, "time limit of 20ms exceeded");
+}
@@ -0,0 +1,336 @@
+use crate::cli::{EXIT_FAILURE, EXIT_OK};
+use crate::lsp::{self, rpc};
+use serde_json::{Value, json};
+use std::io::Cursor;
+
+const URI: &str = "file:///test.monkey";
+
+const SOURCE: &str = "let add = fn(a, b) {
+    let sum = a + b;
+    sum;
+};
+let total = add(1, 2);
+";
+
+/// Replays `messages` as a client session and returns the exit code and
+/// every message the server sent.
+fn session(messages: &[Value]) -> (i32, Vec<Value>) {
+    let mut input = Vec::new();
+    for message in messages {
+        rpc::write_message(&mut input, message).unwrap();
+    }
+    let mut output = Vec::new();
+    let code = lsp::run(Cursor::new(input), &mut output).unwrap();
+    let mut output = Cursor::new(output);
+    let mut replies = Vec::new();
+    while let Some(body) = rpc::read_message(&mut output).unwrap() {
+        replies.push(serde_json::from_str(&body).unwrap());
+    }
+    (code, replies)
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn open(text: &str) -> Value {
+    notification(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": URI, "languageId": "monkey", "version": 1, "text": text },
+        }),
+    )
+}
+
+/// A request at a 0-based line and UTF-16 column of the test document.
+fn at(id: u64, method: &str, line: u32, character: u32) -> Value {
+    request(
+        id,
+        method,
+        json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        }),
+    )
+}
+
+/// Runs `open(text)` followed by `message` and returns the reply to it.
+fn reply_to(text: &str, message: Value) -> Value {
+    let (_, replies) = session(&[open(text), message]);
+    replies
+        .into_iter()
+        .find(|reply| reply.get("id").is_some())
+        .expect("a response")
+}
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 },
+    })
+}
+
+#[test]
+fn test_initialize_and_shutdown() {
+    let (code, replies) = session(&[
+        request(1, "initialize", json!({ "capabilities": {} })),
+        notification("initialized", json!({})),
+        request(2, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ]);
+    assert_eq!(code, EXIT_OK);
+    assert_eq!(replies.len(), 2);
+    let capabilities = &replies[0]["result"]["capabilities"];
+    assert_eq!(capabilities["textDocumentSync"], 2);
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(
+        replies[1],
+        json!({ "jsonrpc": "2.0", "id": 2, "result": null })
+    );
+
+    // Exiting without a shutdown request is an error
+    let (code, _) = session(&[notification("exit", Value::Null)]);
+    assert_eq!(code, EXIT_FAILURE);
+}
+
+#[test]
+fn test_diagnostics_follow_changes() {
+    let (_, replies) = session(&[
+        open("let x = 5;\nlet = 1;\n"),
+        notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "range": range((1, 4), (1, 4)), "text": "y " }],
+            }),
+        ),
+        notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": 3 },
+                "contentChanges": [{ "text": "let z = ;" }],
+            }),
+        ),
+        notification(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": URI } }),
+        ),
+    ]);
+    let diagnostics: Vec<&Value> = replies
+        .iter()
+        .map(|reply| {
+            assert_eq!(reply["method"], "textDocument/publishDiagnostics");
+            assert_eq!(reply["params"]["uri"], URI);
+            &reply["params"]["diagnostics"]
+        })
+        .collect();
+    assert_eq!(diagnostics.len(), 4);
+    assert_eq!(
+        diagnostics[0][0],
+        json!({
+            "range": range((1, 4), (1, 5)),
+            "severity": 1,
+            "source": "monkey",
+            "message": "expected token to be IDENT, got ASSIGN",
+        })
+    );
+    // Fixed by the incremental change, broken by the full one, cleared on close
+    assert_eq!(diagnostics[1], &json!([]));
+    assert_eq!(diagnostics[2][0]["range"], range((0, 8), (0, 9)));
+    assert_eq!(diagnostics[3], &json!([]));
+}
+
+#[test]
+fn test_document_symbols() {
+    let reply = reply_to(
+        SOURCE,
+        request(
+            1,
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": URI } }),
+        ),
+    );
+    let symbols = &reply["result"];
+    assert_eq!(symbols.as_array().unwrap().len(), 2);
+    assert_eq!(symbols[0]["name"], "add");
+    assert_eq!(symbols[0]["kind"], 12);
+    assert_eq!(symbols[0]["range"], range((0, 0), (3, 2)));
+    assert_eq!(symbols[0]["selectionRange"], range((0, 4), (0, 7)));
+    assert_eq!(symbols[0]["children"][0]["name"], "sum");
+    assert_eq!(symbols[0]["children"][0]["kind"], 13);
+    assert_eq!(symbols[1]["name"], "total");
+    assert_eq!(symbols[1]["kind"], 13);
+}
+
+#[test]
+fn test_hover_shows_node_kind() {
+    let hover = |line, character| {
+        reply_to(SOURCE, at(1, "textDocument/hover", line, character))["result"].clone()
+    };
+    assert_eq!(
+        hover(4, 12)["contents"]["value"],
+        "`Identifier`\n\nlet binding `add`"
+    );
+    assert_eq!(hover(4, 12)["range"], range((4, 12), (4, 15)));
+    assert_eq!(hover(4, 15)["contents"]["value"], "`CallExpression`");
+    assert_eq!(hover(4, 16)["contents"]["value"], "`IntegerLiteral`");
+    assert_eq!(
+        hover(1, 14)["contents"]["value"],
+        "`Identifier`\n\nparameter `a`"
+    );
+    assert_eq!(hover(1, 16)["contents"]["value"], "`InfixExpression`");
+    assert_eq!(hover(0, 10)["contents"]["value"], "`FunctionLiteral`");
+    // Past the end of the text
+    assert_eq!(hover(9, 0), Value::Null);
+}
+
+#[test]
+fn test_definition() {
+    let definition = |line, character| {
+        reply_to(SOURCE, at(1, "textDocument/definition", line, character))["result"].clone()
+    };
+    // `sum;` goes to `let sum`, the `b` in `a + b` to the parameter
+    assert_eq!(
+        definition(2, 5),
+        json!({ "uri": URI, "range": range((1, 8), (1, 11)) })
+    );
+    assert_eq!(definition(1, 18)["range"], range((0, 16), (0, 17)));
+    // A cursor just after a name, and a binding site itself
+    assert_eq!(definition(4, 15)["range"], range((0, 4), (0, 7)));
+    assert_eq!(definition(0, 5)["range"], range((0, 4), (0, 7)));
+    // Not an identifier
+    assert_eq!(definition(4, 17), Value::Null);
+}
+
+#[test]
+fn test_definition_with_wide_characters() {
+    let text = "let s = \"ü😀\"; let y = s;";
+    let reply = reply_to(text, at(1, "textDocument/definition", 0, 23));
+    assert_eq!(reply["result"]["range"], range((0, 4), (0, 5)));
+    // The 😀 takes two UTF-16 units, so `y` is at column 19
+    let reply = reply_to(text, at(2, "textDocument/hover", 0, 19));
+    assert_eq!(reply["result"]["range"], range((0, 19), (0, 20)));
+}
+
+#[test]
+fn test_completion_offers_names_in_scope() {
+    let labels = |line, character| -> Vec<String> {
+        let reply = reply_to(SOURCE, at(1, "textDocument/completion", line, character));
+        reply["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let inside = labels(2, 4);
+    for name in ["sum", "a", "b", "add", "total", "let", "fn", "return"] {
+        assert!(
+            inside.contains(&name.to_string()),
+            "{} in {:?}",
+            name,
+            inside
+        );
+    }
+    // Names from the function body are not visible outside it
+    let outside = labels(4, 0);
+    assert!(outside.contains(&"add".to_string()));
+    assert!(!outside.contains(&"sum".to_string()));
+    assert!(!outside.contains(&"a".to_string()));
+}
+
+#[test]
+fn test_semantic_tokens() {
+    let (_, replies) = session(&[
+        request(1, "initialize", json!({ "capabilities": {} })),
+        open("let s = \"😀\nb\"; f(s);"),
+        request(
+            2,
+            "textDocument/semanticTokens/full",
+            json!({ "textDocument": { "uri": URI } }),
+        ),
+    ]);
+    let legend = &replies[0]["result"]["capabilities"]["semanticTokensProvider"]["legend"];
+    let index = |name: &str| {
+        legend["tokenTypes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .position(|t| t == name)
+            .unwrap() as u64
+    };
+    let data: Vec<u64> = serde_json::from_value(replies[2]["result"]["data"].clone()).unwrap();
+    let tokens: Vec<&[u64]> = data.chunks(5).collect();
+    // The string spans two lines and the emoji is two UTF-16 units
+    let expected: [[u64; 5]; 7] = [
+        [0, 0, 3, index("keyword"), 0],
+        [0, 4, 1, index("variable"), 0],
+        [0, 2, 1, index("operator"), 0],
+        [0, 2, 3, index("string"), 0],
+        [1, 0, 2, index("string"), 0],
+        [0, 4, 1, index("function"), 0],
+        [0, 2, 1, index("variable"), 0],
+    ];
+    assert_eq!(tokens, expected);
+}
+
+#[test]
+fn test_rename() {
+    let rename = |line, character, new_name: &str| {
+        let mut message = at(1, "textDocument/rename", line, character);
+        message["params"]["newName"] = json!(new_name);
+        reply_to(SOURCE, message)
+    };
+    let reply = rename(1, 14, "x");
+    let edits = &reply["result"]["changes"][URI];
+    assert_eq!(
+        edits,
+        &json!([
+            { "range": range((0, 13), (0, 14)), "newText": "x" },
+            { "range": range((1, 14), (1, 15)), "newText": "x" },
+        ])
+    );
+    // `b` would capture the use of the parameter `b`
+    let reply = rename(1, 14, "b");
+    assert_eq!(reply["error"]["code"], rpc::REQUEST_FAILED);
+    assert_eq!(
+        reply["error"]["message"],
+        "the rename would change what 'a' at [line 2:15] refers to"
+    );
+    let reply = rename(4, 0, "fn");
+    assert_eq!(reply["error"]["message"], "'fn' is a keyword");
+}
+
+#[test]
+fn test_protocol_errors() {
+    let (_, replies) = session(&[
+        request(1, "textDocument/formatting", json!({})),
+        request(
+            2,
+            "textDocument/hover",
+            json!({ "textDocument": { "uri": URI } }),
+        ),
+        json!({ "jsonrpc": "2.0", "id": 3 }),
+    ]);
+    assert_eq!(replies[0]["error"]["code"], rpc::METHOD_NOT_FOUND);
+    assert_eq!(replies[1]["error"]["code"], rpc::INVALID_PARAMS);
+    assert_eq!(replies[2]["error"]["code"], rpc::INVALID_REQUEST);
+
+    let mut input = Vec::new();
+    let body = "{ not json";
+    input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+    let mut output = Vec::new();
+    lsp::run(Cursor::new(input), &mut output).unwrap();
+    let reply: Value = serde_json::from_str(
+        &rpc::read_message(&mut Cursor::new(output))
+            .unwrap()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(reply["error"]["code"], rpc::PARSE_ERROR);
+    assert_eq!(reply["id"], Value::Null);
+}
@@ -0,0 +1,197 @@
+use crate::code::{Opcode, make};
+use crate::compiler::{Bytecode, compile};
+use crate::disasm::disassemble;
+use crate::lexer::Lexer;
+use crate::mbc::{LoadError, MAGIC, VERSION, decode, encode};
+use crate::object::Object;
+use crate::parser::{Parser, test_helper::*};
+use crate::source::FileId;
+use crate::vm::Vm;
+use std::fs;
+use std::io;
+
+const PROGRAMS: [&str; 4] = [
+    "let fib = fn(n) { if (n < 2) { n; } else { fib(n - 1) + fib(n - 2); }; };\nfib(10);",
+    "let greet = fn(name) { \"hello \" + name; };\ngreet(\"monkey\");",
+    "let adder = fn(a) { fn(b) { a + b; }; };\nlet inc = adder(1);\ninc(41);",
+    "let n = -9223372036854775807 - 1;\nif (n < 0) { len(\"négatif\"); };",
+];
+
+fn compile_source(input: &str) -> Bytecode {
+    let mut p = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    compile(&program).unwrap()
+}
+
+fn run(bytecode: &Bytecode) -> String {
+    Vm::with_output(io::sink())
+        .run(bytecode)
+        .unwrap()
+        .to_string()
+}
+
+/// Compares two programs; functions compare by content rather than identity.
+fn assert_same_program(loaded: &Bytecode, original: &Bytecode) {
+    assert_eq!(loaded.instructions, original.instructions);
+    assert_eq!(loaded.spans, original.spans);
+    assert_eq!(loaded.constants.len(), original.constants.len());
+    for (a, b) in loaded.constants.iter().zip(&original.constants) {
+        match (a, b) {
+            (Object::CompiledFunction(a), Object::CompiledFunction(b)) => assert_eq!(a, b),
+            _ => assert_eq!(a, b),
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_through_a_file() {
+    let dir = std::env::temp_dir().join(format!("monkey-mbc-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (i, source) in PROGRAMS.iter().enumerate() {
+        let bytecode = compile_source(source);
+        let path = dir.join(format!("program{}.mbc", i));
+        fs::write(&path, encode(&bytecode, true)).unwrap();
+        let loaded = decode(&fs::read(&path).unwrap()).unwrap();
+        assert_same_program(&loaded, &bytecode);
+        assert_eq!(run(&loaded), run(&bytecode), "{}", source);
+    }
+}
+
+#[test]
+fn test_round_trip_without_spans() {
+    for source in PROGRAMS {
+        let bytecode = compile_source(source);
+        let with_spans = encode(&bytecode, true);
+        let stripped = encode(&bytecode, false);
+        assert!(stripped.len() < with_spans.len());
+        let loaded = decode(&stripped).unwrap();
+        assert!(loaded.spans.is_empty());
+        assert_eq!(disassemble(&loaded), disassemble(&bytecode));
+        assert_eq!(run(&loaded), run(&bytecode));
+    }
+}
+
+#[test]
+fn test_every_truncation_is_rejected() {
+    for source in PROGRAMS {
+        let bytes = encode(&compile_source(source), true);
+        for length in 0..bytes.len() {
+            assert!(
+                decode(&bytes[..length]).is_err(),
+                "a file cut to {} bytes was accepted",
+                length
+            );
+        }
+    }
+    // The last field is the column of the last span
+    let bytes = encode(&compile_source(PROGRAMS[0]), true);
+    assert_eq!(
+        decode(&bytes[..bytes.len() - 1]),
+        Err(LoadError::Truncated {
+            offset: bytes.len() - 4
+        })
+    );
+}
+
+#[test]
+fn test_corrupt_bytes_never_panic() {
+    for source in PROGRAMS {
+        let bytes = encode(&compile_source(source), true);
+        for position in 0..bytes.len() {
+            for mask in [0x01, 0x80, 0xFF] {
+                let mut corrupted = bytes.clone();
+                corrupted[position] ^= mask;
+                let _ = decode(&corrupted);
+            }
+        }
+    }
+}
+
+/// A file holding `instructions` as its main program and `constants`.
+fn file_with(constants: Vec<Object>, instructions: &[Vec<u8>]) -> Vec<u8> {
+    encode(
+        &Bytecode {
+            instructions: instructions.concat(),
+            constants,
+            spans: Vec::new(),
+        },
+        false,
+    )
+}
+
+fn corrupt_message(bytes: &[u8]) -> String {
+    match decode(bytes) {
+        Err(LoadError::Corrupt { message, .. }) => message,
+        other => panic!("expected a corrupt file error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_header_errors() {
+    let bytes = encode(&compile_source("1;"), true);
+    assert_eq!(decode(b"let x = 1;"), Err(LoadError::NotBytecode));
+    let mut future = bytes.clone();
+    future[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_be_bytes());
+    assert_eq!(
+        decode(&future),
+        Err(LoadError::UnsupportedVersion(VERSION + 1))
+    );
+    assert_eq!(
+        decode(&future).unwrap_err().to_string(),
+        "unsupported bytecode format version 2 (expected 1)"
+    );
+    let mut flags = bytes.clone();
+    flags[7] |= 0x10;
+    assert_eq!(corrupt_message(&flags), "unknown flags 0x0011");
+    let mut trailing = bytes;
+    trailing.push(0);
+    assert_eq!(
+        corrupt_message(&trailing),
+        "unexpected data after the program"
+    );
+}
+
+#[test]
+fn test_invalid_instructions_are_rejected() {
+    let ret = make(Opcode::ReturnValue, &[]);
+    let cases = [
+        (
+            vec![make(Opcode::Constant, &[3]), ret.clone()],
+            "constant 3 does not exist",
+        ),
+        (
+            vec![make(Opcode::Closure, &[0, 0]), ret.clone()],
+            "constant 0 is not a function",
+        ),
+        (
+            vec![make(Opcode::GetLocal, &[0]), ret.clone()],
+            "local 0 does not exist",
+        ),
+        (
+            vec![make(Opcode::GetBuiltin, &[9]), ret.clone()],
+            "builtin 9 does not exist",
+        ),
+        (
+            vec![
+                make(Opcode::Jump, &[1]),
+                make(Opcode::Null, &[]),
+                ret.clone(),
+            ],
+            "jump to 1 is not the start of an instruction",
+        ),
+        (
+            vec![make(Opcode::Null, &[])],
+            "code does not end with ReturnValue",
+        ),
+        (vec![vec![250], ret.clone()], "unknown opcode 250"),
+        (
+            vec![ret.clone(), vec![Opcode::Constant as u8, 0]],
+            "Constant runs past the end of the code",
+        ),
+    ];
+    for (instructions, message) in cases {
+        let bytes = file_with(vec![Object::Integer(1)], &instructions);
+        assert_eq!(corrupt_message(&bytes), message);
+    }
+}
@@ -1,2 +1,19 @@
+pub mod dap_tests;
+pub mod debugger_tests;
+pub mod disasm_tests;
+pub mod formatter_tests;
+pub mod highlight_tests;
+pub mod interpreter_tests;
+pub mod limits_tests;
+pub mod lsp_tests;
+pub mod mbc_tests;
 pub mod parser_expression_tests;
 pub mod parser_statement_tests;
+pub mod parser_type_tests;
+pub mod rename_tests;
+pub mod reparse_tests;
+pub mod repl_tests;
+pub mod resolve_tests;
+pub mod syntax_tests;
+pub mod typeck_tests;
+pub mod vm_tests;
@@ -1,6 +1,7 @@
 use crate::ast::{Node, expression::Expression, statement::Statement};
 use crate::lexer::Lexer;
 use crate::parser::{Parser, test_helper::*};
+use crate::source::FileId;
 
 // =============================================================================
 // Identifier & Integer Literal Tests
@@ -10,7 +11,7 @@ use crate::parser::{Parser, test_helper::*};
 #[test]
 fn test_parsing_identifier_expression() {
     let input = "foobar;".to_string();
-    let l = Lexer::new(input);
+    let l = Lexer::new(input, FileId::DETACHED);
     let mut p = Parser::new(l);
 
     let program = p.parse_program();
@@ -47,7 +48,7 @@ fn test_parsing_identifier_expression() {
 #[test]
 fn test_parsing_integer_literal_expression() {
     let input = "5;".to_string();
-    let l = Lexer::new(input);
+    let l = Lexer::new(input, FileId::DETACHED);
     let mut p = Parser::new(l);
     let program = p.parse_program();
 
@@ -94,7 +95,7 @@ fn test_parsing_prefix_expressions() {
     ];
 
     for (input, expected_operator, expected_right_value) in prefix_tests {
-        let l = Lexer::new(input.to_string());
+        let l = Lexer::new(input.to_string(), FileId::DETACHED);
         let mut p = Parser::new(l);
 
         let program = p.parse_program();
@@ -178,7 +179,7 @@ fn test_parsing_infix_expression() {
     ];
 
     for (input, expected_left_value, expected_operator, expected_right_value) in infix_tests {
-        let l = Lexer::new(input.to_string());
+        let l = Lexer::new(input.to_string(), FileId::DETACHED);
         let mut p = Parser::new(l);
         let program = p.parse_program();
 
@@ -278,7 +279,7 @@ fn test_operator_precedence_parsing() {
     ];
 
     for (input, expected) in tests {
-        let l = Lexer::new(input.to_string());
+        let l = Lexer::new(input.to_string(), FileId::DETACHED);
         let mut p = Parser::new(l);
         let program = p.parse_program();
 
@@ -301,7 +302,7 @@ fn test_operator_precedence_parsing() {
 #[test]
 fn test_parsing_if_expression() {
     let input = "if (x < y) { x; };";
-    let l = Lexer::new(input.to_string());
+    let l = Lexer::new(input.to_string(), FileId::DETACHED);
     let mut p = Parser::new(l);
     let program = p.parse_program();
 
@@ -364,9 +365,10 @@ fn test_parsing_if_expression() {
 
 /// Tests parsing of if-else expressions: if (<condition>) <consequence> else <alternative>
 #[test]
+#[allow(clippy::needless_return)]
 fn test_parsing_if_else_expression() {
     let input = "if (x < y) { x; } else { y; };";
-    let l = Lexer::new(input.to_string());
+    let l = Lexer::new(input.to_string(), FileId::DETACHED);
     let mut p = Parser::new(l);
     let program = p.parse_program();
 
@@ -456,7 +458,7 @@ fn test_parsing_if_else_expression() {
 fn test_parsing_function_literal() {
     let input = "fn(x, y) { x + y; };";
     // Create a new lexer and parser
-    let lexer = Lexer::new(input.to_string());
+    let lexer = Lexer::new(input.to_string(), FileId::DETACHED);
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program();
     check_parser_errors(&parser);
@@ -517,7 +519,7 @@ fn test_parsing_function_literal() {
 fn test_parsing_function_parameter() {
     let input = "fn(x, y) { x + y; };";
     // Create a new lexer and parser
-    let lexer = Lexer::new(input.to_string());
+    let lexer = Lexer::new(input.to_string(), FileId::DETACHED);
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program();
     check_parser_errors(&parser);
@@ -562,7 +564,7 @@ fn test_parsing_function_parameter() {
 fn test_parsing_call_expression() {
     let input = "add(1, 2 * 3, 4 + 5);";
 
-    let l = Lexer::new(input.to_string());
+    let l = Lexer::new(input.to_string(), FileId::DETACHED);
     let mut p = Parser::new(l);
     let program = p.parse_program();
     check_parser_errors(&p);
@@ -603,3 +605,59 @@ fn test_parsing_call_expression() {
     test_infix_expression_str(call_expr.arguments[1].clone(), "2", "*", "3");
     test_infix_expression_str(call_expr.arguments[2].clone(), "4", "+", "5");
 }
+
+// =============================================================================
+// String Literal & Member Expression Tests
+// =============================================================================
+
+/// Tests parsing of a string literal expression.
+#[test]
+fn test_parsing_string_literal_expression() {
+    let input = r#""hello world";"#.to_string();
+    let l = Lexer::new(input, FileId::DETACHED);
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+
+    let stmt = match &program.statements[0] {
+        Statement::Expression(stmt) => stmt,
+        other => panic!("stmt is not an ExpressionStatement. got={:?}", other),
+    };
+    match &stmt.value {
+        Expression::StringLiteral(sl) => assert_eq!(sl.value, "hello world"),
+        other => panic!("exp is not a StringLiteral. got={:?}", other),
+    }
+}
+
+/// Tests parsing of member access, which binds tighter than calls and operators.
+#[test]
+fn test_parsing_member_expressions() {
+    let tests = [
+        ("math.pi;", "math.pi"),
+        ("math.add(1, 2);", "math.add(1, 2)"),
+        ("-math.pi * 2;", "((-math.pi) * 2)"),
+        ("a.b.c;", "a.b.c"),
+    ];
+    for (input, expected) in tests {
+        let l = Lexer::new(input.to_string(), FileId::DETACHED);
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        check_parser_errors(&p);
+
+        assert_eq!(program.to_string(), expected);
+    }
+
+    let l = Lexer::new("math.add;".to_string(), FileId::DETACHED);
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    let member = match &program.statements[0] {
+        Statement::Expression(stmt) => match &stmt.value {
+            Expression::MemberExpression(me) => me.clone(),
+            other => panic!("exp is not a MemberExpression. got={:?}", other),
+        },
+        other => panic!("stmt is not an ExpressionStatement. got={:?}", other),
+    };
+    test_identifier(*member.object, "math");
+    assert_eq!(member.property.value, "add");
+}
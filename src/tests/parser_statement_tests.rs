@@ -1,6 +1,7 @@
 use crate::ast::Statement;
 use crate::lexer::Lexer;
 use crate::parser::{Parser, test_helper::*};
+use crate::source::FileId;
 
 // =============================================================================
 // Let Statement Tests
@@ -8,6 +9,7 @@ use crate::parser::{Parser, test_helper::*};
 
 /// Tests parsing of multiple let statements.
 #[test]
+#[allow(clippy::explicit_auto_deref)]
 fn test_parsing_let_statements() {
     let input = r#"
 let x = 5;
@@ -15,7 +17,7 @@ let y = 10;
 let foobar = 838383;
 "#
     .to_string();
-    let l = Lexer::new(input);
+    let l = Lexer::new(input, FileId::DETACHED);
     let mut p = Parser::new(l);
 
     let program = p.parse_program();
@@ -65,7 +67,7 @@ return 993322;
 "#
     .to_string();
 
-    let l = Lexer::new(input);
+    let l = Lexer::new(input, FileId::DETACHED);
     let mut p = Parser::new(l);
 
     let program = p.parse_program();
@@ -96,7 +98,7 @@ return 993322;
 fn test_parsing_return_statement() {
     let input = "return 5;".to_string();
 
-    let l = Lexer::new(input);
+    let l = Lexer::new(input, FileId::DETACHED);
     let mut p = Parser::new(l);
     let program = p.parse_program();
 
@@ -115,3 +117,101 @@ fn test_parsing_return_statement() {
         "statement is not a ReturnStatement"
     );
 }
+
+// =============================================================================
+// Import & Export Statement Tests
+// =============================================================================
+
+/// Tests parsing of an import statement.
+#[test]
+fn test_parsing_import_statement() {
+    let input = r#"import "lib/math.monkey" as math;"#.to_string();
+
+    let l = Lexer::new(input, FileId::DETACHED);
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+
+    assert_eq!(program.statements.len(), 1);
+    let import = match &program.statements[0] {
+        Statement::Import(import) => import,
+        other => panic!("stmt is not an ImportStatement. got={:?}", other),
+    };
+    assert_eq!(import.path.value, "lib/math.monkey");
+    assert_eq!(import.alias.value, "math");
+    assert_eq!(program.to_string(), r#"import "lib/math.monkey" as math;"#);
+}
+
+/// Tests parsing of an export statement wrapping a let binding.
+#[test]
+fn test_parsing_export_statement() {
+    let input = "export let add = fn(a, b) { a + b; };".to_string();
+
+    let l = Lexer::new(input, FileId::DETACHED);
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+
+    assert_eq!(program.statements.len(), 1);
+    let export = match &program.statements[0] {
+        Statement::Export(export) => export,
+        other => panic!("stmt is not an ExportStatement. got={:?}", other),
+    };
+    assert!(test_let_statement(
+        &Statement::Let(export.statement.clone()),
+        "add"
+    ));
+    assert_eq!(program.to_string(), "export let add = fn(a, b) {(a + b)};");
+}
+
+/// Tests that import and export are rejected inside blocks.
+#[test]
+fn test_import_export_only_at_top_level() {
+    let inputs = [
+        r#"fn() { import "a.monkey" as a; };"#,
+        "if (true) { export let x = 1; };",
+    ];
+    for input in inputs {
+        let l = Lexer::new(input.to_string(), FileId::DETACHED);
+        let mut p = Parser::new(l);
+        p.parse_program();
+
+        assert!(
+            p.errors()
+                .iter()
+                .any(|e| e.message.contains("only allowed at the top level")),
+            "expected top level error for {:?}. got={:?}",
+            input,
+            p.errors()
+        );
+    }
+}
+
+/// Tests that malformed import statements are reported.
+#[test]
+fn test_parsing_invalid_import_statements() {
+    let tests = [
+        ("import math;", "expected token to be STRING, got IDENT"),
+        (
+            r#"import "m.monkey";"#,
+            "expected token to be AS, got SEMICOLON",
+        ),
+        (
+            r#"import "m.monkey" as 5;"#,
+            "expected token to be IDENT, got INT",
+        ),
+        ("export fn() {};", "expected token to be LET, got FUNCTION"),
+    ];
+    for (input, expected) in tests {
+        let l = Lexer::new(input.to_string(), FileId::DETACHED);
+        let mut p = Parser::new(l);
+        p.parse_program();
+
+        assert_eq!(
+            p.errors()[0].message,
+            expected,
+            "wrong first error for {:?}",
+            input
+        );
+    }
+}
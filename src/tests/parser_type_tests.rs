@@ -0,0 +1,164 @@
+use crate::ast::{Program, TypeExpr, expression::Expression, statement::Statement};
+use crate::lexer::Lexer;
+use crate::parser::{Parser, test_helper::*};
+use crate::source::FileId;
+
+fn parse(input: &str) -> Program {
+    let l = Lexer::new(input.to_string(), FileId::DETACHED);
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    program
+}
+
+// =============================================================================
+// Let Statement Annotation Tests
+// =============================================================================
+
+/// Tests parsing of let statements with every kind of type annotation.
+#[test]
+fn test_parsing_let_type_annotations() {
+    let tests = [
+        ("let x: int = 5;", "int"),
+        ("let names: [string] = 5;", "[string]"),
+        ("let ages: {string: int} = 5;", "{string: int}"),
+        (
+            "let f: fn(int, bool) -> [int] = 5;",
+            "fn(int, bool) -> [int]",
+        ),
+        ("let g: fn() -> bool = 5;", "fn() -> bool"),
+        ("let m: int? = 5;", "int?"),
+        ("let n: [int?]? = 5;", "[int?]?"),
+        ("let h: (fn(int) -> int)? = 5;", "(fn(int) -> int)?"),
+    ];
+    for (input, expected) in tests {
+        let program = parse(input);
+        let let_stmt = match &program.statements[0] {
+            Statement::Let(let_stmt) => let_stmt,
+            other => panic!("stmt is not a LetStatement. got={:?}", other),
+        };
+        let annotation = let_stmt
+            .type_annotation
+            .as_ref()
+            .unwrap_or_else(|| panic!("missing annotation for {:?}", input));
+        assert_eq!(annotation.to_string(), expected);
+        assert_eq!(program.to_string(), input);
+    }
+}
+
+/// Tests that the structure of a nested annotation is preserved.
+#[test]
+fn test_parsing_nested_type_annotation() {
+    let program = parse("let f: fn([int]) -> int? = 5;");
+    let annotation = match &program.statements[0] {
+        Statement::Let(let_stmt) => let_stmt.type_annotation.clone().unwrap(),
+        other => panic!("stmt is not a LetStatement. got={:?}", other),
+    };
+    let function = match annotation {
+        TypeExpr::Function(function) => function,
+        other => panic!("annotation is not a FunctionType. got={:?}", other),
+    };
+    assert!(matches!(&function.parameters[0], TypeExpr::Array(_)));
+    match function.return_type.as_ref() {
+        TypeExpr::Optional(optional) => {
+            assert!(matches!(optional.inner.as_ref(), TypeExpr::Named(n) if n.name == "int"))
+        }
+        other => panic!("return type is not an OptionalType. got={:?}", other),
+    }
+}
+
+// =============================================================================
+// Function Annotation Tests
+// =============================================================================
+
+/// Tests parsing of annotated parameters and return types.
+#[test]
+fn test_parsing_function_type_annotations() {
+    let input = "fn(a: int, b: [string], c) -> bool { a; };";
+    let program = parse(input);
+
+    let function = match &program.statements[0] {
+        Statement::Expression(stmt) => match &stmt.value {
+            Expression::FunctionLiteral(function) => function.clone(),
+            other => panic!("exp is not a FunctionLiteral. got={:?}", other),
+        },
+        other => panic!("stmt is not an ExpressionStatement. got={:?}", other),
+    };
+
+    assert_eq!(function.parameters.len(), 3);
+    assert_eq!(function.parameter_types.len(), 3);
+    let types: Vec<Option<String>> = function
+        .parameter_types
+        .iter()
+        .map(|t| t.as_ref().map(|t| t.to_string()))
+        .collect();
+    assert_eq!(
+        types,
+        vec![Some("int".to_string()), Some("[string]".to_string()), None]
+    );
+    assert_eq!(function.return_type.unwrap().to_string(), "bool");
+    assert_eq!(
+        program.to_string(),
+        "fn(a: int, b: [string], c) -> bool {a}"
+    );
+}
+
+/// Tests that a hash return type is not confused with the function body.
+#[test]
+fn test_parsing_hash_return_type() {
+    let program = parse("let f = fn() -> {string: int} { x; };");
+    assert_eq!(program.to_string(), "let f = fn() -> {string: int} {x};");
+}
+
+/// Tests that unannotated code parses exactly as before.
+#[test]
+fn test_unannotated_code_has_no_annotations() {
+    let program = parse("let add = fn(x, y) { x + y; };");
+    let let_stmt = match &program.statements[0] {
+        Statement::Let(let_stmt) => let_stmt,
+        other => panic!("stmt is not a LetStatement. got={:?}", other),
+    };
+    assert!(let_stmt.type_annotation.is_none());
+    match let_stmt.value.as_ref().unwrap() {
+        Expression::FunctionLiteral(function) => {
+            assert!(function.parameter_types.iter().all(Option::is_none));
+            assert!(function.return_type.is_none());
+        }
+        other => panic!("value is not a FunctionLiteral. got={:?}", other),
+    }
+    assert_eq!(program.to_string(), "let add = fn(x, y) {(x + y)};");
+}
+
+/// Tests errors for malformed annotations.
+#[test]
+fn test_parsing_invalid_type_annotations() {
+    let tests = [
+        ("let x: = 5;", "expected type, got ASSIGN"),
+        (
+            "let x: [int = 5;",
+            "expected token to be RBRACKET, got ASSIGN",
+        ),
+        (
+            "let x: {string} = 5;",
+            "expected token to be COLON, got RBRACE",
+        ),
+        (
+            "let x: fn(int) = 5;",
+            "expected token to be ARROW, got ASSIGN",
+        ),
+        ("fn(a:) { a; };", "expected type, got RPAREN"),
+        ("fn() -> 5 { a; };", "expected type, got INT"),
+    ];
+    for (input, expected) in tests {
+        let l = Lexer::new(input.to_string(), FileId::DETACHED);
+        let mut p = Parser::new(l);
+        p.parse_program();
+        assert!(!p.errors().is_empty(), "expected errors for {:?}", input);
+        assert_eq!(
+            p.errors()[0].message,
+            expected,
+            "wrong error for {:?}",
+            input
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::rename::{RenameError, apply_edits, rename};
+
+/// Renames the binding at the `nth` occurrence of `at` in `source`.
+fn rename_at(source: &str, at: &str, nth: usize, new_name: &str) -> Result<String, RenameError> {
+    let (offset, _) = source
+        .match_indices(at)
+        .nth(nth)
+        .unwrap_or_else(|| panic!("no {:?} #{} in {:?}", at, nth, source));
+    rename(source, offset, new_name).map(|edits| apply_edits(source, &edits))
+}
+
+#[test]
+fn test_rename_from_definition_and_use() {
+    let source = "let add = fn(a, b) { a + b; };\nlet f = fn() { add(1, 2); };\nadd(3, 4);\n";
+    let expected = "let sum = fn(a, b) { a + b; };\nlet f = fn() { sum(1, 2); };\nsum(3, 4);\n";
+    assert_eq!(rename_at(source, "add", 0, "sum").unwrap(), expected);
+    assert_eq!(rename_at(source, "add", 2, "sum").unwrap(), expected);
+    // A cursor just after the name
+    let offset = source.find("add(3").unwrap() + 3;
+    assert_eq!(rename(source, offset, "sum").unwrap().len(), 3);
+    // Renaming to the same name changes nothing
+    assert_eq!(rename(source, 4, "add").unwrap(), vec![]);
+}
+
+#[test]
+fn test_rename_edits_are_in_source_order() {
+    let source = "let f = fn(n) { f(n - 1); };";
+    let edits = rename(source, source.find("n)").unwrap(), "count").unwrap();
+    let ranges: Vec<_> = edits.iter().map(|edit| edit.range.clone()).collect();
+    assert_eq!(ranges, vec![11..12, 18..19]);
+    assert!(edits.iter().all(|edit| edit.replacement == "count"));
+}
+
+#[test]
+fn test_rename_respects_shadowing() {
+    let source = "let x = 1;\nlet f = fn(x) { x * 2; };\nif (true) { let x = 3; x; };\nx + f(x);\n";
+    assert_eq!(
+        rename_at(source, "x", 0, "y").unwrap(),
+        "let y = 1;\nlet f = fn(x) { x * 2; };\nif (true) { let x = 3; x; };\ny + f(y);\n"
+    );
+    assert_eq!(
+        rename_at(source, "x", 2, "n").unwrap(),
+        "let x = 1;\nlet f = fn(n) { n * 2; };\nif (true) { let x = 3; x; };\nx + f(x);\n"
+    );
+    assert_eq!(
+        rename_at(source, "x", 4, "z").unwrap(),
+        "let x = 1;\nlet f = fn(x) { x * 2; };\nif (true) { let z = 3; z; };\nx + f(x);\n"
+    );
+}
+
+#[test]
+fn test_rename_refuses_captures() {
+    // The parameter would hide the outer `a` that the body uses
+    let source = "let a = 1;\nlet f = fn(b) { a + b; };\n";
+    assert_eq!(
+        rename_at(source, "b", 0, "a"),
+        Err(RenameError::Capture {
+            span: crate::parser::error::Span::new(2, 17),
+            name: "a".to_string(),
+        })
+    );
+    // The use of `a` in the body would refer to the parameter
+    assert!(matches!(
+        rename_at(source, "a", 0, "b"),
+        Err(RenameError::Capture { .. })
+    ));
+    // A later binding in the same scope
+    let source = "let a = 1;\nlet b = 2;\na;\n";
+    assert!(matches!(
+        rename_at(source, "b", 0, "a"),
+        Err(RenameError::Capture { .. })
+    ));
+    // A builtin called by name
+    let source = "let x = \"s\";\nlen(x);\n";
+    assert!(matches!(
+        rename_at(source, "x", 0, "len"),
+        Err(RenameError::Capture { .. })
+    ));
+    // Shadowing that changes nothing is allowed
+    let source = "let a = 1;\nlet f = fn(b) { b; };\na;\n";
+    assert_eq!(
+        rename_at(source, "b", 0, "a").unwrap(),
+        "let a = 1;\nlet f = fn(a) { a; };\na;\n"
+    );
+}
+
+#[test]
+fn test_rename_refuses_bad_names() {
+    let source = "let x = 1;\nx;\n";
+    for keyword in ["fn", "let", "if", "true", "import", "as"] {
+        assert_eq!(
+            rename_at(source, "x", 0, keyword),
+            Err(RenameError::Keyword(keyword.to_string()))
+        );
+    }
+    for name in ["", "x y", "x2", "é"] {
+        assert_eq!(
+            rename_at(source, "x", 0, name),
+            Err(RenameError::InvalidName(name.to_string()))
+        );
+    }
+}
+
+#[test]
+fn test_rename_targets() {
+    // Import aliases are renamed, but not the member names after them
+    let source = "import \"lib\" as m;\nm.f(m);\n";
+    assert_eq!(
+        rename_at(source, "m;", 0, "lib").unwrap(),
+        "import \"lib\" as lib;\nlib.f(lib);\n"
+    );
+    // Type names are not bindings
+    let source = "let int = 1;\nlet f = fn(x: int) -> int { x + int; };\n";
+    assert_eq!(
+        rename_at(source, "int", 0, "n").unwrap(),
+        "let n = 1;\nlet f = fn(x: int) -> int { x + n; };\n"
+    );
+    assert_eq!(
+        rename_at(source, "int", 1, "n"),
+        Err(RenameError::NoBinding)
+    );
+    // Literals, builtins and member names
+    assert_eq!(
+        rename_at("len(1);", "1", 0, "n"),
+        Err(RenameError::NoBinding)
+    );
+    assert_eq!(
+        rename_at("len(1);", "len", 0, "n"),
+        Err(RenameError::NoBinding)
+    );
+    assert_eq!(
+        rename_at("import \"l\" as m; m.f;", "f", 0, "g"),
+        Err(RenameError::NoBinding)
+    );
+}
+
+#[test]
+fn test_rename_refuses_exports_and_parse_errors() {
+    assert_eq!(
+        rename_at("export let api = 1;\napi;\n", "api", 1, "x"),
+        Err(RenameError::Exported("api".to_string()))
+    );
+    assert!(matches!(
+        rename_at("let x = 1;\nlet = 2;\nx;\n", "x", 0, "y"),
+        Err(RenameError::ParseErrors(_))
+    ));
+}
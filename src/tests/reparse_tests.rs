@@ -0,0 +1,206 @@
+use crate::syntax::{Parse, SyntaxKind, SyntaxNode, TextEdit, parse};
+
+const PROGRAM: &str = r#"import "lib/math" as math;
+
+let add = fn(a: int, b) -> int {
+    let sum = a + b; // sum
+    if (sum > 10) {
+        return math.clamp(sum);
+    } else {
+        sum;
+    };
+    return sum;
+};
+// The answer
+export let x: int? = add(1, 2) * 3;
+puts("done");
+"#;
+
+/// Reparses `old` after `edit` and checks the result against a fresh parse
+/// of the edited text, positions included.
+#[track_caller]
+fn assert_reparse(old: &Parse, edit: &TextEdit) -> Parse {
+    let source = old.syntax().to_string();
+    let new = old.reparse(edit);
+    let text = edit.apply(&source);
+    let fresh = parse(&text);
+    let context = format!("source: {:?}, edit: {:?}", source, edit);
+    assert_eq!(new.syntax().to_string(), text, "{}", context);
+    assert_eq!(
+        new.syntax().debug_tree(),
+        fresh.syntax().debug_tree(),
+        "{}",
+        context
+    );
+    assert_eq!(
+        serde_json::to_string(new.program()).unwrap(),
+        serde_json::to_string(fresh.program()).unwrap(),
+        "{}",
+        context
+    );
+    assert_eq!(new.errors(), fresh.errors(), "{}", context);
+    new
+}
+
+/// The node at `index` among the children of the root.
+fn top_level(parse: &Parse, index: usize) -> SyntaxNode {
+    parse.syntax().children().nth(index).unwrap()
+}
+
+/// Whether the two nodes share their green node.
+fn shares_green(a: &SyntaxNode, b: &SyntaxNode) -> bool {
+    std::ptr::eq(a.green(), b.green())
+}
+
+#[test]
+fn test_edit_inside_statement() {
+    let offset = PROGRAM.find("1, 2").unwrap();
+    let old = parse(PROGRAM);
+    let new = assert_reparse(&old, &TextEdit::new(offset..offset + 1, "40"));
+    assert!(new.errors().is_empty());
+    // The import and the function are reused, the export is new
+    assert!(shares_green(&top_level(&old, 0), &top_level(&new, 0)));
+    assert!(shares_green(&top_level(&old, 1), &top_level(&new, 1)));
+    assert!(!shares_green(&top_level(&old, 2), &top_level(&new, 2)));
+    assert!(shares_green(&top_level(&old, 3), &top_level(&new, 3)));
+}
+
+#[test]
+fn test_edit_inside_block_reuses_siblings() {
+    let offset = PROGRAM.find("return sum").unwrap();
+    let old = parse(PROGRAM);
+    let new = assert_reparse(
+        &old,
+        &TextEdit::new(offset..offset, "let y = sum * 2;\n    "),
+    );
+    let body = |parse: &Parse| -> Vec<SyntaxNode> {
+        let function = top_level(parse, 1);
+        let block = function
+            .descendants()
+            .find(|node| node.kind() == SyntaxKind::BlockExpr)
+            .unwrap();
+        block.children().collect()
+    };
+    let (old_body, new_body) = (body(&old), body(&new));
+    assert_eq!(old_body.len() + 1, new_body.len());
+    // The `let sum` and `if` statements are shared, only the new one is parsed
+    assert!(shares_green(&old_body[0], &new_body[0]));
+    assert!(shares_green(&old_body[1], &new_body[1]));
+    assert_eq!(new_body[2].to_string(), "let y = sum * 2;");
+    // Everything after the function is shared too
+    assert!(shares_green(&top_level(&old, 2), &top_level(&new, 2)));
+}
+
+#[test]
+fn test_erroneous_edit_inside_block_stays_local() {
+    let offset = PROGRAM.find("return sum").unwrap();
+    let old = parse(PROGRAM);
+    // Half-typed, as in an editor
+    let new = assert_reparse(&old, &TextEdit::new(offset..offset, "let x = ;\n    "));
+    assert!(!new.errors().is_empty());
+    let body = |parse: &Parse| -> Vec<SyntaxNode> {
+        let function = top_level(parse, 1);
+        let block = function
+            .descendants()
+            .find(|node| node.kind() == SyntaxKind::BlockExpr)
+            .unwrap();
+        block.children().collect()
+    };
+    let (old_body, new_body) = (body(&old), body(&new));
+    assert!(shares_green(&old_body[0], &new_body[0]));
+    assert!(shares_green(&old_body[1], &new_body[1]));
+    assert!(shares_green(&top_level(&old, 0), &top_level(&new, 0)));
+    assert!(shares_green(&top_level(&old, 2), &top_level(&new, 2)));
+
+    // Finishing the statement is incremental too
+    let offset = new.syntax().to_string().find("= ;").unwrap() + 2;
+    let fixed = assert_reparse(&new, &TextEdit::new(offset..offset, "1"));
+    assert!(fixed.errors().is_empty());
+    assert!(shares_green(&top_level(&new, 2), &top_level(&fixed, 2)));
+}
+
+#[test]
+fn test_edits_that_cross_statements() {
+    let edits = [
+        // Delete a `;`, merging two statements
+        ("let a = 1; let b = 2;", TextEdit::new(9..10, "")),
+        // Open a comment that hides the rest of the line
+        ("let a = 1; let b = 2;\nc;", TextEdit::new(10..11, "//")),
+        // Open a string that runs to the end of the file
+        ("let a = 1; let b = 2;", TextEdit::new(8..9, "\"")),
+        // Unbalance a block
+        ("fn() { a; b; };\nc;", TextEdit::new(13..14, "")),
+        ("fn() { a; b; };\nc;", TextEdit::new(8..8, "{")),
+        // Replace everything
+        ("let a = 1;", TextEdit::new(0..10, "")),
+        ("", TextEdit::new(0..0, "let a = 1;")),
+        // Move an import into a block
+        ("fn() { a; };", TextEdit::new(7..9, "import \"m\" as m;")),
+        // Fix an error
+        ("let = 1; let b = 2;", TextEdit::new(4..4, "a ")),
+    ];
+    for (source, edit) in &edits {
+        assert_reparse(&parse(source), edit);
+    }
+}
+
+#[test]
+fn test_fixing_an_error_at_the_end_of_the_file() {
+    let old = parse("let x = 1;\nlet y = ");
+    assert!(!old.errors().is_empty());
+    let new = assert_reparse(&old, &TextEdit::new(19..19, "2;"));
+    // The errors at EOF go away with the edit that fixes them
+    assert!(new.errors().is_empty(), "{:?}", new.errors());
+    // Breaking the end again brings them back
+    assert_reparse(&new, &TextEdit::new(19..21, "(2"));
+}
+
+#[test]
+fn test_every_small_edit_matches_a_fresh_parse() {
+    let inserts = ["x", "\n", ";", "{", "}", "\"", "//", "} x; {"];
+    let sources = [
+        PROGRAM,
+        "let a = 1;\nlet = 2;\nfn(x) { x; y z; };\nlet c = 3;",
+        "let a = 1;\nlet b = ",
+    ];
+    for source in sources {
+        let old = parse(source);
+        let offsets: Vec<usize> = source
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain([source.len()])
+            .collect();
+        for (i, &offset) in offsets.iter().enumerate() {
+            for insert in inserts {
+                assert_reparse(&old, &TextEdit::new(offset..offset, insert));
+            }
+            if let Some(&next) = offsets.get(i + 1) {
+                assert_reparse(&old, &TextEdit::new(offset..next, ""));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_successive_edits() {
+    let mut text = String::from("let a = 1;\n");
+    let mut current = parse(&text);
+    // Each edit is (text to find, how much of it to replace, replacement)
+    let edits = [
+        ("\n", 1, "\nlet f = fn(x) {\n};\n"),
+        ("{\n", 2, "{\n    return x;\n"),
+        ("1", 1, "42"),
+        ("let a", 0, "// header\n"),
+        ("};\n", 3, "};\nf(a);\n"),
+    ];
+    for (find, len, replacement) in edits {
+        let offset = text.find(find).unwrap();
+        current = assert_reparse(&current, &TextEdit::new(offset..offset + len, replacement));
+        text = current.syntax().to_string();
+    }
+    assert_eq!(
+        text,
+        "// header\nlet a = 42;\nlet f = fn(x) {\n    return x;\n};\nf(a);\n"
+    );
+    assert!(current.errors().is_empty());
+}
@@ -0,0 +1,26 @@
+use crate::repl::repl;
+
+/// Feeds `lines` to the REPL and returns everything it wrote.
+fn session(lines: &str) -> String {
+    let mut output = Vec::new();
+    repl(lines.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+/// Tests that builtins are known to name resolution and `:type`.
+#[test]
+fn test_builtins() {
+    let output = session("puts(1, \"two\");\n:type len(\"a\");\n:type puts(1, 2);\n");
+    assert!(!output.contains("undefined name"), "{}", output);
+    assert!(output.contains("int\n"), "{}", output);
+    assert!(output.contains("null\n"), "{}", output);
+
+    // Shadowing a builtin gives the binding's type
+    let output = session("let len = fn(a, b) { a + b; };\n:type len(1, 2);\n:type len(\"a\");\n");
+    assert!(output.contains("int\n"), "{}", output);
+    assert!(
+        output.contains("function expects 2 arguments, found 1"),
+        "{}",
+        output
+    );
+}
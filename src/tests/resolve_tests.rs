@@ -0,0 +1,269 @@
+use crate::ast::Program;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, error::Span, test_helper::*};
+use crate::resolve::{BindingKind, ResolveError, Resolver, resolve};
+use crate::source::FileId;
+
+fn parse(input: &str) -> Program {
+    let l = Lexer::new(input.to_string(), FileId::DETACHED);
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    program
+}
+
+/// Returns `(line, column)` of the binding each use resolves to, ordered by use position.
+fn resolved_uses(input: &str) -> Vec<((usize, usize), (usize, usize))> {
+    let resolution = resolve(&parse(input));
+    assert!(
+        resolution.errors.is_empty(),
+        "unexpected errors for {:?}: {:?}",
+        input,
+        resolution.errors
+    );
+    let mut uses: Vec<_> = resolution
+        .uses
+        .iter()
+        .map(|(span, id)| {
+            let binding = &resolution.bindings[*id];
+            (
+                (span.line, span.column),
+                (binding.span.line, binding.span.column),
+            )
+        })
+        .collect();
+    uses.sort();
+    uses
+}
+
+// =============================================================================
+// Side Table Tests
+// =============================================================================
+
+/// Tests that uses resolve to let bindings and parameters.
+#[test]
+fn test_resolve_lets_and_parameters() {
+    let input = "let c = 1;\nlet f = fn(a, b) { a + b; };\nf(c, 2);";
+    assert_eq!(
+        resolved_uses(input),
+        vec![
+            ((2, 20), (2, 12)),
+            ((2, 24), (2, 15)),
+            ((3, 1), (2, 5)),
+            ((3, 3), (1, 5)),
+        ]
+    );
+
+    let resolution = resolve(&parse(input));
+    let kinds: Vec<BindingKind> = resolution.bindings.iter().map(|b| b.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            BindingKind::Let,
+            BindingKind::Let,
+            BindingKind::Parameter,
+            BindingKind::Parameter
+        ]
+    );
+    assert_eq!(resolution.uses_of(0), vec![Span::new(3, 3)]);
+}
+
+/// Tests that block statements open their own scope.
+#[test]
+fn test_resolve_block_scopes() {
+    let input = "let x = 1;\nif (true) { let y = 2; y; };\nx;";
+    assert_eq!(
+        resolved_uses(input),
+        vec![((2, 24), (2, 17)), ((3, 1), (1, 5))]
+    );
+
+    let resolution = resolve(&parse("if (true) { let y = 2; y; };\ny;"));
+    assert!(matches!(
+        &resolution.errors[..],
+        [ResolveError::Undefined { name, .. }] if name == "y"
+    ));
+}
+
+/// Tests that functions may refer to themselves and to later definitions.
+#[test]
+fn test_resolve_recursive_and_later_functions() {
+    let input = "let f = fn(n) { f(g(n)); };\nlet g = fn(n) { n; };";
+    assert_eq!(
+        resolved_uses(input),
+        vec![
+            ((1, 17), (1, 5)),
+            ((1, 19), (2, 5)),
+            ((1, 21), (1, 12)),
+            ((2, 17), (2, 12)),
+        ]
+    );
+}
+
+/// Tests that imports bind their alias and member properties are not resolved.
+#[test]
+fn test_resolve_imports() {
+    let input = "import \"math\" as math;\nmath.add(1, 2);";
+    assert_eq!(resolved_uses(input), vec![((2, 1), (1, 18))]);
+}
+
+// =============================================================================
+// Error Tests
+// =============================================================================
+
+/// Tests that undefined names are reported with suggestions.
+#[test]
+fn test_undefined_names() {
+    let resolution = resolve(&parse("let count = 1;\ncuont + total;"));
+    assert_eq!(
+        resolution.errors,
+        vec![
+            ResolveError::Undefined {
+                span: Span::new(2, 1),
+                name: "cuont".to_string(),
+                suggestion: Some("count".to_string()),
+            },
+            ResolveError::Undefined {
+                span: Span::new(2, 9),
+                name: "total".to_string(),
+                suggestion: None,
+            },
+        ]
+    );
+    assert!(resolution.has_errors());
+    let diagnostic = resolution.errors[0].to_diagnostic();
+    assert_eq!(diagnostic.message, "undefined name 'cuont'");
+    assert_eq!(diagnostic.notes, vec!["did you mean 'count'?"]);
+
+    // One-letter names are one edit from every other one-letter name
+    let suggestions = |input: &str| -> Vec<Option<String>> {
+        resolve(&parse(input))
+            .errors
+            .iter()
+            .map(|error| match error {
+                ResolveError::Undefined { suggestion, .. } => suggestion.clone(),
+                other => panic!("unexpected error {:?}", other),
+            })
+            .collect()
+    };
+    assert_eq!(suggestions("let a = 1;\nb;"), vec![None]);
+    assert_eq!(
+        suggestions("let ab = 1;\nac;\nxy;"),
+        vec![Some("ab".to_string()), None]
+    );
+}
+
+/// Tests that builtins resolve without a binding unless a binding hides them.
+#[test]
+fn test_builtins() {
+    let resolution = resolve(&parse(
+        "puts(len(\"a\"));\nlet first = fn(x) { x; };\nfirst(1);",
+    ));
+    assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+    let mut spans: Vec<&Span> = resolution.uses.keys().collect();
+    spans.sort_by_key(|span| (span.line, span.column));
+    // `x` in the body and the call of the `first` that hides the builtin
+    assert_eq!(spans, vec![&Span::new(2, 21), &Span::new(3, 1)]);
+
+    let resolution = resolve(&parse("lne(\"a\");"));
+    assert_eq!(
+        resolution.errors,
+        vec![ResolveError::Undefined {
+            span: Span::new(1, 1),
+            name: "lne".to_string(),
+            suggestion: Some("len".to_string()),
+        }]
+    );
+}
+
+/// Tests that names used before their let in the same function are reported.
+#[test]
+fn test_use_before_definition() {
+    let tests = [
+        ("x;\nlet x = 1;", (1, 1), (2, 5)),
+        ("let x = x;", (1, 9), (1, 5)),
+        ("fn() { y; let y = 1; };", (1, 8), (1, 15)),
+    ];
+    for (input, use_at, defined_at) in tests {
+        let resolution = resolve(&parse(input));
+        match &resolution.errors[..] {
+            [
+                ResolveError::UseBeforeDefinition {
+                    span, definition, ..
+                },
+            ] => {
+                assert_eq!((span.line, span.column), use_at, "input: {}", input);
+                assert_eq!(
+                    (definition.line, definition.column),
+                    defined_at,
+                    "input: {}",
+                    input
+                );
+                assert_eq!(
+                    resolution.errors[0].to_diagnostic().labels,
+                    vec![(definition.clone(), "defined later here".to_string())]
+                );
+            }
+            errors => panic!("unexpected errors for {:?}: {:?}", input, errors),
+        }
+    }
+}
+
+/// Tests that locals captured before their let are reported, and globals are not.
+#[test]
+fn test_captured_before_definition() {
+    let resolution = resolve(&parse(
+        "let f = fn() { let g = fn() { x; }; let x = 5; g(); };\nlet h = fn() { y; };\nlet y = 1;",
+    ));
+    match &resolution.errors[..] {
+        [
+            ResolveError::CapturedBeforeDefinition {
+                span,
+                name,
+                definition,
+            },
+        ] => {
+            assert_eq!(name, "x");
+            assert_eq!((span.line, span.column), (1, 31));
+            assert_eq!((definition.line, definition.column), (1, 41));
+            assert_eq!(
+                resolution.errors[0].to_string(),
+                "'x' is captured before its definition"
+            );
+        }
+        errors => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+/// Tests that shadowing is reported as a warning.
+#[test]
+fn test_shadowing_warnings() {
+    let resolution = resolve(&parse("let x = 1;\nlet f = fn(x) { x; };"));
+    assert_eq!(
+        resolution.errors,
+        vec![ResolveError::Shadowed {
+            span: Span::new(2, 12),
+            name: "x".to_string(),
+            previous: Span::new(1, 5),
+        }]
+    );
+    assert!(!resolution.has_errors());
+    let diagnostic = resolution.errors[0].to_diagnostic();
+    assert_eq!(
+        diagnostic.labels,
+        vec![(Span::new(1, 5), "previous binding here".to_string())]
+    );
+    assert_eq!(
+        resolution.binding_of(&Span::new(2, 17)).unwrap().kind,
+        BindingKind::Parameter
+    );
+}
+
+/// Tests that the global scope persists across programs.
+#[test]
+fn test_resolver_persists_globals() {
+    let mut resolver = Resolver::new();
+    resolver.resolve_program(&parse("let a = 1;"));
+    resolver.resolve_program(&parse("a;"));
+    assert!(resolver.take_errors().is_empty());
+    assert_eq!(resolver.resolution().uses.len(), 1);
+}
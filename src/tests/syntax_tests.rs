@@ -0,0 +1,233 @@
+use crate::lexer::token::TokenType;
+use crate::syntax::{
+    SyntaxKind,
+    ast::{AstNode, Expr, Stmt},
+    parse,
+};
+
+const PROGRAM: &str = r#"// A small program.
+import "lib/math" as math;
+
+let add = fn(a: int, b) -> int {
+    return a + b; // sum
+};
+export let x: int? = -(add(1, 2) * 3);
+if (x == math.zero) { "zero"; } else { !true; };
+"#;
+
+#[track_caller]
+fn assert_round_trip(input: &str) {
+    assert_eq!(
+        parse(input).syntax().to_string(),
+        input,
+        "input: {:?}",
+        input
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    assert_round_trip(PROGRAM);
+    assert_round_trip("");
+    assert_round_trip("   \n\t// only a comment");
+}
+
+#[test]
+fn test_round_trip_invalid_input() {
+    let inputs = [
+        "let = 5;",
+        "let x 5;",
+        "fn(a, { b; };",
+        "f(1, 2;",
+        "if (x { y; }",
+        "}}}) ;;",
+        "let s = \"unterminated",
+        "let x = 1 @ 2 # 3;",
+        "let é = \"ü\"; ∑;",
+        "let a = 1;\0let b = 2;",
+        "let f: fn(int -> = 1;",
+        "import as x; export fn;",
+    ];
+    for input in inputs {
+        assert!(!parse(input).errors().is_empty(), "input: {:?}", input);
+        assert_round_trip(input);
+    }
+    // Every prefix of a valid program, most of which do not parse
+    for (end, _) in PROGRAM.char_indices() {
+        assert_round_trip(&PROGRAM[..end]);
+    }
+}
+
+#[test]
+fn test_tree_keeps_parentheses_and_trivia() {
+    let parse = parse("let x = (1 + 2) * 3; // c\n");
+    assert_eq!(
+        parse.syntax().debug_tree(),
+        r#"Program@0..26
+  LetStmt@0..20
+    LET@0..3 "let"
+    Whitespace@3..4 " "
+    IDENT@4..5 "x"
+    Whitespace@5..6 " "
+    ASSIGN@6..7 "="
+    Whitespace@7..8 " "
+    InfixExpr@8..19
+      ParenExpr@8..15
+        LPAREN@8..9 "("
+        InfixExpr@9..14
+          Literal@9..10
+            INT@9..10 "1"
+          Whitespace@10..11 " "
+          PLUS@11..12 "+"
+          Whitespace@12..13 " "
+          Literal@13..14
+            INT@13..14 "2"
+        RPAREN@14..15 ")"
+      Whitespace@15..16 " "
+      ASTERISK@16..17 "*"
+      Whitespace@17..18 " "
+      Literal@18..19
+        INT@18..19 "3"
+    SEMICOLON@19..20 ";"
+  Whitespace@20..21 " "
+  Comment@21..25 "// c"
+  Whitespace@25..26 "\n"
+"#
+    );
+}
+
+#[test]
+fn test_failed_statements_become_error_nodes() {
+    let parse = parse("let = 5; x;");
+    let kinds: Vec<SyntaxKind> = parse.syntax().children().map(|n| n.kind()).collect();
+    assert_eq!(
+        kinds,
+        [
+            SyntaxKind::Error,
+            SyntaxKind::Error,
+            SyntaxKind::ExprStmt,
+            SyntaxKind::ExprStmt
+        ]
+    );
+}
+
+#[test]
+fn test_typed_view() {
+    let parse = parse(PROGRAM);
+    assert!(parse.errors().is_empty(), "{:?}", parse.errors());
+    let statements: Vec<Stmt> = parse.root().statements().collect();
+    assert_eq!(statements.len(), 4);
+
+    let Stmt::Import(import) = &statements[0] else {
+        panic!("expected import, got {:?}", statements[0]);
+    };
+    assert_eq!(import.path().unwrap().text(), "\"lib/math\"");
+    assert_eq!(import.alias().unwrap().text(), "math");
+
+    let Stmt::Let(add) = &statements[1] else {
+        panic!("expected let, got {:?}", statements[1]);
+    };
+    assert_eq!(add.name().unwrap().text(), "add");
+    let Some(Expr::Fn(function)) = add.value() else {
+        panic!("expected function");
+    };
+    let params: Vec<(String, Option<String>)> = function
+        .param_list()
+        .unwrap()
+        .params()
+        .map(|p| {
+            (
+                p.name().unwrap().text().to_string(),
+                p.type_annotation().map(|t| t.syntax().to_string()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        params,
+        [
+            ("a".to_string(), Some("int".to_string())),
+            ("b".to_string(), None)
+        ]
+    );
+    assert_eq!(function.return_type().unwrap().syntax().to_string(), "int");
+    let body: Vec<Stmt> = function.body().unwrap().statements().collect();
+    let [Stmt::Return(ret)] = body.as_slice() else {
+        panic!("expected one return, got {:?}", body);
+    };
+    let Some(Expr::Infix(sum)) = ret.value() else {
+        panic!("expected infix");
+    };
+    assert_eq!(sum.op().unwrap().kind(), SyntaxKind::Token(TokenType::PLUS));
+
+    let Stmt::Export(export) = &statements[2] else {
+        panic!("expected export, got {:?}", statements[2]);
+    };
+    let x = export.let_stmt().unwrap();
+    assert_eq!(x.type_annotation().unwrap().syntax().to_string(), "int?");
+    let Some(Expr::Prefix(neg)) = x.value() else {
+        panic!("expected prefix");
+    };
+    let Some(Expr::Paren(paren)) = neg.operand() else {
+        panic!("expected parentheses");
+    };
+    let Some(Expr::Infix(mul)) = paren.expr() else {
+        panic!("expected infix");
+    };
+    let Some(Expr::Call(call)) = mul.lhs() else {
+        panic!("expected call");
+    };
+    assert_eq!(call.callee().unwrap().syntax().to_string(), "add");
+    assert_eq!(call.arg_list().unwrap().args().count(), 2);
+
+    let Stmt::Expr(stmt) = &statements[3] else {
+        panic!("expected expression statement, got {:?}", statements[3]);
+    };
+    let Some(Expr::If(if_expr)) = stmt.expr() else {
+        panic!("expected if");
+    };
+    let Some(Expr::Infix(condition)) = if_expr.condition() else {
+        panic!("expected infix condition");
+    };
+    let Some(Expr::Member(member)) = condition.rhs() else {
+        panic!("expected member access");
+    };
+    assert_eq!(member.property().unwrap().text(), "zero");
+    assert_eq!(
+        if_expr.then_branch().unwrap().syntax().to_string(),
+        "{ \"zero\"; }"
+    );
+    assert_eq!(
+        if_expr.else_branch().unwrap().syntax().to_string(),
+        "{ !true; }"
+    );
+}
+
+#[test]
+fn test_positions_and_parents() {
+    let parse = parse("let x = f(y);");
+    let root = parse.syntax();
+    let y = root.token_at_offset(10).unwrap();
+    assert_eq!(y.text(), "y");
+    assert_eq!(y.text_range(), 10..11);
+    let kinds: Vec<SyntaxKind> = std::iter::once(y.parent())
+        .chain(y.parent().ancestors())
+        .map(|n| n.kind())
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            SyntaxKind::NameRef,
+            SyntaxKind::ArgList,
+            SyntaxKind::CallExpr,
+            SyntaxKind::LetStmt,
+            SyntaxKind::Program,
+        ]
+    );
+    assert_eq!(root.descendants().count(), 6);
+    assert_eq!(
+        root.descendant_tokens()
+            .filter(|t| !t.kind().is_trivia())
+            .count(),
+        8
+    );
+}
@@ -0,0 +1,182 @@
+use crate::ast::Program;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, test_helper::*};
+use crate::source::FileId;
+use crate::typeck::{TypeChecker, TypeError, check_program};
+
+fn parse(input: &str) -> Program {
+    let l = Lexer::new(input.to_string(), FileId::DETACHED);
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    program
+}
+
+fn infer(input: &str) -> String {
+    match check_program(&parse(input)) {
+        Ok(ty) => ty.to_string(),
+        Err(errors) => panic!("unexpected type errors for {:?}: {:?}", input, errors),
+    }
+}
+
+fn type_errors(input: &str) -> Vec<TypeError> {
+    match check_program(&parse(input)) {
+        Ok(ty) => panic!("expected type errors for {:?}, got type {}", input, ty),
+        Err(errors) => errors,
+    }
+}
+
+// =============================================================================
+// Inference Tests
+// =============================================================================
+
+/// Tests inference of literals, prefix and infix expressions.
+#[test]
+fn test_infer_operators() {
+    let tests = [
+        ("5;", "int"),
+        ("true;", "bool"),
+        ("\"monkey\";", "string"),
+        ("-5;", "int"),
+        ("!5;", "bool"),
+        ("1 + 2 * 3;", "int"),
+        ("\"a\" + \"b\";", "string"),
+        ("1 < 2;", "bool"),
+        ("true == false;", "bool"),
+        ("let x = 5; x;", "int"),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(infer(input), expected, "input: {}", input);
+    }
+}
+
+/// Tests inference of if expressions with and without else.
+#[test]
+fn test_infer_if_expressions() {
+    assert_eq!(infer("if (1 < 2) { 10; } else { 20; };"), "int");
+    assert_eq!(infer("if (true) { 10; };"), "null");
+}
+
+/// Tests inference of function literals and calls.
+#[test]
+fn test_infer_functions() {
+    let tests = [
+        ("fn(x) { x; };", "fn('a) -> 'a"),
+        ("fn(x, y) { x + y; };", "fn(int, int) -> int"),
+        ("fn(f, x) { f(x); };", "fn(fn('a) -> 'b, 'a) -> 'b"),
+        ("fn(x) { return x < 1; };", "fn(int) -> bool"),
+        ("let add = fn(a, b) { a + b; }; add(1, 2);", "int"),
+        ("fn(a: bool) -> bool { a; };", "fn(bool) -> bool"),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(infer(input), expected, "input: {}", input);
+    }
+}
+
+/// Tests that let-bound functions are polymorphic.
+#[test]
+fn test_let_polymorphism() {
+    let input = "let id = fn(x) { x; }; id(1); id(true);";
+    assert_eq!(infer(input), "bool");
+
+    let mut checker = TypeChecker::new();
+    checker.check_program(&parse(input));
+    assert_eq!(
+        checker.global_type("id").unwrap().to_string(),
+        "fn('a) -> 'a"
+    );
+}
+
+/// Tests that parameters are monomorphic inside the function body.
+#[test]
+fn test_parameters_are_monomorphic() {
+    let errors = type_errors("fn(f) { f(1); f(true); };");
+    assert_eq!(errors[0].message, "type mismatch: expected int, found bool");
+}
+
+/// Tests recursive functions bound with let.
+#[test]
+fn test_infer_recursive_function() {
+    let input = "let fact = fn(n) { if (n < 2) { 1; } else { n * fact(n - 1); }; }; fact;";
+    assert_eq!(infer(input), "fn(int) -> int");
+}
+
+/// Tests that builtins are typed and can be shadowed.
+#[test]
+fn test_infer_builtins() {
+    let tests = [
+        ("len(\"a\");", "int"),
+        ("fn(xs: [int]) { len(xs); };", "fn([int]) -> int"),
+        ("puts();", "null"),
+        ("puts(1, \"two\", true);", "null"),
+        ("fn(xs: [int]) { first(xs); };", "fn([int]) -> int"),
+        ("fn(xs) { last(xs) + \"b\"; };", "fn([string]) -> string"),
+        ("fn(xs: [bool]) { rest(xs); };", "fn([bool]) -> [bool]"),
+        ("fn(xs) { push(xs, 2); };", "fn([int]) -> [int]"),
+        ("puts;", "fn('a) -> null"),
+        ("let puts = fn(x) { x; }; puts(1);", "int"),
+    ];
+    for (input, expected) in tests {
+        assert_eq!(infer(input), expected, "input: {}", input);
+    }
+
+    let errors = type_errors("fn(xs: [int]) { push(xs, true); };");
+    assert_eq!(errors[0].message, "type mismatch: expected int, found bool");
+    let errors = type_errors("let puts = fn(x) { x; }; puts(1, 2);");
+    assert_eq!(errors[0].message, "function expects 1 argument, found 2");
+}
+
+// =============================================================================
+// Error Tests
+// =============================================================================
+
+/// Tests that type errors carry a message and the span of the offending expression.
+#[test]
+fn test_type_errors() {
+    let tests = [
+        ("1 + true;", "type mismatch: expected int, found bool", 1, 5),
+        ("5(1);", "cannot call a value of type int", 1, 1),
+        (
+            "if (1) { 2; };",
+            "type mismatch: expected bool, found int",
+            1,
+            5,
+        ),
+        (
+            "if (true) { 1; } else { false; };",
+            "type mismatch: expected int, found bool",
+            1,
+            25,
+        ),
+        (
+            "let x: int = true;",
+            "type mismatch: expected int, found bool",
+            1,
+            14,
+        ),
+        ("y;", "unknown identifier 'y'", 1, 1),
+        (
+            "let f = fn(a) { a; }; f(1, 2);",
+            "function expects 1 argument, found 2",
+            1,
+            23,
+        ),
+        ("let x: number = 5;", "unknown type 'number'", 1, 8),
+        (
+            "fn(x) { x(x); };",
+            "cannot construct an infinite type (a value is used as its own argument)",
+            1,
+            9,
+        ),
+    ];
+    for (input, message, line, column) in tests {
+        let errors = type_errors(input);
+        assert_eq!(errors[0].message, message, "input: {}", input);
+        assert_eq!(
+            (errors[0].span.line, errors[0].span.column),
+            (line, column),
+            "input: {}",
+            input
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! The shared corpus: every program runs on both the AST interpreter and the
+//! bytecode VM, which must agree on its value, its output and its errors.
+
+use crate::ast::Program;
+use crate::compiler::compile;
+use crate::evaluator::Evaluator;
+use crate::lexer::Lexer;
+use crate::object::RuntimeError;
+use crate::parser::{Parser, test_helper::*};
+use crate::source::FileId;
+use crate::vm::Vm;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// A writer whose contents can still be read after it is handed to an engine.
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub(crate) fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn parse(input: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(input.to_string(), FileId::DETACHED));
+    let program = p.parse_program();
+    check_parser_errors(&p);
+    program
+}
+
+/// Runs `input` on the evaluator.
+/// # Returns
+/// The displayed value or error message, and what `puts` wrote.
+fn run_evaluator(input: &str) -> (Result<String, String>, String) {
+    let output = SharedBuffer::default();
+    let mut evaluator = Evaluator::with_output(output.clone());
+    let result = evaluator
+        .eval_program(&parse(input))
+        .map(|value| value.to_string())
+        .map_err(|error| error.message);
+    (result, output.contents())
+}
+
+/// Compiles and runs `input` on the VM, like `run_evaluator`.
+fn run_vm(input: &str) -> (Result<String, String>, String) {
+    let output = SharedBuffer::default();
+    let bytecode = match compile(&parse(input)) {
+        Ok(bytecode) => bytecode,
+        Err(error) => return (Err(error.message), String::new()),
+    };
+    let mut vm = Vm::with_output(output.clone());
+    let result = vm
+        .run(&bytecode)
+        .map(|value| value.to_string())
+        .map_err(|error| error.message);
+    (result, output.contents())
+}
+
+/// Checks that both engines produce `expected` for `input`.
+fn assert_agree(input: &str, expected: Result<&str, &str>) {
+    let expected = expected.map(str::to_string).map_err(str::to_string);
+    let (evaluated, evaluator_output) = run_evaluator(input);
+    let (executed, vm_output) = run_vm(input);
+    assert_eq!(evaluated, expected, "evaluator result for {:?}", input);
+    assert_eq!(executed, expected, "vm result for {:?}", input);
+    assert_eq!(evaluator_output, vm_output, "output for {:?}", input);
+}
+
+#[test]
+fn test_literals_and_operators() {
+    let tests = [
+        ("5;", "5"),
+        ("true;", "true"),
+        ("\"monkey\";", "monkey"),
+        ("1 + 2 * 3 - 4 / 2;", "5"),
+        ("-(5 + 5);", "-10"),
+        ("!true;", "false"),
+        ("!!5;", "true"),
+        ("!0;", "false"),
+        ("1 < 2 == true;", "true"),
+        ("3 > 4;", "false"),
+        ("\"a\" + \"b\" == \"ab\";", "true"),
+        ("1 == true;", "false"),
+        ("1 != \"1\";", "true"),
+        ("", "null"),
+        ("let a = 1;", "null"),
+    ];
+    for (input, expected) in tests {
+        assert_agree(input, Ok(expected));
+    }
+}
+
+#[test]
+fn test_conditionals() {
+    let tests = [
+        ("if (true) { 10; };", "10"),
+        ("if (false) { 10; };", "null"),
+        ("if (1 > 2) { 10; } else { 20; };", "20"),
+        ("if (0) { 1; } else { 2; };", "1"),
+        ("if (if (false) { 1; }) { 1; } else { 2; };", "2"),
+        ("if (true) { let a = 1; };", "null"),
+    ];
+    for (input, expected) in tests {
+        assert_agree(input, Ok(expected));
+    }
+}
+
+#[test]
+fn test_bindings_and_scopes() {
+    let tests = [
+        ("let a = 5; let b = a * 2; a + b;", "15"),
+        ("let a = 1; { let a = 2; a; };", "2"),
+        ("let a = 1; { let a = 2; }; a;", "1"),
+        ("let a = 1; { a; let a = 2; a; };", "2"),
+        ("let a = 1; let a = a + 1; a;", "2"),
+    ];
+    for (input, expected) in tests {
+        assert_agree(input, Ok(expected));
+    }
+}
+
+#[test]
+fn test_functions_and_closures() {
+    let tests = [
+        ("let add = fn(a, b) { a + b; }; add(1, 2);", "3"),
+        ("fn() { 5; }();", "5"),
+        ("fn() { }();", "null"),
+        ("fn() { return 1; 2; }();", "1"),
+        (
+            "let f = fn(x) { if (x > 1) { return 1; }; 0; }; f(2) + f(0);",
+            "1",
+        ),
+        (
+            "let adder = fn(a) { fn(b) { a + b; }; }; let addTwo = adder(2); addTwo(3);",
+            "5",
+        ),
+        (
+            "let f = fn(a) { fn(b) { fn(c) { a + b + c; }; }; }; f(1)(2)(3);",
+            "6",
+        ),
+        (
+            "let fib = fn(n) { if (n < 2) { n; } else { fib(n - 1) + fib(n - 2); }; }; fib(15);",
+            "610",
+        ),
+        (
+            "let outer = fn() { let count = fn(n) { if (n == 0) { 0; } else { 1 + count(n - 1); }; }; count(5); }; outer();",
+            "5",
+        ),
+        (
+            "let even = fn(n) { if (n == 0) { true; } else { odd(n - 1); }; }; let odd = fn(n) { if (n == 0) { false; } else { even(n - 1); }; }; even(10);",
+            "true",
+        ),
+        ("let a = 1; let f = fn() { a; }; let a = 2; f();", "1"),
+        ("let f = fn() { 1; }; f;", "<fn f>"),
+        ("fn() { 1; };", "<fn>"),
+        ("len;", "<builtin len>"),
+        ("return 7; 8;", "7"),
+    ];
+    for (input, expected) in tests {
+        assert_agree(input, Ok(expected));
+    }
+}
+
+#[test]
+fn test_builtins() {
+    assert_agree("len(\"four\");", Ok("4"));
+    assert_agree("puts(\"a\", 1 + 1, true);", Ok("null"));
+    let (_, output) = run_vm("puts(\"a\", 1 + 1, true);");
+    assert_eq!(output, "a\n2\ntrue\n");
+}
+
+#[test]
+fn test_runtime_errors() {
+    let tests = [
+        ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+        ("-true;", "unknown operator: -BOOLEAN"),
+        ("\"a\" - \"b\";", "unknown operator: STRING - STRING"),
+        ("1 / 0;", "division by zero"),
+        ("9223372036854775807 + 1;", "integer overflow"),
+        ("1();", "not a function: INTEGER"),
+        (
+            "fn(a) { a; }();",
+            "wrong number of arguments: want=1, got=0",
+        ),
+        ("len(1);", "argument to `len` not supported, got INTEGER"),
+        (
+            "len(\"a\", \"b\");",
+            "wrong number of arguments: want=1, got=2",
+        ),
+        (
+            "first(1);",
+            "argument to `first` must be ARRAY, got INTEGER",
+        ),
+        ("push(\"a\");", "wrong number of arguments: want=2, got=1"),
+        ("let f = fn(n) { f(n + 1); }; f(0);", "stack overflow"),
+        ("x;", "identifier not found: x"),
+        ("import \"m\" as m;", "import is not supported"),
+        (
+            "let a = fn() { 1; }; a.b;",
+            "member access is not supported",
+        ),
+        (
+            "let f = fn() { let g = fn() { x; }; let x = 5; g(); }; puts(f());",
+            "'x' is captured before its definition",
+        ),
+    ];
+    for (input, expected) in tests {
+        assert_agree(input, Err(expected));
+    }
+}
+
+#[test]
+fn test_output_before_an_error_matches() {
+    assert_agree(
+        "let f = fn(x) { puts(x); x + true; }; f(1);",
+        Err("type mismatch: INTEGER + BOOLEAN"),
+    );
+}
+
+#[test]
+fn test_vm_globals_persist_between_runs() {
+    let mut compiler = crate::compiler::Compiler::new();
+    let mut vm = Vm::with_output(io::sink());
+    compiler.compile_program(&parse("let a = 40;")).unwrap();
+    vm.run(&compiler.bytecode()).unwrap();
+    let mut evaluator = Evaluator::with_output(io::sink());
+    evaluator.eval_program(&parse("let a = 40;")).unwrap();
+
+    let program = parse("a + 2;");
+    compiler.compile_program(&program).unwrap();
+    assert_eq!(vm.run(&compiler.bytecode()).unwrap().to_string(), "42");
+    assert_eq!(evaluator.eval_program(&program).unwrap().to_string(), "42");
+}
+
+/// Runs `input` on both engines and returns the errors they stop with.
+fn run_errors(input: &str) -> (RuntimeError, RuntimeError) {
+    let program = parse(input);
+    let evaluated = Evaluator::with_output(io::sink())
+        .eval_program(&program)
+        .unwrap_err();
+    let executed = Vm::with_output(io::sink())
+        .run(&compile(&program).unwrap())
+        .unwrap_err();
+    (evaluated, executed)
+}
+
+#[test]
+fn test_error_spans_and_traces_agree() {
+    let tests = [
+        "1 + true;",
+        "let f = fn(a, b) {\n  a / b;\n};\nf(1, 0);",
+        "let inner = fn(x) { -x; };\nlet outer = fn(x) { inner(x); };\nfn() { outer(true); }();",
+        "let f = fn() { 1 / 0; };\nlet g = fn() { f(); };\ng();",
+        "let f = fn(n) { f(n + 1); };\nf(0);",
+        "let f = fn(a) { a; };\nf(1, 2);",
+        "let x = 1;\nx(2);",
+        "len(1);",
+    ];
+    for input in tests {
+        let (evaluated, executed) = run_errors(input);
+        assert!(evaluated.span.is_some(), "no span for {:?}", input);
+        assert_eq!(evaluated.span, executed.span, "span for {:?}", input);
+        assert_eq!(evaluated.trace, executed.trace, "trace for {:?}", input);
+    }
+}
@@ -0,0 +1,635 @@
+//! Static type checking with Hindley-Milner inference.
+//!
+//! The checker walks an `ast::Program` and infers a type for every expression
+//! without requiring annotations. `let` bindings are generalized, so
+//! `let id = fn(x) { x; };` can be used at several types. Annotations written
+//! with the syntax from `ast::types` are honoured and must agree with the
+//! inferred types.
+//!
+//! Mismatches such as `1 + true` or calling a non-function are collected as
+//! `TypeError`s, each carrying the `Span` of the offending expression. They
+//! convert into `Diagnostic`s for rendering.
+//!
+//! The builtins from `object::BUILTINS` are bound in the global scope, so a
+//! program may shadow them like any other name. `puts` takes any number of
+//! arguments, which a function type cannot express, so its calls are checked
+//! on their own. `first` and `last` are typed as returning the element, even
+//! though they give `null` for an empty array.
+
+pub mod types;
+
+use crate::ast::{
+    Program,
+    expression::{
+        BlockStatement, CallExpression, Expression, FunctionLiteral, IfExpression, InfixExpression,
+        PrefixExpression,
+    },
+    statement::{LetStatement, Statement},
+    types::TypeExpr,
+};
+use crate::diagnostic::Diagnostic;
+use crate::object::BUILTINS;
+use crate::parser::error::Span;
+use std::collections::HashMap;
+use std::fmt;
+pub use types::{Scheme, Type, TypeVar};
+
+/// A type error found while checking a program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl TypeError {
+    /// Converts the error into a renderable diagnostic.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.span.clone(), self.message.clone())
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.span, self.message)
+    }
+}
+
+/// Checks a whole program with a fresh checker.
+///
+/// # Returns
+/// - `Ok(Type)` with the type of the last statement if there are no errors
+/// - `Err(Vec<TypeError>)` with every error found
+pub fn check_program(program: &Program) -> Result<Type, Vec<TypeError>> {
+    let mut checker = TypeChecker::new();
+    let ty = checker.check_program(program);
+    let errors = checker.take_errors();
+    if errors.is_empty() {
+        Ok(ty)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Why two types failed to unify.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnifyError {
+    Mismatch,
+    /// A variable would have to contain itself, e.g. `'a = fn('a) -> 'b`.
+    Infinite,
+}
+
+/// A Hindley-Milner type checker.
+///
+/// Bindings made by checked programs persist, so one checker can be fed
+/// successive REPL inputs.
+#[derive(Debug, Clone)]
+pub struct TypeChecker {
+    /// Lexical scopes, innermost last.
+    scopes: Vec<HashMap<String, Scheme>>,
+    /// Solution of each type variable, indexed by variable id.
+    bindings: Vec<Option<Type>>,
+    /// Return type of each enclosing function, innermost last.
+    return_types: Vec<Type>,
+    errors: Vec<TypeError>,
+    /// The scheme each builtin was bound with, to tell it from a binding
+    /// that shadows it.
+    builtins: HashMap<String, Scheme>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeChecker {
+    /// Creates a checker whose global scope holds only the builtins.
+    pub fn new() -> Self {
+        let mut checker = Self {
+            scopes: vec![HashMap::new()],
+            bindings: Vec::new(),
+            return_types: Vec::new(),
+            errors: Vec::new(),
+            builtins: HashMap::new(),
+        };
+        for builtin in &BUILTINS {
+            let scheme = checker.builtin_scheme(builtin.name);
+            checker.bind(builtin.name, scheme.clone());
+            checker.builtins.insert(builtin.name.to_string(), scheme);
+        }
+        checker
+    }
+
+    /// Returns the type of a builtin, generalized over fresh variables.
+    fn builtin_scheme(&mut self, name: &str) -> Scheme {
+        let a = self.fresh_var();
+        let array = Type::Array(Box::new(a.clone()));
+        let function = |params: Vec<Type>, ret: Type| Type::Function(params, Box::new(ret));
+        let ty = match name {
+            "len" => function(vec![a.clone()], Type::Int),
+            "first" | "last" => function(vec![array], a.clone()),
+            "rest" => function(vec![array.clone()], array),
+            "push" => function(vec![array.clone(), a.clone()], array),
+            // A single argument when `puts` is passed around as a value;
+            // calls of it take any number, see `infer_call`
+            _ => function(vec![a.clone()], Type::Null),
+        };
+        let Type::Var(var) = a else {
+            unreachable!("fresh_var returns a variable")
+        };
+        Scheme {
+            vars: vec![var],
+            ty,
+        }
+    }
+
+    /// Returns the errors collected so far.
+    pub fn errors(&self) -> &[TypeError] {
+        &self.errors
+    }
+
+    /// Removes and returns the errors collected so far.
+    pub fn take_errors(&mut self) -> Vec<TypeError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Returns the generalized type of a global binding, if there is one.
+    pub fn global_type(&self, name: &str) -> Option<Type> {
+        self.scopes[0]
+            .get(name)
+            .map(|scheme| self.resolve(&scheme.ty))
+    }
+
+    /// Checks every statement of the program and returns the type of the last one.
+    ///
+    /// Errors are collected and can be read with `errors` or `take_errors`.
+    pub fn check_program(&mut self, program: &Program) -> Type {
+        let mut last = Type::Null;
+        for stmt in &program.statements {
+            last = self.infer_statement(stmt);
+        }
+        self.resolve(&last)
+    }
+
+    // ============ STATEMENTS ============
+
+    fn infer_statement(&mut self, stmt: &Statement) -> Type {
+        match stmt {
+            Statement::Let(let_stmt) => {
+                self.infer_let(let_stmt);
+                Type::Null
+            }
+            Statement::Export(export) => {
+                self.infer_let(&export.statement);
+                Type::Null
+            }
+            Statement::Return(ret) => {
+                let ty = match &ret.value {
+                    Some(value) => self.infer_expression(value),
+                    None => Type::Null,
+                };
+                if let Some(expected) = self.return_types.last().cloned() {
+                    let span = ret
+                        .value
+                        .as_ref()
+                        .map(expression_span)
+                        .unwrap_or_else(|| Span::from_token(&ret.token));
+                    self.unify_at(&expected, &ty, span);
+                }
+                // Control never continues past a return, so it fits any context
+                self.fresh_var()
+            }
+            Statement::Expression(stmt) => self.infer_expression(&stmt.value),
+            Statement::Import(import) => {
+                // Module members are not typed yet, so the alias is left open
+                let ty = self.fresh_var();
+                self.bind(&import.alias.value, Scheme::mono(ty));
+                Type::Null
+            }
+        }
+    }
+
+    /// Infers a let binding and generalizes it (let-polymorphism).
+    fn infer_let(&mut self, let_stmt: &LetStatement) {
+        let annotation = let_stmt
+            .type_annotation
+            .as_ref()
+            .and_then(|t| self.convert_annotation(t));
+
+        let ty = match &let_stmt.value {
+            Some(value) => {
+                // Functions may refer to themselves, so bind the name first
+                let recursive = matches!(value, Expression::FunctionLiteral(_));
+                let placeholder = self.fresh_var();
+                if recursive {
+                    self.push_scope();
+                    self.bind(&let_stmt.name.value, Scheme::mono(placeholder.clone()));
+                }
+                let ty = self.infer_expression(value);
+                if recursive {
+                    self.pop_scope();
+                    self.unify_at(&placeholder, &ty, expression_span(value));
+                }
+                if let Some(annotation) = &annotation {
+                    self.unify_at(annotation, &ty, expression_span(value));
+                }
+                ty
+            }
+            None => annotation.unwrap_or_else(|| self.fresh_var()),
+        };
+
+        let scheme = self.generalize(&ty);
+        self.bind(&let_stmt.name.value, scheme);
+    }
+
+    // ============ EXPRESSIONS ============
+
+    fn infer_expression(&mut self, expr: &Expression) -> Type {
+        match expr {
+            Expression::IntegerLiteral(_) => Type::Int,
+            Expression::BooleanLiteral(_) => Type::Bool,
+            Expression::StringLiteral(_) => Type::String,
+            Expression::Identifier(ident) => match self.lookup(&ident.value) {
+                Some(scheme) => self.instantiate(&scheme),
+                None => {
+                    self.error(
+                        Span::from_token(&ident.token),
+                        format!("unknown identifier '{}'", ident.value),
+                    );
+                    self.fresh_var()
+                }
+            },
+            Expression::PrefixExpression(prefix) => self.infer_prefix(prefix),
+            Expression::InfixExpression(infix) => self.infer_infix(infix),
+            Expression::IfExpression(if_expr) => self.infer_if(if_expr),
+            Expression::BlockStatement(block) => self.infer_block(block),
+            Expression::FunctionLiteral(function) => self.infer_function(function),
+            Expression::CallExpression(call) => self.infer_call(call),
+            Expression::MemberExpression(member) => {
+                self.infer_expression(&member.object);
+                self.fresh_var()
+            }
+        }
+    }
+
+    fn infer_prefix(&mut self, prefix: &PrefixExpression) -> Type {
+        let right = self.infer_expression(&prefix.right);
+        match prefix.operator.as_str() {
+            "-" => {
+                self.unify_at(&Type::Int, &right, expression_span(&prefix.right));
+                Type::Int
+            }
+            // `!` accepts any value and negates its truthiness
+            _ => Type::Bool,
+        }
+    }
+
+    fn infer_infix(&mut self, infix: &InfixExpression) -> Type {
+        let left = self.infer_expression(&infix.left);
+        let right = self.infer_expression(&infix.right);
+        let left_span = expression_span(&infix.left);
+        let right_span = expression_span(&infix.right);
+        match infix.operator.as_str() {
+            "+" => {
+                // `+` also concatenates strings; use strings only when one side already is
+                let operand = if self.resolve(&left) == Type::String
+                    || self.resolve(&right) == Type::String
+                {
+                    Type::String
+                } else {
+                    Type::Int
+                };
+                self.unify_at(&operand, &left, left_span);
+                self.unify_at(&operand, &right, right_span);
+                operand
+            }
+            "-" | "*" | "/" => {
+                self.unify_at(&Type::Int, &left, left_span);
+                self.unify_at(&Type::Int, &right, right_span);
+                Type::Int
+            }
+            "<" | ">" => {
+                self.unify_at(&Type::Int, &left, left_span);
+                self.unify_at(&Type::Int, &right, right_span);
+                Type::Bool
+            }
+            _ => {
+                // `==` and `!=` compare values of the same type
+                self.unify_at(&left, &right, right_span);
+                Type::Bool
+            }
+        }
+    }
+
+    fn infer_if(&mut self, if_expr: &IfExpression) -> Type {
+        let condition = self.infer_expression(&if_expr.condition);
+        self.unify_at(&Type::Bool, &condition, expression_span(&if_expr.condition));
+        let consequence = self.infer_expression(&if_expr.consequence);
+        match &if_expr.alternative {
+            Some(alternative) => {
+                let other = self.infer_expression(alternative);
+                self.unify_at(&consequence, &other, value_span(alternative));
+                consequence
+            }
+            None => Type::Null,
+        }
+    }
+
+    fn infer_block(&mut self, block: &BlockStatement) -> Type {
+        self.push_scope();
+        let mut last = Type::Null;
+        for stmt in &block.statements {
+            last = self.infer_statement(stmt);
+        }
+        self.pop_scope();
+        last
+    }
+
+    fn infer_function(&mut self, function: &FunctionLiteral) -> Type {
+        self.push_scope();
+        let mut params = Vec::new();
+        for (i, param) in function.parameters.iter().enumerate() {
+            let annotated = function
+                .parameter_types
+                .get(i)
+                .and_then(|t| t.as_ref())
+                .and_then(|t| self.convert_annotation(t));
+            let ty = annotated.unwrap_or_else(|| self.fresh_var());
+            self.bind(&param.value, Scheme::mono(ty.clone()));
+            params.push(ty);
+        }
+
+        let ret = match &function.return_type {
+            Some(annotation) => self
+                .convert_annotation(annotation)
+                .unwrap_or_else(|| self.fresh_var()),
+            None => self.fresh_var(),
+        };
+        self.return_types.push(ret.clone());
+        let body = self.infer_block(&function.body);
+        self.return_types.pop();
+        self.unify_at(&ret, &body, block_span(&function.body));
+        self.pop_scope();
+
+        Type::Function(params, Box::new(ret))
+    }
+
+    fn infer_call(&mut self, call: &CallExpression) -> Type {
+        if let Expression::Identifier(ident) = call.function.as_ref()
+            && ident.value == "puts"
+            && self.lookup(&ident.value).as_ref() == self.builtins.get("puts")
+        {
+            for arg in &call.arguments {
+                self.infer_expression(arg);
+            }
+            return Type::Null;
+        }
+        let callee = self.infer_expression(&call.function);
+        let args: Vec<Type> = call
+            .arguments
+            .iter()
+            .map(|arg| self.infer_expression(arg))
+            .collect();
+        let callee_span = expression_span(&call.function);
+
+        match self.resolve(&callee) {
+            Type::Function(params, ret) => {
+                if params.len() != args.len() {
+                    self.error(
+                        callee_span,
+                        format!(
+                            "function expects {} argument{}, found {}",
+                            params.len(),
+                            if params.len() == 1 { "" } else { "s" },
+                            args.len()
+                        ),
+                    );
+                    return *ret;
+                }
+                for ((param, arg), expr) in params.iter().zip(&args).zip(&call.arguments) {
+                    self.unify_at(param, arg, expression_span(expr));
+                }
+                *ret
+            }
+            Type::Var(_) => {
+                let ret = self.fresh_var();
+                let expected = Type::Function(args, Box::new(ret.clone()));
+                self.unify_at(&expected, &callee, callee_span);
+                ret
+            }
+            other => {
+                self.error(
+                    callee_span,
+                    format!("cannot call a value of type {}", other),
+                );
+                self.fresh_var()
+            }
+        }
+    }
+
+    // ============ ANNOTATIONS ============
+
+    /// Converts a written annotation into a type, reporting unknown type names.
+    fn convert_annotation(&mut self, annotation: &TypeExpr) -> Option<Type> {
+        Some(match annotation {
+            TypeExpr::Named(named) => match named.name.as_str() {
+                "int" => Type::Int,
+                "bool" => Type::Bool,
+                "string" => Type::String,
+                "null" => Type::Null,
+                other => {
+                    self.error(
+                        Span::from_token(&named.token),
+                        format!("unknown type '{}'", other),
+                    );
+                    return None;
+                }
+            },
+            TypeExpr::Array(array) => {
+                Type::Array(Box::new(self.convert_annotation(&array.element)?))
+            }
+            TypeExpr::Hash(hash) => Type::Hash(
+                Box::new(self.convert_annotation(&hash.key)?),
+                Box::new(self.convert_annotation(&hash.value)?),
+            ),
+            TypeExpr::Function(function) => {
+                let mut params = Vec::new();
+                for param in &function.parameters {
+                    params.push(self.convert_annotation(param)?);
+                }
+                let ret = self.convert_annotation(&function.return_type)?;
+                Type::Function(params, Box::new(ret))
+            }
+            TypeExpr::Optional(optional) => {
+                Type::Optional(Box::new(self.convert_annotation(&optional.inner)?))
+            }
+        })
+    }
+
+    // ============ ENVIRONMENT ============
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn bind(&mut self, name: &str, scheme: Scheme) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), scheme);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Scheme> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    // ============ UNIFICATION ============
+
+    fn fresh_var(&mut self) -> Type {
+        let var = self.bindings.len() as TypeVar;
+        self.bindings.push(None);
+        Type::Var(var)
+    }
+
+    /// Applies the current solution to a type, as deeply as it is known.
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(v) => match &self.bindings[*v as usize] {
+                Some(bound) => self.resolve(bound),
+                None => ty.clone(),
+            },
+            Type::Int | Type::Bool | Type::String | Type::Null => ty.clone(),
+            Type::Array(element) => Type::Array(Box::new(self.resolve(element))),
+            Type::Optional(inner) => Type::Optional(Box::new(self.resolve(inner))),
+            Type::Hash(key, value) => {
+                Type::Hash(Box::new(self.resolve(key)), Box::new(self.resolve(value)))
+            }
+            Type::Function(params, ret) => Type::Function(
+                params.iter().map(|p| self.resolve(p)).collect(),
+                Box::new(self.resolve(ret)),
+            ),
+        }
+    }
+
+    /// Unifies `expected` with `found`, reporting a mismatch at `span`.
+    fn unify_at(&mut self, expected: &Type, found: &Type, span: Span) {
+        let message = match self.unify(expected, found) {
+            Ok(()) => return,
+            Err(UnifyError::Mismatch) => format!(
+                "type mismatch: expected {}, found {}",
+                self.resolve(expected),
+                self.resolve(found)
+            ),
+            Err(UnifyError::Infinite) => {
+                "cannot construct an infinite type (a value is used as its own argument)"
+                    .to_string()
+            }
+        };
+        self.error(span, message);
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> Result<(), UnifyError> {
+        let a = self.resolve(a);
+        let b = self.resolve(b);
+        match (&a, &b) {
+            (Type::Var(x), Type::Var(y)) if x == y => Ok(()),
+            (Type::Var(v), other) | (other, Type::Var(v)) => {
+                // The occurs check rejects infinite types like 'a = fn('a) -> int
+                if other.contains_var(*v) {
+                    return Err(UnifyError::Infinite);
+                }
+                self.bindings[*v as usize] = Some(other.clone());
+                Ok(())
+            }
+            (Type::Int, Type::Int)
+            | (Type::Bool, Type::Bool)
+            | (Type::String, Type::String)
+            | (Type::Null, Type::Null) => Ok(()),
+            (Type::Array(x), Type::Array(y)) | (Type::Optional(x), Type::Optional(y)) => {
+                self.unify(x, y)
+            }
+            (Type::Hash(k1, v1), Type::Hash(k2, v2)) => {
+                self.unify(k1, k2)?;
+                self.unify(v1, v2)
+            }
+            (Type::Function(p1, r1), Type::Function(p2, r2)) if p1.len() == p2.len() => {
+                for (x, y) in p1.iter().zip(p2) {
+                    self.unify(x, y)?;
+                }
+                self.unify(r1, r2)
+            }
+            _ => Err(UnifyError::Mismatch),
+        }
+    }
+
+    // ============ GENERALIZATION ============
+
+    /// Quantifies the variables of `ty` that are not fixed by the environment.
+    fn generalize(&self, ty: &Type) -> Scheme {
+        let ty = self.resolve(ty);
+        let mut env_vars = Vec::new();
+        for scope in &self.scopes {
+            for scheme in scope.values() {
+                let mut vars = Vec::new();
+                self.resolve(&scheme.ty).free_vars(&mut vars);
+                env_vars.extend(vars.into_iter().filter(|v| !scheme.vars.contains(v)));
+            }
+        }
+        let mut vars = Vec::new();
+        ty.free_vars(&mut vars);
+        vars.retain(|v| !env_vars.contains(v));
+        Scheme { vars, ty }
+    }
+
+    /// Replaces the quantified variables of a scheme with fresh ones.
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        let mapping: HashMap<TypeVar, Type> =
+            scheme.vars.iter().map(|v| (*v, self.fresh_var())).collect();
+        self.resolve(&scheme.ty).substitute(&mapping)
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        log::debug!("{} {}", span, message);
+        self.errors.push(TypeError { span, message });
+    }
+}
+
+/// Returns the span where an expression starts in the source.
+fn expression_span(expr: &Expression) -> Span {
+    Span::from_token(expr.first_token())
+}
+
+/// Returns the span of the statement producing a block's value, or of the block if empty.
+fn block_span(block: &BlockStatement) -> Span {
+    block
+        .statements
+        .last()
+        .map(statement_span)
+        .unwrap_or_else(|| Span::from_token(&block.token))
+}
+
+/// Like `expression_span`, but points into blocks at the statement producing their value.
+fn value_span(expr: &Expression) -> Span {
+    match expr {
+        Expression::BlockStatement(block) => block_span(block),
+        _ => expression_span(expr),
+    }
+}
+
+/// Returns the span where a statement starts in the source.
+fn statement_span(stmt: &Statement) -> Span {
+    match stmt {
+        Statement::Expression(stmt) => expression_span(&stmt.value),
+        Statement::Let(stmt) => Span::from_token(&stmt.token),
+        Statement::Return(stmt) => Span::from_token(&stmt.token),
+        Statement::Import(stmt) => Span::from_token(&stmt.token),
+        Statement::Export(stmt) => Span::from_token(&stmt.token),
+    }
+}
@@ -0,0 +1,172 @@
+//! Types and type schemes used by the type checker.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Identifier of a type variable.
+pub type TypeVar = u32;
+
+/// A Monkey type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Bool,
+    String,
+    /// The type of expressions that produce no value (e.g., an `if` without `else`).
+    Null,
+    /// An unknown type to be solved by unification.
+    Var(TypeVar),
+    Array(Box<Type>),
+    Hash(Box<Type>, Box<Type>),
+    Function(Vec<Type>, Box<Type>),
+    Optional(Box<Type>),
+}
+
+impl Type {
+    /// Collects the type variables occurring in the type, in order of appearance.
+    pub fn free_vars(&self, out: &mut Vec<TypeVar>) {
+        match self {
+            Type::Int | Type::Bool | Type::String | Type::Null => {}
+            Type::Var(v) => {
+                if !out.contains(v) {
+                    out.push(*v);
+                }
+            }
+            Type::Array(element) | Type::Optional(element) => element.free_vars(out),
+            Type::Hash(key, value) => {
+                key.free_vars(out);
+                value.free_vars(out);
+            }
+            Type::Function(params, ret) => {
+                for param in params {
+                    param.free_vars(out);
+                }
+                ret.free_vars(out);
+            }
+        }
+    }
+
+    /// Returns true if the type variable `var` occurs in the type.
+    pub fn contains_var(&self, var: TypeVar) -> bool {
+        let mut vars = Vec::new();
+        self.free_vars(&mut vars);
+        vars.contains(&var)
+    }
+
+    /// Replaces type variables according to `mapping`, leaving others untouched.
+    pub fn substitute(&self, mapping: &HashMap<TypeVar, Type>) -> Type {
+        match self {
+            Type::Var(v) => mapping.get(v).cloned().unwrap_or(Type::Var(*v)),
+            Type::Int | Type::Bool | Type::String | Type::Null => self.clone(),
+            Type::Array(element) => Type::Array(Box::new(element.substitute(mapping))),
+            Type::Optional(inner) => Type::Optional(Box::new(inner.substitute(mapping))),
+            Type::Hash(key, value) => Type::Hash(
+                Box::new(key.substitute(mapping)),
+                Box::new(value.substitute(mapping)),
+            ),
+            Type::Function(params, ret) => Type::Function(
+                params.iter().map(|p| p.substitute(mapping)).collect(),
+                Box::new(ret.substitute(mapping)),
+            ),
+        }
+    }
+
+    /// Writes the type, naming variables `'a`, `'b`, ... in order of appearance.
+    fn fmt_with_names(&self, f: &mut fmt::Formatter<'_>, names: &[TypeVar]) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Null => write!(f, "null"),
+            Type::Var(v) => {
+                let index = names.iter().position(|n| n == v).unwrap_or(0);
+                write!(f, "'{}", var_name(index))
+            }
+            Type::Array(element) => {
+                write!(f, "[")?;
+                element.fmt_with_names(f, names)?;
+                write!(f, "]")
+            }
+            Type::Hash(key, value) => {
+                write!(f, "{{")?;
+                key.fmt_with_names(f, names)?;
+                write!(f, ": ")?;
+                value.fmt_with_names(f, names)?;
+                write!(f, "}}")
+            }
+            Type::Function(params, ret) => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    param.fmt_with_names(f, names)?;
+                }
+                write!(f, ") -> ")?;
+                ret.fmt_with_names(f, names)
+            }
+            Type::Optional(inner) => {
+                if matches!(inner.as_ref(), Type::Function(..)) {
+                    write!(f, "(")?;
+                    inner.fmt_with_names(f, names)?;
+                    write!(f, ")?")
+                } else {
+                    inner.fmt_with_names(f, names)?;
+                    write!(f, "?")
+                }
+            }
+        }
+    }
+}
+
+/// Returns `a`, `b`, ..., `z`, `a1`, `b1`, ... for the given index.
+fn var_name(index: usize) -> String {
+    let letter = (b'a' + (index % 26) as u8) as char;
+    match index / 26 {
+        0 => letter.to_string(),
+        n => format!("{}{}", letter, n),
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Vec::new();
+        self.free_vars(&mut names);
+        self.fmt_with_names(f, &names)
+    }
+}
+
+/// A type generalized over some of its variables, e.g. `forall 'a. fn('a) -> 'a`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scheme {
+    pub vars: Vec<TypeVar>,
+    pub ty: Type,
+}
+
+impl Scheme {
+    /// A scheme with no quantified variables.
+    pub fn mono(ty: Type) -> Self {
+        Self {
+            vars: Vec::new(),
+            ty,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_display_names_variables_in_order() {
+        let ty = Type::Function(
+            vec![Type::Var(7), Type::Array(Box::new(Type::Var(3)))],
+            Box::new(Type::Optional(Box::new(Type::Var(7)))),
+        );
+        assert_eq!(ty.to_string(), "fn('a, ['b]) -> 'a?");
+        assert_eq!(
+            Type::Hash(Box::new(Type::String), Box::new(Type::Int)).to_string(),
+            "{string: int}"
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! A stack virtual machine that runs `compiler` bytecode.
+//!
+//! Values live on a single operand stack. Each call pushes a `Frame` holding
+//! the running closure, its instruction pointer and the stack slot where
+//! its arguments and locals start (the base pointer); the callee itself sits
+//! just below. Returning truncates the stack back below the callee and
+//! pushes the result.
+//!
+//! Globals persist between runs, so a REPL can compile each line with the
+//! same `Compiler` and run it on the same `Vm`. Runtime errors carry the
+//! same messages as the `evaluator`'s, and the same spans and trace when the
+//! bytecode has a span table.
+//!
+//! Runs are bounded by the evaluator's `Limits`. A step is one instruction
+//! here rather than one expression, so a step limit stops the two engines
+//! at different points of the same program.
+
+use crate::code::{Opcode, span_at};
+use crate::compiler::Bytecode;
+use crate::evaluator::{CLOCK_INTERVAL, Limits, allocation_size};
+use crate::object::{
+    self, BUILTINS, Closure, CompiledFunction, Limit, Object, RuntimeError, TraceFrame,
+};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::Instant;
+
+/// The most values the operand stack holds at once.
+pub const STACK_SIZE: usize = 2048;
+
+/// A function call in progress.
+#[derive(Debug)]
+struct Frame {
+    closure: Rc<Closure>,
+    /// The offset of the next instruction to run.
+    ip: usize,
+    /// The stack index of the first argument.
+    base_pointer: usize,
+}
+
+/// Runs bytecode.
+pub struct Vm {
+    constants: Vec<Object>,
+    globals: Vec<Option<Object>>,
+    stack: Vec<Object>,
+    frames: Vec<Frame>,
+    /// Where `puts` writes.
+    output: Box<dyn Write>,
+    limits: Limits,
+    /// What the current run has used so far.
+    steps: u64,
+    allocated: usize,
+    deadline: Option<Instant>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    /// A VM whose `puts` writes to stdout.
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// A VM whose `puts` writes to `output`.
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        Vm {
+            constants: Vec::new(),
+            globals: Vec::new(),
+            stack: Vec::with_capacity(STACK_SIZE),
+            frames: Vec::new(),
+            output: Box::new(output),
+            limits: Limits::default(),
+            steps: 0,
+            allocated: 0,
+            deadline: None,
+        }
+    }
+
+    /// Bounds every later run by `limits`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Runs a compiled program.
+    /// # Returns
+    /// The value of the program, or the error that stopped it.
+    pub fn run(&mut self, bytecode: &Bytecode) -> Result<Object, RuntimeError> {
+        self.steps = 0;
+        self.allocated = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.constants = bytecode.constants.clone();
+        let main = CompiledFunction {
+            name: None,
+            instructions: bytecode.instructions.clone(),
+            num_locals: 0,
+            num_parameters: 0,
+            spans: bytecode.spans.clone(),
+        };
+        self.frames.push(Frame {
+            closure: Rc::new(Closure {
+                function: Rc::new(main),
+                free: Vec::new(),
+            }),
+            ip: 0,
+            base_pointer: 0,
+        });
+        let mut failed_at = 0;
+        let result = self
+            .execute(&mut failed_at)
+            .map_err(|error| self.locate(error, failed_at));
+        self.stack.clear();
+        self.frames.clear();
+        result
+    }
+
+    /// Runs the frames until the main one returns.
+    /// # Parameters
+    /// - `current`: Set to the offset of each instruction before it runs, so
+    ///   an error can be located in the top frame
+    fn execute(&mut self, current: &mut usize) -> Result<Object, RuntimeError> {
+        loop {
+            let frame = self.frames.last().expect("a running frame");
+            let closure = Rc::clone(&frame.closure);
+            let base_pointer = frame.base_pointer;
+            let instructions = &closure.function.instructions;
+            let mut ip = frame.ip;
+            // Runs the frame until it calls or returns
+            loop {
+                *current = ip;
+                self.step()?;
+                let op = instructions
+                    .get(ip)
+                    .and_then(|&byte| Opcode::from_byte(byte))
+                    .ok_or_else(invalid_bytecode)?;
+                let operands = ip + 1;
+                ip += op.width();
+                match op {
+                    Opcode::Constant => {
+                        let index = operand(instructions, operands, 2)?;
+                        let value = self.constants.get(index).ok_or_else(invalid_bytecode)?;
+                        let value = value.clone();
+                        self.allocate(&value)?;
+                        self.push(value)?;
+                    }
+                    Opcode::Pop => {
+                        self.pop()?;
+                    }
+                    Opcode::Add
+                    | Opcode::Sub
+                    | Opcode::Mul
+                    | Opcode::Div
+                    | Opcode::Equal
+                    | Opcode::NotEqual
+                    | Opcode::GreaterThan
+                    | Opcode::LessThan => {
+                        let right = self.pop()?;
+                        let left = self.pop()?;
+                        let value = object::infix(operator(op), &left, &right)?;
+                        self.allocate(&value)?;
+                        self.push(value)?;
+                    }
+                    Opcode::Minus | Opcode::Bang => {
+                        let right = self.pop()?;
+                        self.push(object::prefix(operator(op), &right)?)?;
+                    }
+                    Opcode::True => self.push(Object::Boolean(true))?,
+                    Opcode::False => self.push(Object::Boolean(false))?,
+                    Opcode::Null => self.push(Object::Null)?,
+                    Opcode::Jump => ip = operand(instructions, operands, 2)?,
+                    Opcode::JumpNotTruthy => {
+                        let target = operand(instructions, operands, 2)?;
+                        if !self.pop()?.is_truthy() {
+                            ip = target;
+                        }
+                    }
+                    Opcode::GetGlobal => {
+                        let index = operand(instructions, operands, 2)?;
+                        let value =
+                            self.globals.get(index).cloned().flatten().ok_or_else(|| {
+                                RuntimeError::new("a global is used before its definition")
+                            })?;
+                        self.push(value)?;
+                    }
+                    Opcode::SetGlobal => {
+                        let index = operand(instructions, operands, 2)?;
+                        let value = self.pop()?;
+                        if index >= self.globals.len() {
+                            self.globals.resize(index + 1, None);
+                        }
+                        self.globals[index] = Some(value);
+                    }
+                    Opcode::GetLocal => {
+                        let index = base_pointer + operand(instructions, operands, 1)?;
+                        let value = self.stack.get(index).ok_or_else(invalid_bytecode)?;
+                        self.push(value.clone())?;
+                    }
+                    Opcode::SetLocal => {
+                        let index = base_pointer + operand(instructions, operands, 1)?;
+                        let value = self.pop()?;
+                        *self.stack.get_mut(index).ok_or_else(invalid_bytecode)? = value;
+                    }
+                    Opcode::GetBuiltin => {
+                        let index = operand(instructions, operands, 1)?;
+                        let builtin = BUILTINS.get(index).ok_or_else(invalid_bytecode)?;
+                        self.push(Object::Builtin(builtin))?;
+                    }
+                    Opcode::GetFree => {
+                        let index = operand(instructions, operands, 1)?;
+                        let value = closure.free.get(index).ok_or_else(invalid_bytecode)?;
+                        self.push(value.clone())?;
+                    }
+                    Opcode::CurrentClosure => self.push(Object::Closure(Rc::clone(&closure)))?,
+                    Opcode::Closure => {
+                        let index = operand(instructions, operands, 2)?;
+                        let count = operand(instructions, operands + 2, 1)?;
+                        let Some(Object::CompiledFunction(function)) = self.constants.get(index)
+                        else {
+                            return Err(invalid_bytecode());
+                        };
+                        let function = Rc::clone(function);
+                        let start = self
+                            .stack
+                            .len()
+                            .checked_sub(count)
+                            .ok_or_else(invalid_bytecode)?;
+                        let free = self.stack.split_off(start);
+                        let value = Object::Closure(Rc::new(Closure { function, free }));
+                        self.allocate(&value)?;
+                        self.push(value)?;
+                    }
+                    Opcode::Call => {
+                        let count = operand(instructions, operands, 1)?;
+                        self.frames.last_mut().expect("a running frame").ip = ip;
+                        if self.call(count)? {
+                            break;
+                        }
+                    }
+                    Opcode::ReturnValue => {
+                        let value = self.pop()?;
+                        let frame = self.frames.pop().expect("a running frame");
+                        if self.frames.is_empty() {
+                            return Ok(value);
+                        }
+                        self.stack.truncate(frame.base_pointer - 1);
+                        self.push(value)?;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds the failing instruction's span and a trace of the frames to
+    /// `error`, as far as the span tables tell.
+    /// # Parameters
+    /// - `error`: The error `execute` returned
+    /// - `failed_at`: The offset of the failing instruction in the top frame
+    fn locate(&self, mut error: RuntimeError, failed_at: usize) -> RuntimeError {
+        let mut frames = self.frames.iter().rev();
+        let Some(mut callee) = frames.next() else {
+            return error;
+        };
+        if error.span.is_none() {
+            error.span = span_at(&callee.closure.function.spans, failed_at).cloned();
+        }
+        for caller in frames {
+            // The caller's `ip` is just past its `Call` instruction
+            let call = caller.ip.saturating_sub(1);
+            error.trace.push(TraceFrame {
+                function: callee.closure.function.name.clone(),
+                call_site: span_at(&caller.closure.function.spans, call).cloned(),
+            });
+            callee = caller;
+        }
+        error
+    }
+
+    /// Calls the value below the top `count` arguments.
+    /// # Returns
+    /// Whether a new frame was pushed; builtins run to completion instead.
+    fn call(&mut self, count: usize) -> Result<bool, RuntimeError> {
+        let callee = self
+            .stack
+            .len()
+            .checked_sub(count + 1)
+            .ok_or_else(invalid_bytecode)?;
+        match &self.stack[callee] {
+            Object::Closure(closure) => {
+                let closure = Rc::clone(closure);
+                let function = &closure.function;
+                if count != function.num_parameters {
+                    return Err(RuntimeError::new(format!(
+                        "wrong number of arguments: want={}, got={}",
+                        function.num_parameters, count
+                    )));
+                }
+                if self.frames.len() > self.limits.max_call_depth {
+                    return Err(stack_overflow());
+                }
+                let base_pointer = callee + 1;
+                let top = base_pointer + function.num_locals.max(count);
+                if top > STACK_SIZE {
+                    return Err(stack_overflow());
+                }
+                self.stack.resize(top, Object::Null);
+                self.frames.push(Frame {
+                    closure,
+                    ip: 0,
+                    base_pointer,
+                });
+                Ok(true)
+            }
+            Object::Builtin(builtin) => {
+                let result = (builtin.func)(&self.stack[callee + 1..], &mut *self.output)?;
+                self.allocate(&result)?;
+                self.stack.truncate(callee);
+                self.push(result)?;
+                Ok(false)
+            }
+            Object::Native(native) => {
+                let result = native.call(&self.stack[callee + 1..])?;
+                self.allocate(&result)?;
+                self.stack.truncate(callee);
+                self.push(result)?;
+                Ok(false)
+            }
+            other => Err(RuntimeError::new(format!(
+                "not a function: {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    fn push(&mut self, value: Object) -> Result<(), RuntimeError> {
+        if self.stack.len() >= STACK_SIZE {
+            return Err(stack_overflow());
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Object, RuntimeError> {
+        self.stack.pop().ok_or_else(invalid_bytecode)
+    }
+
+    /// Counts an instruction and checks the step and time limits.
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+        if let Some(max) = self.limits.max_steps
+            && self.steps > max
+        {
+            return Err(RuntimeError::limit_exceeded(
+                Limit::Steps,
+                format!("step limit of {} exceeded", max),
+                None,
+            ));
+        }
+        if let Some(deadline) = self.deadline
+            && self.steps.is_multiple_of(CLOCK_INTERVAL)
+            && Instant::now() >= deadline
+        {
+            return Err(RuntimeError::limit_exceeded(
+                Limit::Time,
+                format!(
+                    "time limit of {:?} exceeded",
+                    self.limits.timeout.unwrap_or_default()
+                ),
+                None,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Counts the memory a newly created value holds against the limit.
+    fn allocate(&mut self, value: &Object) -> Result<(), RuntimeError> {
+        self.allocated = self.allocated.saturating_add(allocation_size(value));
+        match self.limits.max_allocated_bytes {
+            Some(max) if self.allocated > max => Err(RuntimeError::limit_exceeded(
+                Limit::Memory,
+                format!("memory limit of {} bytes exceeded", max),
+                None,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The operator an arithmetic or comparison opcode applies.
+fn operator(op: Opcode) -> &'static str {
+    match op {
+        Opcode::Add => "+",
+        Opcode::Sub | Opcode::Minus => "-",
+        Opcode::Mul => "*",
+        Opcode::Div => "/",
+        Opcode::Equal => "==",
+        Opcode::NotEqual => "!=",
+        Opcode::GreaterThan => ">",
+        Opcode::LessThan => "<",
+        Opcode::Bang => "!",
+        _ => unreachable!("{:?} is not an operator", op),
+    }
+}
+
+/// Reads a big-endian operand of `width` bytes at `offset`.
+fn operand(instructions: &[u8], offset: usize, width: usize) -> Result<usize, RuntimeError> {
+    instructions
+        .get(offset..offset + width)
+        .map(|bytes| {
+            bytes
+                .iter()
+                .fold(0, |value, &byte| value << 8 | byte as usize)
+        })
+        .ok_or_else(invalid_bytecode)
+}
+
+fn stack_overflow() -> RuntimeError {
+    RuntimeError::limit_exceeded(Limit::CallDepth, "stack overflow", None)
+}
+
+/// Bytecode the compiler would not produce, such as a jump past the end.
+fn invalid_bytecode() -> RuntimeError {
+    RuntimeError::new("invalid bytecode")
+}